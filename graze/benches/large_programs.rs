@@ -0,0 +1,109 @@
+//! Tokenize, parse and run generated scripts of 100k instructions.
+//! Run with `cargo bench`.
+
+use criterion::{criterion_group, criterion_main, BatchSize, Criterion, Throughput};
+use graze::{parse_file, testing::Recorder, Payload, Runtime, StringTokenizer, TokenSource};
+
+const INSTRUCTIONS: usize = 100_000;
+
+/// A script mixing the common kinds of instructions: bindings,
+/// arithmetic, builtin calls and drawing.
+fn script() -> String {
+    let mut script = String::from("#screen 100 100\n#let x 0\n");
+    for i in 0..INSTRUCTIONS {
+        let line = match i % 5 {
+            0 => format!("#let x ($x + {i} * 2 - 1)\n"),
+            1 => format!("circle (pnt2 {} {}) 1.5mm\n", i % 100, i % 7),
+            2 => format!("line (pnt2 0 0) (vec2 {} 1) => drop\n", i % 10),
+            3 => "#let p (jump (pnt2 1 2) 3 4)\n".to_string(),
+            _ => format!("#let a (add (mul $x 2) (sqrt {i}))\n"),
+        };
+        script.push_str(&line);
+    }
+    script
+}
+
+fn tokenize(c: &mut Criterion) {
+    let source = script();
+    let mut group = c.benchmark_group("tokenize");
+    group.throughput(Throughput::Bytes(source.len() as u64));
+    group.bench_function("large", |b| {
+        b.iter(|| {
+            let mut tokenizer = StringTokenizer::new(&source);
+            while tokenizer.read_token().unwrap().payload != Payload::Eof {}
+        })
+    });
+    group.finish();
+}
+
+fn parse(c: &mut Criterion) {
+    let source = script();
+    let mut group = c.benchmark_group("parse");
+    group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+    group.bench_function("large", |b| {
+        b.iter(|| parse_file(&mut StringTokenizer::new(&source)).unwrap())
+    });
+    group.finish();
+}
+
+/// A loop running a short body often enough to execute as many
+/// instructions as [`script`].
+fn loop_script() -> String {
+    let items = (0..INSTRUCTIONS / 3)
+        .map(|i| i.to_string())
+        .collect::<Vec<_>>();
+    format!(
+        "#let x 0\n#for i in (list {})\n{}#end\n",
+        items.join(" "),
+        "#let x ($x + $i * 2 - 1)\ncircle (pnt2 $i 1) 2\n#let p (jump (pnt2 1 2) 3 4)\n"
+    )
+}
+
+/// Drawing that doesn't depend on earlier instructions, so that it can
+/// run in parallel.
+fn independent_script() -> String {
+    let mut script = String::from("#screen 100 100\n");
+    for i in 0..INSTRUCTIONS {
+        let line = match i % 3 {
+            0 => format!("circle (pnt2 {} {}) 1.5mm\n", i % 100, i % 7),
+            1 => format!("line (pnt2 0 0) (vec2 {} (sqrt {i}))\n", i % 10),
+            _ => format!("#let p{i} (jump (pnt2 1 2) 3 {i}) => circle $p{i} 2\n"),
+        };
+        script.push_str(&line);
+    }
+    script
+}
+
+fn execute(c: &mut Criterion) {
+    let mut group = c.benchmark_group("execute");
+    group.throughput(Throughput::Elements(INSTRUCTIONS as u64));
+    for (name, source) in [("large", script()), ("loop", loop_script())] {
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        group.bench_function(name, |b| {
+            b.iter_batched(
+                || program.clone(),
+                |program| Runtime::<Recorder>::default().execute(program).unwrap(),
+                BatchSize::LargeInput,
+            )
+        });
+    }
+
+    let program = parse_file(&mut StringTokenizer::new(&independent_script())).unwrap();
+    for threads in [1, 4] {
+        group.bench_function(format!("independent/{threads}"), |b| {
+            b.iter_batched(
+                || program.clone(),
+                |program| {
+                    let mut runtime = Runtime::<Recorder>::default();
+                    runtime.set_threads(threads);
+                    runtime.execute(program).unwrap()
+                },
+                BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, tokenize, parse, execute);
+criterion_main!(benches);
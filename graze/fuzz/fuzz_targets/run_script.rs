@@ -0,0 +1,34 @@
+//! Tokenize, parse, check, format and run arbitrary input, none of which
+//! may panic. Run with `cargo fuzz run run_script` from `graze/`.
+#![no_main]
+
+use graze::{
+    check::check_program, format::format_program, parse_file, parse_file_tolerant,
+    testing::Recorder, tokenize_with_spans, Clipped, Runtime, RuntimeConfig, StringTokenizer,
+};
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|source: &str| {
+    tokenize_with_spans(source);
+    parse_file_tolerant(&mut StringTokenizer::new(&source));
+
+    let Ok(program) = parse_file(&mut StringTokenizer::new(&source)) else {
+        return;
+    };
+    check_program(&program);
+    format_program(&program);
+
+    // Without limits, loops can legitimately run out of time or memory
+    let mut runtime = Runtime::<Clipped<Recorder>>::default();
+    runtime.set_config(RuntimeConfig {
+        readable_paths: Some(Vec::new()),
+        max_instructions: Some(10_000),
+        max_output: Some(10_000),
+        max_loop_iterations: Some(1_000),
+        max_call_depth: Some(64),
+        max_stack: Some(1_000),
+        max_draw_commands: Some(1_000),
+        ..Default::default()
+    });
+    let _ = runtime.execute(program);
+});
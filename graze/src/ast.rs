@@ -1,62 +1,153 @@
 use smol_str::SmolStr;
 use thiserror::Error;
 
-use crate::token::{self, Keyword, Number, Payload, Position, Token, TokenSource};
+use crate::token::{
+    self, Keyword, Number, Operator, Payload, Position, Span, Token, TokenSource, Trivia, Unit,
+};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Program {
     pub instructions: Vec<Instruction>,
+    /// Comments and blank lines, kept for formatting.
+    pub trivia: Vec<Trivia>,
 }
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Instruction {
     pub expressions: Vec<Expression>,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Expression {
     pub content: ExpressionContent,
     pub draw_result: bool,
     pub position: Position,
+    /// End of the `;`, `=>` or newline that closed this expression.
+    pub end: Position,
+    /// The source text of the expression, without what closed it.
+    pub span: Span,
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum ExpressionContent {
     Literal(Literal),
     Variable(SmolStr),
     FunctionCall {
         name: SmolStr,
         args: Vec<Argument>,
+        /// `key=value` options after or between the arguments, in order.
+        #[cfg_attr(
+            feature = "serde",
+            serde(default, skip_serializing_if = "Vec::is_empty")
+        )]
+        options: Vec<(SmolStr, Argument)>,
     },
     Let {
         name: SmolStr,
         init: Option<Argument>,
     },
+    /// Like `Let`, but the binding may never be reassigned.
+    Const {
+        name: SmolStr,
+        init: Option<Argument>,
+    },
+    /// Bind `name` to a number meant to be tuned between `min` and `max`,
+    /// starting out at `default` unless the host set it beforehand.
+    Param {
+        name: SmolStr,
+        min: Argument,
+        max: Argument,
+        default: Argument,
+    },
     Screen(Argument, Argument),
+    View {
+        x: Argument,
+        y: Argument,
+        width: Argument,
+        height: Argument,
+        flip_y: bool,
+    },
+    Page,
+    /// Put the origin at the `top-left` or `bottom-left` corner of the
+    /// visible area.
+    Origin {
+        bottom: bool,
+    },
+    /// Make y grow `down` the page, like in SVG, or `up`, like in math.
+    YDir {
+        up: bool,
+    },
+    /// Name the drawing, like `#meta title`.
+    Title(Argument),
+    /// Describe the drawing, like `#meta description`.
+    Desc(Argument),
+    /// Document metadata, named after one of [`crate::METADATA_NAMES`].
+    Meta {
+        name: SmolStr,
+        value: Argument,
+    },
+    /// A block of instructions with its own variable scope.
+    Scope(Vec<Instruction>),
+    /// Run `body` once per item of a list, each time in a fresh scope
+    /// with the item bound to `name`.
+    For {
+        name: SmolStr,
+        items: Argument,
+        body: Vec<Instruction>,
+    },
+    /// Define the symbol `name` as what `body` draws, in coordinates of
+    /// its own, to be drawn later with `place`.
+    Symbol {
+        name: SmolStr,
+        body: Vec<Instruction>,
+    },
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Argument {
     Variable(SmolStr),
     Literal(Literal),
     Parenthesized(Box<ExpressionContent>),
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Literal {
     Number(Number),
+    /// A length, converted to millimeters when evaluated.
+    Quantity(Number, Unit),
+    String(SmolStr),
 }
 
 #[derive(Debug, Error)]
 #[error("Error parsing file at {at}: {kind}")]
 pub struct Error {
     at: Position,
+    span: Span,
     kind: ErrorKind,
 }
 
 impl Error {
-    pub fn new(at: Position, kind: ErrorKind) -> Self {
-        Self { at, kind }
+    pub fn new(at: Position, span: Span, kind: ErrorKind) -> Self {
+        Self { at, span, kind }
+    }
+
+    pub fn position(&self) -> Position {
+        self.at
+    }
+
+    /// The bytes of the source the error is about, usually a single token.
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
     }
 }
 
@@ -72,145 +163,564 @@ pub enum ErrorKind {
     UnclosedDelimiter,
     #[error("Expected identifier")]
     ExpectedIdentifier,
+    #[error("Block is missing its closing #end")]
+    UnclosedBlock,
+    #[error("{0} is reserved but not supported yet")]
+    ReservedKeyword(Keyword),
+    #[error("Blocks and parentheses are nested more than {MAX_NESTING} deep")]
+    TooDeeplyNested,
+    #[error("Option {0} is given more than once")]
+    DuplicateOption(SmolStr),
+    #[error("{0} is not a corner #origin supports, use top-left or bottom-left")]
+    UnknownCorner(SmolStr),
+}
+
+impl ErrorKind {
+    /// See [`token::ErrorKind::code`]. Parser errors are numbered from
+    /// E0101.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::InvalidToken(kind) => kind.code(),
+            ErrorKind::UnexpectedToken(_) => "E0101",
+            ErrorKind::ExpectedExpression => "E0102",
+            ErrorKind::UnclosedDelimiter => "E0103",
+            ErrorKind::ExpectedIdentifier => "E0104",
+            ErrorKind::UnclosedBlock => "E0105",
+            ErrorKind::ReservedKeyword(_) => "E0106",
+            ErrorKind::TooDeeplyNested => "E0107",
+            ErrorKind::DuplicateOption(_) => "E0108",
+            ErrorKind::UnknownCorner(_) => "E0109",
+        }
+    }
 }
 
 impl From<token::Error> for Error {
     fn from(value: token::Error) -> Self {
         Self {
             at: value.at,
+            span: value.span,
             kind: value.kind.into(),
         }
     }
 }
 
+/// How deeply blocks and parentheses may nest, so that parsing and
+/// running a program can't overflow the stack.
+pub const MAX_NESTING: usize = 64;
+
 pub fn parse_file<S>(source: &mut S) -> Result<Program, Error>
 where
     S: TokenSource,
 {
     let mut program = Program::default();
-    while let Some(instruction) = parse_instruction(source)? {
+    program.instructions.reserve(source.lines_hint());
+    while let Some(instruction) = parse_instruction(source, 0)? {
         program.instructions.push(instruction);
     }
+    program.trivia = source.take_trivia();
     Ok(program)
 }
 
-fn parse_instruction<S>(source: &mut S) -> Result<Option<Instruction>, Error>
+/// Parse a whole file without stopping at the first syntax error.
+/// Every error is recorded and parsing resumes on the next line, so the
+/// returned program contains all instructions that parsed cleanly.
+pub fn parse_file_tolerant<S>(source: &mut S) -> (Program, Vec<Error>)
+where
+    S: TokenSource,
+{
+    let mut program = Program::default();
+    program.instructions.reserve(source.lines_hint());
+    let mut errors = vec![];
+    loop {
+        match parse_instruction(source, 0) {
+            Ok(Some(instruction)) => program.instructions.push(instruction),
+            Ok(None) => break,
+            Err(error) => {
+                errors.push(error);
+                if !skip_line(source) {
+                    break;
+                }
+            }
+        }
+    }
+    program.trivia = source.take_trivia();
+    (program, errors)
+}
+
+/// Discard tokens up to and including the next newline.
+/// Returns `false` if the end of the input was reached instead.
+fn skip_line<S>(source: &mut S) -> bool
 where
     S: TokenSource,
 {
-    let mut result = Instruction::default();
+    // The failing token may have been the newline itself
+    if source.position().is_line_start() {
+        return true;
+    }
+    loop {
+        match source.read_token().map(|token| token.payload) {
+            Ok(Payload::Newline | Payload::VoidNewline) => return true,
+            Ok(Payload::Eof) => return false,
+            _ => {}
+        }
+    }
+}
+
+fn parse_instruction<S>(source: &mut S, depth: usize) -> Result<Option<Instruction>, Error>
+where
+    S: TokenSource,
+{
+    let mut result = Instruction {
+        // Most instructions are a single expression
+        expressions: Vec::with_capacity(1),
+    };
 
     loop {
         let position = source.position();
-        let Some(content) = parse_expr(source)? else {
+        let first = source.read_token()?;
+        let start = first.span.start;
+        let Some(content) = parse_expr_from(source, first, depth)? else {
+            if result.expressions.is_empty() && source.peek_token()?.payload != Payload::Eof {
+                // Empty line, parse the next one
+                continue;
+            }
             break;
         };
+        let span = start..source.offset();
         let Token {
             payload: join,
             position: end,
+            span: join_span,
         } = source.read_token()?;
         let draw_result = match join {
-            Payload::Pipe => false,
+            Payload::Pipe | Payload::VoidNewline => false,
             Payload::Concat | Payload::Newline | Payload::Eof => true,
-            other => return Err(Error::new(end, ErrorKind::UnexpectedToken(other))),
+            other => {
+                return Err(Error::new(
+                    end,
+                    join_span,
+                    ErrorKind::UnexpectedToken(other),
+                ))
+            }
         };
 
         result.expressions.push(Expression {
             content,
             draw_result,
             position,
+            end,
+            span,
         });
 
-        if let Payload::Newline | Payload::Eof = join {
-            break;
+        match join {
+            Payload::Newline | Payload::VoidNewline | Payload::Eof => break,
+            // A pipe at the end of a line continues the instruction on the next one
+            Payload::Pipe => {
+                while source.peek_token()?.payload == Payload::Newline {
+                    source.read_token()?;
+                }
+            }
+            _ => {}
         }
     }
 
     if result.expressions.is_empty() {
-        if source.peek_token()?.payload == Payload::Eof {
-            Ok(None)
-        } else {
-            // Empty line, parse the next one
-            parse_instruction(source)
-        }
+        Ok(None)
     } else {
         Ok(Some(result))
     }
 }
 
-fn parse_expr<S>(source: &mut S) -> Result<Option<ExpressionContent>, Error>
+fn parse_expr<S>(source: &mut S, depth: usize) -> Result<Option<ExpressionContent>, Error>
 where
     S: TokenSource,
 {
-    let Token { payload, position } = source.read_token()?;
+    let first = source.read_token()?;
+    parse_expr_from(source, first, depth)
+}
+
+/// Parse the rest of an expression starting with the token `first`.
+fn parse_expr_from<S>(
+    source: &mut S,
+    first: Token,
+    depth: usize,
+) -> Result<Option<ExpressionContent>, Error>
+where
+    S: TokenSource,
+{
+    let Token {
+        payload,
+        position,
+        span,
+    } = first;
+    let error = |kind| Error::new(position, span.clone(), kind);
 
     let content = match payload {
         Payload::LitNumber(number) => ExpressionContent::Literal(Literal::Number(number)),
+        Payload::LitQuantity(number, unit) => {
+            ExpressionContent::Literal(Literal::Quantity(number, unit))
+        }
+        Payload::LitString(string) => ExpressionContent::Literal(Literal::String(string)),
         Payload::Variable(name) => ExpressionContent::Variable(name),
         Payload::Name(name) => {
-            println!("name: {name}");
             let mut args = vec![];
-            while let Some(arg) = parse_arg(source)? {
-                args.push(arg);
+            let mut options: Vec<(SmolStr, Argument)> = vec![];
+            loop {
+                if let Some(arg) = parse_arg(source, depth)? {
+                    args.push(arg);
+                    continue;
+                }
+                let option = match &source.peek_token()?.payload {
+                    Payload::Key(option) => option.clone(),
+                    _ => break,
+                };
+                let key = source.read_token()?;
+                if options.iter().any(|(name, _)| *name == option) {
+                    return Err(Error::new(
+                        key.position,
+                        key.span,
+                        ErrorKind::DuplicateOption(option),
+                    ));
+                }
+                let value = match parse_arg(source, depth)? {
+                    Some(value) => value,
+                    // A bare word is a string, e.g. `anchor=middle`
+                    None => match source.read_token()? {
+                        Token {
+                            payload: Payload::Name(word),
+                            ..
+                        } => Argument::Literal(Literal::String(word)),
+                        _ => {
+                            return Err(Error::new(
+                                key.position,
+                                key.span,
+                                ErrorKind::ExpectedExpression,
+                            ))
+                        }
+                    },
+                };
+                options.push((option, value));
+            }
+            ExpressionContent::FunctionCall {
+                name,
+                args,
+                options,
             }
-            ExpressionContent::FunctionCall { name, args }
         }
-        Payload::Keyword(Keyword::Let) => {
-            let Token { payload, position } = source.read_token()?;
+        Payload::Keyword(keyword @ (Keyword::Let | Keyword::Const)) => {
+            let token = source.read_token()?;
 
-            let Payload::Name(name) = payload else {
-                return Err(Error::new(position, ErrorKind::ExpectedIdentifier));
+            let Payload::Name(name) = token.payload else {
+                return Err(Error::new(
+                    token.position,
+                    token.span,
+                    ErrorKind::ExpectedIdentifier,
+                ));
             };
 
-            let init = parse_arg(source)?;
+            let init = parse_arg(source, depth)?;
 
-            ExpressionContent::Let { name, init }
+            if keyword == Keyword::Const {
+                ExpressionContent::Const { name, init }
+            } else {
+                ExpressionContent::Let { name, init }
+            }
+        }
+        Payload::Keyword(Keyword::Param) => {
+            let token = source.read_token()?;
+            let Payload::Name(name) = token.payload else {
+                return Err(Error::new(
+                    token.position,
+                    token.span,
+                    ErrorKind::ExpectedIdentifier,
+                ));
+            };
+            let mut next = || {
+                parse_arg(source, depth)
+                    .and_then(|arg| arg.ok_or(error(ErrorKind::ExpectedExpression)))
+            };
+            let (min, max, default) = (next()?, next()?, next()?);
+
+            ExpressionContent::Param {
+                name,
+                min,
+                max,
+                default,
+            }
         }
         Payload::Keyword(Keyword::Screen) => {
-            let x = parse_arg(source)
-                .and_then(|x| x.ok_or(Error::new(position, ErrorKind::ExpectedExpression)))?;
-            let y = parse_arg(source)
-                .and_then(|y| y.ok_or(Error::new(position, ErrorKind::ExpectedExpression)))?;
+            let x = parse_arg(source, depth)
+                .and_then(|x| x.ok_or(error(ErrorKind::ExpectedExpression)))?;
+            let y = parse_arg(source, depth)
+                .and_then(|y| y.ok_or(error(ErrorKind::ExpectedExpression)))?;
 
             ExpressionContent::Screen(x, y)
         }
-        Payload::Newline | Payload::Eof => return Ok(None),
-        other => return Err(Error::new(position, ErrorKind::UnexpectedToken(other))),
+        Payload::Keyword(Keyword::View) => {
+            let mut next = || {
+                parse_arg(source, depth)
+                    .and_then(|arg| arg.ok_or(error(ErrorKind::ExpectedExpression)))
+            };
+            let (x, y, width, height) = (next()?, next()?, next()?, next()?);
+
+            let flip_y =
+                matches!(&source.peek_token()?.payload, Payload::Name(name) if name == "flip");
+            if flip_y {
+                source.read_token()?;
+            }
+
+            ExpressionContent::View {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            }
+        }
+        Payload::Keyword(Keyword::Page) => ExpressionContent::Page,
+        Payload::Keyword(Keyword::Origin) => {
+            // `bottom-left` is read as a name, a minus and another name
+            let mut corner = String::new();
+            for _ in 0..3 {
+                let token = source.read_token()?;
+                match token.payload {
+                    Payload::Name(word) => corner.push_str(&word),
+                    Payload::Operator(Operator::Sub) => corner.push('-'),
+                    _ => break,
+                }
+            }
+            match corner.as_str() {
+                "top-left" => ExpressionContent::Origin { bottom: false },
+                "bottom-left" => ExpressionContent::Origin { bottom: true },
+                _ => return Err(error(ErrorKind::UnknownCorner(corner.into()))),
+            }
+        }
+        Payload::Keyword(Keyword::YDir) => {
+            let Token {
+                payload,
+                position,
+                span,
+            } = source.read_token()?;
+            match payload {
+                Payload::Name(word) if word == "up" => ExpressionContent::YDir { up: true },
+                Payload::Name(word) if word == "down" => ExpressionContent::YDir { up: false },
+                other => {
+                    return Err(Error::new(
+                        position,
+                        span,
+                        ErrorKind::UnexpectedToken(other),
+                    ))
+                }
+            }
+        }
+        Payload::Keyword(keyword @ (Keyword::Title | Keyword::Desc)) => {
+            let text = parse_arg(source, depth)?.ok_or(error(ErrorKind::ExpectedExpression))?;
+            if keyword == Keyword::Title {
+                ExpressionContent::Title(text)
+            } else {
+                ExpressionContent::Desc(text)
+            }
+        }
+        Payload::Keyword(Keyword::Meta) => {
+            let token = source.read_token()?;
+            let Payload::Name(name) = token.payload else {
+                return Err(Error::new(
+                    token.position,
+                    token.span,
+                    ErrorKind::ExpectedIdentifier,
+                ));
+            };
+            let value = parse_arg(source, depth)?.ok_or(Error::new(
+                token.position,
+                token.span,
+                ErrorKind::ExpectedExpression,
+            ))?;
+
+            ExpressionContent::Meta { name, value }
+        }
+        Payload::Keyword(Keyword::Scope) => {
+            ExpressionContent::Scope(parse_block(source, position, &span, depth + 1)?)
+        }
+        Payload::Keyword(Keyword::For) => {
+            let token = source.read_token()?;
+            let Payload::Name(name) = token.payload else {
+                return Err(Error::new(
+                    token.position,
+                    token.span,
+                    ErrorKind::ExpectedIdentifier,
+                ));
+            };
+
+            let Token {
+                payload,
+                position,
+                span,
+            } = source.read_token()?;
+            let error = |kind| Error::new(position, span.clone(), kind);
+            match payload {
+                Payload::Name(word) if word == "in" => {}
+                other => return Err(error(ErrorKind::UnexpectedToken(other))),
+            }
+
+            let items = parse_arg(source, depth)?.ok_or(error(ErrorKind::ExpectedExpression))?;
+            let body = parse_block(source, position, &span, depth + 1)?;
+
+            ExpressionContent::For { name, items, body }
+        }
+        Payload::Keyword(Keyword::Symbol) => {
+            let token = source.read_token()?;
+            let Payload::Name(name) = token.payload else {
+                return Err(Error::new(
+                    token.position,
+                    token.span,
+                    ErrorKind::ExpectedIdentifier,
+                ));
+            };
+            let body = parse_block(source, position, &span, depth + 1)?;
+
+            ExpressionContent::Symbol { name, body }
+        }
+        Payload::Keyword(keyword) if keyword.is_reserved() => {
+            return Err(error(ErrorKind::ReservedKeyword(keyword)))
+        }
+        Payload::Newline | Payload::VoidNewline | Payload::Eof => return Ok(None),
+        other => return Err(error(ErrorKind::UnexpectedToken(other))),
     };
 
     Ok(Some(content))
 }
 
-fn parse_arg<S>(source: &mut S) -> Result<Option<Argument>, Error>
+/// Parse instructions up to and including the `#end` closing a block
+/// that was opened by the token at `start` and `span`.
+fn parse_block<S>(
+    source: &mut S,
+    start: Position,
+    span: &Span,
+    depth: usize,
+) -> Result<Vec<Instruction>, Error>
+where
+    S: TokenSource,
+{
+    if depth > MAX_NESTING {
+        return Err(Error::new(start, span.clone(), ErrorKind::TooDeeplyNested));
+    }
+    let mut body = vec![];
+    loop {
+        match source.peek_token()?.payload {
+            Payload::Newline | Payload::VoidNewline => {
+                source.read_token()?;
+            }
+            Payload::Keyword(Keyword::End) => {
+                source.read_token()?;
+                return Ok(body);
+            }
+            Payload::Eof => return Err(Error::new(start, span.clone(), ErrorKind::UnclosedBlock)),
+            _ => body.extend(parse_instruction(source, depth)?),
+        }
+    }
+}
+
+/// Parse an expression inside parentheses, where `+ - * /` may join
+/// operands. Operators are turned into calls to the matching builtins.
+fn parse_infix<S>(
+    source: &mut S,
+    min_precedence: u8,
+    start: Position,
+    span: &Span,
+    depth: usize,
+) -> Result<ExpressionContent, Error>
+where
+    S: TokenSource,
+{
+    if depth > MAX_NESTING {
+        return Err(Error::new(start, span.clone(), ErrorKind::TooDeeplyNested));
+    }
+    let mut lhs = if source.peek_token()?.payload == Payload::Operator(Operator::Sub) {
+        // Unary minus, `-x` is `0 - x`
+        source.read_token()?;
+        let operand = parse_infix(source, u8::MAX, start, span, depth + 1)?;
+        ExpressionContent::FunctionCall {
+            name: Operator::Sub.function().into(),
+            args: vec![
+                Argument::Literal(Literal::Number(Number::Integer(0))),
+                operand.into(),
+            ],
+            options: vec![],
+        }
+    } else if source.peek_token()?.payload == Payload::ParenL {
+        parse_parenthesized(source, depth + 1)?
+    } else {
+        parse_expr(source, depth)?.ok_or(Error::new(
+            start,
+            span.clone(),
+            ErrorKind::ExpectedExpression,
+        ))?
+    };
+
+    while let Payload::Operator(operator) = source.peek_token()?.payload {
+        if operator.precedence() < min_precedence {
+            break;
+        }
+        source.read_token()?;
+
+        let rhs = parse_infix(source, operator.precedence() + 1, start, span, depth + 1)?;
+        lhs = ExpressionContent::FunctionCall {
+            name: operator.function().into(),
+            args: vec![lhs.into(), rhs.into()],
+            options: vec![],
+        };
+    }
+
+    Ok(lhs)
+}
+
+impl From<ExpressionContent> for Argument {
+    fn from(value: ExpressionContent) -> Self {
+        match value {
+            ExpressionContent::Literal(literal) => Argument::Literal(literal),
+            ExpressionContent::Variable(name) => Argument::Variable(name),
+            other => Argument::Parenthesized(Box::new(other)),
+        }
+    }
+}
+
+fn parse_arg<S>(source: &mut S, depth: usize) -> Result<Option<Argument>, Error>
 where
     S: TokenSource,
 {
-    let start = source.peek_token()?;
-    let arg = match start.payload {
-        Payload::Variable(name) => Argument::Variable(name),
-        Payload::LitNumber(number) => Argument::Literal(Literal::Number(number)),
+    let arg = match &source.peek_token()?.payload {
+        Payload::Variable(name) => Argument::Variable(name.clone()),
+        Payload::LitNumber(number) => Argument::Literal(Literal::Number(*number)),
+        Payload::LitQuantity(number, unit) => Argument::Literal(Literal::Quantity(*number, *unit)),
+        Payload::LitString(string) => Argument::Literal(Literal::String(string.clone())),
         Payload::ParenL => {
-            source.read_token().expect(
-                "Did not expect error reading token when peeking that same token worked fine",
-            );
-            let Some(expr) = parse_expr(source)? else {
-                return Err(Error::new(start.position, ErrorKind::ExpectedExpression));
-            };
-            let next = source.peek_token()?;
-            let Payload::ParenR = next.payload else {
-                return Err(Error::new(start.position, ErrorKind::UnclosedDelimiter));
-            };
-            Argument::Parenthesized(Box::new(expr))
+            let expr = parse_parenthesized(source, depth + 1)?;
+            return Ok(Some(Argument::Parenthesized(Box::new(expr))));
         }
         _ => return Ok(None),
     };
-    source
-        .read_token()
-        .expect("Did not expect error reading token when peeking that same token worked fine");
+    source.read_token()?;
 
     Ok(Some(arg))
 }
 
+/// Parse an expression in parentheses, starting at the opening one.
+fn parse_parenthesized<S>(source: &mut S, depth: usize) -> Result<ExpressionContent, Error>
+where
+    S: TokenSource,
+{
+    let open = source.read_token()?;
+    let expr = parse_infix(source, 0, open.position, &open.span, depth)?;
+    let Payload::ParenR = source.read_token()?.payload else {
+        return Err(Error::new(
+            open.position,
+            open.span,
+            ErrorKind::UnclosedDelimiter,
+        ));
+    };
+    Ok(expr)
+}
+
 #[cfg(test)]
 mod test {
     use super::*;
@@ -220,7 +730,7 @@ mod test {
     fn test_parse_literal_number() {
         let input = "42";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_expr(&mut source).unwrap();
+        let result = parse_expr(&mut source, 0).unwrap();
         assert_eq!(
             result,
             Some(ExpressionContent::Literal(Literal::Number(
@@ -233,7 +743,7 @@ mod test {
     fn test_parse_variable() {
         let input = "$x";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_expr(&mut source).unwrap();
+        let result = parse_expr(&mut source, 0).unwrap();
         assert_eq!(result, Some(ExpressionContent::Variable(SmolStr::new("x"))));
     }
 
@@ -241,7 +751,7 @@ mod test {
     fn test_parse_function_call() {
         let input = "foo 42 $x";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_expr(&mut source).unwrap();
+        let result = parse_expr(&mut source, 0).unwrap();
         assert_eq!(
             result,
             Some(ExpressionContent::FunctionCall {
@@ -250,6 +760,36 @@ mod test {
                     Argument::Literal(Literal::Number(Number::Integer(42))),
                     Argument::Variable(SmolStr::new("x")),
                 ],
+                options: vec![],
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_const_statement() {
+        let input = "#const WIDTH 210";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_expr(&mut source, 0).unwrap();
+        assert_eq!(
+            result,
+            Some(ExpressionContent::Const {
+                name: SmolStr::new("WIDTH"),
+                init: Some(Argument::Literal(Literal::Number(Number::Integer(210)))),
+            })
+        );
+    }
+
+    #[test]
+    fn test_parse_string_argument() {
+        let input = "layer \"outline\"";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_expr(&mut source, 0).unwrap();
+        assert_eq!(
+            result,
+            Some(ExpressionContent::FunctionCall {
+                name: SmolStr::new("layer"),
+                args: vec![Argument::Literal(Literal::String("outline".into()))],
+                options: vec![],
             })
         );
     }
@@ -258,7 +798,7 @@ mod test {
     fn test_parse_let_statement() {
         let input = "#let x 42";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_expr(&mut source).unwrap();
+        let result = parse_expr(&mut source, 0).unwrap();
         assert_eq!(
             result,
             Some(ExpressionContent::Let {
@@ -272,7 +812,7 @@ mod test {
     fn test_parse_parenthesized_expression() {
         let input = "(42)";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_arg(&mut source).unwrap();
+        let result = parse_arg(&mut source, 0).unwrap();
         assert_eq!(
             result,
             Some(Argument::Parenthesized(Box::new(
@@ -281,11 +821,113 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parse_view() {
+        let input = "#view 0 0 10 $h flip";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_expr(&mut source, 0).unwrap();
+        assert_eq!(
+            result,
+            Some(ExpressionContent::View {
+                x: Argument::Literal(Literal::Number(Number::Integer(0))),
+                y: Argument::Literal(Literal::Number(Number::Integer(0))),
+                width: Argument::Literal(Literal::Number(Number::Integer(10))),
+                height: Argument::Variable("h".into()),
+                flip_y: true,
+            })
+        );
+        assert_eq!(source.read_token().unwrap().payload, Payload::Eof);
+    }
+
+    #[test]
+    fn test_parse_metadata() {
+        let mut source = StringTokenSource::new(&"#title \"Plan\"\n#meta creator $who");
+        assert_eq!(
+            parse_expr(&mut source, 0).unwrap(),
+            Some(ExpressionContent::Title(Argument::Literal(
+                Literal::String("Plan".into())
+            )))
+        );
+        source.read_token().unwrap();
+        assert_eq!(
+            parse_expr(&mut source, 0).unwrap(),
+            Some(ExpressionContent::Meta {
+                name: "creator".into(),
+                value: Argument::Variable("who".into()),
+            })
+        );
+
+        let mut source = StringTokenSource::new(&"#meta \"creator\" 1");
+        assert_eq!(
+            parse_expr(&mut source, 0).unwrap_err().kind(),
+            &ErrorKind::ExpectedIdentifier
+        );
+    }
+
+    #[test]
+    fn test_void_newline() {
+        let input = "circle $c 1!\n1; 2!\n1 => dup!\n#scope\n3!\n#end\n";
+        let program = parse_file(&mut StringTokenSource::new(&input)).unwrap();
+        let draws = program
+            .instructions
+            .iter()
+            .map(|instruction| {
+                (instruction.expressions.iter())
+                    .map(|expression| expression.draw_result)
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            draws,
+            [
+                vec![false],
+                vec![true, false],
+                vec![false, false],
+                vec![true]
+            ]
+        );
+        let ExpressionContent::Scope(body) = &program.instructions[3].expressions[0].content else {
+            panic!("Expected a scope");
+        };
+        assert!(!body[0].expressions[0].draw_result);
+    }
+
+    #[test]
+    fn test_parse_options() {
+        let input = "text $p size=4 \"label\" anchor=middle";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_expr(&mut source, 0).unwrap();
+        let string = |s: &str| Argument::Literal(Literal::String(s.into()));
+        assert_eq!(
+            result,
+            Some(ExpressionContent::FunctionCall {
+                name: SmolStr::new("text"),
+                args: vec![Argument::Variable("p".into()), string("label")],
+                options: vec![
+                    (
+                        "size".into(),
+                        Argument::Literal(Literal::Number(Number::Integer(4)))
+                    ),
+                    ("anchor".into(), string("middle")),
+                ],
+            })
+        );
+
+        let mut source = StringTokenSource::new(&"text size=1 size=2");
+        let error = parse_expr(&mut source, 0).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::DuplicateOption("size".into()));
+        assert_eq!(error.span, 12..17);
+
+        let mut source = StringTokenSource::new(&"text size=;");
+        let error = parse_expr(&mut source, 0).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::ExpectedExpression);
+    }
+
     #[test]
     fn test_parse_instruction() {
         let input = "42 => #let x";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_instruction(&mut source).unwrap();
+        let result = parse_instruction(&mut source, 0).unwrap();
         assert!(result.is_some());
         let instruction = result.unwrap();
         assert_eq!(instruction.expressions.len(), 2);
@@ -303,6 +945,63 @@ mod test {
         );
     }
 
+    #[test]
+    fn test_parse_pipe_across_lines() {
+        let input = "pnt2 0 0 =>\n\n  vec2 1 1 =>\nline\nfoo";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_file(&mut source).unwrap();
+        assert_eq!(result.instructions.len(), 2);
+        assert_eq!(result.instructions[0].expressions.len(), 3);
+    }
+
+    #[test]
+    fn test_parse_reserved_keyword() {
+        let mut source = StringTokenSource::new(&"#repeat 3");
+        let error = parse_expr(&mut source, 0).unwrap_err();
+        assert_eq!(error.kind(), &ErrorKind::ReservedKeyword(Keyword::Repeat));
+    }
+
+    #[test]
+    fn test_parse_infix() {
+        let mut source = StringTokenSource::new(&"($x + 5 * (-2))");
+        let call = |name: &str, args| ExpressionContent::FunctionCall {
+            name: name.into(),
+            args,
+            options: vec![],
+        };
+        let number = |n| Argument::Literal(Literal::Number(Number::Integer(n)));
+
+        let negative_two = call("sub", vec![number(0), number(2)]);
+        let product = call(
+            "mul",
+            vec![number(5), Argument::Parenthesized(Box::new(negative_two))],
+        );
+        let sum = call(
+            "add",
+            vec![
+                Argument::Variable("x".into()),
+                Argument::Parenthesized(Box::new(product)),
+            ],
+        );
+        assert_eq!(
+            parse_arg(&mut source, 0).unwrap(),
+            Some(Argument::Parenthesized(Box::new(sum)))
+        );
+    }
+
+    #[test]
+    fn test_parse_infix_is_left_associative() {
+        let mut source = StringTokenSource::new(&"(8 - 2 - 1)");
+        let Some(Argument::Parenthesized(expr)) = parse_arg(&mut source, 0).unwrap() else {
+            panic!("Expected a parenthesized argument");
+        };
+        let ExpressionContent::FunctionCall { name, args, .. } = *expr else {
+            panic!("Expected a call to sub");
+        };
+        assert_eq!(name, "sub");
+        assert!(matches!(&args[0], Argument::Parenthesized(_)));
+    }
+
     #[test]
     fn test_parse_file() {
         let input = "42 ; print \nfoo 42 $x\n#let y 42";
@@ -311,36 +1010,237 @@ mod test {
         assert_eq!(result.instructions.len(), 3);
     }
 
+    #[test]
+    fn test_parse_scope_block() {
+        let input = "#scope\n\n#let x 1\nfoo $x\n#end\nbar";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_file(&mut source).unwrap();
+        assert_eq!(result.instructions.len(), 2);
+        let ExpressionContent::Scope(body) = &result.instructions[0].expressions[0].content else {
+            panic!("Expected a scope block");
+        };
+        assert_eq!(body.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_for_loop() {
+        let input = "#for p in $points\ncircle $p 1\n#end";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_expr(&mut source, 0).unwrap();
+        let Some(ExpressionContent::For { name, items, body }) = result else {
+            panic!("Expected a for loop");
+        };
+        assert_eq!(name, "p");
+        assert_eq!(items, Argument::Variable("points".into()));
+        assert_eq!(body.len(), 1);
+    }
+
+    #[test]
+    fn test_parse_symbol() {
+        let input = "#symbol bolt
+circle (pnt2 0 0) 2
+line (pnt2 0 0) (vec2 2 0)
+#end";
+        let mut source = StringTokenSource::new(&input);
+        let Some(ExpressionContent::Symbol { name, body }) = parse_expr(&mut source, 0).unwrap()
+        else {
+            panic!("Expected a symbol definition");
+        };
+        assert_eq!(name, "bolt");
+        assert_eq!(body.len(), 2);
+
+        let mut source = StringTokenSource::new(
+            &"#symbol 5
+#end",
+        );
+        assert_eq!(
+            parse_expr(&mut source, 0).unwrap_err().kind,
+            ErrorKind::ExpectedIdentifier
+        );
+    }
+
+    #[test]
+    fn test_parse_origin_and_ydir() {
+        let mut source = StringTokenSource::new(
+            &"#origin bottom-left
+#ydir up",
+        );
+        let program = parse_file(&mut source).unwrap();
+        let contents = program
+            .instructions
+            .iter()
+            .map(|instruction| instruction.expressions[0].content.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            contents,
+            [
+                ExpressionContent::Origin { bottom: true },
+                ExpressionContent::YDir { up: true }
+            ]
+        );
+
+        let mut source = StringTokenSource::new(&"#origin top-right");
+        assert_eq!(
+            parse_file(&mut source).unwrap_err().kind,
+            ErrorKind::UnknownCorner("top-right".into())
+        );
+        let mut source = StringTokenSource::new(&"#ydir left");
+        assert_eq!(
+            parse_file(&mut source).unwrap_err().kind,
+            ErrorKind::UnexpectedToken(Payload::Name("left".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_param() {
+        let mut source = StringTokenSource::new(&"#param width 10 (2 * $w) 20");
+        let program = parse_file(&mut source).unwrap();
+        let ExpressionContent::Param {
+            name,
+            min,
+            max,
+            default,
+        } = &program.instructions[0].expressions[0].content
+        else {
+            panic!("Expected a parameter");
+        };
+        assert_eq!(name, "width");
+        assert_eq!(
+            min,
+            &Argument::Literal(Literal::Number(Number::Integer(10)))
+        );
+        assert!(matches!(max, Argument::Parenthesized(_)));
+        assert_eq!(
+            default,
+            &Argument::Literal(Literal::Number(Number::Integer(20)))
+        );
+
+        let mut source = StringTokenSource::new(&"#param width 10 20");
+        assert_eq!(
+            parse_file(&mut source).unwrap_err().kind,
+            ErrorKind::ExpectedExpression
+        );
+    }
+
+    #[test]
+    fn test_unclosed_block_error() {
+        let input = "#scope\n#let x 1\n";
+        let mut source = StringTokenSource::new(&input);
+        let result = parse_file(&mut source);
+        assert_eq!(result.unwrap_err().kind, ErrorKind::UnclosedBlock);
+    }
+
+    #[test]
+    fn test_parse_file_tolerant() {
+        let input = "foo )\nbar 1\n#let\n#let 5 'x\nbaz";
+        let mut source = StringTokenSource::new(&input);
+        let (program, errors) = parse_file_tolerant(&mut source);
+
+        assert_eq!(program.instructions.len(), 2);
+        let kinds = errors.iter().map(Error::kind).collect::<Vec<_>>();
+        assert_eq!(
+            kinds,
+            [
+                &ErrorKind::UnexpectedToken(Payload::ParenR),
+                &ErrorKind::ExpectedIdentifier,
+                &ErrorKind::ExpectedIdentifier,
+            ]
+        );
+        assert!(matches!(
+            &program.instructions[1].expressions[0].content,
+            ExpressionContent::FunctionCall { name, .. } if name == "baz"
+        ));
+    }
+
     #[test]
     fn test_unexpected_token_error() {
         let input = "42 @";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_instruction(&mut source);
+        let result = parse_instruction(&mut source, 0);
         assert!(result.is_err());
         let error = result.unwrap_err();
         assert_eq!(
             error.kind,
             ErrorKind::UnexpectedToken(Payload::Name(SmolStr::new("@")))
         );
+        assert_eq!(error.span(), 3..4);
+    }
+
+    #[test]
+    fn test_expression_spans() {
+        let input = "#let a (1 + 2)  =>\n  line $a;foo\n#scope\n#end";
+        let program = parse_file(&mut StringTokenSource::new(&input)).unwrap();
+        let spans = program
+            .instructions
+            .iter()
+            .flat_map(|instruction| &instruction.expressions)
+            .map(|expression| &input[expression.span.clone()])
+            .collect::<Vec<_>>();
+        assert_eq!(spans, ["#let a (1 + 2)", "line $a", "foo", "#scope\n#end"]);
+
+        let input = "#for x in $xs\n";
+        let error = parse_file(&mut StringTokenSource::new(&input)).unwrap_err();
+        assert_eq!(error.kind, ErrorKind::UnclosedBlock);
+        assert_eq!(&input[error.span()], "in");
     }
 
     #[test]
     fn test_expected_identifier_error() {
         let input = "#let 42";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_expr(&mut source);
+        let result = parse_expr(&mut source, 0);
         assert!(result.is_err());
         let error = result.unwrap_err();
         assert_eq!(error.kind, ErrorKind::ExpectedIdentifier);
     }
 
+    #[test]
+    fn test_nesting_limit() {
+        let parse = |source: String| {
+            parse_file(&mut StringTokenSource::new(&source)).map_err(|error| error.kind)
+        };
+        let parens =
+            |open: &str, depth| format!("#let a {}1{}", open.repeat(depth), ")".repeat(depth));
+        let scopes = |depth| "#scope\n".repeat(depth) + &"#end\n".repeat(depth);
+
+        assert!(parse(parens("(", MAX_NESTING)).is_ok());
+        assert!(parse(scopes(MAX_NESTING)).is_ok());
+        assert_eq!(
+            parse(parens("(", MAX_NESTING + 1)).unwrap_err(),
+            ErrorKind::TooDeeplyNested
+        );
+        assert_eq!(
+            parse(parens("(-", MAX_NESTING / 2 + 1)).unwrap_err(),
+            ErrorKind::TooDeeplyNested
+        );
+        assert_eq!(
+            parse(scopes(MAX_NESTING + 1)).unwrap_err(),
+            ErrorKind::TooDeeplyNested
+        );
+
+        let blank_lines = format!("{}foo", "\n".repeat(100_000));
+        let program = parse_file(&mut StringTokenSource::new(&blank_lines)).unwrap();
+        assert_eq!(program.instructions.len(), 1);
+    }
+
     #[test]
     fn test_unclosed_delimiter_error() {
         let input = "(42";
         let mut source = StringTokenSource::new(&input);
-        let result = parse_arg(&mut source);
+        let result = parse_arg(&mut source, 0);
         assert!(result.is_err());
         let error = result.unwrap_err();
         assert_eq!(error.kind, ErrorKind::UnclosedDelimiter);
     }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_round_trip() {
+        let input = "#let r 2.5cm\npnt2 0 0 => circle ($r * 2)";
+        let program = parse_file(&mut StringTokenSource::new(&input)).unwrap();
+
+        let json = serde_json::to_string(&program).unwrap();
+        let decoded: Program = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded.instructions, program.instructions);
+    }
 }
@@ -0,0 +1,160 @@
+//! Construct programs from Rust code instead of parsing source text.
+
+use smol_str::SmolStr;
+
+use crate::{
+    ast::{Argument, Expression, ExpressionContent, Instruction, Literal, Program},
+    token::{Number, Operator, Position, Span},
+};
+
+#[derive(Debug, Default)]
+pub struct ProgramBuilder {
+    program: Program,
+}
+
+impl ProgramBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn instruction(mut self, instruction: InstructionBuilder) -> Self {
+        self.program.instructions.push(instruction.build());
+        self
+    }
+
+    pub fn build(self) -> Program {
+        self.program
+    }
+}
+
+/// A single line of a program: expressions that share one stack.
+#[derive(Debug, Default)]
+pub struct InstructionBuilder {
+    instruction: Instruction,
+}
+
+impl InstructionBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append an expression whose result is drawn, like `expr;`.
+    pub fn draw(self, content: ExpressionContent) -> Self {
+        self.push(content, true)
+    }
+
+    /// Append an expression whose result is only passed on, like `expr =>`.
+    pub fn pipe(self, content: ExpressionContent) -> Self {
+        self.push(content, false)
+    }
+
+    fn push(mut self, content: ExpressionContent, draw_result: bool) -> Self {
+        self.instruction.expressions.push(Expression {
+            content,
+            draw_result,
+            position: Position::default(),
+            end: Position::default(),
+            span: Span::default(),
+        });
+        self
+    }
+
+    pub fn build(self) -> Instruction {
+        self.instruction
+    }
+}
+
+/// Call the function `name`.
+pub fn call(
+    name: impl Into<SmolStr>,
+    args: impl IntoIterator<Item = Argument>,
+) -> ExpressionContent {
+    ExpressionContent::FunctionCall {
+        name: name.into(),
+        args: args.into_iter().collect(),
+        options: vec![],
+    }
+}
+
+/// Call the function `name` with `key=value` options.
+pub fn call_with(
+    name: impl Into<SmolStr>,
+    args: impl IntoIterator<Item = Argument>,
+    options: impl IntoIterator<Item = (SmolStr, Argument)>,
+) -> ExpressionContent {
+    ExpressionContent::FunctionCall {
+        name: name.into(),
+        args: args.into_iter().collect(),
+        options: options.into_iter().collect(),
+    }
+}
+
+/// Bind the value of `init`, or the last result if there is none, to `name`.
+pub fn bind(name: impl Into<SmolStr>, init: Option<Argument>) -> ExpressionContent {
+    ExpressionContent::Let {
+        name: name.into(),
+        init,
+    }
+}
+
+/// Read the variable `name`.
+pub fn var(name: impl Into<SmolStr>) -> Argument {
+    Argument::Variable(name.into())
+}
+
+impl From<u64> for Argument {
+    fn from(value: u64) -> Self {
+        Argument::Literal(Literal::Number(Number::Integer(value)))
+    }
+}
+
+/// Number literals can't be negative, so this builds `0 - x` the way
+/// the parser does for `-x`.
+impl From<i64> for Argument {
+    fn from(value: i64) -> Self {
+        if value < 0 {
+            Argument::Parenthesized(Box::new(call(
+                Operator::Sub.function(),
+                [0u64.into(), value.unsigned_abs().into()],
+            )))
+        } else {
+            (value as u64).into()
+        }
+    }
+}
+
+impl From<f64> for Argument {
+    fn from(value: f64) -> Self {
+        Argument::Literal(Literal::Number(Number::Float(value)))
+    }
+}
+
+impl From<&str> for Argument {
+    fn from(value: &str) -> Self {
+        Argument::Literal(Literal::String(value.into()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{runtime::Runtime, util::test_helpers::*};
+
+    #[test]
+    fn test_build_and_run() {
+        let program = ProgramBuilder::new()
+            .instruction(InstructionBuilder::new().pipe(bind("r", Some(2u64.into()))))
+            .instruction(
+                InstructionBuilder::new()
+                    .pipe(call("pnt2", [1u64.into(), 2.5.into()]))
+                    .draw(call("circle", [var("r")])),
+            )
+            .instruction(InstructionBuilder::new().pipe(call("print", [(-3i64).into()])))
+            .build();
+
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.execute(program).unwrap();
+        assert_eq!(runtime.output(), "-3\n");
+        assert_eq!(runtime.finish().commands.len(), 1);
+    }
+}
@@ -0,0 +1,855 @@
+//! Find type mismatches and stack underflows without running a program.
+//!
+//! Every builtin has a fixed stack effect, so the checker can follow the
+//! stack of each instruction the same way the runtime does, only with
+//! kinds of values instead of values. Anything it can't know, such as
+//! variables set by the host or functions without a declared [`Effect`],
+//! becomes [`Kind::Any`] and is never reported.
+
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+use thiserror::Error;
+
+use crate::{
+    ast::{Argument, ExpressionContent, Instruction, Literal, Program},
+    plugin::Functions,
+    token::{Position, Span, Unit},
+};
+
+/// What the checker knows about a value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Kind {
+    Scalar,
+    Bool,
+    String,
+    Point,
+    Vector,
+    Line,
+    Circle,
+    Path,
+    Angle,
+    Transform,
+    List,
+    Group,
+    /// Could be anything.
+    Any,
+}
+
+impl Kind {
+    fn accepts(self, found: Kind) -> bool {
+        self == Kind::Any || found == Kind::Any || self == found
+    }
+}
+
+impl std::fmt::Display for Kind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Kind::Any => write!(f, "any value"),
+            other => write!(f, "{other:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+#[error("Error checking file at {at}: {kind}")]
+pub struct Error {
+    at: Position,
+    span: Span,
+    kind: ErrorKind,
+}
+
+impl Error {
+    pub fn position(&self) -> Position {
+        self.at
+    }
+
+    /// The source text of the expression the error is about.
+    pub fn span(&self) -> Span {
+        self.span.clone()
+    }
+
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+}
+
+#[derive(Debug, Error, PartialEq)]
+pub enum ErrorKind {
+    #[error("{function} expects {}, found {}", alternatives(.expected), sequence(.found))]
+    Mismatch {
+        function: SmolStr,
+        /// Every accepted list of arguments, in stack order.
+        expected: Vec<Vec<Kind>>,
+        found: Vec<Kind>,
+    },
+    #[error("{function} needs {needed} values but the stack only holds {found}")]
+    Underflow {
+        function: SmolStr,
+        needed: usize,
+        found: usize,
+    },
+}
+
+impl ErrorKind {
+    /// See [`crate::token::ErrorKind::code`]. Checker errors are numbered
+    /// from E0201.
+    pub fn code(&self) -> &'static str {
+        match self {
+            ErrorKind::Mismatch { .. } => "E0201",
+            ErrorKind::Underflow { .. } => "E0202",
+        }
+    }
+}
+
+fn sequence(kinds: &[Kind]) -> String {
+    let kinds = kinds.iter().map(Kind::to_string).collect::<Vec<_>>();
+    format!("({})", kinds.join(" "))
+}
+
+fn alternatives(expected: &[Vec<Kind>]) -> String {
+    let expected = expected
+        .iter()
+        .map(|kinds| sequence(kinds))
+        .collect::<Vec<_>>();
+    expected.join(" or ")
+}
+
+/// How a builtin changes the stack, declared along with it with
+/// [`Functions::define_fn_with_effect`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Effect {
+    /// Pop one of the input lists, top of the stack last, and push the
+    /// matching outputs. The last output is the result of the builtin.
+    Overloads(&'static [(&'static [Kind], &'static [Kind])]),
+    /// Pop `n` values and push them back in the given order, by index.
+    Shuffle(usize, &'static [usize]),
+    /// Pop the arguments of the call and push a single value.
+    TakeAll(Kind),
+    /// Pop a value of the kind and push any number of values.
+    Unpack(Kind),
+}
+
+impl Effect {
+    /// How many values the stack has to hold at least.
+    pub fn needed(&self) -> usize {
+        match self {
+            Effect::Overloads(overloads) => overloads
+                .iter()
+                .map(|(inputs, _)| inputs.len())
+                .min()
+                .unwrap_or_default(),
+            Effect::Shuffle(n, _) => *n,
+            Effect::TakeAll(_) => 0,
+            Effect::Unpack(_) => 1,
+        }
+    }
+
+    /// A signature for the documentation of `name`, e.g.
+    /// `circle Point Scalar -> Circle`. Overloads are separated by `|`.
+    pub fn signature(&self, name: &str) -> String {
+        let words = |kinds: &[Kind]| kinds.iter().map(|kind| format!(" {kind:?}")).collect();
+        let (inputs, outputs): (Vec<String>, Vec<String>) = match self {
+            Effect::Overloads(overloads) => overloads
+                .iter()
+                .map(|(inputs, outputs)| (words(inputs), words(outputs)))
+                .unzip(),
+            Effect::Shuffle(n, order) => {
+                let letter = |i: usize| format!(" {}", char::from(b'a' + i as u8));
+                let inputs = (0..*n).map(letter).collect();
+                let outputs = order.iter().map(|&i| letter(i)).collect();
+                (vec![inputs], vec![outputs])
+            }
+            Effect::TakeAll(kind) => (vec![" ...".into()], vec![format!(" {kind:?}")]),
+            Effect::Unpack(kind) => (vec![format!(" {kind:?}")], vec![" ...".into()]),
+        };
+        let overloads = inputs
+            .iter()
+            .zip(&outputs)
+            .map(|(inputs, outputs)| format!("{name}{inputs} ->{outputs}"))
+            .collect::<Vec<_>>();
+        overloads.join(" | ")
+    }
+}
+
+/// The effect of the builtin `function` of the standard library.
+pub(crate) fn builtin_effect(function: &str) -> Option<Effect> {
+    use Effect::*;
+    use Kind::*;
+
+    let effect = match function {
+        "add" => Overloads(&[
+            (&[Scalar, Scalar], &[Scalar]),
+            (&[Angle, Angle], &[Angle]),
+            (&[Vector, Vector], &[Vector]),
+            (&[Vector, Point], &[Point]),
+            (&[Point, Vector], &[Point]),
+        ]),
+        "sub" => Overloads(&[
+            (&[Scalar, Scalar], &[Scalar]),
+            (&[Angle, Angle], &[Angle]),
+            (&[Vector, Vector], &[Vector]),
+            (&[Point, Point], &[Vector]),
+            (&[Point, Vector], &[Point]),
+        ]),
+        "mul" => Overloads(&[
+            (&[Scalar, Scalar], &[Scalar]),
+            (&[Angle, Scalar], &[Angle]),
+            (&[Scalar, Angle], &[Angle]),
+            (&[Vector, Scalar], &[Vector]),
+            (&[Scalar, Vector], &[Vector]),
+        ]),
+        "div" => Overloads(&[
+            (&[Scalar, Scalar], &[Scalar]),
+            (&[Vector, Scalar], &[Vector]),
+        ]),
+        "dup" => Shuffle(1, &[0, 0]),
+        "swap" => Shuffle(2, &[1, 0]),
+        "drop" => Shuffle(1, &[]),
+        "over" => Shuffle(2, &[0, 1, 0]),
+        "rot" => Shuffle(3, &[1, 2, 0]),
+        "dbg" => Shuffle(1, &[0]),
+        "print" => Overloads(&[(&[Any], &[])]),
+        "deg" | "rad" => Overloads(&[(&[Scalar], &[Angle])]),
+        "sin" | "cos" | "tan" => Overloads(&[(&[Angle], &[Scalar])]),
+        "sqrt" | "to_float" => Overloads(&[(&[Scalar], &[Scalar])]),
+        "to_int" => Overloads(&[(&[Scalar], &[Scalar]), (&[Bool], &[Scalar])]),
+        "lt" | "le" | "gt" | "ge" => {
+            Overloads(&[(&[Scalar, Scalar], &[Bool]), (&[Angle, Angle], &[Bool])])
+        }
+        "eq" | "ne" => Overloads(&[
+            (&[Scalar, Scalar], &[Bool]),
+            (&[Angle, Angle], &[Bool]),
+            (&[String, String], &[Bool]),
+            (&[Bool, Bool], &[Bool]),
+        ]),
+        "and" | "or" => Overloads(&[(&[Bool, Bool], &[Bool])]),
+        "not" => Overloads(&[(&[Bool], &[Bool])]),
+        "to_bool" => Overloads(&[(&[Any], &[Bool])]),
+        "min" | "max" => Overloads(&[(&[Scalar, Scalar], &[Scalar])]),
+        "clamp" => Overloads(&[(&[Scalar, Scalar, Scalar], &[Scalar])]),
+        "dot" => Overloads(&[(&[Vector, Vector], &[Scalar])]),
+        "vec2" => Overloads(&[(&[Scalar, Scalar], &[Vector])]),
+        "line" => Overloads(&[(&[Point, Vector], &[Line]), (&[Point, Point], &[Line])]),
+        "pnt2" => Overloads(&[(&[Scalar, Scalar], &[Point])]),
+        "lvec" => Overloads(&[(&[Point], &[Vector])]),
+        "x" | "y" => Overloads(&[(&[Point], &[Scalar]), (&[Vector], &[Scalar])]),
+        "path_len" => Overloads(&[(&[Path], &[Scalar]), (&[Line], &[Scalar])]),
+        "along" => Overloads(&[(&[Path, Scalar], &[Point]), (&[Line, Scalar], &[Point])]),
+        "snap" => Overloads(&[(&[Point, Scalar], &[Point]), (&[Vector, Scalar], &[Vector])]),
+        "snap_mode" => Overloads(&[(&[Scalar], &[])]),
+        "xy" => Overloads(&[
+            (&[Point], &[Scalar, Scalar]),
+            (&[Vector], &[Scalar, Scalar]),
+        ]),
+        "jump" => Overloads(&[(&[Point, Scalar, Scalar], &[Point])]),
+        "dist" => Overloads(&[
+            (&[Point, Point], &[Scalar]),
+            (&[Point, Line], &[Scalar]),
+            (&[Line, Point], &[Scalar]),
+        ]),
+        "foot" => Overloads(&[(&[Point, Line], &[Point])]),
+        "circle" => Overloads(&[(&[Point, Scalar], &[Circle])]),
+        "angle_at" => Overloads(&[(&[Point, Point, Point], &[Angle])]),
+        "circle_thru" => Overloads(&[(&[Point, Point], &[Circle])]),
+        "circumcircle" | "incircle" => Overloads(&[(&[Point, Point, Point], &[Circle])]),
+        "isect" => Overloads(&[(&[Line, Line], &[Point])]),
+        // A tangent only leaves one point, but two is the common case
+        "isect_cl" => Overloads(&[
+            (&[Circle, Line], &[Point, Point]),
+            (&[Line, Circle], &[Point, Point]),
+        ]),
+        "isect_cc" => Overloads(&[(&[Circle, Circle], &[Point, Point])]),
+        "fillet" => Overloads(&[(&[Line, Line, Scalar], &[Line, Path, Line])]),
+        "chamfer" => Overloads(&[(&[Line, Line, Scalar], &[Line, Line, Line])]),
+        "trim" | "extend_to" => Overloads(&[(&[Line, Line], &[Line]), (&[Line, Circle], &[Line])]),
+        "mirror" => Overloads(&[
+            (&[Point, Line], &[Point]),
+            (&[Vector, Line], &[Vector]),
+            (&[Line, Line], &[Line]),
+            (&[Circle, Line], &[Circle]),
+            (&[List, Line], &[List]),
+            (&[Group, Line], &[Group]),
+        ]),
+        "push_tf" | "pop_tf" | "endlayer" | "endclip" | "endframe" => Overloads(&[(&[], &[])]),
+        "translate" => Overloads(&[(&[Vector], &[]), (&[Scalar, Scalar], &[])]),
+        "rotate" => Overloads(&[(&[Angle], &[])]),
+        "scale" => Overloads(&[(&[Scalar], &[]), (&[Vector], &[])]),
+        "tf_translate" => {
+            Overloads(&[(&[Vector], &[Transform]), (&[Scalar, Scalar], &[Transform])])
+        }
+        "tf_rotate" => Overloads(&[(&[Angle], &[Transform])]),
+        "tf_scale" => Overloads(&[(&[Scalar], &[Transform]), (&[Vector], &[Transform])]),
+        "compose" => Overloads(&[(&[Transform, Transform], &[Transform])]),
+        "apply" => Overloads(&[
+            (&[Transform, Point], &[Point]),
+            (&[Transform, Vector], &[Vector]),
+            (&[Transform, Line], &[Line]),
+            (&[Transform, Circle], &[Circle]),
+            (&[Transform, Path], &[Path]),
+            (&[Transform, List], &[List]),
+            (&[Transform, Group], &[Group]),
+        ]),
+        "transform" => Overloads(&[(&[Transform], &[])]),
+        "frame_at" => Overloads(&[(&[Point, Vector], &[Transform])]),
+        "in_frame" => Overloads(&[(&[Transform], &[])]),
+        "layer" => Overloads(&[(&[String], &[])]),
+        "place" => Overloads(&[(&[String, Point], &[])]),
+        "list" => TakeAll(List),
+        "group" => TakeAll(Group),
+        "get" => Overloads(&[(&[List, Scalar], &[Any])]),
+        "push" => Overloads(&[(&[List, Any], &[List])]),
+        "len_list" => Overloads(&[(&[List], &[Scalar])]),
+        "unpack" => Unpack(List),
+        "seed" => Overloads(&[(&[Scalar], &[])]),
+        "rand" => Overloads(&[(&[], &[Scalar])]),
+        "rand_range" => Overloads(&[(&[Scalar, Scalar], &[Scalar])]),
+        "grid" => Overloads(&[(&[Scalar], &[])]),
+        "axes" | "help" => Overloads(&[(&[], &[])]),
+        "ticks" => Overloads(&[(&[Scalar, Scalar], &[])]),
+        "dim" => Overloads(&[(&[Point, Point, Scalar], &[])]),
+        "mark_angle" => Overloads(&[(&[Point, Point, Point], &[])]),
+        "plot_fn" => Overloads(&[(&[String, Scalar, Scalar, Scalar], &[Path])]),
+        "plot" => Overloads(&[(&[List], &[Path])]),
+        "scatter" => Overloads(&[(&[List, Scalar], &[List])]),
+        "frame" => Overloads(&[(&[String, String], &[])]),
+        "image" => Overloads(&[(&[Point, Scalar, Scalar, String], &[])]),
+        "linestyle" => Overloads(&[(&[String], &[])]),
+        "markers" => Overloads(&[(&[String, String], &[])]),
+        "arrow" => Overloads(&[(&[Point, Point], &[])]),
+        "hatch" => Overloads(&[
+            (&[Circle, Angle, Scalar], &[]),
+            (&[List, Angle, Scalar], &[]),
+            (&[Path, Angle, Scalar], &[]),
+        ]),
+        "ngon" => Overloads(&[(&[Point, Scalar, Scalar], &[Path])]),
+        "star" => Overloads(&[(&[Point, Scalar, Scalar, Scalar], &[Path])]),
+        "rect" => Overloads(&[(&[Point, Scalar, Scalar], &[Path])]),
+        "rrect" => Overloads(&[(&[Point, Scalar, Scalar, Scalar], &[Path])]),
+        "clip" => Overloads(&[(&[Circle], &[]), (&[Path], &[]), (&[List], &[])]),
+        "hull" => Overloads(&[(&[List], &[Path]), (&[Path], &[Path])]),
+        "bbox" => Overloads(&[(&[Any], &[Path])]),
+        "offset" | "offset_round" => Overloads(&[
+            (&[Line, Scalar], &[Line]),
+            (&[Circle, Scalar], &[Circle]),
+            (&[Path, Scalar], &[Path]),
+        ]),
+        _ => return None,
+    };
+    Some(effect)
+}
+
+/// The stack of the instruction being checked.
+#[derive(Default)]
+struct Stack {
+    kinds: Vec<Kind>,
+    /// Values of unknown kind and number may lie below `kinds`.
+    open: bool,
+}
+
+impl Stack {
+    /// Forget everything, e.g. after a call with an unknown effect.
+    fn forget(&mut self) {
+        self.kinds.clear();
+        self.open = true;
+    }
+
+    /// The top `n` kinds, padded with `Any` if the stack is open.
+    fn top(&self, n: usize) -> Option<Vec<Kind>> {
+        if self.kinds.len() >= n {
+            Some(self.kinds[self.kinds.len() - n..].to_vec())
+        } else if self.open {
+            let mut top = vec![Kind::Any; n - self.kinds.len()];
+            top.extend_from_slice(&self.kinds);
+            Some(top)
+        } else {
+            None
+        }
+    }
+
+    fn pop(&mut self, n: usize) {
+        let len = self.kinds.len().saturating_sub(n);
+        self.kinds.truncate(len);
+    }
+}
+
+struct Checker<'f> {
+    /// The builtins to check calls against, or `None` for the standard
+    /// library.
+    functions: Option<&'f Functions>,
+    /// Kinds of variables, innermost scope last.
+    scopes: Vec<HashMap<SmolStr, Kind>>,
+    errors: Vec<Error>,
+    /// The source text of the expression being checked.
+    span: Span,
+}
+
+/// Check every instruction of `program`, assuming the default
+/// [`StackPolicy::Clear`](crate::StackPolicy::Clear).
+pub fn check_program(program: &Program) -> Vec<Error> {
+    check(program, None)
+}
+
+/// Like [`check_program`], but check calls against the effects declared
+/// with `functions`, e.g. those of a runtime with host functions, see
+/// [`crate::Runtime::check`].
+pub fn check_with(program: &Program, functions: &Functions) -> Vec<Error> {
+    check(program, Some(functions))
+}
+
+fn check(program: &Program, functions: Option<&Functions>) -> Vec<Error> {
+    let mut checker = Checker {
+        functions,
+        scopes: vec![HashMap::new()],
+        errors: vec![],
+        span: Span::default(),
+    };
+    for instruction in &program.instructions {
+        checker.instruction(instruction);
+    }
+    checker.errors
+}
+
+impl Checker<'_> {
+    fn instruction(&mut self, instruction: &Instruction) {
+        let mut stack = Stack::default();
+        for expression in &instruction.expressions {
+            let at = expression.position;
+            self.span = expression.span.clone();
+            if let Some(kind) = self.content(&expression.content, false, &mut stack, at) {
+                stack.kinds.push(kind);
+            }
+        }
+    }
+
+    fn block(&mut self, body: &[Instruction], binding: Option<SmolStr>) {
+        let mut scope = HashMap::new();
+        if let Some(name) = binding {
+            scope.insert(name, Kind::Any);
+        }
+        self.scopes.push(scope);
+        for instruction in body {
+            self.instruction(instruction);
+        }
+        self.scopes.pop();
+    }
+
+    fn lookup(&self, name: &str) -> Kind {
+        self.scopes
+            .iter()
+            .rev()
+            .find_map(|scope| scope.get(name).copied())
+            .unwrap_or(Kind::Any)
+    }
+
+    fn error(&mut self, at: Position, kind: ErrorKind) {
+        self.errors.push(Error {
+            at,
+            span: self.span.clone(),
+            kind,
+        });
+    }
+
+    /// Follow one expression, `nested` in the arguments of another or not,
+    /// returning the kind of its result if it has one.
+    fn content(
+        &mut self,
+        content: &ExpressionContent,
+        nested: bool,
+        stack: &mut Stack,
+        at: Position,
+    ) -> Option<Kind> {
+        match content {
+            ExpressionContent::Literal(literal) => Some(literal_kind(literal)),
+            ExpressionContent::Variable(name) => Some(self.lookup(name)),
+            ExpressionContent::FunctionCall {
+                name,
+                args,
+                options,
+            } => {
+                // See the compiler for where builtins like `list` collect from
+                let base = (nested || !args.is_empty()).then_some(stack.kinds.len());
+                for arg in args {
+                    if let Some(kind) = self.argument(arg, stack, at) {
+                        stack.kinds.push(kind);
+                    }
+                }
+                // Builtins check the kinds of their options themselves
+                for (_, value) in options {
+                    self.argument(value, stack, at);
+                }
+                self.call(name, base, stack, at)
+            }
+            ExpressionContent::Let { name, init } | ExpressionContent::Const { name, init } => {
+                let kind = match init {
+                    Some(init) => self.argument(init, stack, at).unwrap_or(Kind::Any),
+                    None => match stack.top(1) {
+                        Some(top) => {
+                            stack.pop(1);
+                            top[0]
+                        }
+                        None => {
+                            let keyword = match content {
+                                ExpressionContent::Let { .. } => "#let",
+                                _ => "#const",
+                            };
+                            self.underflow(keyword, 1, stack, at);
+                            Kind::Any
+                        }
+                    },
+                };
+                self.scopes
+                    .last_mut()
+                    .expect("The global scope always exists")
+                    .insert(name.clone(), kind);
+                Some(kind)
+            }
+            ExpressionContent::Param {
+                name,
+                min,
+                max,
+                default,
+            } => {
+                self.expect_all("#param", Kind::Scalar, &[min, max, default], stack, at);
+                self.scopes
+                    .last_mut()
+                    .expect("The global scope always exists")
+                    .insert(name.clone(), Kind::Scalar);
+                Some(Kind::Scalar)
+            }
+            ExpressionContent::Screen(x, y) => {
+                self.expect_all("#screen", Kind::Scalar, &[x, y], stack, at);
+                None
+            }
+            ExpressionContent::View {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => {
+                self.expect_all("#view", Kind::Scalar, &[x, y, width, height], stack, at);
+                None
+            }
+            ExpressionContent::Page
+            | ExpressionContent::Origin { .. }
+            | ExpressionContent::YDir { .. } => None,
+            ExpressionContent::Title(text) => {
+                self.expect_all("#title", Kind::String, &[text], stack, at);
+                None
+            }
+            ExpressionContent::Desc(text) => {
+                self.expect_all("#desc", Kind::String, &[text], stack, at);
+                None
+            }
+            ExpressionContent::Meta { value, .. } => {
+                self.expect_all("#meta", Kind::String, &[value], stack, at);
+                None
+            }
+            ExpressionContent::Scope(body) => {
+                self.block(body, None);
+                None
+            }
+            ExpressionContent::For { name, items, body } => {
+                let kind = self.argument(items, stack, at).unwrap_or(Kind::Any);
+                if !Kind::List.accepts(kind) {
+                    self.mismatch("#for", vec![vec![Kind::List]], vec![kind], at);
+                }
+                self.block(body, Some(name.clone()));
+                None
+            }
+            ExpressionContent::Symbol { body, .. } => {
+                self.block(body, None);
+                None
+            }
+        }
+    }
+
+    fn argument(&mut self, arg: &Argument, stack: &mut Stack, at: Position) -> Option<Kind> {
+        match arg {
+            Argument::Variable(name) => Some(self.lookup(name)),
+            Argument::Literal(literal) => Some(literal_kind(literal)),
+            Argument::Parenthesized(content) => self.content(content, true, stack, at),
+        }
+    }
+
+    fn expect_all(
+        &mut self,
+        keyword: &str,
+        expected: Kind,
+        args: &[&Argument],
+        stack: &mut Stack,
+        at: Position,
+    ) {
+        let found = args
+            .iter()
+            .map(|arg| self.argument(arg, stack, at).unwrap_or(Kind::Any))
+            .collect::<Vec<_>>();
+        if !found.iter().all(|kind| expected.accepts(*kind)) {
+            self.mismatch(keyword, vec![vec![expected; args.len()]], found, at);
+        }
+    }
+
+    /// Follow a call of `name` whose arguments start at `base`, or take up
+    /// the whole stack.
+    fn call(
+        &mut self,
+        name: &SmolStr,
+        base: Option<usize>,
+        stack: &mut Stack,
+        at: Position,
+    ) -> Option<Kind> {
+        let effect = match self.functions {
+            Some(functions) => functions.effect(name),
+            None => builtin_effect(name),
+        };
+        let Some(effect) = effect else {
+            // Host functions can do anything to the stack
+            stack.forget();
+            return None;
+        };
+
+        let outputs = match effect {
+            Effect::TakeAll(kind) => {
+                match base {
+                    Some(base) => stack.kinds.truncate(base),
+                    None => {
+                        stack.kinds.clear();
+                        stack.open = false;
+                    }
+                }
+                vec![kind]
+            }
+            Effect::Shuffle(n, order) => {
+                let Some(top) = stack.top(n) else {
+                    self.underflow(name, n, stack, at);
+                    return None;
+                };
+                stack.pop(n);
+                order.iter().map(|&i| top[i]).collect()
+            }
+            Effect::Unpack(kind) => {
+                match stack.top(1) {
+                    None => self.underflow(name, 1, stack, at),
+                    Some(top) if !kind.accepts(top[0]) => {
+                        self.mismatch(name, vec![vec![kind]], top, at);
+                    }
+                    Some(_) => {}
+                }
+                // Nothing is known about how many values come out
+                stack.forget();
+                return None;
+            }
+            Effect::Overloads(overloads) => {
+                let fits = |(inputs, _): &&(&[Kind], &[Kind])| {
+                    stack.top(inputs.len()).is_some_and(|top| {
+                        inputs
+                            .iter()
+                            .zip(&top)
+                            .all(|(input, found)| input.accepts(*found))
+                    })
+                };
+                let matching = overloads.iter().filter(fits).collect::<Vec<_>>();
+
+                let Some(&&(inputs, outputs)) = matching.first() else {
+                    let needed = overloads.iter().map(|(inputs, _)| inputs.len()).min();
+                    let needed = needed.unwrap_or_default();
+                    match stack.top(needed) {
+                        None => self.underflow(name, needed, stack, at),
+                        Some(_) => {
+                            let arity = overloads.iter().map(|(inputs, _)| inputs.len()).max();
+                            let found = stack
+                                .top(arity.unwrap_or_default())
+                                .unwrap_or_else(|| stack.kinds.clone());
+                            let expected = overloads
+                                .iter()
+                                .map(|(inputs, _)| inputs.to_vec())
+                                .collect();
+                            self.mismatch(name, expected, found, at);
+                        }
+                    }
+                    stack.forget();
+                    return None;
+                };
+
+                stack.pop(inputs.len());
+                if matching.iter().all(|(_, other)| *other == outputs) {
+                    outputs.to_vec()
+                } else {
+                    // Several overloads fit values of unknown kind
+                    vec![Kind::Any; outputs.len()]
+                }
+            }
+        };
+
+        // The last output is the function's result, the rest it pushes itself
+        let (result, pushed) = outputs.split_last()?;
+        stack.kinds.extend_from_slice(pushed);
+        Some(*result)
+    }
+
+    fn underflow(&mut self, function: &str, needed: usize, stack: &mut Stack, at: Position) {
+        let found = stack.kinds.len();
+        self.error(
+            at,
+            ErrorKind::Underflow {
+                function: function.into(),
+                needed,
+                found,
+            },
+        );
+        stack.forget();
+    }
+
+    fn mismatch(
+        &mut self,
+        function: &str,
+        expected: Vec<Vec<Kind>>,
+        found: Vec<Kind>,
+        at: Position,
+    ) {
+        self.error(
+            at,
+            ErrorKind::Mismatch {
+                function: function.into(),
+                expected,
+                found,
+            },
+        );
+    }
+}
+
+fn literal_kind(literal: &Literal) -> Kind {
+    match literal {
+        Literal::Number(_) => Kind::Scalar,
+        Literal::Quantity(_, Unit::Degree | Unit::Radian) => Kind::Angle,
+        Literal::Quantity(..) => Kind::Scalar,
+        Literal::String(_) => Kind::String,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ast::parse_file, runtime::Runtime, token::StringTokenizer, util::test_helpers::Recorder,
+    };
+
+    fn check(source: &str) -> Vec<ErrorKind> {
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        check_program(&program)
+            .into_iter()
+            .map(|error| error.kind)
+            .collect()
+    }
+
+    #[test]
+    fn test_every_builtin_has_an_effect() {
+        let runtime = Runtime::<Recorder>::default();
+        for name in runtime.function_names() {
+            assert!(builtin_effect(name).is_some(), "{name} has no stack effect");
+            assert_eq!(runtime.function_effect(name), builtin_effect(name));
+        }
+    }
+
+    #[test]
+    fn test_valid_scripts() {
+        let source = "
+#let c (circle (pnt2 1 2) 3)
+pnt2 0 0 => pnt2 1 1 => line; $c
+#for p in (list (pnt2 0 0) (pnt2 1 1))
+    circle $p (x (vec2 1 2))
+#end
+isect_cc $c (circle (pnt2 2 2) 3) => line
+translate 1 2; rotate 90deg; $unknown => dup => add
+host_fn => circle
+";
+        assert_eq!(check(source), []);
+    }
+
+    #[test]
+    fn test_mismatch() {
+        assert_eq!(
+            check("circle 3 (pnt2 1 2)"),
+            [ErrorKind::Mismatch {
+                function: "circle".into(),
+                expected: vec![vec![Kind::Point, Kind::Scalar]],
+                found: vec![Kind::Scalar, Kind::Point],
+            }]
+        );
+        assert_eq!(check("#let s \"a\"\nsqrt $s\n#for x in 3\n#end").len(), 2);
+        assert_eq!(
+            check("#view 0 0 \"wide\" 10").pop().unwrap().to_string(),
+            "#view expects (Scalar Scalar Scalar Scalar), found (Scalar Scalar String Scalar)"
+        );
+    }
+
+    #[test]
+    fn test_underflow() {
+        assert_eq!(
+            check("pnt2 1 => circle"),
+            [ErrorKind::Underflow {
+                function: "pnt2".into(),
+                needed: 2,
+                found: 1,
+            }]
+        );
+        assert_eq!(check("xy (pnt2 1 2) => pnt2 => circle 1").len(), 0);
+        assert_eq!(check("unpack (pnt2 1 2)").len(), 1);
+        assert_eq!(check("unpack (list 1 2) => add").len(), 0);
+        // Stacks don't carry over to the next instruction
+        assert_eq!(check("pnt2 1 2\nswap").len(), 1);
+        assert_eq!(check("1; 2; list => len_list => #let n").len(), 0);
+        assert_eq!(check("circle (pnt2 0 0) (get (list 1 2) 0)").len(), 0);
+    }
+
+    #[test]
+    fn test_host_effects() {
+        const ROW: Effect = Effect::Overloads(&[(&[Kind::Scalar], &[Kind::List])]);
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn_with_effect("row", ROW, |_| Ok(crate::Value::Void));
+        let program = parse_file(&mut StringTokenizer::new(
+            &"row (pnt2 1 2)
+row 1 => plot",
+        ))
+        .unwrap();
+
+        let errors = runtime.check(&program);
+        assert_eq!(errors.len(), 1);
+        assert_eq!(
+            errors[0].to_string().split(": ").last(),
+            Some("row expects (Scalar), found (Point)")
+        );
+        assert_eq!(check_program(&program), []);
+    }
+
+    #[test]
+    fn test_effect_signatures() {
+        assert_eq!(
+            builtin_effect("x").unwrap().signature("x"),
+            "x Point -> Scalar | x Vector -> Scalar"
+        );
+        assert_eq!(
+            builtin_effect("rot").unwrap().signature("rot"),
+            "rot a b c -> b c a"
+        );
+        assert_eq!(
+            builtin_effect("list").unwrap().signature("list"),
+            "list ... -> List"
+        );
+        assert_eq!(builtin_effect("translate").unwrap().needed(), 1);
+        assert_eq!(builtin_effect("list").unwrap().needed(), 0);
+        assert_eq!(
+            builtin_effect("unpack").unwrap().signature("unpack"),
+            "unpack List -> ..."
+        );
+    }
+
+    #[test]
+    fn test_error_spans() {
+        let source = "#let r 1\npnt2 1 => circle $r; 2";
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let errors = check_program(&program);
+        assert_eq!(&source[errors[0].span()], "pnt2 1");
+    }
+}
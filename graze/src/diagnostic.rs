@@ -0,0 +1,355 @@
+//! Errors shown the way script authors read them: with the line they are
+//! about, the offending part underlined and a hint on how to fix it.
+//!
+//! ```text
+//! Expected closing delimiter [E0103]
+//!  --> drawing.grz:3:20
+//!   |
+//! 3 | pnt2 0 0 => circle (mul $r 2
+//!   |                    ^
+//!   = hint: add the missing )
+//! ```
+
+use std::fmt::Write;
+
+use crate::{
+    ast::{Error as ParseError, ErrorKind as ParseErrorKind},
+    check::{Error as CheckError, ErrorKind as CheckErrorKind},
+    runtime::{Error, Warning},
+    stdlib::builtin_doc,
+    token::{ErrorKind as TokenErrorKind, Keyword, Position, Span},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    /// The script can't be run.
+    Error,
+    /// The script runs, but probably not as intended.
+    Warning,
+}
+
+impl Severity {
+    pub fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// A problem with a script.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    /// Identifies the kind of problem, e.g. `E0305`. Codes never change
+    /// meaning, so tools can match on them.
+    pub code: &'static str,
+    pub message: String,
+    /// The bytes of the source the problem is about, if it is about a
+    /// single place.
+    pub span: Option<Span>,
+    pub hint: Option<String>,
+}
+
+impl Diagnostic {
+    /// An error about `span`.
+    pub fn new(code: &'static str, message: impl Into<String>, span: Span) -> Self {
+        Self {
+            severity: Severity::Error,
+            code,
+            message: message.into(),
+            span: Some(span),
+            hint: None,
+        }
+    }
+
+    pub fn with_hint(mut self, hint: impl Into<String>) -> Self {
+        self.hint = Some(hint.into());
+        self
+    }
+
+    /// An error that stopped a script while it ran the expression at
+    /// `span`, see [`crate::Runtime::expression_span`].
+    pub fn runtime(error: &Error, span: Span) -> Self {
+        let hint = match error {
+            Error::FunctionNotFound {
+                suggestion: None, ..
+            } => Some("functions come from the standard library or modules".into()),
+            Error::VariableNotFound {
+                name,
+                suggestion: None,
+            } => Some(format!("bind it first, e.g. #let {name} 1")),
+            Error::ConstantReassigned(_) => Some("use #let for values that change".into()),
+            Error::StackUnderflow | Error::MissingArgument | Error::NotEnoughArguments { .. } => {
+                Some("pass more arguments, or pipe values in with =>".into())
+            }
+            Error::UnknownVisibleArea => Some("start the script with e.g. #screen 100 100".into()),
+            Error::TransformStackUnderflow => Some("every pop_tf needs an earlier push_tf".into()),
+            Error::LayerUnderflow => Some("every endlayer needs an earlier layer".into()),
+            Error::ClipUnderflow => Some("every endclip needs an earlier clip".into()),
+            Error::FrameUnderflow => Some("every endframe needs an earlier in_frame".into()),
+            Error::UnknownSymbol(name) => Some(format!("define it first with #symbol {name}")),
+            Error::ParamOutOfRange(name) => Some(format!(
+                "keep {name} between the minimum and maximum of its #param"
+            )),
+            Error::InexactInteger => Some("round with to_int, or use to_float first".into()),
+            _ => None,
+        };
+        Self {
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.to_string(),
+            span: Some(span),
+            hint,
+        }
+    }
+
+    /// A warning about `source`, which the warning's position refers to.
+    pub fn warning(warning: &Warning, source: &str) -> Self {
+        let span = warning.position().map(|at| {
+            let start = offset(source, at);
+            start..start
+        });
+        let hint = match warning {
+            Warning::UnusedValues { .. } => Some("pipe them on with =>, or remove them".into()),
+            Warning::UnusedVariable(_) => Some("remove it, or use it with $".into()),
+            _ => None,
+        };
+        Self {
+            severity: Severity::Warning,
+            code: warning.code(),
+            message: warning.to_string(),
+            span,
+            hint,
+        }
+    }
+
+    /// The one-based line and column in `source` the problem is about.
+    pub fn location(&self, source: &str) -> Option<(usize, usize)> {
+        let start = self.span.as_ref()?.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line = source[..start].matches('\n').count() + 1;
+        Some((line, source[line_start..start].chars().count() + 1))
+    }
+
+    /// The message, followed by the line of `source` the problem is about
+    /// with the span underlined and the hint, if any. `name` is what to
+    /// call the source, usually its file name.
+    pub fn render(&self, name: &str, source: &str) -> String {
+        let mut rendered = format!("{} [{}]\n", self.message, self.code);
+        let (Some(span), Some((number, column))) = (&self.span, self.location(source)) else {
+            let _ = write!(rendered, " --> {name}");
+            if let Some(hint) = &self.hint {
+                let _ = write!(rendered, "\n  = hint: {hint}");
+            }
+            return rendered;
+        };
+
+        let start = span.start.min(source.len());
+        let line_start = source[..start].rfind('\n').map_or(0, |newline| newline + 1);
+        let line_end = source[start..]
+            .find(['\r', '\n'])
+            .map_or(source.len(), |newline| start + newline);
+        let line = &source[line_start..line_end];
+
+        // Spans running over several lines are underlined up to the end of
+        // the first one. Tabs are kept so the carets line up.
+        let padding: String = source[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+        let width = source[start..span.end.clamp(start, line_end)]
+            .chars()
+            .count()
+            .max(1);
+
+        let number = number.to_string();
+        let gutter = " ".repeat(number.len());
+        let _ = writeln!(rendered, "{gutter}--> {name}:{number}:{column}");
+        let _ = writeln!(rendered, "{gutter} |");
+        let _ = writeln!(rendered, "{number} | {line}");
+        let _ = write!(rendered, "{gutter} | {padding}{}", "^".repeat(width));
+        if let Some(hint) = &self.hint {
+            let _ = write!(rendered, "\n{gutter} = hint: {hint}");
+        }
+        rendered
+    }
+}
+
+/// The byte offset of `at` in `source`, moved past blanks to the start of
+/// what is there, since positions often point at the end of the previous
+/// token.
+fn offset(source: &str, at: Position) -> usize {
+    let line_start = match at.line() {
+        0 => 0,
+        line => source
+            .match_indices('\n')
+            .nth(line - 1)
+            .map_or(source.len(), |(newline, _)| newline + 1),
+    };
+    let line = &source[line_start..];
+    let column = line
+        .char_indices()
+        .nth(at.column())
+        .map_or(line.len(), |(offset, _)| offset);
+    let blanks = line[column..]
+        .find(|c: char| c == '\n' || !c.is_whitespace())
+        .unwrap_or(0);
+    line_start + column + blanks
+}
+
+impl From<&ParseError> for Diagnostic {
+    fn from(error: &ParseError) -> Self {
+        let hint = match error.kind() {
+            ParseErrorKind::InvalidToken(kind) => match kind {
+                TokenErrorKind::InvalidPipe => Some("write => to pass a value on".into()),
+                TokenErrorKind::ExpectedNewlineAfterBang => Some("a ! can only end a line".into()),
+                TokenErrorKind::InvalidKeyword => {
+                    let names = Keyword::ALL
+                        .iter()
+                        .filter(|(_, keyword)| !keyword.is_reserved())
+                        .map(|(name, _)| format!("#{name}"))
+                        .collect::<Vec<_>>();
+                    Some(format!("the keywords are {}", names.join(", ")))
+                }
+                TokenErrorKind::UnterminatedString => {
+                    Some("close the string with \" on the same line".into())
+                }
+                TokenErrorKind::InvalidEscape => {
+                    Some("only \\n, \\\" and \\\\ may follow a backslash".into())
+                }
+                _ => None,
+            },
+            ParseErrorKind::UnclosedDelimiter => Some("add the missing )".into()),
+            ParseErrorKind::ExpectedIdentifier => Some("names can't start with a digit".into()),
+            ParseErrorKind::UnclosedBlock => {
+                Some("close the block with #end on a line of its own".into())
+            }
+            ParseErrorKind::TooDeeplyNested => {
+                Some("bind parts of the expression with #let first".into())
+            }
+            _ => None,
+        };
+        Self {
+            severity: Severity::Error,
+            code: error.kind().code(),
+            message: error.kind().to_string(),
+            span: Some(error.span()),
+            hint,
+        }
+    }
+}
+
+impl From<&CheckError> for Diagnostic {
+    fn from(error: &CheckError) -> Self {
+        let (CheckErrorKind::Mismatch { function, .. }
+        | CheckErrorKind::Underflow { function, .. }) = error.kind();
+        Self {
+            severity: Severity::Error,
+            code: error.kind().code(),
+            message: error.kind().to_string(),
+            span: Some(error.span()),
+            hint: builtin_doc(function).map(|doc| format!("usage: {}", doc.signature)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ast::parse_file, check::check_program, testing::Recorder, token::StringTokenizer, Runtime,
+    };
+
+    #[test]
+    fn test_render() {
+        let source = "#screen 10 10\n\tpnt2 0 0 => circel 1\n";
+        let span = source.find("circel").unwrap()..source.len() - 1;
+        let diagnostic = Diagnostic::runtime(
+            &Error::FunctionNotFound {
+                name: "circel".into(),
+                suggestion: Some("circle".into()),
+            },
+            span,
+        );
+        assert_eq!(
+            diagnostic.render("drawing.grz", source),
+            "Function circel not in scope; did you mean circle? [E0305]
+ --> drawing.grz:2:14
+  |
+2 | \tpnt2 0 0 => circel 1
+  | \t            ^^^^^^^^"
+        );
+
+        // The end of the input, and spans over several lines
+        let diagnostic = Diagnostic::new("E0103", "Expected closing delimiter", 3..3);
+        assert!(diagnostic.render("-", "(1 ").ends_with("1 | (1 \n  |    ^"));
+        let diagnostic = Diagnostic::new("E0105", "Block", 0..12).with_hint("close it");
+        assert!(diagnostic
+            .render("-", "#scope\n#end")
+            .ends_with("1 | #scope\n  | ^^^^^^\n  = hint: close it"));
+    }
+
+    #[test]
+    fn test_errors() {
+        let source = "#let x (1 + 2\n";
+        let error = parse_file(&mut StringTokenizer::new(&source)).unwrap_err();
+        let diagnostic = Diagnostic::from(&error);
+        assert_eq!(&source[diagnostic.span.unwrap()], "(");
+        assert_eq!(diagnostic.hint.as_deref(), Some("add the missing )"));
+        assert_eq!(diagnostic.code, "E0103");
+
+        let source = "circle 3 (pnt2 1 2)";
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let diagnostic = Diagnostic::from(&check_program(&program)[0]);
+        assert_eq!(diagnostic.span, Some(0..source.len()));
+        assert!(diagnostic.hint.unwrap().starts_with("usage: circle"));
+
+        let source = "#let a 1\n#let b 2\nvec2 $a $size";
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        let error = runtime.execute(program).unwrap_err();
+        let diagnostic = Diagnostic::runtime(&error, runtime.expression_span());
+        assert_eq!(&source[diagnostic.span.unwrap()], "vec2 $a $size");
+        assert_eq!(
+            diagnostic.hint.as_deref(),
+            Some("bind it first, e.g. #let size 1")
+        );
+    }
+
+    #[test]
+    fn test_warnings() {
+        let source =
+            "#let unused 1\n#screen 10 10\n  circle (pnt2 1 1) 1; 3 => circle (pnt2 20 20) 1";
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.execute(program).unwrap();
+        let diagnostics = runtime
+            .warnings()
+            .iter()
+            .map(|warning| Diagnostic::warning(warning, source))
+            .collect::<Vec<_>>();
+        let found = diagnostics
+            .iter()
+            .map(|diagnostic| (diagnostic.code, diagnostic.location(source)))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            found,
+            [
+                ("W0004", Some((3, 29))),
+                ("W0002", Some((3, 3))),
+                ("W0003", None)
+            ]
+        );
+        assert_eq!(diagnostics[0].severity, Severity::Warning);
+        assert!(diagnostics[2]
+            .render("a.grz", source)
+            .ends_with("[W0003]\n --> a.grz\n  = hint: remove it, or use it with $"));
+    }
+}
@@ -0,0 +1,419 @@
+//! Print programs back to source text in one canonical style.
+
+use std::{fmt::Write, iter::Peekable, slice};
+
+use crate::{
+    ast::{Argument, ExpressionContent, Instruction, Literal, Program},
+    token::{Keyword, Number, Operator, Position, Trivia},
+};
+
+const INDENT: &str = "    ";
+
+/// Pretty-print `program`: one instruction per line, `; ` and ` => `
+/// between expressions, no padding inside parentheses and block bodies
+/// indented by four spaces. Comments stay where they were and runs of
+/// blank lines shrink to one.
+pub fn format_program(program: &Program) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        trivia: program.trivia.iter().peekable(),
+        depth: 0,
+        blank: false,
+        block_start: true,
+    };
+    for instruction in &program.instructions {
+        formatter.instruction(instruction);
+    }
+    formatter.flush(usize::MAX);
+    formatter.out
+}
+
+/// Print a single expression in the same style. Blocks span several lines.
+pub fn format_expression(content: &ExpressionContent) -> String {
+    let mut formatter = Formatter {
+        out: String::new(),
+        trivia: [].iter().peekable(),
+        depth: 0,
+        blank: false,
+        block_start: false,
+    };
+    formatter.content(content, 0, 0);
+    formatter.out
+}
+
+struct Formatter<'p> {
+    out: String,
+    trivia: Peekable<slice::Iter<'p, Trivia>>,
+    depth: usize,
+    /// A blank line was skipped and is kept before the next line.
+    blank: bool,
+    /// Nothing was written since the start of the file or current block.
+    block_start: bool,
+}
+
+/// The line the last character before `end` is on.
+fn last_line(end: Position) -> usize {
+    if end.is_line_start() {
+        end.line().saturating_sub(1)
+    } else {
+        end.line()
+    }
+}
+
+impl Formatter<'_> {
+    fn start_line(&mut self) {
+        if self.blank && !self.block_start {
+            self.out.push('\n');
+        }
+        self.blank = false;
+        self.block_start = false;
+        for _ in 0..self.depth {
+            self.out.push_str(INDENT);
+        }
+    }
+
+    /// Write the comments and blank lines above `line`.
+    fn flush(&mut self, line: usize) {
+        while let Some(trivia) = self.trivia.next_if(|trivia| trivia.line() < line) {
+            match trivia {
+                Trivia::Comment { text, .. } => {
+                    self.start_line();
+                    writeln!(self.out, "//{text}").expect("Writing to a String never fails");
+                }
+                Trivia::BlankLine(_) => self.blank = true,
+            }
+        }
+    }
+
+    /// Append the comment that followed code on `line`, if any.
+    fn trailing(&mut self, line: usize) {
+        let comment = self.trivia.next_if(|trivia| {
+            matches!(trivia, Trivia::Comment { line: at, trailing: true, .. } if *at == line)
+        });
+        if let Some(Trivia::Comment { text, .. }) = comment {
+            write!(self.out, " //{text}").expect("Writing to a String never fails");
+        }
+    }
+
+    fn instruction(&mut self, instruction: &Instruction) {
+        let (Some(first), Some(last)) = (
+            instruction.expressions.first(),
+            instruction.expressions.last(),
+        ) else {
+            return;
+        };
+        self.flush(first.position.line());
+        self.start_line();
+
+        let mut expressions = instruction.expressions.iter().peekable();
+        while let Some(expression) = expressions.next() {
+            let start = expression.position.line();
+            self.content(&expression.content, start, last_line(expression.end));
+            let join = match (expression.draw_result, expressions.peek().is_some()) {
+                (true, true) => "; ",
+                (true, false) => "",
+                (false, true) => " => ",
+                (false, false) => "!",
+            };
+            self.out.push_str(join);
+        }
+
+        self.trailing(last_line(last.end));
+        self.out.push('\n');
+    }
+
+    /// `start` and `end` are the lines the enclosing expression spans,
+    /// used to place comments around block bodies.
+    fn content(&mut self, content: &ExpressionContent, start: usize, end: usize) {
+        match content {
+            ExpressionContent::Literal(literal) => self.literal(literal),
+            ExpressionContent::Variable(name) => {
+                write!(self.out, "${name}").expect("Writing to a String never fails")
+            }
+            ExpressionContent::FunctionCall {
+                name,
+                args,
+                options,
+            } => {
+                self.out.push_str(name);
+                for arg in args {
+                    self.out.push(' ');
+                    self.argument(arg, start, end);
+                }
+                for (option, value) in options {
+                    write!(self.out, " {option}=").expect("Writing to a String never fails");
+                    self.argument(value, start, end);
+                }
+            }
+            ExpressionContent::Let { name, init } | ExpressionContent::Const { name, init } => {
+                let keyword = match content {
+                    ExpressionContent::Let { .. } => Keyword::Let,
+                    _ => Keyword::Const,
+                };
+                write!(self.out, "{keyword} {name}").expect("Writing to a String never fails");
+                if let Some(init) = init {
+                    self.out.push(' ');
+                    self.argument(init, start, end);
+                }
+            }
+            ExpressionContent::Param {
+                name,
+                min,
+                max,
+                default,
+            } => {
+                write!(self.out, "{} {name}", Keyword::Param)
+                    .expect("Writing to a String never fails");
+                for arg in [min, max, default] {
+                    self.out.push(' ');
+                    self.argument(arg, start, end);
+                }
+            }
+            ExpressionContent::Screen(x, y) => {
+                write!(self.out, "{}", Keyword::Screen).expect("Writing to a String never fails");
+                for arg in [x, y] {
+                    self.out.push(' ');
+                    self.argument(arg, start, end);
+                }
+            }
+            ExpressionContent::View {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => {
+                write!(self.out, "{}", Keyword::View).expect("Writing to a String never fails");
+                for arg in [x, y, width, height] {
+                    self.out.push(' ');
+                    self.argument(arg, start, end);
+                }
+                if *flip_y {
+                    self.out.push_str(" flip");
+                }
+            }
+            ExpressionContent::Page => {
+                write!(self.out, "{}", Keyword::Page).expect("Writing to a String never fails")
+            }
+            ExpressionContent::Origin { bottom } => {
+                let corner = if *bottom { "bottom-left" } else { "top-left" };
+                write!(self.out, "{} {corner}", Keyword::Origin)
+                    .expect("Writing to a String never fails");
+            }
+            ExpressionContent::YDir { up } => {
+                let direction = if *up { "up" } else { "down" };
+                write!(self.out, "{} {direction}", Keyword::YDir)
+                    .expect("Writing to a String never fails");
+            }
+            ExpressionContent::Title(text) | ExpressionContent::Desc(text) => {
+                let keyword = match content {
+                    ExpressionContent::Title(_) => Keyword::Title,
+                    _ => Keyword::Desc,
+                };
+                write!(self.out, "{keyword} ").expect("Writing to a String never fails");
+                self.argument(text, start, end);
+            }
+            ExpressionContent::Meta { name, value } => {
+                write!(self.out, "{} {name} ", Keyword::Meta)
+                    .expect("Writing to a String never fails");
+                self.argument(value, start, end);
+            }
+            ExpressionContent::Scope(body) => {
+                write!(self.out, "{}", Keyword::Scope).expect("Writing to a String never fails");
+                self.block(body, start, end);
+            }
+            ExpressionContent::For { name, items, body } => {
+                write!(self.out, "{} {name} in ", Keyword::For)
+                    .expect("Writing to a String never fails");
+                self.argument(items, start, end);
+                self.block(body, start, end);
+            }
+            ExpressionContent::Symbol { name, body } => {
+                write!(self.out, "{} {name}", Keyword::Symbol)
+                    .expect("Writing to a String never fails");
+                self.block(body, start, end);
+            }
+        }
+    }
+
+    fn block(&mut self, body: &[Instruction], start: usize, end: usize) {
+        self.trailing(start);
+        self.out.push('\n');
+
+        self.depth += 1;
+        self.block_start = true;
+        for instruction in body {
+            self.instruction(instruction);
+        }
+        self.flush(end);
+        self.blank = false;
+        self.depth -= 1;
+
+        self.start_line();
+        write!(self.out, "{}", Keyword::End).expect("Writing to a String never fails");
+    }
+
+    fn argument(&mut self, arg: &Argument, start: usize, end: usize) {
+        match arg {
+            Argument::Variable(name) => {
+                write!(self.out, "${name}").expect("Writing to a String never fails")
+            }
+            Argument::Literal(literal) => self.literal(literal),
+            Argument::Parenthesized(inner) => {
+                self.out.push('(');
+                match infix(inner) {
+                    // `-x` is parsed as `0 - x`
+                    Some((
+                        Operator::Sub,
+                        Argument::Literal(Literal::Number(Number::Integer(0))),
+                        rhs,
+                    )) => {
+                        self.out.push('-');
+                        self.argument(rhs, start, end);
+                    }
+                    Some((operator, lhs, rhs)) => {
+                        self.argument(lhs, start, end);
+                        write!(self.out, " {} ", operator.symbol())
+                            .expect("Writing to a String never fails");
+                        self.argument(rhs, start, end);
+                    }
+                    None => self.content(inner, start, end),
+                }
+                self.out.push(')');
+            }
+        }
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        match literal {
+            Literal::Number(number) => self.number(*number),
+            Literal::Quantity(number, unit) => {
+                self.number(*number);
+                self.out.push_str(unit.suffix());
+            }
+            Literal::String(string) => {
+                self.out.push('"');
+                for c in string.chars() {
+                    match c {
+                        '"' => self.out.push_str("\\\""),
+                        '\\' => self.out.push_str("\\\\"),
+                        '\n' => self.out.push_str("\\n"),
+                        c => self.out.push(c),
+                    }
+                }
+                self.out.push('"');
+            }
+        }
+    }
+
+    fn number(&mut self, number: Number) {
+        match number {
+            Number::Integer(value) => {
+                write!(self.out, "{value}").expect("Writing to a String never fails")
+            }
+            // Keep the decimal point so the literal stays a float
+            Number::Float(value) if value.fract() == 0.0 => {
+                write!(self.out, "{value}.0").expect("Writing to a String never fails")
+            }
+            Number::Float(value) => {
+                write!(self.out, "{value}").expect("Writing to a String never fails")
+            }
+        }
+    }
+}
+
+/// Split a call to an arithmetic builtin back into the infix
+/// expression it was parsed from.
+fn infix(content: &ExpressionContent) -> Option<(Operator, &Argument, &Argument)> {
+    let ExpressionContent::FunctionCall {
+        name,
+        args,
+        options,
+    } = content
+    else {
+        return None;
+    };
+    let operator = Operator::ALL
+        .into_iter()
+        .find(|operator| operator.function() == name)?;
+    match args.as_slice() {
+        [lhs, rhs] if options.is_empty() => Some((operator, lhs, rhs)),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ast::parse_file, token::StringTokenizer};
+
+    fn format(source: &str) -> String {
+        format_program(&parse_file(&mut StringTokenizer::new(&source)).unwrap())
+    }
+
+    #[test]
+    fn test_spacing() {
+        assert_eq!(
+            format("pnt2 1   2=>circle 3 ;line (pnt2 0 0)(vec2 1.0 2.5)\n"),
+            "pnt2 1 2 => circle 3; line (pnt2 0 0) (vec2 1.0 2.5)\n"
+        );
+        assert_eq!(
+            format("#let  r (2+3 * $x)\n#const d (-$r)\n#view 0 0 10mm 2in flip\n"),
+            "#let r (2 + (3 * $x))\n#const d (-$r)\n#view 0 0 10mm 2in flip\n"
+        );
+        assert_eq!(
+            format("#title  \"Plan\"\n#desc $about\n#meta  date \"2024\""),
+            "#title \"Plan\"\n#desc $about\n#meta date \"2024\"\n"
+        );
+        assert_eq!(format("#param  w 1 10 (2+2)"), "#param w 1 10 (2 + 2)\n");
+        assert_eq!(
+            format("pnt2 0 0 =>circle 1 !\n1;2!\n"),
+            "pnt2 0 0 => circle 1!\n1; 2!\n"
+        );
+        assert_eq!(
+            format("print \"say \\\"hi\\\"\""),
+            "print \"say \\\"hi\\\"\"\n"
+        );
+        assert_eq!(
+            format("arrow $a  end=dot $b start=(0+1)"),
+            "arrow $a $b end=\"dot\" start=(0 + 1)\n"
+        );
+    }
+
+    #[test]
+    fn test_blocks_and_comments() {
+        let source = "
+// Outline
+#let points (list (pnt2 0 0) (pnt2 1 1))
+
+
+#for p in $points // every corner
+  circle $p 1
+      // spare
+#end
+#scope
+    dbg
+#end   // done
+#symbol dot
+ circle (pnt2 0 0) 1
+#end
+";
+        let expected = "\
+// Outline
+#let points (list (pnt2 0 0) (pnt2 1 1))
+
+#for p in $points // every corner
+    circle $p 1
+    // spare
+#end
+#scope
+    dbg
+#end // done
+#symbol dot
+    circle (pnt2 0 0) 1
+#end
+";
+        assert_eq!(format(source), expected);
+        assert_eq!(format(expected), expected);
+    }
+}
@@ -1,11 +1,36 @@
 mod ast;
+pub mod builder;
+pub mod check;
+pub mod diagnostic;
+pub mod format;
+mod native;
 mod output;
+mod plugin;
 mod runtime;
 mod stdlib;
+pub mod testing;
 mod token;
 mod util;
 
-pub use ast::{parse_file, Program};
-pub use output::{DrawBuffer, DrawCommand, Mm};
-pub use runtime::{Error, Runtime};
-pub use token::TokenSource;
+pub use ast::{
+    parse_file, parse_file_tolerant, Argument, Error as ParseError, Expression, ExpressionContent,
+    Instruction, Literal, Program,
+};
+pub use native::{Native, NativeFn, NativeResult};
+pub use output::{
+    Clipped, DrawBuffer, DrawCommand, EpsOutput, GcodeOutput, HpglOutput, LineStyle, Marker, Mm,
+    TikzOutput, METADATA_NAMES,
+};
+pub use plugin::{FunctionDoc, Functions, StdlibModule};
+pub use runtime::{
+    Cache, Context, Error, Extension, Limit, Observer, Param, Runtime, RuntimeConfig, Snapshot,
+    Stack, StackPolicy, Value, ValueKind, Warning,
+};
+pub use stdlib::{
+    builtin_doc, Angle, BuiltinDoc, NumericPolicy, Path, Point, Random, Scalar, Transform, Vector,
+    DOCS as BUILTIN_DOCS,
+};
+pub use token::{
+    tokenize_with_spans, Keyword, Number, Operator, Payload, Position, Span, StringTokenizer,
+    Token, TokenClass, TokenSource, Trivia, Unit,
+};
@@ -0,0 +1,220 @@
+//! Builtins written as plain Rust functions of typed arguments, e.g.
+//! `|center: Point, radius: Scalar| Ok(Value::Circle(center, radius))`.
+//! Popping the arguments, checking their types and declaring the stack
+//! effect is done once here instead of in every builtin.
+
+use std::sync::Arc;
+
+use smol_str::SmolStr;
+
+use crate::{
+    check::{Effect, Kind},
+    reverse_pop,
+    runtime::{Error, Stack, Value},
+    stdlib::{Angle, Group, Path, Point, Scalar, Transform, Vector},
+};
+
+/// A type builtins can take from the stack and give back to scripts.
+pub trait Native: Sized {
+    /// What the checker knows about values of this type.
+    const KIND: Kind;
+
+    /// Take the value apart, failing with [`Error::TypeError`] if it is of
+    /// another type.
+    fn from_value(value: Value) -> Result<Self, Error>;
+
+    fn into_value(self) -> Value;
+}
+
+/// What a builtin can return: a [`Native`] type, a tuple of them for
+/// several values or `()` for builtins that only draw or change state.
+pub trait NativeResult {
+    /// The kinds pushed, empty for `()`.
+    const OUTPUTS: &'static [Kind];
+
+    /// Push all values but the last and return that, see
+    /// [`Stack::push_results`].
+    fn into_result(self, stack: &mut Stack) -> Value;
+}
+
+impl<T: Native> NativeResult for T {
+    const OUTPUTS: &'static [Kind] = &[T::KIND];
+
+    fn into_result(self, _: &mut Stack) -> Value {
+        self.into_value()
+    }
+}
+
+impl NativeResult for () {
+    const OUTPUTS: &'static [Kind] = &[];
+
+    fn into_result(self, _: &mut Stack) -> Value {
+        Value::Void
+    }
+}
+
+macro_rules! native_tuple {
+    ($($type:ident $value:ident),+) => {
+        impl<$($type: Native),+> NativeResult for ($($type,)+) {
+            const OUTPUTS: &'static [Kind] = &[$($type::KIND),+];
+
+            fn into_result(self, stack: &mut Stack) -> Value {
+                let ($($value,)+) = self;
+                stack.push_results([$($value.into_value()),+])
+            }
+        }
+    };
+}
+
+native_tuple!(A a, B b);
+native_tuple!(A a, B b, C c);
+native_tuple!(A a, B b, C c, D d);
+
+macro_rules! native {
+    ($type:ty, $kind:ident, $value:pat => $inner:expr, $outer:ident => $wrapped:expr) => {
+        impl Native for $type {
+            const KIND: Kind = Kind::$kind;
+
+            fn from_value(value: Value) -> Result<Self, Error> {
+                match value {
+                    $value => Ok($inner),
+                    _ => Err(Error::TypeError),
+                }
+            }
+
+            fn into_value(self) -> Value {
+                let $outer = self;
+                $wrapped
+            }
+        }
+
+        impl TryFrom<Value> for $type {
+            type Error = Error;
+
+            fn try_from(value: Value) -> Result<Self, Error> {
+                Native::from_value(value)
+            }
+        }
+
+        impl From<$type> for Value {
+            fn from(value: $type) -> Value {
+                Native::into_value(value)
+            }
+        }
+    };
+}
+
+native!(Scalar, Scalar, Value::Scalar(scalar) => scalar, scalar => Value::Scalar(scalar));
+native!(bool, Bool, Value::Bool(bool) => bool, bool => Value::Bool(bool));
+native!(f64, Scalar, Value::Scalar(scalar) => scalar.into(), float => Value::Scalar(float.into()));
+native!(SmolStr, String, Value::String(string) => string, string => Value::String(string));
+native!(Point, Point, Value::Point(point) => point, point => Value::Point(point));
+native!(Vector, Vector, Value::Vector(vector) => vector, vector => Value::Vector(vector));
+native!(Angle, Angle, Value::Angle(angle) => angle, angle => Value::Angle(angle));
+native!(Transform, Transform, Value::Transform(transform) => transform, transform => Value::Transform(transform));
+native!(Arc<Path>, Path, Value::Path(path) => path, path => Value::Path(path));
+native!(Arc<Vec<Value>>, List, Value::List(items) => items, items => Value::List(items));
+native!(Arc<Group>, Group, Value::Group(group) => group, group => Value::Group(group));
+
+/// Any value, unchecked.
+impl Native for Value {
+    const KIND: Kind = Kind::Any;
+
+    fn from_value(value: Value) -> Result<Self, Error> {
+        Ok(value)
+    }
+
+    fn into_value(self) -> Value {
+        self
+    }
+}
+
+/// A Rust function or closure that can be defined as a builtin with
+/// [`crate::Functions::define_native`]. `Args` are the types of its
+/// arguments, top of the stack last.
+pub trait NativeFn<Args>: 'static {
+    /// The effect to declare, derived from the argument and result types.
+    const EFFECT: Effect;
+
+    /// Pop the arguments off `stack` and call the function with them.
+    fn run(&mut self, stack: &mut Stack) -> Result<Value, Error>;
+}
+
+impl<F, R> NativeFn<()> for F
+where
+    F: FnMut() -> Result<R, Error> + 'static,
+    R: NativeResult,
+{
+    const EFFECT: Effect = Effect::Overloads(&[(&[], R::OUTPUTS)]);
+
+    fn run(&mut self, stack: &mut Stack) -> Result<Value, Error> {
+        self().map(|result| result.into_result(stack))
+    }
+}
+
+macro_rules! native_fn {
+    ($($arg:ident $value:ident),+) => {
+        impl<F, R, $($arg),+> NativeFn<($($arg,)+)> for F
+        where
+            F: FnMut($($arg),+) -> Result<R, Error> + 'static,
+            R: NativeResult,
+            $($arg: Native),+
+        {
+            const EFFECT: Effect = Effect::Overloads(&[(&[$($arg::KIND),+], R::OUTPUTS)]);
+
+            fn run(&mut self, stack: &mut Stack) -> Result<Value, Error> {
+                reverse_pop!(stack => $($value),+);
+                self($($arg::from_value($value)?),+).map(|result| result.into_result(stack))
+            }
+        }
+    };
+}
+
+native_fn!(A a);
+native_fn!(A a, B b);
+native_fn!(A a, B b, C c);
+native_fn!(A a, B b, C c, D d);
+native_fn!(A a, B b, C c, D d, E e);
+native_fn!(A a, B b, C c, D d, E e, G g);
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_native_fn() {
+        let mut circle = |center: Point, radius: Scalar| -> Result<Value, Error> {
+            Ok(Value::Circle(center, radius))
+        };
+        let mut stack = dummy_stack([scalar(5), point(1, 2), scalar(3)]);
+        assert_values_eq(circle.run(&mut stack), circle_value(1, 2, 3));
+        assert_eq!(stack.values(), [scalar(5)]);
+
+        let mut stack = dummy_stack([scalar(1), scalar(2)]);
+        assert_eq!(circle.run(&mut stack), Err(Error::TypeError));
+        assert_eq!(circle.run(&mut stack), Err(Error::MissingArgument));
+    }
+
+    #[test]
+    fn test_native_effects() {
+        fn effect<Args, F: NativeFn<Args>>(_: &F) -> Effect {
+            F::EFFECT
+        }
+
+        let translate = |_: Vector| Ok(());
+        assert_eq!(
+            effect(&translate),
+            Effect::Overloads(&[(&[Kind::Vector], &[])])
+        );
+        let get = |_: Arc<Vec<Value>>, _: Scalar| Ok(Value::Void);
+        assert_eq!(effect(&get).signature("get"), "get List Scalar -> Any");
+        let halves = |x: Scalar| Ok((x / 2.into(), x / 2.into()));
+        assert_eq!(
+            effect(&halves).signature("halves"),
+            "halves Scalar -> Scalar Scalar"
+        );
+        let name = || Ok(SmolStr::new("abc"));
+        assert_eq!(effect(&name).signature("name"), "name -> String");
+    }
+}
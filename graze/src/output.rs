@@ -1,9 +1,202 @@
-use crate::{runtime::Value, stdlib::Scalar};
+use smol_str::SmolStr;
 
+mod clip;
+mod eps;
+mod gcode;
+mod hpgl;
+mod sheet;
+mod tikz;
+
+pub use clip::Clipped;
+pub use eps::EpsOutput;
+pub use gcode::GcodeOutput;
+pub use hpgl::HpglOutput;
+pub use tikz::TikzOutput;
+
+use crate::{
+    runtime::Value,
+    stdlib::{Scalar, Transform},
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum DrawCommand {
-    Line { from: (Mm, Mm), to: (Mm, Mm) },
-    Circle { at: (Mm, Mm), radius: Mm },
-    Resize { x: Mm, y: Mm },
+    Line {
+        from: (Mm, Mm),
+        to: (Mm, Mm),
+    },
+    Circle {
+        at: (Mm, Mm),
+        radius: Mm,
+    },
+    Resize {
+        x: Mm,
+        y: Mm,
+    },
+    /// Map the given window of drawing coordinates onto the canvas.
+    /// With `flip_y`, y grows upwards like in math-style coordinates.
+    SetView {
+        x: Mm,
+        y: Mm,
+        width: Mm,
+        height: Mm,
+        flip_y: bool,
+    },
+    /// Open a named group; everything up to the matching
+    /// [`DrawCommand::EndLayer`] belongs to it. Layers may nest.
+    BeginLayer {
+        name: SmolStr,
+    },
+    EndLayer,
+    /// A line of text centered on `at`, `size` tall.
+    Text {
+        at: (Mm, Mm),
+        text: SmolStr,
+        size: Mm,
+    },
+    /// The image file at `path`, stretched over the rectangle from the
+    /// corner `at` to `at + (width, height)`.
+    Image {
+        at: (Mm, Mm),
+        width: Mm,
+        height: Mm,
+        path: SmolStr,
+    },
+    /// A polyline through `points`, back to the first one if `closed`.
+    Path {
+        points: Vec<(Mm, Mm)>,
+        closed: bool,
+    },
+    /// Only draw inside the polygon `region` until the matching
+    /// [`DrawCommand::EndClip`]. Clips may nest, and backends without
+    /// native clipping can use [`Clipped`].
+    BeginClip {
+        region: Vec<(Mm, Mm)>,
+    },
+    EndClip,
+    /// Define the symbol `name` as everything up to the matching
+    /// [`DrawCommand::EndSymbol`], in coordinates of its own. Nothing is
+    /// drawn until the symbol is placed. Backends without native symbols
+    /// can remember the commands and replay them for each
+    /// [`DrawCommand::PlaceSymbol`].
+    BeginSymbol {
+        name: SmolStr,
+    },
+    EndSymbol,
+    /// Draw the symbol `name`, mapped through `transform`.
+    PlaceSymbol {
+        name: SmolStr,
+        transform: Transform,
+    },
+    /// Dash every line, circle and path drawn afterwards.
+    SetLineStyle {
+        style: LineStyle,
+    },
+    /// Decorate the ends of every line drawn afterwards.
+    SetMarkers {
+        start: Option<Marker>,
+        end: Option<Marker>,
+    },
+    /// Describe the whole document, see [`METADATA_NAMES`]. Setting a name
+    /// again replaces its value.
+    Metadata {
+        name: SmolStr,
+        value: SmolStr,
+    },
+}
+
+/// What `#meta` can describe, the elements of Dublin Core.
+pub const METADATA_NAMES: &[&str] = &[
+    "contributor",
+    "coverage",
+    "creator",
+    "date",
+    "description",
+    "format",
+    "identifier",
+    "language",
+    "publisher",
+    "relation",
+    "rights",
+    "source",
+    "subject",
+    "title",
+    "type",
+];
+
+/// A line type from ISO 128, set with the `linestyle` builtin.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LineStyle {
+    #[default]
+    Continuous,
+    /// Dashes, for hidden edges.
+    Hidden,
+    /// Long dashes and dots, for center lines and axes of symmetry.
+    Center,
+    /// Long dashes and pairs of dots, for adjacent parts and extreme
+    /// positions of moving parts.
+    Phantom,
+}
+
+impl LineStyle {
+    pub const ALL: [LineStyle; 4] = [
+        LineStyle::Continuous,
+        LineStyle::Hidden,
+        LineStyle::Center,
+        LineStyle::Phantom,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            LineStyle::Continuous => "continuous",
+            LineStyle::Hidden => "hidden",
+            LineStyle::Center => "center",
+            LineStyle::Phantom => "phantom",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|style| style.name() == name)
+    }
+
+    /// Alternating dash and gap lengths in millimeters, for lines
+    /// 0.25mm wide. Empty for continuous lines.
+    pub fn dashes(self) -> &'static [f64] {
+        match self {
+            LineStyle::Continuous => &[],
+            LineStyle::Hidden => &[3.0, 0.75],
+            LineStyle::Center => &[6.0, 0.75, 0.125, 0.75],
+            LineStyle::Phantom => &[6.0, 0.75, 0.125, 0.75, 0.125, 0.75],
+        }
+    }
+}
+
+/// A decoration at one end of a line.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Marker {
+    /// An arrowhead pointing away from the line.
+    Arrow,
+    Dot,
+    /// A short bar across the line.
+    Bar,
+}
+
+impl Marker {
+    pub const ALL: [Marker; 3] = [Marker::Arrow, Marker::Dot, Marker::Bar];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Marker::Arrow => "arrow",
+            Marker::Dot => "dot",
+            Marker::Bar => "bar",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.into_iter().find(|marker| marker.name() == name)
+    }
 }
 
 impl From<Value> for Option<DrawCommand> {
@@ -14,20 +207,61 @@ impl From<Value> for Option<DrawCommand> {
                 let to = ((p.x + v.x).into(), (p.y + v.y).into());
                 Some(DrawCommand::Line { from, to })
             }
+            Value::Circle(c, r) => Some(DrawCommand::Circle {
+                at: (c.x.into(), c.y.into()),
+                radius: r.into(),
+            }),
+            Value::Path(path) => Some(DrawCommand::Path {
+                points: path
+                    .points
+                    .iter()
+                    .map(|point| (point.x.into(), point.y.into()))
+                    .collect(),
+                closed: path.closed,
+            }),
 
             _ => None,
         }
     }
 }
 
+/// `value` with at most `decimals` decimals, without trailing zeros and
+/// never as `-0`.
+fn number(value: f64, decimals: usize) -> String {
+    let text = format!("{value:.decimals$}");
+    let text = text.trim_end_matches('0').trim_end_matches('.');
+    match text {
+        "-0" => "0".into(),
+        text => text.into(),
+    }
+}
+
+impl Value {
+    /// Whether drawing this value produces any output.
+    pub fn is_drawable(&self) -> bool {
+        match self {
+            Value::Line(..) | Value::Circle(..) | Value::Path(..) => true,
+            Value::List(items) => items.iter().any(Value::is_drawable),
+            Value::Group(group) => group.items.iter().any(Value::is_drawable),
+            _ => false,
+        }
+    }
+}
+
 pub trait DrawBuffer {
     fn reset(&mut self);
 
     fn draw(&mut self, command: DrawCommand);
 
+    /// Finish the current canvas and continue on a fresh one. Buffers that
+    /// only keep one canvas keep drawing on it.
+    fn new_page(&mut self) {}
+
     fn flush(&mut self);
 }
 
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Mm(pub f64);
 
 impl From<Scalar> for Mm {
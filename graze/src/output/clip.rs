@@ -0,0 +1,387 @@
+use std::{collections::HashMap, f64::consts::TAU};
+
+use smol_str::SmolStr;
+
+use super::{DrawBuffer, DrawCommand, Mm};
+
+/// Segments approximating a circle that has to be cut.
+const CIRCLE_SEGMENTS: usize = 64;
+
+type Xy = (f64, f64);
+
+fn xy((x, y): (Mm, Mm)) -> Xy {
+    (x.0, y.0)
+}
+
+fn mm((x, y): Xy) -> (Mm, Mm) {
+    (Mm(x), Mm(y))
+}
+
+fn cross(a: Xy, b: Xy) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+/// The point a fraction `t` of the way from `from` to `to`, exactly at
+/// the ends so that cut pieces still meet.
+fn lerp(from: Xy, to: Xy, t: f64) -> Xy {
+    (from.0 * (1.0 - t) + to.0 * t, from.1 * (1.0 - t) + to.1 * t)
+}
+
+/// Whether `point` lies inside `region`, by the even-odd rule.
+fn contains(region: &[Xy], point: Xy) -> bool {
+    let mut inside = false;
+    for (i, &a) in region.iter().enumerate() {
+        let b = region[(i + 1) % region.len()];
+        if (a.1 > point.1) != (b.1 > point.1)
+            && point.0 < a.0 + (point.1 - a.1) / (b.1 - a.1) * (b.0 - a.0)
+        {
+            inside = !inside;
+        }
+    }
+    inside
+}
+
+/// A backend wrapper that cuts everything drawn between
+/// [`DrawCommand::BeginClip`] and [`DrawCommand::EndClip`] to the clip
+/// regions itself, for backends that can't clip natively. Symbols placed
+/// inside a clip region are drawn out and cut like everything else.
+#[derive(Debug, Default)]
+pub struct Clipped<Backend> {
+    inner: Backend,
+    /// Every open clip region, innermost last.
+    regions: Vec<Vec<Xy>>,
+    /// What every symbol defined so far draws.
+    symbols: HashMap<SmolStr, Vec<DrawCommand>>,
+    /// The symbols being defined and their commands so far, innermost last.
+    defining: Vec<(SmolStr, Vec<DrawCommand>)>,
+}
+
+impl<Backend> Clipped<Backend> {
+    pub fn new(inner: Backend) -> Self {
+        Self {
+            inner,
+            regions: Vec::new(),
+            symbols: HashMap::new(),
+            defining: Vec::new(),
+        }
+    }
+
+    pub fn inner(&self) -> &Backend {
+        &self.inner
+    }
+
+    pub fn into_inner(self) -> Backend {
+        self.inner
+    }
+
+    fn visible(&self, point: Xy) -> bool {
+        self.regions.iter().all(|region| contains(region, point))
+    }
+
+    /// The parts of the segment from `from` to `to` inside every region,
+    /// as ranges of its parameter from 0 to 1.
+    fn cut(&self, from: Xy, to: Xy) -> Vec<(f64, f64)> {
+        let direction = (to.0 - from.0, to.1 - from.1);
+        let mut cuts = vec![0.0, 1.0];
+        for region in &self.regions {
+            for (i, &a) in region.iter().enumerate() {
+                let b = region[(i + 1) % region.len()];
+                let edge = (b.0 - a.0, b.1 - a.1);
+                let denominator = cross(direction, edge);
+                if denominator.abs() < 1e-12 {
+                    continue;
+                }
+                let offset = (a.0 - from.0, a.1 - from.1);
+                let t = cross(offset, edge) / denominator;
+                let u = cross(offset, direction) / denominator;
+                if t > 0.0 && t < 1.0 && (0.0..=1.0).contains(&u) {
+                    cuts.push(t);
+                }
+            }
+        }
+        cuts.sort_by(f64::total_cmp);
+        cuts.dedup();
+
+        let mut pieces: Vec<(f64, f64)> = Vec::new();
+        for pair in cuts.windows(2) {
+            let (start, end) = (pair[0], pair[1]);
+            if !self.visible(lerp(from, to, (start + end) / 2.0)) {
+                continue;
+            }
+            match pieces.last_mut() {
+                Some(last) if last.1 == start => last.1 = end,
+                _ => pieces.push((start, end)),
+            }
+        }
+        pieces
+    }
+}
+
+impl<Backend: DrawBuffer> Clipped<Backend> {
+    /// Draw the visible parts of the polyline through `points`.
+    fn draw_polyline(&mut self, points: &[Xy], closed: bool) {
+        let count = if closed {
+            points.len()
+        } else {
+            points.len().saturating_sub(1)
+        };
+        let mut runs: Vec<Vec<Xy>> = Vec::new();
+        let mut whole = true;
+        for i in 0..count {
+            let (from, to) = (points[i], points[(i + 1) % points.len()]);
+            let pieces = self.cut(from, to);
+            whole &= pieces == [(0.0, 1.0)];
+            for (start, end) in pieces {
+                let (start, end) = (lerp(from, to, start), lerp(from, to, end));
+                match runs.last_mut() {
+                    Some(run) if run.last() == Some(&start) => run.push(end),
+                    _ => runs.push(vec![start, end]),
+                }
+            }
+        }
+
+        if whole {
+            let points = points.iter().copied().map(mm).collect();
+            self.inner.draw(DrawCommand::Path { points, closed });
+            return;
+        }
+        // A closed path may have been cut open anywhere, so the run
+        // through its first point continues the last one
+        if closed && runs.len() > 1 && runs[runs.len() - 1].last() == runs[0].first() {
+            let mut last = runs.pop().expect("There are at least two runs");
+            last.extend(runs[0].drain(1..));
+            runs[0] = last;
+        }
+        for run in runs {
+            let points = run.into_iter().map(mm).collect();
+            self.inner.draw(DrawCommand::Path {
+                points,
+                closed: false,
+            });
+        }
+    }
+}
+
+impl<Backend: DrawBuffer> DrawBuffer for Clipped<Backend> {
+    fn reset(&mut self) {
+        self.regions.clear();
+        self.symbols.clear();
+        self.defining.clear();
+        self.inner.reset();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { ref name } => {
+                self.defining.push((name.clone(), Vec::new()));
+                self.inner.draw(command);
+            }
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+                self.inner.draw(command);
+            }
+            // Symbols have coordinates of their own, which aren't clipped
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command.clone());
+                }
+                self.inner.draw(command);
+            }
+            DrawCommand::PlaceSymbol { name, transform } if !self.regions.is_empty() => {
+                let commands = self.symbols.get(&name).cloned().unwrap_or_default();
+                for command in commands {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::BeginClip { region } => {
+                self.regions.push(region.into_iter().map(xy).collect());
+            }
+            DrawCommand::EndClip => {
+                self.regions.pop();
+            }
+            command if self.regions.is_empty() => self.inner.draw(command),
+            DrawCommand::Line { from, to } => {
+                let (from, to) = (xy(from), xy(to));
+                for (start, end) in self.cut(from, to) {
+                    self.inner.draw(DrawCommand::Line {
+                        from: mm(lerp(from, to, start)),
+                        to: mm(lerp(from, to, end)),
+                    });
+                }
+            }
+            DrawCommand::Circle { at, radius } => {
+                let points = (0..CIRCLE_SEGMENTS)
+                    .map(|i| {
+                        let (sin, cos) = (TAU * i as f64 / CIRCLE_SEGMENTS as f64).sin_cos();
+                        (at.0 .0 + radius.0 * cos, at.1 .0 + radius.0 * sin)
+                    })
+                    .collect::<Vec<_>>();
+                if points.iter().all(|&point| self.visible(point)) {
+                    self.inner.draw(DrawCommand::Circle { at, radius });
+                } else {
+                    self.draw_polyline(&points, true);
+                }
+            }
+            DrawCommand::Path { points, closed } => {
+                let points = points.into_iter().map(xy).collect::<Vec<_>>();
+                self.draw_polyline(&points, closed);
+            }
+            DrawCommand::Text { at, text, size } => {
+                if self.visible(xy(at)) {
+                    self.inner.draw(DrawCommand::Text { at, text, size });
+                }
+            }
+            DrawCommand::Image {
+                at,
+                width,
+                height,
+                path,
+            } => {
+                // Like text, images are kept or dropped as a whole
+                let center = (at.0 .0 + width.0 / 2.0, at.1 .0 + height.0 / 2.0);
+                if self.visible(center) {
+                    self.inner.draw(DrawCommand::Image {
+                        at,
+                        width,
+                        height,
+                        path,
+                    });
+                }
+            }
+            command => self.inner.draw(command),
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.regions.clear();
+        self.inner.new_page();
+    }
+
+    fn flush(&mut self) {
+        self.inner.flush();
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{stdlib::Transform, util::test_helpers::Recorder};
+
+    fn clipped() -> Clipped<Recorder> {
+        let mut output = Clipped::<Recorder>::default();
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        output.draw(DrawCommand::BeginClip {
+            region: square.into_iter().map(mm).collect(),
+        });
+        output
+    }
+
+    #[test]
+    fn test_lines_are_cut() {
+        let mut output = clipped();
+        output.draw(DrawCommand::Line {
+            from: mm((-5.0, 5.0)),
+            to: mm((15.0, 5.0)),
+        });
+        output.draw(DrawCommand::Line {
+            from: mm((-5.0, 20.0)),
+            to: mm((15.0, 20.0)),
+        });
+        output.draw(DrawCommand::EndClip);
+        output.draw(DrawCommand::Line {
+            from: mm((-5.0, 20.0)),
+            to: mm((15.0, 20.0)),
+        });
+
+        assert_eq!(
+            output.into_inner().commands,
+            [
+                DrawCommand::Line {
+                    from: mm((0.0, 5.0)),
+                    to: mm((10.0, 5.0)),
+                },
+                DrawCommand::Line {
+                    from: mm((-5.0, 20.0)),
+                    to: mm((15.0, 20.0)),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_shapes_are_cut() {
+        let mut output = clipped();
+        output.draw(DrawCommand::Circle {
+            at: mm((5.0, 5.0)),
+            radius: Mm(2.0),
+        });
+        output.draw(DrawCommand::Circle {
+            at: mm((10.0, 5.0)),
+            radius: Mm(2.0),
+        });
+        output.draw(DrawCommand::Path {
+            points: [(5.0, 5.0), (15.0, 5.0), (15.0, 8.0), (5.0, 8.0)]
+                .into_iter()
+                .map(mm)
+                .collect(),
+            closed: true,
+        });
+
+        let commands = output.into_inner().commands;
+        assert!(matches!(commands[0], DrawCommand::Circle { .. }));
+        assert!(matches!(
+            commands[1],
+            DrawCommand::Path { closed: false, .. }
+        ));
+        assert_eq!(
+            commands[2],
+            DrawCommand::Path {
+                points: [(10.0, 8.0), (5.0, 8.0), (5.0, 5.0), (10.0, 5.0)]
+                    .into_iter()
+                    .map(mm)
+                    .collect(),
+                closed: false,
+            }
+        );
+        assert_eq!(commands.len(), 3);
+    }
+
+    #[test]
+    fn test_placed_symbols_are_cut() {
+        let mut output = Clipped::<Recorder>::default();
+        let line = DrawCommand::Line {
+            from: mm((0.0, 0.0)),
+            to: mm((10.0, 0.0)),
+        };
+        output.draw(DrawCommand::BeginSymbol {
+            name: "dash".into(),
+        });
+        output.draw(line.clone());
+        output.draw(DrawCommand::EndSymbol);
+        let square = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+        output.draw(DrawCommand::BeginClip {
+            region: square.into_iter().map(mm).collect(),
+        });
+        output.draw(DrawCommand::PlaceSymbol {
+            name: "dash".into(),
+            transform: Transform::translate(5.0, 5.0),
+        });
+
+        assert_eq!(
+            output.into_inner().commands,
+            [
+                DrawCommand::BeginSymbol {
+                    name: "dash".into()
+                },
+                line,
+                DrawCommand::EndSymbol,
+                DrawCommand::Line {
+                    from: mm((5.0, 5.0)),
+                    to: mm((10.0, 5.0)),
+                },
+            ]
+        );
+    }
+}
@@ -0,0 +1,394 @@
+use std::{collections::HashMap, fmt::Display};
+
+use smol_str::SmolStr;
+
+use super::{sheet::Sheet, DrawBuffer, DrawCommand, LineStyle, Mm};
+
+/// Decimals kept in coordinates and lengths, a micrometer.
+const DECIMALS: usize = 3;
+
+/// PostScript points in a millimeter.
+const POINTS_PER_MM: f64 = 72.0 / 25.4;
+
+/// The width of every line in millimeters, which [`LineStyle::dashes`] is
+/// made for.
+const LINE_WIDTH: f64 = 0.25;
+
+/// Glyph width relative to the height, to guess how wide text is.
+const GLYPH_ASPECT: f64 = 0.6;
+
+type Xy = (f64, f64);
+
+fn number(value: f64) -> String {
+    super::number(value, DECIMALS)
+}
+
+/// `text` as a PostScript string.
+fn string(text: &str) -> String {
+    let mut string = String::from("(");
+    for char in text.chars() {
+        if matches!(char, '(' | ')' | '\\') {
+            string.push('\\');
+        }
+        string.push(char);
+    }
+    string.push(')');
+    string
+}
+
+/// The smallest rectangle around everything drawn, in millimeters.
+#[derive(Debug, Clone, Copy)]
+struct Bounds {
+    min: Xy,
+    max: Xy,
+}
+
+impl Bounds {
+    fn include(bounds: &mut Option<Bounds>, (x, y): Xy, margin: f64) {
+        let (min, max) = ((x - margin, y - margin), (x + margin, y + margin));
+        *bounds = Some(match *bounds {
+            Some(bounds) => Bounds {
+                min: (bounds.min.0.min(min.0), bounds.min.1.min(min.1)),
+                max: (bounds.max.0.max(max.0), bounds.max.1.max(max.1)),
+            },
+            None => Bounds { min, max },
+        });
+    }
+}
+
+/// Renders draw commands into Encapsulated PostScript, one document per
+/// page, for embedding in LaTeX and other typesetting.
+///
+/// Coordinates are written in millimeters, and the bounding box fits
+/// tightly around everything drawn rather than the page. Lines are
+/// 0.25mm wide, and text is set in Helvetica. Images and markers are left
+/// out, and symbols are drawn out wherever they are placed.
+#[derive(Debug)]
+pub struct EpsOutput {
+    /// The drawing operators of the current page.
+    body: Vec<String>,
+    bounds: Option<Bounds>,
+    sheet: Sheet,
+    line_style: LineStyle,
+    /// The line style when each open clip began, which ending the clip
+    /// brings back, innermost last.
+    clips: Vec<LineStyle>,
+    /// All pages before the current one.
+    finished: Vec<String>,
+    /// The document's title and creator.
+    title: Option<SmolStr>,
+    creator: Option<SmolStr>,
+    /// What every symbol defined so far draws.
+    symbols: HashMap<SmolStr, Vec<DrawCommand>>,
+    /// The symbols being defined and their commands so far, innermost last.
+    defining: Vec<(SmolStr, Vec<DrawCommand>)>,
+}
+
+impl Default for EpsOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl EpsOutput {
+    pub fn new() -> Self {
+        Self {
+            body: Vec::new(),
+            bounds: None,
+            sheet: Sheet::default(),
+            line_style: LineStyle::default(),
+            clips: Vec::new(),
+            finished: Vec::new(),
+            title: None,
+            creator: None,
+            symbols: HashMap::new(),
+            defining: Vec::new(),
+        }
+    }
+
+    /// Every page drawn so far, the current one last.
+    pub fn pages(&self) -> Vec<String> {
+        let mut pages = self.finished.clone();
+        pages.push(self.to_string());
+        pages
+    }
+
+    /// A point in drawing coordinates, on the page.
+    fn point(&self, point: (Mm, Mm)) -> Xy {
+        self.sheet.point(point)
+    }
+
+    /// Stroke the polyline through `points`, back to the first one if
+    /// `closed`.
+    fn stroke(&mut self, points: &[Xy], closed: bool) {
+        let Some((first, rest)) = points.split_first() else {
+            return;
+        };
+        let mut path = format!("newpath {} {} moveto", number(first.0), number(first.1));
+        for point in rest {
+            path.push_str(&format!(" {} {} lineto", number(point.0), number(point.1)));
+        }
+        if closed {
+            path.push_str(" closepath");
+        }
+        path.push_str(" stroke");
+        self.body.push(path);
+        for &point in points {
+            Bounds::include(&mut self.bounds, point, LINE_WIDTH / 2.0);
+        }
+    }
+
+    fn set_dash(&mut self, style: LineStyle) {
+        let dashes = style
+            .dashes()
+            .iter()
+            .map(|&length| number(length))
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.body.push(format!("[{dashes}] 0 setdash"));
+    }
+}
+
+impl DrawBuffer for EpsOutput {
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { name } => {
+                self.defining.push((name, Vec::new()));
+                return;
+            }
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+                return;
+            }
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        match command {
+            DrawCommand::Line { from, to } => {
+                let points = [self.point(from), self.point(to)];
+                self.stroke(&points, false);
+            }
+            DrawCommand::Circle { at, radius } => {
+                let (center, radius) = (self.point(at), self.sheet.length(radius));
+                self.body.push(format!(
+                    "newpath {} {} {} 0 360 arc closepath stroke",
+                    number(center.0),
+                    number(center.1),
+                    number(radius),
+                ));
+                Bounds::include(&mut self.bounds, center, radius + LINE_WIDTH / 2.0);
+            }
+            DrawCommand::Path { points, closed } => {
+                let points = points
+                    .into_iter()
+                    .map(|point| self.point(point))
+                    .collect::<Vec<_>>();
+                self.stroke(&points, closed);
+            }
+            DrawCommand::Text { at, text, size } => {
+                let (at, size) = (self.point(at), self.sheet.length(size));
+                // Centered on `at`, with the baseline a little below it
+                self.body.push(format!(
+                    "/Helvetica findfont {size} scalefont setfont {x} {y} moveto {text} \
+                     dup stringwidth pop 2 div neg {drop} rmoveto show",
+                    size = number(size),
+                    x = number(at.0),
+                    y = number(at.1),
+                    text = string(&text),
+                    drop = number(-0.35 * size),
+                ));
+                let width = size * GLYPH_ASPECT * text.chars().count() as f64;
+                Bounds::include(&mut self.bounds, (at.0 - width / 2.0, at.1), size / 2.0);
+                Bounds::include(&mut self.bounds, (at.0 + width / 2.0, at.1), size / 2.0);
+            }
+            DrawCommand::Resize { x, y } => self.sheet.resize(x, y),
+            DrawCommand::SetView {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => self.sheet.set_view(x, y, width, height, flip_y),
+            DrawCommand::BeginClip { region } => {
+                let region = region
+                    .into_iter()
+                    .map(|point| self.point(point))
+                    .map(|(x, y)| format!("{} {}", number(x), number(y)))
+                    .collect::<Vec<_>>();
+                if let Some((first, rest)) = region.split_first() {
+                    let lines = rest
+                        .iter()
+                        .map(|point| format!(" {point} lineto"))
+                        .collect::<String>();
+                    self.body.push(format!(
+                        "gsave newpath {first} moveto{lines} closepath eoclip newpath"
+                    ));
+                    self.clips.push(self.line_style);
+                }
+            }
+            DrawCommand::EndClip => {
+                if let Some(style) = self.clips.pop() {
+                    self.body.push("grestore".into());
+                    // Restoring the graphics state undoes dashes set since
+                    if style != self.line_style {
+                        self.set_dash(self.line_style);
+                    }
+                }
+            }
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let commands = self.symbols.get(&name).cloned().unwrap_or_default();
+                for command in commands {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::SetLineStyle { style } => {
+                if style != self.line_style {
+                    self.line_style = style;
+                    self.set_dash(style);
+                }
+            }
+            DrawCommand::Metadata { name, value } => match name.as_str() {
+                "title" => self.title = Some(value),
+                "creator" => self.creator = Some(value),
+                _ => {}
+            },
+            DrawCommand::Image { .. }
+            | DrawCommand::BeginLayer { .. }
+            | DrawCommand::EndLayer
+            | DrawCommand::SetMarkers { .. }
+            | DrawCommand::BeginSymbol { .. }
+            | DrawCommand::EndSymbol => {}
+        }
+    }
+
+    fn new_page(&mut self) {
+        let page = self.to_string();
+        self.finished.push(page);
+        self.body.clear();
+        self.bounds = None;
+        self.clips.clear();
+        // Every document starts out with continuous lines
+        if self.line_style != LineStyle::Continuous {
+            self.set_dash(self.line_style);
+        }
+    }
+
+    fn flush(&mut self) {}
+}
+
+impl Display for EpsOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let (min, max) = self
+            .bounds
+            .map_or(((0.0, 0.0), (0.0, 0.0)), |bounds| (bounds.min, bounds.max));
+        let points = [min.0, min.1, max.0, max.1].map(|mm| mm * POINTS_PER_MM);
+        writeln!(f, "%!PS-Adobe-3.0 EPSF-3.0")?;
+        writeln!(
+            f,
+            "%%BoundingBox: {} {} {} {}",
+            points[0].floor(),
+            points[1].floor(),
+            points[2].ceil(),
+            points[3].ceil(),
+        )?;
+        let points = points.map(number).join(" ");
+        writeln!(f, "%%HiResBoundingBox: {points}")?;
+        writeln!(
+            f,
+            "%%Creator: {}",
+            self.creator.as_deref().unwrap_or("graze")
+        )?;
+        if let Some(title) = &self.title {
+            writeln!(f, "%%Title: {title}")?;
+        }
+        writeln!(f, "%%EndComments")?;
+        // Draw in millimeters
+        writeln!(f, "72 25.4 div dup scale")?;
+        writeln!(
+            f,
+            "{} setlinewidth 1 setlinecap 1 setlinejoin",
+            number(LINE_WIDTH)
+        )?;
+        for line in &self.body {
+            writeln!(f, "{line}")?;
+        }
+        writeln!(f, "showpage")?;
+        writeln!(f, "%%EOF")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_document() {
+        let mut output = EpsOutput::new();
+        output.draw(DrawCommand::Resize {
+            x: Mm(100.0),
+            y: Mm(50.0),
+        });
+        output.draw(DrawCommand::Metadata {
+            name: "title".into(),
+            value: "Bracket".into(),
+        });
+        output.draw(DrawCommand::Line {
+            from: (Mm(10.0), Mm(10.0)),
+            to: (Mm(20.0), Mm(10.0)),
+        });
+        output.draw(DrawCommand::SetLineStyle {
+            style: LineStyle::Hidden,
+        });
+        output.draw(DrawCommand::Circle {
+            at: (Mm(20.0), Mm(20.0)),
+            radius: Mm(5.0),
+        });
+        let eps = output.to_string();
+
+        assert!(eps.starts_with("%!PS-Adobe-3.0 EPSF-3.0\n%%BoundingBox: 27 70 72 114\n"));
+        assert!(eps.contains("%%Title: Bracket\n"));
+        assert!(eps.contains("newpath 10 40 moveto 20 40 lineto stroke\n"));
+        assert!(eps.contains("[3 0.75] 0 setdash\nnewpath 20 30 5 0 360 arc closepath stroke\n"));
+        assert!(eps.ends_with("showpage\n%%EOF\n"));
+    }
+
+    #[test]
+    fn test_clips_and_pages() {
+        let mut output = EpsOutput::new();
+        output.draw(DrawCommand::BeginClip {
+            region: vec![(Mm(0.0), Mm(0.0)), (Mm(1.0), Mm(0.0)), (Mm(0.0), Mm(1.0))],
+        });
+        output.draw(DrawCommand::SetLineStyle {
+            style: LineStyle::Center,
+        });
+        output.draw(DrawCommand::EndClip);
+        output.draw(DrawCommand::Text {
+            at: (Mm(0.0), Mm(0.0)),
+            text: "a (b)".into(),
+            size: Mm(2.0),
+        });
+        output.new_page();
+        let pages = output.pages();
+
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].contains(
+            "gsave newpath 0 0 moveto 1 0 lineto 0 -1 lineto closepath eoclip newpath\n"
+        ));
+        assert!(pages[0].contains("grestore\n[6 0.75 0.125 0.75] 0 setdash\n"));
+        assert!(pages[0].contains("(a \\(b\\)) dup stringwidth"));
+        assert!(pages[1].contains("%%BoundingBox: 0 0 0 0\n"));
+        assert!(pages[1].contains("[6 0.75 0.125 0.75] 0 setdash\nshowpage"));
+    }
+}
@@ -0,0 +1,424 @@
+use std::{collections::HashMap, fmt::Display};
+
+use smol_str::SmolStr;
+
+use super::{sheet::Sheet, DrawBuffer, DrawCommand, Mm};
+
+/// Decimals kept in coordinates, a micrometer.
+const DECIMALS: usize = 3;
+
+type Xy = (f64, f64);
+
+/// `value` rounded to the decimals that are written.
+fn round(value: f64) -> f64 {
+    let factor = 10f64.powi(DECIMALS as i32);
+    (value * factor).round() / factor
+}
+
+fn number(value: f64) -> String {
+    super::number(value, DECIMALS)
+}
+
+fn cross(a: Xy, b: Xy) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+/// The center of the circle through `a`, `b` and `c`, unless they are in
+/// a line.
+fn center(a: Xy, b: Xy, c: Xy) -> Option<Xy> {
+    let (ab, ac) = ((b.0 - a.0, b.1 - a.1), (c.0 - a.0, c.1 - a.1));
+    let denominator = 2.0 * cross(ab, ac);
+    if denominator.abs() < 1e-12 {
+        return None;
+    }
+    let (ab2, ac2) = (ab.0 * ab.0 + ab.1 * ab.1, ac.0 * ac.0 + ac.1 * ac.1);
+    Some((
+        a.0 + (ac.1 * ab2 - ab.1 * ac2) / denominator,
+        a.1 + (ab.0 * ac2 - ac.0 * ab2) / denominator,
+    ))
+}
+
+/// An arc through all of `points` within `tolerance`, turning the same
+/// way at every corner, as its center and whether it turns clockwise.
+fn arc(points: &[Xy], tolerance: f64) -> Option<(Xy, bool)> {
+    let (first, last) = (points[0], points[points.len() - 1]);
+    let center = center(first, points[points.len() / 2], last)?;
+    let radius = (first.0 - center.0).hypot(first.1 - center.1);
+    let fits = points
+        .iter()
+        .all(|point| ((point.0 - center.0).hypot(point.1 - center.1) - radius).abs() <= tolerance);
+    let turns = points
+        .windows(3)
+        .map(|corner| {
+            let a = (corner[1].0 - corner[0].0, corner[1].1 - corner[0].1);
+            let b = (corner[2].0 - corner[1].0, corner[2].1 - corner[1].1);
+            cross(a, b)
+        })
+        .collect::<Vec<_>>();
+    let clockwise = turns[0] < 0.0;
+    let steady = turns
+        .iter()
+        .all(|&turn| turn.abs() > 1e-12 && (turn < 0.0) == clockwise);
+    (fits && steady).then_some((center, clockwise))
+}
+
+/// Renders draw commands into a G-code program for laser cutters, CNC
+/// machines and pen plotters driven by one.
+///
+/// Lines and paths become straight moves at the feed rate, with the tool
+/// raised for the rapid moves between them. Runs of path points that lie
+/// on a circle within the arc tolerance become arc moves, and circles are
+/// a single arc. Machines have no notion of pages, so the program pauses
+/// before each new one. Text, images, line styles, markers and metadata
+/// are left out. Machines can't clip, so wrap the output in
+/// [`super::Clipped`] to cut clip regions beforehand.
+#[derive(Debug)]
+pub struct GcodeOutput {
+    program: Vec<String>,
+    sheet: Sheet,
+    /// Millimeters per minute while the tool is down.
+    feed_rate: f64,
+    /// Whether the feed rate was written since it last changed.
+    feed_written: bool,
+    /// The largest distance from path points to the arc replacing them,
+    /// or zero to keep every segment.
+    arc_tolerance: f64,
+    tool_down: String,
+    tool_up: String,
+    /// Where the tool is, in millimeters, and whether it is down.
+    at: Option<Xy>,
+    down: bool,
+    /// What every symbol defined so far draws.
+    symbols: HashMap<SmolStr, Vec<DrawCommand>>,
+    /// The symbols being defined and their commands so far, innermost last.
+    defining: Vec<(SmolStr, Vec<DrawCommand>)>,
+    flushed: bool,
+}
+
+impl Default for GcodeOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl GcodeOutput {
+    pub fn new() -> Self {
+        Self {
+            program: Vec::new(),
+            sheet: Sheet::default(),
+            feed_rate: 1000.0,
+            feed_written: false,
+            arc_tolerance: 0.01,
+            tool_down: "M3".into(),
+            tool_up: "M5".into(),
+            at: None,
+            down: false,
+            symbols: HashMap::new(),
+            defining: Vec::new(),
+            flushed: false,
+        }
+    }
+
+    /// Move at `feed_rate` millimeters per minute while the tool is down,
+    /// 1000 by default.
+    pub fn set_feed_rate(&mut self, feed_rate: f64) {
+        self.feed_rate = feed_rate;
+        self.feed_written = false;
+    }
+
+    /// Replace runs of path points with an arc if none of them is further
+    /// than `tolerance` millimeters from it, 0.01 by default. Zero keeps
+    /// every segment.
+    pub fn set_arc_tolerance(&mut self, tolerance: f64) {
+        self.arc_tolerance = tolerance;
+    }
+
+    /// Lower and raise the tool with the commands `down` and `up`, which
+    /// are `M3` and `M5` to switch a laser or spindle on and off by
+    /// default. Set them before drawing anything, as commands already
+    /// written keep the old ones.
+    pub fn set_tool_commands(&mut self, down: impl Into<String>, up: impl Into<String>) {
+        self.tool_down = down.into();
+        self.tool_up = up.into();
+    }
+
+    /// A point in drawing coordinates, on the machine.
+    fn point(&self, point: (Mm, Mm)) -> Xy {
+        let (x, y) = self.sheet.point(point);
+        (round(x), round(y))
+    }
+
+    fn raise(&mut self) {
+        if self.down {
+            self.program.push(self.tool_up.clone());
+            self.down = false;
+        }
+    }
+
+    /// Move to `to` with the tool raised, unless it is already there.
+    fn travel(&mut self, to: Xy) {
+        if self.at != Some(to) {
+            self.raise();
+            self.program
+                .push(format!("G0 X{} Y{}", number(to.0), number(to.1)));
+            self.at = Some(to);
+        }
+    }
+
+    /// Move to `to` with the tool down, `arc` giving the center and
+    /// direction of an arc move.
+    fn cut(&mut self, to: Xy, arc: Option<(Xy, bool)>) {
+        if !self.down {
+            self.program.push(self.tool_down.clone());
+            self.down = true;
+        }
+        let from = self.at.unwrap_or((0.0, 0.0));
+        let mut command = match arc {
+            Some((center, clockwise)) => format!(
+                "{} X{} Y{} I{} J{}",
+                if clockwise { "G2" } else { "G3" },
+                number(to.0),
+                number(to.1),
+                number(center.0 - from.0),
+                number(center.1 - from.1),
+            ),
+            None => format!("G1 X{} Y{}", number(to.0), number(to.1)),
+        };
+        if !self.feed_written {
+            command.push_str(&format!(" F{}", number(self.feed_rate)));
+            self.feed_written = true;
+        }
+        self.program.push(command);
+        self.at = Some(to);
+    }
+
+    /// Cut through `points`, with arcs where they fit.
+    fn polyline(&mut self, points: &[Xy]) {
+        let Some(&first) = points.first() else {
+            return;
+        };
+        self.travel(first);
+        let mut start = 0;
+        while start + 1 < points.len() {
+            let (mut end, mut fitted) = (start + 1, None);
+            // Any three points are on a circle, so arcs need four
+            let mut candidate = start + 3;
+            while self.arc_tolerance > 0.0 && candidate < points.len() {
+                let Some(fit) = arc(&points[start..=candidate], self.arc_tolerance) else {
+                    break;
+                };
+                (end, fitted) = (candidate, Some(fit));
+                candidate += 1;
+            }
+            self.cut(points[end], fitted);
+            start = end;
+        }
+    }
+}
+
+impl DrawBuffer for GcodeOutput {
+    fn reset(&mut self) {
+        self.program.clear();
+        self.sheet = Sheet::default();
+        self.feed_written = false;
+        self.at = None;
+        self.down = false;
+        self.symbols.clear();
+        self.defining.clear();
+        self.flushed = false;
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { name } => {
+                self.defining.push((name, Vec::new()));
+                return;
+            }
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+                return;
+            }
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        match command {
+            DrawCommand::Line { from, to } => {
+                let points = [self.point(from), self.point(to)];
+                self.polyline(&points);
+            }
+            DrawCommand::Circle { at, radius } => {
+                let (center, radius) = (self.point(at), round(self.sheet.length(radius)));
+                let start = (center.0 + radius, center.1);
+                self.travel(start);
+                self.cut(start, Some((center, true)));
+            }
+            DrawCommand::Path { points, closed } => {
+                let mut points = points
+                    .into_iter()
+                    .map(|point| self.point(point))
+                    .collect::<Vec<_>>();
+                if let (true, Some(&first)) = (closed, points.first()) {
+                    points.push(first);
+                }
+                self.polyline(&points);
+            }
+            DrawCommand::Resize { x, y } => self.sheet.resize(x, y),
+            DrawCommand::SetView {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => self.sheet.set_view(x, y, width, height, flip_y),
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let commands = self.symbols.get(&name).cloned().unwrap_or_default();
+                for command in commands {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::Text { .. }
+            | DrawCommand::Image { .. }
+            | DrawCommand::BeginLayer { .. }
+            | DrawCommand::EndLayer
+            | DrawCommand::BeginClip { .. }
+            | DrawCommand::EndClip
+            | DrawCommand::SetLineStyle { .. }
+            | DrawCommand::SetMarkers { .. }
+            | DrawCommand::Metadata { .. }
+            | DrawCommand::BeginSymbol { .. }
+            | DrawCommand::EndSymbol => {}
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.raise();
+        // Wait for the next sheet to be put in
+        self.program.push("M0".into());
+        self.at = None;
+    }
+
+    fn flush(&mut self) {
+        if !self.flushed {
+            self.raise();
+            self.program.push("M2".into());
+            self.flushed = true;
+        }
+    }
+}
+
+impl Display for GcodeOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Millimeters, absolute coordinates, tool raised
+        writeln!(f, "G21")?;
+        writeln!(f, "G90")?;
+        writeln!(f, "{}", self.tool_up)?;
+        for command in &self.program {
+            writeln!(f, "{command}")?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stdlib::Transform;
+
+    fn mm(points: &[Xy]) -> Vec<(Mm, Mm)> {
+        points.iter().map(|&(x, y)| (Mm(x), Mm(y))).collect()
+    }
+
+    #[test]
+    fn test_moves() {
+        let mut output = GcodeOutput::new();
+        output.set_feed_rate(600.0);
+        output.draw(DrawCommand::Resize {
+            x: Mm(100.0),
+            y: Mm(50.0),
+        });
+        output.draw(DrawCommand::Line {
+            from: (Mm(10.0), Mm(10.0)),
+            to: (Mm(20.0), Mm(10.0)),
+        });
+        output.draw(DrawCommand::Path {
+            points: mm(&[(20.0, 10.0), (20.0, 20.0), (10.5, 20.0)]),
+            closed: true,
+        });
+        output.draw(DrawCommand::Circle {
+            at: (Mm(50.0), Mm(25.0)),
+            radius: Mm(5.0),
+        });
+        output.flush();
+        output.flush();
+
+        assert_eq!(
+            output.to_string(),
+            "G21\nG90\nM5\nG0 X10 Y40\nM3\nG1 X20 Y40 F600\nG1 X20 Y30\nG1 X10.5 Y30\n\
+             G1 X20 Y40\nM5\nG0 X55 Y25\nM3\nG2 X55 Y25 I-5 J0\nM5\nM2\n"
+        );
+    }
+
+    #[test]
+    fn test_arcs() {
+        // A quarter circle around the origin, counterclockwise with y up
+        let quarter = (0..=8)
+            .map(|i| {
+                let (sin, cos) = (std::f64::consts::FRAC_PI_2 * i as f64 / 8.0).sin_cos();
+                (10.0 * cos, -10.0 * sin)
+            })
+            .collect::<Vec<_>>();
+        let mut output = GcodeOutput::new();
+        output.set_tool_commands("G1 Z0", "G0 Z5");
+        output.draw(DrawCommand::Path {
+            points: mm(&quarter),
+            closed: false,
+        });
+        output.draw(DrawCommand::Line {
+            from: (Mm(0.0), Mm(-10.0)),
+            to: (Mm(-5.0), Mm(-10.0)),
+        });
+        assert_eq!(
+            output.to_string(),
+            "G21\nG90\nG0 Z5\nG0 X10 Y0\nG1 Z0\nG3 X0 Y10 I-10 J0 F1000\nG1 X-5 Y10\n"
+        );
+
+        let mut output = GcodeOutput::new();
+        output.set_arc_tolerance(0.0);
+        output.draw(DrawCommand::Path {
+            points: mm(&quarter),
+            closed: false,
+        });
+        assert_eq!(output.to_string().matches("G1 ").count(), 8);
+    }
+
+    #[test]
+    fn test_pages_and_symbols() {
+        let mut output = GcodeOutput::new();
+        output.draw(DrawCommand::BeginSymbol {
+            name: "tick".into(),
+        });
+        output.draw(DrawCommand::Line {
+            from: (Mm(0.0), Mm(0.0)),
+            to: (Mm(0.0), Mm(-1.0)),
+        });
+        output.draw(DrawCommand::EndSymbol);
+        output.new_page();
+        output.draw(DrawCommand::PlaceSymbol {
+            name: "tick".into(),
+            transform: Transform::translate(2.0, 0.0),
+        });
+
+        assert_eq!(
+            output.to_string(),
+            "G21\nG90\nM5\nM0\nG0 X2 Y0\nM3\nG1 X2 Y1 F1000\n"
+        );
+    }
+}
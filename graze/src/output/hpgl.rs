@@ -0,0 +1,334 @@
+use std::{collections::HashMap, fmt::Display};
+
+use smol_str::SmolStr;
+
+use super::{sheet::Sheet, DrawBuffer, DrawCommand, LineStyle, Mm};
+
+/// Plotter units in a millimeter, the usual HP-GL resolution.
+const UNITS_PER_MM: f64 = 40.0;
+
+/// The pen used outside of layers that were given one.
+const DEFAULT_PEN: u32 = 1;
+
+/// Glyph width relative to the height, for the plotter's stick font.
+const GLYPH_ASPECT: f64 = 0.6;
+
+/// Renders draw commands into an HP-GL program for pen plotters.
+///
+/// Plotters have no fills or colors to speak of, only pens. Each layer
+/// can be drawn with a pen of its own, see [`HpglOutput::set_layer_pen`],
+/// and everything else is drawn with pen 1. Line styles become line types,
+/// while images, markers and metadata are left out. Plotters can't clip,
+/// so wrap the output in [`super::Clipped`] to cut clip regions beforehand.
+#[derive(Debug)]
+pub struct HpglOutput {
+    program: String,
+    sheet: Sheet,
+    /// The pen given to each layer.
+    layer_pens: HashMap<SmolStr, u32>,
+    /// The pen of every open layer, innermost last.
+    pens: Vec<u32>,
+    /// The pen in the plotter's hand, if any.
+    selected: Option<u32>,
+    /// Where the pen is, in plotter units.
+    at: Option<(i64, i64)>,
+    /// What every symbol defined so far draws.
+    symbols: HashMap<SmolStr, Vec<DrawCommand>>,
+    /// The symbols being defined and their commands so far, innermost last.
+    defining: Vec<(SmolStr, Vec<DrawCommand>)>,
+    flushed: bool,
+}
+
+impl Default for HpglOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HpglOutput {
+    pub fn new() -> Self {
+        Self {
+            program: String::from("IN;PA;"),
+            sheet: Sheet::default(),
+            layer_pens: HashMap::new(),
+            pens: Vec::new(),
+            selected: None,
+            at: None,
+            symbols: HashMap::new(),
+            defining: Vec::new(),
+            flushed: false,
+        }
+    }
+
+    /// Draw everything in the layer `name` with `pen`, including nested
+    /// layers that weren't given a pen of their own.
+    pub fn set_layer_pen(&mut self, name: impl Into<SmolStr>, pen: u32) {
+        self.layer_pens.insert(name.into(), pen);
+    }
+
+    fn pen(&self) -> u32 {
+        self.pens.last().copied().unwrap_or(DEFAULT_PEN)
+    }
+
+    /// A length in millimeters, in plotter units.
+    fn length(&self, length: Mm) -> f64 {
+        self.sheet.length(length) * UNITS_PER_MM
+    }
+
+    /// A point in drawing coordinates, in plotter units.
+    fn point(&self, point: (Mm, Mm)) -> (i64, i64) {
+        let (x, y) = self.sheet.point(point);
+        (
+            (x * UNITS_PER_MM).round() as i64,
+            (y * UNITS_PER_MM).round() as i64,
+        )
+    }
+
+    /// Pick up the pen of the current layer, unless it is already selected.
+    fn select_pen(&mut self) {
+        let pen = self.pen();
+        if self.selected != Some(pen) {
+            self.program.push_str(&format!("PU;SP{pen};"));
+            self.selected = Some(pen);
+        }
+    }
+
+    fn pen_up(&mut self, (x, y): (i64, i64)) {
+        self.program.push_str(&format!("PU{x},{y};"));
+        self.at = Some((x, y));
+    }
+
+    /// Draw through `points`, starting from wherever the pen is if that is
+    /// the first point.
+    fn polyline(&mut self, points: &[(i64, i64)]) {
+        let Some((&first, rest)) = points.split_first() else {
+            return;
+        };
+        self.select_pen();
+        if self.at != Some(first) {
+            self.pen_up(first);
+        }
+        if rest.is_empty() {
+            return;
+        }
+        let coordinates = rest
+            .iter()
+            .map(|(x, y)| format!("{x},{y}"))
+            .collect::<Vec<_>>()
+            .join(",");
+        self.program.push_str(&format!("PD{coordinates};"));
+        self.at = rest.last().copied();
+    }
+}
+
+impl DrawBuffer for HpglOutput {
+    fn reset(&mut self) {
+        let layer_pens = std::mem::take(&mut self.layer_pens);
+        *self = Self::new();
+        self.layer_pens = layer_pens;
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { name } => {
+                self.defining.push((name, Vec::new()));
+                return;
+            }
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+                return;
+            }
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        match command {
+            DrawCommand::Line { from, to } => {
+                // Lines continuing the last one are drawn without lifting
+                let points = [self.point(from), self.point(to)];
+                self.polyline(&points);
+            }
+            DrawCommand::Circle { at, radius } => {
+                let (center, radius) = (self.point(at), self.length(radius));
+                self.select_pen();
+                self.pen_up(center);
+                // CI lowers the pen by itself and returns to the center
+                self.program.push_str(&format!("CI{};", radius.round()));
+            }
+            DrawCommand::Path { points, closed } => {
+                let mut points = points
+                    .into_iter()
+                    .map(|point| self.point(point))
+                    .collect::<Vec<_>>();
+                if let (true, Some(&first)) = (closed, points.first()) {
+                    points.push(first);
+                }
+                self.polyline(&points);
+            }
+            DrawCommand::Text { at, text, size } => {
+                // Character sizes are in centimeters
+                let height = self.length(size) / UNITS_PER_MM / 10.0;
+                let width = height * GLYPH_ASPECT;
+                let at = self.point(at);
+                self.select_pen();
+                self.pen_up(at);
+                self.program
+                    .push_str(&format!("SI{width:.3},{height:.3};LO5;LB{text}\x03"));
+                // Labels move the pen past the last character
+                self.at = None;
+            }
+            DrawCommand::Resize { x, y } => self.sheet.resize(x, y),
+            DrawCommand::SetView {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => self.sheet.set_view(x, y, width, height, flip_y),
+            DrawCommand::BeginLayer { name } => {
+                let pen = self.layer_pens.get(&name).copied().unwrap_or(self.pen());
+                self.pens.push(pen);
+            }
+            DrawCommand::EndLayer => {
+                self.pens.pop();
+            }
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let commands = self.symbols.get(&name).cloned().unwrap_or_default();
+                for command in commands {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::SetLineStyle { style } => {
+                let line_type = match style {
+                    LineStyle::Continuous => "LT;",
+                    LineStyle::Hidden => "LT2;",
+                    LineStyle::Center => "LT4;",
+                    LineStyle::Phantom => "LT6;",
+                };
+                self.program.push_str(line_type);
+            }
+            DrawCommand::Image { .. }
+            | DrawCommand::BeginClip { .. }
+            | DrawCommand::EndClip
+            | DrawCommand::SetMarkers { .. }
+            | DrawCommand::Metadata { .. }
+            | DrawCommand::BeginSymbol { .. }
+            | DrawCommand::EndSymbol => {}
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.program.push_str("PU;PG;");
+        self.at = None;
+    }
+
+    fn flush(&mut self) {
+        if !self.flushed {
+            self.program.push_str("PU;SP0;");
+            self.flushed = true;
+        }
+    }
+}
+
+impl Display for HpglOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.program)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::stdlib::Transform;
+
+    fn line(from: (f64, f64), to: (f64, f64)) -> DrawCommand {
+        DrawCommand::Line {
+            from: (Mm(from.0), Mm(from.1)),
+            to: (Mm(to.0), Mm(to.1)),
+        }
+    }
+
+    #[test]
+    fn test_shapes() {
+        let mut output = HpglOutput::new();
+        output.draw(DrawCommand::Resize {
+            x: Mm(100.0),
+            y: Mm(50.0),
+        });
+        output.draw(line((10.0, 10.0), (20.0, 10.0)));
+        output.draw(line((20.0, 10.0), (20.0, 20.0)));
+        output.draw(DrawCommand::Circle {
+            at: (Mm(50.0), Mm(25.0)),
+            radius: Mm(5.0),
+        });
+        output.draw(DrawCommand::SetLineStyle {
+            style: LineStyle::Hidden,
+        });
+        output.draw(DrawCommand::Path {
+            points: vec![(Mm(0.0), Mm(0.0)), (Mm(10.0), Mm(0.0)), (Mm(0.0), Mm(5.0))],
+            closed: true,
+        });
+        output.flush();
+        output.flush();
+
+        assert_eq!(
+            output.to_string(),
+            "IN;PA;PU;SP1;PU400,1600;PD800,1600;PD800,1200;PU2000,1000;CI200;\
+             LT2;PU0,2000;PD400,2000,0,1800,0,2000;PU;SP0;"
+        );
+    }
+
+    #[test]
+    fn test_layer_pens() {
+        let mut output = HpglOutput::new();
+        output.set_layer_pen("ink", 2);
+        output.draw(DrawCommand::BeginLayer { name: "ink".into() });
+        output.draw(DrawCommand::BeginLayer {
+            name: "inner".into(),
+        });
+        output.draw(line((0.0, 0.0), (1.0, 0.0)));
+        output.draw(DrawCommand::EndLayer);
+        output.draw(DrawCommand::EndLayer);
+        output.draw(line((1.0, 0.0), (2.0, 0.0)));
+
+        assert_eq!(
+            output.to_string(),
+            "IN;PA;PU;SP2;PU0,0;PD40,0;PU;SP1;PD80,0;"
+        );
+    }
+
+    #[test]
+    fn test_view_and_symbols() {
+        let mut output = HpglOutput::new();
+        output.draw(DrawCommand::Resize {
+            x: Mm(100.0),
+            y: Mm(50.0),
+        });
+        // A window twice as wide as the page is tall, y growing upwards
+        output.draw(DrawCommand::SetView {
+            x: Mm(0.0),
+            y: Mm(0.0),
+            width: Mm(20.0),
+            height: Mm(10.0),
+            flip_y: true,
+        });
+        output.draw(DrawCommand::BeginSymbol {
+            name: "tick".into(),
+        });
+        output.draw(line((0.0, 0.0), (0.0, 1.0)));
+        output.draw(DrawCommand::EndSymbol);
+        output.draw(DrawCommand::PlaceSymbol {
+            name: "tick".into(),
+            transform: Transform::translate(2.0, 0.0),
+        });
+
+        assert_eq!(output.to_string(), "IN;PA;PU;SP1;PU400,0;PD400,200;");
+    }
+}
@@ -0,0 +1,75 @@
+use super::Mm;
+
+/// Where a window of drawing coordinates ends up on the page.
+#[derive(Debug, Clone, Copy)]
+struct View {
+    x: f64,
+    /// The top of the window, after flipping.
+    top: f64,
+    flip_y: bool,
+    scale: f64,
+    /// Where the window's corner lands on the page.
+    offset: (f64, f64),
+}
+
+/// The page of a machine that counts y upwards from the bottom edge, like
+/// plotters, cutters and engravers do, and the window of drawing
+/// coordinates shown on it.
+#[derive(Debug, Clone, Copy, Default)]
+pub(super) struct Sheet {
+    /// The page width and height in millimeters.
+    page: (f64, f64),
+    view: Option<View>,
+}
+
+impl Sheet {
+    /// Follow [`super::DrawCommand::Resize`].
+    pub fn resize(&mut self, width: Mm, height: Mm) {
+        self.page = (width.0, height.0);
+    }
+
+    /// Follow [`super::DrawCommand::SetView`], fitting the window on the
+    /// page and centering it like SVG does.
+    pub fn set_view(&mut self, x: Mm, y: Mm, width: Mm, height: Mm, flip_y: bool) {
+        let top = if flip_y { -(y.0 + height.0) } else { y.0 };
+        let (page_width, page_height) = self.page;
+        let (scale, offset) = if page_width > 0.0 && page_height > 0.0 {
+            let scale = (page_width / width.0).min(page_height / height.0);
+            let offset = (
+                (page_width - width.0 * scale) / 2.0,
+                (page_height - height.0 * scale) / 2.0,
+            );
+            (scale, offset)
+        } else {
+            (1.0, (0.0, 0.0))
+        };
+        self.view = Some(View {
+            x: x.0,
+            top,
+            flip_y,
+            scale,
+            offset,
+        });
+    }
+
+    /// A length in drawing coordinates, in millimeters on the page.
+    pub fn length(&self, length: Mm) -> f64 {
+        length.0 * self.view.map_or(1.0, |view| view.scale)
+    }
+
+    /// A point in drawing coordinates, in millimeters from the bottom left
+    /// corner of the page.
+    pub fn point(&self, (x, y): (Mm, Mm)) -> (f64, f64) {
+        let (x, y) = match self.view {
+            Some(view) => {
+                let y = if view.flip_y { -y.0 } else { y.0 };
+                (
+                    (x.0 - view.x) * view.scale + view.offset.0,
+                    (y - view.top) * view.scale + view.offset.1,
+                )
+            }
+            None => (x.0, y.0),
+        };
+        (x, self.page.1 - y)
+    }
+}
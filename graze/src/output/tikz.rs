@@ -0,0 +1,372 @@
+use std::{collections::HashMap, fmt::Display};
+
+use smol_str::SmolStr;
+
+use super::{sheet::Sheet, DrawBuffer, DrawCommand, LineStyle, Marker, Mm};
+
+/// Decimals kept in coordinates and lengths, a micrometer.
+const DECIMALS: usize = 3;
+
+/// TeX points in a millimeter.
+const POINTS_PER_MM: f64 = 72.27 / 25.4;
+
+type Xy = (f64, f64);
+
+fn number(value: f64) -> String {
+    super::number(value, DECIMALS)
+}
+
+fn coordinate((x, y): Xy) -> String {
+    format!("({},{})", number(x), number(y))
+}
+
+/// `text` with the characters TeX treats specially escaped.
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    for char in text.chars() {
+        match char {
+            '\\' => escaped.push_str(r"\textbackslash{}"),
+            '~' => escaped.push_str(r"\textasciitilde{}"),
+            '^' => escaped.push_str(r"\textasciicircum{}"),
+            '{' | '}' | '$' | '&' | '#' | '_' | '%' => {
+                escaped.push('\\');
+                escaped.push(char);
+            }
+            char => escaped.push(char),
+        }
+    }
+    escaped
+}
+
+/// The arrow tip from the `arrows.meta` library for `marker`.
+fn tip(marker: Marker) -> &'static str {
+    match marker {
+        Marker::Arrow => "Stealth",
+        Marker::Dot => "Circle",
+        Marker::Bar => "Bar",
+    }
+}
+
+/// Renders draw commands into TikZ code, one `tikzpicture` per page, to be
+/// pasted into LaTeX documents and edited there.
+///
+/// One unit is a millimeter, with y growing upwards as usual in TikZ.
+/// Layers and clips become scopes, line styles become dash patterns and
+/// markers become arrow tips, which need `\usetikzlibrary{arrows.meta}`.
+/// Images are included with `graphicx`, and symbols are drawn out
+/// wherever they are placed.
+#[derive(Debug)]
+pub struct TikzOutput {
+    /// The lines of the current picture, indented.
+    body: Vec<String>,
+    /// How many scopes are open.
+    depth: usize,
+    sheet: Sheet,
+    line_style: LineStyle,
+    markers: (Option<Marker>, Option<Marker>),
+    /// All pages before the current one.
+    finished: Vec<String>,
+    /// What every symbol defined so far draws.
+    symbols: HashMap<SmolStr, Vec<DrawCommand>>,
+    /// The symbols being defined and their commands so far, innermost last.
+    defining: Vec<(SmolStr, Vec<DrawCommand>)>,
+}
+
+impl Default for TikzOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl TikzOutput {
+    pub fn new() -> Self {
+        Self {
+            body: Vec::new(),
+            depth: 0,
+            sheet: Sheet::default(),
+            line_style: LineStyle::default(),
+            markers: (None, None),
+            finished: Vec::new(),
+            symbols: HashMap::new(),
+            defining: Vec::new(),
+        }
+    }
+
+    /// Every page drawn so far, the current one last.
+    pub fn pages(&self) -> Vec<String> {
+        let mut pages = self.finished.clone();
+        pages.push(self.to_string());
+        pages
+    }
+
+    /// A point in drawing coordinates, on the page.
+    fn point(&self, point: (Mm, Mm)) -> Xy {
+        self.sheet.point(point)
+    }
+
+    fn line(&mut self, line: String) {
+        self.body
+            .push(format!("{}{line}", "  ".repeat(self.depth + 1)));
+    }
+
+    /// `\draw` with the current line style and, for lines, markers.
+    fn draw_command(&self, line: bool) -> String {
+        let mut options = Vec::new();
+        if let [dash, gaps @ ..] = self.line_style.dashes() {
+            let mut pattern = format!("dash pattern=on {}mm", number(*dash));
+            for (i, length) in gaps.iter().enumerate() {
+                let kind = if i % 2 == 0 { "off" } else { "on" };
+                pattern.push_str(&format!(" {kind} {}mm", number(*length)));
+            }
+            options.push(pattern);
+        }
+        if let (true, (start, end)) = (line, self.markers) {
+            if start.is_some() || end.is_some() {
+                let (start, end) = (start.map_or("", tip), end.map_or("", tip));
+                options.push(format!("{start}-{end}"));
+            }
+        }
+        match options.is_empty() {
+            true => r"\draw".into(),
+            false => format!(r"\draw[{}]", options.join(", ")),
+        }
+    }
+
+    fn path(&mut self, points: &[Xy], closed: bool) {
+        if points.is_empty() {
+            return;
+        }
+        let mut path = points
+            .iter()
+            .map(|&point| coordinate(point))
+            .collect::<Vec<_>>()
+            .join(" -- ");
+        if closed {
+            path.push_str(" -- cycle");
+        }
+        self.line(format!("{} {path};", self.draw_command(false)));
+    }
+}
+
+impl DrawBuffer for TikzOutput {
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { name } => {
+                self.defining.push((name, Vec::new()));
+                return;
+            }
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+                return;
+            }
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command);
+                }
+                return;
+            }
+            _ => {}
+        }
+
+        match command {
+            DrawCommand::Line { from, to } => {
+                let (from, to) = (self.point(from), self.point(to));
+                let draw = self.draw_command(true);
+                self.line(format!(
+                    "{draw} {} -- {};",
+                    coordinate(from),
+                    coordinate(to)
+                ));
+            }
+            DrawCommand::Circle { at, radius } => {
+                let (at, radius) = (self.point(at), self.sheet.length(radius));
+                let draw = self.draw_command(false);
+                self.line(format!(
+                    "{draw} {} circle [radius={}];",
+                    coordinate(at),
+                    number(radius)
+                ));
+            }
+            DrawCommand::Path { points, closed } => {
+                let points = points
+                    .into_iter()
+                    .map(|point| self.point(point))
+                    .collect::<Vec<_>>();
+                self.path(&points, closed);
+            }
+            DrawCommand::Text { at, text, size } => {
+                let (at, size) = (self.point(at), self.sheet.length(size) * POINTS_PER_MM);
+                let size = number(size);
+                self.line(format!(
+                    r"\node[font=\fontsize{{{size}pt}}{{{size}pt}}\selectfont] at {} {{{}}};",
+                    coordinate(at),
+                    escape(&text)
+                ));
+            }
+            DrawCommand::Image {
+                at,
+                width,
+                height,
+                path,
+            } => {
+                let corner = (at.0, Mm(at.1 .0 + height.0));
+                let (a, b) = (self.point(at), self.point(corner));
+                let corner = (a.0, a.1.min(b.1));
+                let (width, height) = (self.sheet.length(width), self.sheet.length(height));
+                self.line(format!(
+                    r"\node[anchor=south west, inner sep=0] at {} {{\includegraphics[width={}mm, height={}mm]{{{path}}}}};",
+                    coordinate(corner),
+                    number(width),
+                    number(height),
+                ));
+            }
+            DrawCommand::Resize { x, y } => self.sheet.resize(x, y),
+            DrawCommand::SetView {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => self.sheet.set_view(x, y, width, height, flip_y),
+            DrawCommand::BeginLayer { name } => {
+                self.line(format!(r"\begin{{scope}} % {name}"));
+                self.depth += 1;
+            }
+            DrawCommand::BeginClip { region } => {
+                self.line(r"\begin{scope}".into());
+                self.depth += 1;
+                let region = region
+                    .into_iter()
+                    .map(|point| coordinate(self.point(point)))
+                    .collect::<Vec<_>>()
+                    .join(" -- ");
+                self.line(format!(r"\clip {region} -- cycle;"));
+            }
+            DrawCommand::EndLayer | DrawCommand::EndClip => {
+                if self.depth > 0 {
+                    self.depth -= 1;
+                    self.line(r"\end{scope}".into());
+                }
+            }
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let commands = self.symbols.get(&name).cloned().unwrap_or_default();
+                for command in commands {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::SetLineStyle { style } => self.line_style = style,
+            DrawCommand::SetMarkers { start, end } => self.markers = (start, end),
+            DrawCommand::Metadata { .. }
+            | DrawCommand::BeginSymbol { .. }
+            | DrawCommand::EndSymbol => {}
+        }
+    }
+
+    fn new_page(&mut self) {
+        let page = self.to_string();
+        self.finished.push(page);
+        self.body.clear();
+        self.depth = 0;
+    }
+
+    fn flush(&mut self) {}
+}
+
+impl Display for TikzOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, r"\begin{{tikzpicture}}[x=1mm, y=1mm]")?;
+        for line in &self.body {
+            writeln!(f, "{line}")?;
+        }
+        // Close the scopes still open, as TeX insists
+        for depth in (0..self.depth).rev() {
+            writeln!(f, r"{}\end{{scope}}", "  ".repeat(depth + 1))?;
+        }
+        writeln!(f, r"\end{{tikzpicture}}")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_picture() {
+        let mut output = TikzOutput::new();
+        output.draw(DrawCommand::Resize {
+            x: Mm(100.0),
+            y: Mm(50.0),
+        });
+        output.draw(DrawCommand::BeginLayer {
+            name: "outline".into(),
+        });
+        output.draw(DrawCommand::SetMarkers {
+            start: None,
+            end: Some(Marker::Arrow),
+        });
+        output.draw(DrawCommand::Line {
+            from: (Mm(10.0), Mm(10.0)),
+            to: (Mm(20.0), Mm(10.0)),
+        });
+        output.draw(DrawCommand::SetLineStyle {
+            style: LineStyle::Center,
+        });
+        output.draw(DrawCommand::Path {
+            points: vec![(Mm(0.0), Mm(0.0)), (Mm(10.0), Mm(0.0)), (Mm(0.0), Mm(5.0))],
+            closed: true,
+        });
+        output.draw(DrawCommand::EndLayer);
+        output.draw(DrawCommand::Circle {
+            at: (Mm(20.0), Mm(20.0)),
+            radius: Mm(5.5),
+        });
+
+        assert_eq!(
+            output.to_string(),
+            r"\begin{tikzpicture}[x=1mm, y=1mm]
+  \begin{scope} % outline
+    \draw[-Stealth] (10,40) -- (20,40);
+    \draw[dash pattern=on 6mm off 0.75mm on 0.125mm off 0.75mm] (0,50) -- (10,50) -- (0,45) -- cycle;
+  \end{scope}
+  \draw[dash pattern=on 6mm off 0.75mm on 0.125mm off 0.75mm] (20,30) circle [radius=5.5];
+\end{tikzpicture}
+"
+        );
+    }
+
+    #[test]
+    fn test_text_clips_and_pages() {
+        let mut output = TikzOutput::new();
+        output.draw(DrawCommand::BeginClip {
+            region: vec![(Mm(0.0), Mm(0.0)), (Mm(1.0), Mm(0.0)), (Mm(0.0), Mm(1.0))],
+        });
+        output.draw(DrawCommand::Text {
+            at: (Mm(0.0), Mm(0.0)),
+            text: "50% of $x_1$".into(),
+            size: Mm(2.54),
+        });
+        output.new_page();
+        let pages = output.pages();
+
+        assert_eq!(
+            pages[0],
+            r"\begin{tikzpicture}[x=1mm, y=1mm]
+  \begin{scope}
+    \clip (0,0) -- (1,0) -- (0,-1) -- cycle;
+    \node[font=\fontsize{7.227pt}{7.227pt}\selectfont] at (0,0) {50\% of \$x\_1\$};
+  \end{scope}
+\end{tikzpicture}
+"
+        );
+        assert_eq!(
+            pages[1],
+            "\\begin{tikzpicture}[x=1mm, y=1mm]\n\\end{tikzpicture}\n"
+        );
+    }
+}
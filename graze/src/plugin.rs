@@ -0,0 +1,228 @@
+//! Packs of builtins that can be added to a runtime, either compiled in
+//! or, with the `dylib` feature, loaded from a shared library.
+
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+
+use crate::{
+    check::Effect,
+    native::NativeFn,
+    runtime::{Context, Error, Stack, Value},
+    util::closest,
+};
+
+pub(crate) type Function = Box<dyn FnMut(&mut Stack, &mut Context) -> Result<Value, Error>>;
+
+/// What a builtin expects and does, for `help` and editor tooling.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FunctionDoc {
+    /// Arguments in stack order and the result, e.g. `pnt2 x y -> Point`.
+    pub signature: String,
+    pub summary: String,
+}
+
+/// Every builtin known to a runtime, by name.
+#[derive(Default)]
+pub struct Functions {
+    /// Where each builtin is in `table`, so that calls hash the name
+    /// once instead of removing and reinserting it.
+    indices: HashMap<SmolStr, usize>,
+    /// `None` while the builtin runs.
+    table: Vec<Option<Function>>,
+    /// Whether each builtin was defined without access to the [`Context`].
+    stateless: Vec<bool>,
+    /// Whether each builtin is a stateless one of the standard library,
+    /// which threads running instructions in parallel have a copy of.
+    parallel: Vec<bool>,
+    docs: Vec<Option<FunctionDoc>>,
+    effects: Vec<Option<Effect>>,
+}
+
+impl Functions {
+    /// Define a builtin. Closures may capture and modify host state.
+    pub fn define_fn<F>(&mut self, name: &str, mut function: F)
+    where
+        F: FnMut(&mut Stack) -> Result<Value, Error> + 'static,
+    {
+        self.insert(
+            name,
+            Box::new(move |stack, _: &mut Context| function(stack)),
+            true,
+        );
+    }
+
+    /// Define a builtin along with its signature, e.g. `row index -> Scalar`,
+    /// and a sentence on what it does.
+    pub fn define_fn_with_doc<F>(&mut self, name: &str, signature: &str, summary: &str, function: F)
+    where
+        F: FnMut(&mut Stack) -> Result<Value, Error> + 'static,
+    {
+        self.define_fn(name, function);
+        self.document(name, signature, summary);
+    }
+
+    /// Define a builtin that changes the stack as `effect` says. Calls
+    /// with too few values on the stack fail before reaching `function`,
+    /// and [`crate::check::check_with`] can check calls of it.
+    pub fn define_fn_with_effect<F>(&mut self, name: &str, effect: Effect, function: F)
+    where
+        F: FnMut(&mut Stack) -> Result<Value, Error> + 'static,
+    {
+        self.define_fn(name, function);
+        self.declare(name, effect);
+        self.document(name, &effect.signature(name), "");
+    }
+
+    /// Define a builtin from a function of typed arguments, which are
+    /// popped and checked before calling it. Its stack effect is declared
+    /// from the types, see [`Functions::define_fn_with_effect`].
+    pub fn define_native<Args, F>(&mut self, name: &str, mut function: F)
+    where
+        F: NativeFn<Args>,
+    {
+        self.insert(
+            name,
+            Box::new(move |stack, _: &mut Context| function.run(stack)),
+            true,
+        );
+        self.declare(name, F::EFFECT);
+        self.document(name, &F::EFFECT.signature(name), "");
+    }
+
+    /// Define a builtin that needs access to the runtime [`Context`].
+    pub fn define_stateful_fn<F>(&mut self, name: &str, function: F)
+    where
+        F: FnMut(&mut Stack, &mut Context) -> Result<Value, Error> + 'static,
+    {
+        self.insert(name, Box::new(function), false);
+    }
+
+    /// Document the builtin `name`, if it is defined.
+    pub fn document(&mut self, name: &str, signature: &str, summary: &str) {
+        if let Some(index) = self.index(name) {
+            self.docs[index] = Some(FunctionDoc {
+                signature: signature.into(),
+                summary: summary.into(),
+            });
+        }
+    }
+
+    /// Declare the stack effect of the builtin `name`, if it is defined.
+    pub fn declare(&mut self, name: &str, effect: Effect) {
+        if let Some(index) = self.index(name) {
+            self.effects[index] = Some(effect);
+        }
+    }
+
+    /// The declared stack effect of the builtin `name`, if any.
+    pub fn effect(&self, name: &str) -> Option<Effect> {
+        self.effects[self.index(name)?]
+    }
+
+    /// How many values the builtin at `index` needs on the stack, if it
+    /// declared its effect.
+    pub(crate) fn needed(&self, index: usize) -> Option<usize> {
+        self.effects[index].map(|effect| effect.needed())
+    }
+
+    /// The documentation of the builtin `name`, if it has any.
+    pub fn doc(&self, name: &str) -> Option<&FunctionDoc> {
+        self.docs[self.index(name)?].as_ref()
+    }
+
+    fn insert(&mut self, name: &str, function: Function, stateless: bool) {
+        match self.indices.get(name) {
+            Some(&index) => {
+                self.table[index] = Some(function);
+                self.stateless[index] = stateless;
+                self.parallel[index] = false;
+                // The old documentation was about the old builtin
+                self.docs[index] = None;
+                self.effects[index] = None;
+            }
+            None => {
+                self.indices.insert(SmolStr::new(name), self.table.len());
+                self.table.push(Some(function));
+                self.stateless.push(stateless);
+                self.parallel.push(false);
+                self.docs.push(None);
+                self.effects.push(None);
+            }
+        }
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.indices.keys().map(SmolStr::as_str)
+    }
+
+    pub(crate) fn index(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    /// The error for calling `name`, which isn't defined.
+    pub(crate) fn not_found(&self, name: &str) -> Error {
+        Error::FunctionNotFound {
+            name: name.into(),
+            suggestion: closest(name, self.names()).map(SmolStr::new),
+        }
+    }
+
+    /// Let the stateless builtins defined so far run in parallel. Only
+    /// called once the standard library is registered.
+    pub(crate) fn allow_parallel(&mut self) {
+        self.parallel.clone_from(&self.stateless);
+    }
+
+    /// Whether the builtin `name` may run on a thread of its own.
+    pub(crate) fn is_parallel(&self, name: &str) -> bool {
+        self.index(name).is_some_and(|index| self.parallel[index])
+    }
+
+    /// Remove a builtin while it runs, so that it can be handed the
+    /// context holding this table.
+    pub(crate) fn take(&mut self, index: usize) -> Option<Function> {
+        self.table.get_mut(index)?.take()
+    }
+
+    pub(crate) fn put_back(&mut self, index: usize, function: Function) {
+        self.table[index] = Some(function);
+    }
+}
+
+/// A named pack of builtins, e.g. gears or paper frames.
+pub trait StdlibModule {
+    fn name(&self) -> &str;
+    fn register(&self, functions: &mut Functions);
+}
+
+/// Export `$module` from a plugin library so [`Runtime::load_module`]
+/// can find it. The plugin must be built with the same compiler and
+/// graze version as the host.
+///
+/// [`Runtime::load_module`]: crate::Runtime::load_module
+#[macro_export]
+macro_rules! export_module {
+    ($module:expr) => {
+        #[no_mangle]
+        pub fn graze_module() -> Box<dyn $crate::StdlibModule> {
+            Box::new($module)
+        }
+    };
+}
+
+#[cfg(feature = "dylib")]
+pub(crate) fn load(
+    path: &std::ffi::OsStr,
+) -> Result<(libloading::Library, Box<dyn StdlibModule>), Error> {
+    let error = |err: libloading::Error| Error::ModuleLoad(err.to_string());
+    // SAFETY: The caller of `Runtime::load_module` vouches for the library
+    unsafe {
+        let library = libloading::Library::new(path).map_err(error)?;
+        let constructor = library
+            .get::<fn() -> Box<dyn StdlibModule>>(b"graze_module")
+            .map_err(error)?;
+        let module = constructor();
+        Ok((library, module))
+    }
+}
@@ -1,19 +1,457 @@
-use std::collections::HashMap;
+use std::{collections::HashSet, path::PathBuf, sync::Arc};
 
 use smol_str::SmolStr;
 use thiserror::Error;
 
 use crate::{
-    ast::{Argument, ExpressionContent, Instruction, Literal, Program},
-    output::{DrawBuffer, DrawCommand},
-    stdlib::{self, Point, Scalar, Vector},
+    ast::{Expression, Program},
+    check::{builtin_effect, check_with, Effect, Error as CheckError},
+    native::NativeFn,
+    output::{DrawBuffer, DrawCommand, LineStyle, Marker, Mm},
+    plugin::{FunctionDoc, Functions, StdlibModule},
+    stdlib::{self, Angle, NumericPolicy, Path, Point, Random, Scalar, Transform, Vector},
+    token::{Position, Span},
 };
 
+mod bytecode;
+mod cache;
+mod parallel;
+mod scope;
+
+pub use cache::Cache;
+use scope::{Scopes, Variable};
+
 pub struct Runtime<Backend> {
     stack: Stack,
-    variables: HashMap<SmolStr, Value>,
-    functions: HashMap<SmolStr, Function>,
+    context: Context,
     draw: Backend,
+    warnings: Vec<Warning>,
+    /// Every `#param` run so far, in the order first declared.
+    params: Vec<Param>,
+    /// Start of the expression currently being executed.
+    position: Position,
+    /// Source text of the expression currently being executed.
+    span: Span,
+    stack_policy: StackPolicy,
+    /// How many threads [`Runtime::execute`] may use.
+    threads: usize,
+    /// Shared libraries that loaded modules came from. Declared last so
+    /// the functions they contain are dropped first.
+    #[cfg(feature = "dylib")]
+    libraries: Vec<libloading::Library>,
+}
+
+/// What happens to the values left on the stack once an instruction ends.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub enum StackPolicy {
+    /// Start every instruction with an empty stack.
+    #[default]
+    Clear,
+    /// Keep values around for the following instructions.
+    Keep,
+}
+
+/// Limits for running scripts that can't be trusted, e.g. in a web
+/// playground, and how they do arithmetic. The default limits nothing.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct RuntimeConfig {
+    /// Directories builtins may read files from, or `None` for anywhere.
+    pub readable_paths: Option<Vec<PathBuf>>,
+    /// How many instructions may run, counting each loop iteration.
+    pub max_instructions: Option<usize>,
+    /// How many bytes of text the script may print.
+    pub max_output: Option<usize>,
+    /// How many times `#for` loops may run their body, all loops together.
+    pub max_loop_iterations: Option<usize>,
+    /// How deeply functions may call each other through [`Context::call`].
+    pub max_call_depth: Option<usize>,
+    /// How many values the stack may hold.
+    pub max_stack: Option<usize>,
+    /// How many commands the script may draw.
+    pub max_draw_commands: Option<usize>,
+    /// What arithmetic does with integers that have no exact integer result.
+    pub numeric_policy: NumericPolicy,
+}
+
+/// A limit of [`RuntimeConfig`] that a script ran into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Limit {
+    Instructions,
+    Output,
+    LoopIterations,
+    CallDepth,
+    Stack,
+    DrawCommands,
+}
+
+impl std::fmt::Display for Limit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Limit::Instructions => write!(f, "instruction"),
+            Limit::Output => write!(f, "output"),
+            Limit::LoopIterations => write!(f, "loop iteration"),
+            Limit::CallDepth => write!(f, "call depth"),
+            Limit::Stack => write!(f, "stack size"),
+            Limit::DrawCommands => write!(f, "draw command"),
+        }
+    }
+}
+
+/// Runtime state that builtins may inspect and modify.
+#[derive(Default)]
+pub struct Context {
+    transform: Transform,
+    saved_transforms: Vec<Transform>,
+    /// Layers and clip regions that are open, innermost last.
+    open_groups: Vec<Group>,
+    pending: Vec<DrawCommand>,
+    output: String,
+    /// Where printed text goes instead of `output`, if set.
+    log: Option<Box<dyn std::io::Write>>,
+    random: Random,
+    /// The visible drawing area as `(x, y, width, height)`, once known.
+    visible: Option<(f64, f64, f64, f64)>,
+    axes: Axes,
+    /// Markers at the start and end of lines drawn from now on.
+    markers: (Option<Marker>, Option<Marker>),
+    line_style: LineStyle,
+    /// The grid points and vectors are snapped to, if any.
+    snap: Option<Scalar>,
+    /// Names of the symbols defined so far.
+    symbols: HashSet<SmolStr>,
+    /// The transforms in effect outside the symbols being defined,
+    /// innermost last. Symbols are drawn without any transform.
+    defining: Vec<Transform>,
+    scopes: Scopes,
+    functions: Functions,
+    config: RuntimeConfig,
+    /// Bytes printed so far, whether to the log or to `output`.
+    printed: usize,
+    /// Instructions run so far.
+    executed: usize,
+    /// Loop iterations run so far.
+    iterations: usize,
+    /// Calls through [`Context::call`] that haven't returned yet.
+    depth: usize,
+    /// Commands handed to the backend so far.
+    drawn: usize,
+}
+
+impl Context {
+    /// The transform applied to everything drawn from now on.
+    pub fn transform(&self) -> Transform {
+        self.transform
+    }
+
+    /// Apply `transform` before the current one.
+    pub fn compose_transform(&mut self, transform: Transform) {
+        self.transform = self.transform.then(transform);
+    }
+
+    pub fn push_transform(&mut self) {
+        self.saved_transforms.push(self.transform);
+    }
+
+    pub fn pop_transform(&mut self) -> Result<(), Error> {
+        self.transform = self
+            .saved_transforms
+            .pop()
+            .ok_or(Error::TransformStackUnderflow)?;
+        Ok(())
+    }
+
+    /// Queue a command for the backend. It is drawn once the
+    /// current builtin returns.
+    pub fn draw(&mut self, command: DrawCommand) {
+        // Past the limit, the runtime fails once the builtin returns
+        let queued = self.drawn + self.pending.len();
+        if self
+            .config
+            .max_draw_commands
+            .is_some_and(|max| queued > max)
+        {
+            return;
+        }
+        self.pending.push(command);
+    }
+
+    /// Write a line of text for the user to read.
+    pub fn print(&mut self, text: impl std::fmt::Display) {
+        let text = text.to_string();
+        self.printed += text.len() + 1;
+        if self.config.max_output.is_some_and(|max| self.printed > max) {
+            return;
+        }
+        match &mut self.log {
+            // A broken log shouldn't abort the drawing
+            Some(log) => writeln!(log, "{text}").unwrap_or_default(),
+            None => {
+                use std::fmt::Write;
+                writeln!(self.output, "{text}").expect("Writing to a String never fails")
+            }
+        }
+    }
+
+    /// Everything printed while no log was set.
+    pub fn output(&self) -> &str {
+        &self.output
+    }
+
+    /// The visible drawing area as `(x, y, width, height)`, once `#screen`
+    /// or `#view` set it, in the coordinates `#origin` and `#ydir` chose.
+    pub fn visible_area(&self) -> Option<(f64, f64, f64, f64)> {
+        let (x, y, width, height) = self.visible?;
+        let page = self.page();
+        let (x, y) = (x - page.e, (y - page.f) * page.d);
+        let y = if self.axes.up { y - height } else { y };
+        Some((x, y, width, height))
+    }
+
+    /// The transform from the coordinates `#origin` and `#ydir` chose to
+    /// those of the backend.
+    fn page(&self) -> Transform {
+        let (x, y, _, height) = self.visible.unwrap_or_default();
+        let (e, f) = match self.axes.bottom {
+            None => (0.0, 0.0),
+            Some(false) => (x, y),
+            Some(true) => (x, y + height),
+        };
+        let d = if self.axes.up { -1.0 } else { 1.0 };
+        Transform {
+            d,
+            e,
+            f,
+            ..Transform::IDENTITY
+        }
+    }
+
+    /// Every builtin known to the runtime.
+    pub fn functions(&self) -> &Functions {
+        &self.functions
+    }
+
+    /// Call the builtin `name` on `stack`, e.g. to evaluate a function
+    /// the script passed by name. A builtin can't call itself this way.
+    pub fn call(&mut self, name: &str, stack: &mut Stack) -> Result<Value, Error> {
+        let index = self
+            .functions
+            .index(name)
+            .ok_or_else(|| self.functions.not_found(name))?;
+        self.call_index(index, name, stack, vec![])
+    }
+
+    /// Call the builtin at `index` of the function table with `options`,
+    /// once [`Context::call`] or the compiler looked up `name`. Fails if
+    /// the builtin didn't read every option.
+    fn call_index(
+        &mut self,
+        index: usize,
+        name: &str,
+        stack: &mut Stack,
+        options: Vec<(SmolStr, Value)>,
+    ) -> Result<Value, Error> {
+        if self
+            .config
+            .max_call_depth
+            .is_some_and(|max| self.depth >= max)
+        {
+            return Err(Error::LimitExceeded(Limit::CallDepth));
+        }
+        let found = stack.values().len();
+        if let Some(needed) = self
+            .functions
+            .needed(index)
+            .filter(|&needed| needed > found)
+        {
+            return Err(Error::NotEnoughArguments {
+                function: name.into(),
+                needed,
+                found,
+            });
+        }
+        let mut function = self
+            .functions
+            .take(index)
+            .ok_or_else(|| Error::FunctionNotFound {
+                name: name.into(),
+                suggestion: None,
+            })?;
+        // Builtins calling others must not hand their own options on
+        let outer = std::mem::replace(&mut stack.options, options);
+        stack.numeric_policy = self.config.numeric_policy;
+        stack.snap = self.snap;
+        self.depth += 1;
+        let result = function(stack, self);
+        self.depth -= 1;
+        self.functions.put_back(index, function);
+        let unread = std::mem::replace(&mut stack.options, outer);
+        match unread.into_iter().next() {
+            Some((option, _)) if result.is_ok() => Err(Error::UnknownOption {
+                function: name.into(),
+                option,
+            }),
+            _ => result,
+        }
+    }
+
+    /// Check that builtins may read the file at `path`, returning its
+    /// canonical form if they may.
+    pub fn readable_path(&self, path: impl AsRef<std::path::Path>) -> Result<PathBuf, Error> {
+        let path = path.as_ref();
+        let Some(allowed) = &self.config.readable_paths else {
+            return Ok(path.to_path_buf());
+        };
+        let denied = || Error::AccessDenied(path.to_path_buf());
+        let canonical = path.canonicalize().map_err(|_| denied())?;
+        allowed
+            .iter()
+            .filter_map(|directory| directory.canonicalize().ok())
+            .any(|directory| canonical.starts_with(directory))
+            .then_some(canonical)
+            .ok_or_else(denied)
+    }
+
+    /// Fail if the script went past one of the configured limits.
+    fn check_limits(&self, stack: &Stack) -> Result<(), Error> {
+        let config = &self.config;
+        let exceeded = [
+            (self.executed, config.max_instructions, Limit::Instructions),
+            (self.printed, config.max_output, Limit::Output),
+            (
+                self.iterations,
+                config.max_loop_iterations,
+                Limit::LoopIterations,
+            ),
+            (stack.len(), config.max_stack, Limit::Stack),
+            (self.drawn, config.max_draw_commands, Limit::DrawCommands),
+        ]
+        .into_iter()
+        .find(|(count, max, _)| max.is_some_and(|max| *count > max));
+        match exceeded {
+            Some((_, _, limit)) => Err(Error::LimitExceeded(limit)),
+            None => Ok(()),
+        }
+    }
+
+    /// Read a variable of the running script, like `$name` does.
+    pub fn variable(&mut self, name: &str) -> Option<Value> {
+        self.read(self.scopes.names.index(name)?)
+    }
+
+    /// Read the variable whose name was interned at `index`.
+    fn read(&mut self, index: usize) -> Option<Value> {
+        let variable = self.scopes.get_mut(index)?;
+        variable.used = true;
+        Some(variable.value.clone())
+    }
+
+    /// The generator behind the random builtins.
+    pub fn random(&mut self) -> &mut Random {
+        &mut self.random
+    }
+
+    /// The markers at the start and end of lines drawn from now on.
+    pub fn markers(&self) -> (Option<Marker>, Option<Marker>) {
+        self.markers
+    }
+
+    pub fn set_markers(&mut self, start: Option<Marker>, end: Option<Marker>) {
+        self.markers = (start, end);
+        self.draw(DrawCommand::SetMarkers { start, end });
+    }
+
+    /// The grid spacing new points and vectors are snapped to, if any.
+    pub fn snap(&self) -> Option<Scalar> {
+        self.snap
+    }
+
+    /// Snap the points and vectors made with `pnt2` and `vec2` from now on
+    /// to multiples of `spacing`, or stop snapping.
+    pub fn set_snap(&mut self, spacing: Option<Scalar>) {
+        self.snap = spacing;
+    }
+
+    /// Whether `#symbol` defined `name` earlier, so that it can be placed.
+    pub fn has_symbol(&self, name: &str) -> bool {
+        self.symbols.contains(name)
+    }
+
+    /// The dash pattern of everything drawn from now on.
+    pub fn line_style(&self) -> LineStyle {
+        self.line_style
+    }
+
+    pub fn set_line_style(&mut self, style: LineStyle) {
+        self.line_style = style;
+        self.draw(DrawCommand::SetLineStyle { style });
+    }
+
+    pub fn begin_layer(&mut self, name: SmolStr) {
+        self.open_groups.push(Group::Layer);
+        self.draw(DrawCommand::BeginLayer { name });
+    }
+
+    pub fn end_layer(&mut self) -> Result<(), Error> {
+        if self.open_groups.last() != Some(&Group::Layer) {
+            return Err(Error::LayerUnderflow);
+        }
+        self.open_groups.pop();
+        self.draw(DrawCommand::EndLayer);
+        Ok(())
+    }
+
+    /// Only draw inside the polygon `region` until the matching
+    /// [`Context::end_clip`].
+    pub fn begin_clip(&mut self, region: Vec<(Mm, Mm)>) {
+        self.open_groups.push(Group::Clip);
+        self.draw(DrawCommand::BeginClip { region });
+    }
+
+    pub fn end_clip(&mut self) -> Result<(), Error> {
+        if self.open_groups.last() != Some(&Group::Clip) {
+            return Err(Error::ClipUnderflow);
+        }
+        self.open_groups.pop();
+        self.draw(DrawCommand::EndClip);
+        Ok(())
+    }
+
+    /// Draw in the local coordinate frame `frame` until the matching
+    /// [`Context::end_frame`].
+    pub fn begin_frame(&mut self, frame: Transform) {
+        self.open_groups.push(Group::Frame(self.transform));
+        self.compose_transform(frame);
+    }
+
+    /// Go back to the coordinates from before the innermost frame.
+    pub fn end_frame(&mut self) -> Result<(), Error> {
+        let Some(&Group::Frame(outside)) = self.open_groups.last() else {
+            return Err(Error::FrameUnderflow);
+        };
+        self.open_groups.pop();
+        self.transform = outside;
+        Ok(())
+    }
+}
+
+/// How the script's coordinates map onto the backend's, set with `#origin`
+/// and `#ydir`.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+struct Axes {
+    /// Whether the origin is at the bottom left corner of the visible area
+    /// instead of the top left one, once `#origin` put it there.
+    bottom: Option<bool>,
+    /// Whether y grows upwards.
+    up: bool,
+}
+
+/// Something opened by a builtin that has to be closed in reverse order.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Group {
+    Layer,
+    Clip,
+    /// A local frame, holding the transform from outside it.
+    Frame(Transform),
 }
 
 impl<Backend> Default for Runtime<Backend>
@@ -23,20 +461,219 @@ where
     fn default() -> Self {
         let mut runtime = Self {
             stack: Stack::default(),
-            variables: HashMap::default(),
-            functions: HashMap::default(),
+            context: Context::default(),
             draw: Backend::default(),
+            warnings: Vec::new(),
+            params: Vec::new(),
+            position: Position::default(),
+            span: Span::default(),
+            stack_policy: StackPolicy::default(),
+            threads: 1,
+            #[cfg(feature = "dylib")]
+            libraries: Vec::new(),
         };
 
         stdlib::register(&mut runtime);
+        runtime.context.functions.allow_parallel();
+        let functions = &mut runtime.context.functions;
+        for doc in stdlib::DOCS {
+            functions.document(doc.name, doc.signature, doc.summary);
+            if let Some(effect) = builtin_effect(doc.name) {
+                functions.declare(doc.name, effect);
+            }
+        }
 
         runtime
     }
 }
 
 impl<Backend> Runtime<Backend> {
-    pub fn define_fn(&mut self, name: &str, function: Function) {
-        self.functions.insert(SmolStr::new(name), function);
+    /// Define a builtin. Closures may capture and modify host state.
+    pub fn define_fn<F>(&mut self, name: &str, function: F)
+    where
+        F: FnMut(&mut Stack) -> Result<Value, Error> + 'static,
+    {
+        self.context.functions.define_fn(name, function);
+    }
+
+    /// Define a builtin along with its signature, e.g. `row index -> Scalar`,
+    /// and a sentence on what it does, for `help` and editor tooling.
+    pub fn define_fn_with_doc<F>(&mut self, name: &str, signature: &str, summary: &str, function: F)
+    where
+        F: FnMut(&mut Stack) -> Result<Value, Error> + 'static,
+    {
+        self.context
+            .functions
+            .define_fn_with_doc(name, signature, summary, function);
+    }
+
+    /// Define a builtin that changes the stack as `effect` says, see
+    /// [`Functions::define_fn_with_effect`].
+    pub fn define_fn_with_effect<F>(&mut self, name: &str, effect: Effect, function: F)
+    where
+        F: FnMut(&mut Stack) -> Result<Value, Error> + 'static,
+    {
+        self.context
+            .functions
+            .define_fn_with_effect(name, effect, function);
+    }
+
+    /// Define a builtin from a function of typed arguments, see
+    /// [`Functions::define_native`].
+    pub fn define_native<Args, F>(&mut self, name: &str, function: F)
+    where
+        F: NativeFn<Args>,
+    {
+        self.context.functions.define_native(name, function);
+    }
+
+    /// Define a builtin that needs access to the runtime [`Context`].
+    pub fn define_stateful_fn<F>(&mut self, name: &str, function: F)
+    where
+        F: FnMut(&mut Stack, &mut Context) -> Result<Value, Error> + 'static,
+    {
+        self.context.functions.define_stateful_fn(name, function);
+    }
+
+    /// Names of every builtin, in no particular order.
+    pub fn function_names(&self) -> impl Iterator<Item = &str> {
+        self.context.functions.names()
+    }
+
+    /// The documentation of the builtin `name`, if it has any. Every
+    /// builtin of the standard library has.
+    pub fn function_doc(&self, name: &str) -> Option<&FunctionDoc> {
+        self.context.functions.doc(name)
+    }
+
+    /// The declared stack effect of the builtin `name`, if any. Every
+    /// builtin of the standard library has one.
+    pub fn function_effect(&self, name: &str) -> Option<Effect> {
+        self.context.functions.effect(name)
+    }
+
+    /// Find type mismatches and stack underflows in `program` without
+    /// running it, knowing about the builtins defined so far.
+    pub fn check(&self, program: &Program) -> Vec<CheckError> {
+        check_with(program, &self.context.functions)
+    }
+
+    pub fn register_module(&mut self, module: &dyn StdlibModule) {
+        module.register(&mut self.context.functions);
+    }
+
+    /// Load a plugin library that exports a module with [`export_module!`].
+    ///
+    /// # Safety
+    /// Loading a library runs its initialization code, and the library must
+    /// have been built against this exact version of graze.
+    ///
+    /// [`export_module!`]: crate::export_module
+    #[cfg(feature = "dylib")]
+    pub unsafe fn load_module(&mut self, path: impl AsRef<std::ffi::OsStr>) -> Result<(), Error> {
+        let (library, module) = crate::plugin::load(path.as_ref())?;
+        self.register_module(module.as_ref());
+        self.libraries.push(library);
+        Ok(())
+    }
+
+    /// Register every builtin of `extension`.
+    pub fn install(&mut self, extension: impl Extension) {
+        extension.register(self);
+    }
+
+    /// Bind a global variable from host code, e.g. to pass parameters to a
+    /// script before running it. Like a `#param`, it takes precedence over
+    /// a `#let` of the script at the top level, which only gives a default
+    /// for when the host doesn't. Fails if `name` is a constant.
+    pub fn set_variable(&mut self, name: &str, value: Value) -> Result<(), Error> {
+        let scopes = &mut self.context.scopes;
+        match scopes.global_mut(name) {
+            Some(global) if global.constant => Err(Error::ConstantReassigned(name.into())),
+            Some(global) => {
+                global.value = value;
+                global.host = true;
+                Ok(())
+            }
+            None => {
+                // Nothing warns about variables the host bound
+                let index = scopes.names.intern(&name.into());
+                let variable = Variable {
+                    value,
+                    constant: false,
+                    used: true,
+                    host: true,
+                };
+                scopes.bind_in(0, index, variable);
+                Ok(())
+            }
+        }
+    }
+
+    /// Read a variable from host code, e.g. a result computed by a script.
+    pub fn get_variable(&self, name: &str) -> Option<&Value> {
+        self.context
+            .scopes
+            .find(name)
+            .map(|variable| &variable.value)
+    }
+
+    /// Everything printed by the script so far, unless it went to a log.
+    pub fn output(&self) -> &str {
+        self.context.output()
+    }
+
+    /// Send everything the script prints to `log` instead of [`Runtime::output`].
+    pub fn set_log(&mut self, log: impl std::io::Write + 'static) {
+        self.context.log = Some(Box::new(log));
+    }
+
+    pub fn set_stack_policy(&mut self, policy: StackPolicy) {
+        self.stack_policy = policy;
+    }
+
+    /// Run top-level instructions that don't depend on each other on up to
+    /// `threads` threads. Only instructions calling stateless builtins of
+    /// the standard library qualify, and [`StackPolicy::Keep`] runs
+    /// everything in order. The default of 1 never starts a thread.
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
+
+    /// The backend commands are drawn to, e.g. to configure it before
+    /// running a script.
+    pub fn backend_mut(&mut self) -> &mut Backend {
+        &mut self.draw
+    }
+
+    /// Restrict what scripts run from now on may do.
+    pub fn set_config(&mut self, config: RuntimeConfig) {
+        self.context.config = config;
+    }
+
+    /// The source text of the expression that is running, or that ran
+    /// last. After an error, this is the expression it came from.
+    pub fn expression_span(&self) -> Span {
+        self.span.clone()
+    }
+
+    /// The tunable parameters the script declared with `#param`, for
+    /// frontends to show sliders for. Set a new value with
+    /// [`Runtime::set_variable`] and run the script again to apply it.
+    pub fn params(&self) -> &[Param] {
+        &self.params
+    }
+
+    /// Everything suspicious noticed so far.
+    pub fn warnings(&self) -> &[Warning] {
+        &self.warnings
+    }
+
+    fn warn(&mut self, warning: Warning) {
+        // Loops can repeat the same problem many times over
+        if self.warnings.last() != Some(&warning) {
+            self.warnings.push(warning);
+        }
     }
 }
 
@@ -45,105 +682,264 @@ where
     Backend: DrawBuffer,
 {
     pub fn execute(&mut self, program: Program) -> Result<(), Error> {
-        for instruction in program.instructions {
-            self.execute_instruction(instruction)?;
+        if self.threads > 1 && self.stack_policy == StackPolicy::Clear {
+            return self.execute_parallel(program);
+        }
+        self.execute_with_hooks(program, &mut ())
+    }
+
+    /// Like [`Runtime::execute`], but let `observer` watch every expression run.
+    pub fn execute_with_hooks(
+        &mut self,
+        program: Program,
+        observer: &mut dyn Observer,
+    ) -> Result<(), Error> {
+        self.resolve(&program)?;
+        // Compiling one instruction at a time keeps large programs from
+        // holding all of their bytecode at once
+        let mut ops = Vec::new();
+        for instruction in &program.instructions {
+            ops.clear();
+            let context = &mut self.context;
+            bytecode::compile(
+                instruction,
+                &context.functions,
+                &mut context.scopes.names,
+                &mut ops,
+            );
+            self.run(&ops, observer)?;
         }
+        self.warn_unused_variables();
         Ok(())
     }
 
-    fn execute_instruction(&mut self, instruction: Instruction) -> Result<(), Error> {
-        for expression in instruction.expressions {
-            let value = self.execute_expression(expression.content)?;
-            self.stack.push(value);
-            if !expression.draw_result {
-                continue;
-            }
+    /// Fail before running `program` if it calls a builtin that doesn't
+    /// exist or reads a variable that is never bound, pointing at the
+    /// expression that does.
+    fn resolve(&mut self, program: &Program) -> Result<(), Error> {
+        let context = &self.context;
+        bytecode::resolve(program, &context.functions, &context.scopes).map_err(
+            |(err, expression)| {
+                self.position = expression.position;
+                self.span.clone_from(&expression.span);
+                err
+            },
+        )
+    }
 
-            if let Some(cmd) = value.into() {
-                self.draw.draw(cmd);
-            }
+    fn snapshot(&self) -> Snapshot<'_> {
+        Snapshot {
+            stack: self.stack.values(),
+            scopes: &self.context.scopes,
         }
+    }
 
-        self.stack.clear();
+    /// Read the variable whose name was interned at `index`.
+    fn load(&mut self, index: usize) -> Result<Value, Error> {
+        self.context.read(index).ok_or_else(|| {
+            let scopes = &self.context.scopes;
+            scopes.not_found(scopes.names.name(index), [])
+        })
+    }
 
-        Ok(())
+    #[cfg(test)]
+    fn lookup(&mut self, name: &str) -> Result<Value, Error> {
+        match self.context.scopes.names.index(name) {
+            Some(index) => self.load(index),
+            None => Err(self.context.scopes.not_found(name, [])),
+        }
     }
 
-    fn execute_expression(&mut self, expression: ExpressionContent) -> Result<Value, Error> {
-        match expression {
-            ExpressionContent::Literal(literal) => {
-                let value = match literal {
-                    Literal::Number(number) => Value::Scalar(number.try_into()?),
-                };
-                Ok(value)
-            }
-            ExpressionContent::Variable(name) => self
-                .variables
-                .get(&name)
-                .copied()
-                .ok_or(Error::VariableNotFound(name)),
-            ExpressionContent::FunctionCall { name, args } => {
-                for arg in args {
-                    let value = self.execute_argument(arg)?;
-                    self.stack.push(value);
-                }
+    /// Bind `name` in the innermost scope, shadowing outer bindings.
+    fn bind(&mut self, name: &SmolStr, value: Value, constant: bool) -> Result<(), Error> {
+        let scopes = &mut self.context.scopes;
+        let index = scopes.names.intern(name);
+        self.bind_index(index, value, constant)
+    }
 
-                let function = self
-                    .functions
-                    .get(&name)
-                    .ok_or(Error::FunctionNotFound(name))?;
+    /// Like [`Runtime::bind`], for the name interned at `index`.
+    fn bind_index(&mut self, index: usize, value: Value, constant: bool) -> Result<(), Error> {
+        let scopes = &mut self.context.scopes;
+        let depth = scopes.len() - 1;
+        let constant = match scopes.get_in(depth, index) {
+            Some(earlier) if earlier.constant => {
+                return Err(Error::ConstantReassigned(scopes.names.name(index).clone()));
+            }
+            // The value the host set wins, see `set_variable`
+            Some(earlier) if earlier.host => return Ok(()),
+            _ => constant,
+        };
+        let variable = Variable {
+            value,
+            constant,
+            used: false,
+            host: false,
+        };
+        scopes.bind(index, variable);
+        Ok(())
+    }
 
-                function(&mut self.stack)
+    /// Report variables of the innermost scope that were never read.
+    fn warn_unused_variables(&mut self) {
+        let mut unused = self.context.scopes.take_unused();
+        unused.sort();
+        for name in unused {
+            self.warn(Warning::UnusedVariable(name));
+        }
+    }
+
+    fn draw_value(&mut self, value: Value) {
+        match value {
+            Value::List(items) => {
+                for item in items.iter() {
+                    self.draw_value(item.clone());
+                }
             }
-            ExpressionContent::Let { name, init } => {
-                let value = if let Some(init) = init {
-                    self.execute_argument(init)?
-                } else {
-                    self.stack.pop()?
-                };
-                self.variables.insert(name, value);
-                Ok(value)
-            }
-            ExpressionContent::Screen(argument, argument1) => {
-                let (Value::Scalar(x), Value::Scalar(y)) = (
-                    self.execute_argument(argument)?,
-                    self.execute_argument(argument1)?,
-                ) else {
-                    return Err(Error::InvalidArgument);
-                };
+            Value::Group(group) => {
+                if let Some(style) = group.style {
+                    self.emit(DrawCommand::SetLineStyle { style });
+                }
+                for item in &group.items {
+                    self.draw_value(item.clone());
+                }
+                if group.style.is_some() {
+                    let style = self.context.line_style();
+                    self.emit(DrawCommand::SetLineStyle { style });
+                }
+            }
+            value => {
+                if let Some(cmd) = value.into() {
+                    self.emit(cmd);
+                }
+            }
+        }
+    }
 
-                self.draw.draw(DrawCommand::Resize {
-                    x: x.into(),
-                    y: y.into(),
-                });
+    /// Send a command to the backend, mapped through the current transform
+    /// and onto the page.
+    fn emit(&mut self, command: DrawCommand) {
+        let mut transform = self.context.transform();
+        // Symbols are drawn in coordinates of their own, not the page's
+        if self.context.defining.is_empty() {
+            transform = self.context.page().then(transform);
+        }
+        let command = transform.apply(command);
+        match &command {
+            DrawCommand::Resize { x, y } if self.context.visible.is_none() => {
+                self.context.visible = Some((0.0, 0.0, x.0, y.0));
+            }
+            DrawCommand::SetView {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => self.context.visible = Some((x.0, y.0, width.0, height.0)),
+            _ => {}
+        }
+        if self.context.defining.is_empty() && !self.is_visible(&command) {
+            self.warn(Warning::OutsideScreen { at: self.position });
+        }
+        self.context.drawn += 1;
+        self.draw.draw(command);
+    }
 
-                Ok(Value::Void)
+    fn is_visible(&self, command: &DrawCommand) -> bool {
+        let Some((x, y, width, height)) = self.context.visible else {
+            return true;
+        };
+        let inside = |px: f64, py: f64| {
+            let tolerance = 1e-9;
+            (x - tolerance..=x + width + tolerance).contains(&px)
+                && (y - tolerance..=y + height + tolerance).contains(&py)
+        };
+        match command {
+            DrawCommand::Line { from, to } => {
+                inside(from.0 .0, from.1 .0) && inside(to.0 .0, to.1 .0)
+            }
+            DrawCommand::Circle { at, radius } => {
+                inside(at.0 .0 - radius.0, at.1 .0 - radius.0)
+                    && inside(at.0 .0 + radius.0, at.1 .0 + radius.0)
             }
+            DrawCommand::Path { points, .. } => points.iter().all(|(x, y)| inside(x.0, y.0)),
+            _ => true,
         }
     }
 
-    fn execute_argument(&mut self, argument: Argument) -> Result<Value, Error> {
-        match argument {
-            Argument::Variable(name) => self
-                .variables
-                .get(&name)
-                .copied()
-                .ok_or(Error::VariableNotFound(name)),
-            Argument::Literal(literal) => match literal {
-                Literal::Number(number) => Ok(Value::Scalar(number.try_into()?)),
-            },
-            Argument::Parenthesized(content) => self.execute_expression(*content),
+    fn close_groups(&mut self) {
+        for group in std::mem::take(&mut self.context.open_groups)
+            .into_iter()
+            .rev()
+        {
+            match group {
+                Group::Layer => self.draw.draw(DrawCommand::EndLayer),
+                Group::Clip => self.draw.draw(DrawCommand::EndClip),
+                Group::Frame(_) => {}
+            }
         }
     }
 
-    pub fn finish(mut self) {
-        self.draw.flush()
+    /// Close any layers and clips left open, flush the backend and hand
+    /// it back.
+    pub fn finish(mut self) -> Backend {
+        self.close_groups();
+        self.draw.flush();
+        self.draw
+    }
+}
+
+/// Watches a program run, e.g. to step through it or explain what it does.
+pub trait Observer {
+    /// Called right before `expression` runs.
+    fn before(&mut self, _expression: &Expression, _state: Snapshot) {}
+
+    /// Called once the expression starting at `position` has run and its
+    /// result was pushed and drawn.
+    fn after(&mut self, _position: Position, _state: Snapshot) {}
+}
+
+/// Watch nothing.
+impl Observer for () {}
+
+/// The state of a running program, as seen by an [`Observer`].
+#[derive(Clone, Copy)]
+pub struct Snapshot<'r> {
+    stack: &'r [Value],
+    scopes: &'r Scopes,
+}
+
+impl<'r> Snapshot<'r> {
+    /// The current instruction's stack, bottom first.
+    pub fn stack(&self) -> &'r [Value] {
+        self.stack
+    }
+
+    pub fn variable(&self, name: &str) -> Option<&'r Value> {
+        self.scopes.find(name).map(|variable| &variable.value)
+    }
+
+    /// Every variable in scope, sorted by name. Shadowed bindings are left out.
+    pub fn variables(&self) -> Vec<(&'r str, &'r Value)> {
+        let mut variables = self
+            .scopes
+            .visible()
+            .map(|(name, variable)| (name.as_str(), &variable.value))
+            .collect::<Vec<_>>();
+        variables.sort_by_key(|(name, _)| *name);
+        variables
     }
 }
 
 #[derive(Default)]
 pub struct Stack {
     stack: Vec<Value>,
+    /// The `key=value` options passed to the running builtin.
+    options: Vec<(SmolStr, Value)>,
+    numeric_policy: NumericPolicy,
+    snap: Option<Scalar>,
+    /// Where the arguments of the running builtin start, if it collects
+    /// all of them.
+    base: usize,
 }
 
 impl Stack {
@@ -158,38 +954,1121 @@ impl Stack {
         self.stack.pop().ok_or(Error::StackUnderflow)
     }
 
+    /// Push all but the last of `values` and return the last one, for
+    /// builtins returning several values. The next expression finds them
+    /// on the stack in order, the last one drawn if the result is.
+    pub fn push_results(&mut self, values: impl IntoIterator<Item = Value>) -> Value {
+        let mut last = Value::Void;
+        for value in values {
+            self.push(std::mem::replace(&mut last, value));
+        }
+        last
+    }
+
+    pub fn len(&self) -> usize {
+        self.stack.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stack.is_empty()
+    }
+
+    /// Every value, bottom first.
+    pub fn values(&self) -> &[Value] {
+        &self.stack
+    }
+
+    /// Remove and return every argument of the running builtin, bottom
+    /// first. For `list 1 2` nested in another call, these are 1 and 2
+    /// but not the arguments of the outer call pushed before them.
+    pub fn take_all(&mut self) -> Vec<Value> {
+        let base = self.base.min(self.stack.len());
+        self.stack.split_off(base)
+    }
+
+    /// Remove and return the option `name` passed to the running builtin,
+    /// e.g. the 4 of `text $p "label" size=4`. Options left unread once
+    /// the builtin returns are an error.
+    pub fn option(&mut self, name: &str) -> Option<Value> {
+        let index = self.options.iter().position(|(key, _)| key == name)?;
+        Some(self.options.remove(index).1)
+    }
+
+    /// How the running builtin should treat integer arithmetic whose
+    /// result isn't an integer, see [`RuntimeConfig::numeric_policy`].
+    pub fn numeric_policy(&self) -> NumericPolicy {
+        self.numeric_policy
+    }
+
+    /// `value` snapped to the grid of `snap_mode`, if it is on. Builtins
+    /// constructing geometry call this on what they make.
+    pub fn snapped(&self, value: Value) -> Value {
+        match self.snap {
+            Some(spacing) => stdlib::snapped(value, spacing),
+            None => value,
+        }
+    }
+
     fn clear(&mut self) {
         self.stack.clear();
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
+    #[default]
     Void,
     Scalar(Scalar),
+    Bool(bool),
+    String(SmolStr),
     Point(Point),
     Vector(Vector),
     Line(Point, Vector),
+    Circle(Point, Scalar),
+    Path(Arc<Path>),
+    Angle(Angle),
+    Transform(Transform),
+    List(Arc<Vec<Value>>),
+    Group(Arc<stdlib::Group>),
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Void => write!(f, "void"),
+            Value::Scalar(scalar) => write!(f, "{scalar}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::String(string) => write!(f, "{string}"),
+            Value::Point(point) => write!(f, "{point}"),
+            Value::Vector(vector) => write!(f, "{vector}"),
+            Value::Line(start, direction) => write!(f, "line {start} {direction}"),
+            Value::Circle(center, radius) => write!(f, "circle {center} {radius}"),
+            Value::Path(path) => write!(f, "{path}"),
+            Value::Angle(angle) => write!(f, "{angle}"),
+            Value::Transform(transform) => write!(f, "{transform}"),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{item}")?;
+                }
+                write!(f, "]")
+            }
+            Value::Group(group) => write!(f, "{group}"),
+        }
+    }
+}
+
+/// The type of a [`Value`], without its contents.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum ValueKind {
+    Void,
+    Scalar,
+    Bool,
+    String,
+    Point,
+    Vector,
+    Line,
+    Circle,
+    Path,
+    Angle,
+    Transform,
+    List,
+    Group,
+}
+
+impl std::fmt::Display for ValueKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
+impl Value {
+    pub fn kind(&self) -> ValueKind {
+        match self {
+            Value::Void => ValueKind::Void,
+            Value::Scalar(_) => ValueKind::Scalar,
+            Value::Bool(_) => ValueKind::Bool,
+            Value::String(_) => ValueKind::String,
+            Value::Point(_) => ValueKind::Point,
+            Value::Vector(_) => ValueKind::Vector,
+            Value::Line(..) => ValueKind::Line,
+            Value::Circle(..) => ValueKind::Circle,
+            Value::Path(_) => ValueKind::Path,
+            Value::Angle(_) => ValueKind::Angle,
+            Value::Transform(_) => ValueKind::Transform,
+            Value::List(_) => ValueKind::List,
+            Value::Group(_) => ValueKind::Group,
+        }
+    }
+
+    pub fn as_scalar(&self) -> Option<Scalar> {
+        match self {
+            Value::Scalar(scalar) => Some(*scalar),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(bool) => Some(*bool),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(string) => Some(string),
+            _ => None,
+        }
+    }
+
+    pub fn as_point(&self) -> Option<Point> {
+        match self {
+            Value::Point(point) => Some(*point),
+            _ => None,
+        }
+    }
+
+    pub fn as_vector(&self) -> Option<Vector> {
+        match self {
+            Value::Vector(vector) => Some(*vector),
+            _ => None,
+        }
+    }
+
+    /// The start and direction of a line.
+    pub fn as_line(&self) -> Option<(Point, Vector)> {
+        match self {
+            Value::Line(start, direction) => Some((*start, *direction)),
+            _ => None,
+        }
+    }
+
+    /// The center and radius of a circle.
+    pub fn as_circle(&self) -> Option<(Point, Scalar)> {
+        match self {
+            Value::Circle(center, radius) => Some((*center, *radius)),
+            _ => None,
+        }
+    }
+
+    pub fn as_path(&self) -> Option<&Path> {
+        match self {
+            Value::Path(path) => Some(path),
+            _ => None,
+        }
+    }
+
+    pub fn as_angle(&self) -> Option<Angle> {
+        match self {
+            Value::Angle(angle) => Some(*angle),
+            _ => None,
+        }
+    }
+
+    pub fn as_transform(&self) -> Option<Transform> {
+        match self {
+            Value::Transform(transform) => Some(*transform),
+            _ => None,
+        }
+    }
+
+    pub fn as_group(&self) -> Option<&stdlib::Group> {
+        match self {
+            Value::Group(group) => Some(group),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
+        match self {
+            Value::List(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    /// Whether conditionals like `#if` treat this as true: void, false,
+    /// zero and empty strings, lists and groups are false, anything else is
+    /// true.
+    pub fn is_truthy(&self) -> bool {
+        match self {
+            Value::Void => false,
+            Value::Bool(bool) => *bool,
+            Value::Scalar(scalar) => !scalar.is_zero(),
+            Value::String(string) => !string.is_empty(),
+            Value::List(items) => !items.is_empty(),
+            Value::Group(group) => !group.items.is_empty(),
+            Value::Point(_)
+            | Value::Vector(_)
+            | Value::Line(..)
+            | Value::Circle(..)
+            | Value::Path(_)
+            | Value::Angle(_)
+            | Value::Transform(_) => true,
+        }
+    }
 }
 
-type Function = fn(&mut Stack) -> Result<Value, Error>;
+/// A set of builtins provided by the host application, usually sharing
+/// some state like a table of data to plot.
+pub trait Extension {
+    fn register<Backend>(self, runtime: &mut Runtime<Backend>);
+}
 
-#[derive(Debug, Error, PartialEq, Eq)]
+#[derive(Debug, Clone, Error, PartialEq, Eq)]
 pub enum Error {
     #[error("Fatal: stack underflow")]
     StackUnderflow,
     #[error("Invalid argument")]
     InvalidArgument,
-    #[error("Variable {0} not in scope")]
-    VariableNotFound(SmolStr),
-    #[error("Function {0} not in scope")]
-    FunctionNotFound(SmolStr),
+    #[error("Variable {name} not in scope{}", did_you_mean(.suggestion))]
+    VariableNotFound {
+        name: SmolStr,
+        /// A variable in scope spelled like `name`.
+        suggestion: Option<SmolStr>,
+    },
+    #[error("{0} is a constant and cannot be reassigned")]
+    ConstantReassigned(SmolStr),
+    #[error("Function {name} not in scope{}", did_you_mean(.suggestion))]
+    FunctionNotFound {
+        name: SmolStr,
+        /// A defined function spelled like `name`.
+        suggestion: Option<SmolStr>,
+    },
     #[error("Invalid type for operation")]
     TypeError,
     #[error("Integer literal too large to fit in a 64-bit integer")]
     IntLiteralTooLarge,
     #[error("Too few arguments for this function call")]
     MissingArgument,
+    #[error("{function} needs {needed} values but the stack only holds {found}")]
+    NotEnoughArguments {
+        function: SmolStr,
+        needed: usize,
+        found: usize,
+    },
+    #[error("Symbol {0} is not defined")]
+    UnknownSymbol(SmolStr),
+    #[error("Symbol {0} is already defined")]
+    SymbolRedefined(SmolStr),
+    #[error("Parameter {0} is outside of its range")]
+    ParamOutOfRange(SmolStr),
+    #[error("{function} has no option {option}")]
+    UnknownOption { function: SmolStr, option: SmolStr },
     #[error("Non-real result")]
     NonRealResult,
+    #[error("Division by zero")]
+    DivisionByZero,
+    #[error("Integer arithmetic has no exact integer result")]
+    InexactInteger,
+    #[error("Result is too large to represent")]
+    Overflow,
+    #[error("Index {0} is out of bounds")]
+    IndexOutOfBounds(i64),
+    #[error("No intersection")]
+    NoIntersection,
+    #[error("pop_tf without a matching push_tf")]
+    TransformStackUnderflow,
+    #[error("endlayer without a matching layer")]
+    LayerUnderflow,
+    #[error("endclip without a matching clip")]
+    ClipUnderflow,
+    #[error("endframe without a matching in_frame")]
+    FrameUnderflow,
+    #[error("Could not load module: {0}")]
+    ModuleLoad(String),
+    #[error("The visible area is unknown until #screen or #view sets it")]
+    UnknownVisibleArea,
+    #[error("#meta {0} is not one of the Dublin Core elements")]
+    UnknownMetadata(SmolStr),
+    #[error("Reading {} is not allowed", .0.display())]
+    AccessDenied(PathBuf),
+    #[error("Script exceeded the {0} limit")]
+    LimitExceeded(Limit),
+}
+
+fn did_you_mean(suggestion: &Option<SmolStr>) -> String {
+    suggestion
+        .as_ref()
+        .map(|suggestion| format!("; did you mean {suggestion}?"))
+        .unwrap_or_default()
+}
+
+impl Error {
+    /// See [`crate::token::ErrorKind::code`]. Runtime errors are numbered
+    /// from E0301.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Error::StackUnderflow => "E0301",
+            Error::InvalidArgument => "E0302",
+            Error::VariableNotFound { .. } => "E0303",
+            Error::ConstantReassigned(_) => "E0304",
+            Error::FunctionNotFound { .. } => "E0305",
+            Error::TypeError => "E0306",
+            Error::IntLiteralTooLarge => "E0307",
+            Error::MissingArgument => "E0308",
+            Error::NonRealResult => "E0309",
+            Error::DivisionByZero => "E0310",
+            Error::Overflow => "E0311",
+            Error::IndexOutOfBounds(_) => "E0312",
+            Error::NoIntersection => "E0313",
+            Error::TransformStackUnderflow => "E0314",
+            Error::LayerUnderflow => "E0315",
+            Error::ClipUnderflow => "E0316",
+            Error::ModuleLoad(_) => "E0317",
+            Error::UnknownVisibleArea => "E0318",
+            Error::UnknownMetadata(_) => "E0319",
+            Error::AccessDenied(_) => "E0320",
+            Error::LimitExceeded(_) => "E0321",
+            Error::NotEnoughArguments { .. } => "E0322",
+            Error::UnknownOption { .. } => "E0323",
+            Error::UnknownSymbol(_) => "E0324",
+            Error::SymbolRedefined(_) => "E0325",
+            Error::FrameUnderflow => "E0326",
+            Error::ParamOutOfRange(_) => "E0327",
+            Error::InexactInteger => "E0328",
+        }
+    }
+}
+
+/// A number declared with `#param`, meant to be tuned between `min` and
+/// `max`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Param {
+    pub name: SmolStr,
+    pub min: Scalar,
+    pub max: Scalar,
+    pub default: Scalar,
+    /// What the script ran with, the default unless the host set another.
+    pub value: Scalar,
+}
+
+/// Something that is allowed, but probably not what the author meant.
+#[derive(Debug, Clone, Error, PartialEq)]
+pub enum Warning {
+    #[error("Result of the expression at {at} cannot be drawn")]
+    Undrawable { at: Position },
+    #[error("{count} value(s) left unused on the stack by the instruction at {at}")]
+    UnusedValues { at: Position, count: usize },
+    #[error("Variable {0} is never used")]
+    UnusedVariable(SmolStr),
+    #[error("Drawing at {at} extends outside the screen")]
+    OutsideScreen { at: Position },
+}
+
+impl Warning {
+    /// Like [`Error::code`], numbered from W0001.
+    pub fn code(&self) -> &'static str {
+        match self {
+            Warning::Undrawable { .. } => "W0001",
+            Warning::UnusedValues { .. } => "W0002",
+            Warning::UnusedVariable(_) => "W0003",
+            Warning::OutsideScreen { .. } => "W0004",
+        }
+    }
+
+    /// Where in the script the problem is, if it is about one place.
+    pub fn position(&self) -> Option<Position> {
+        match self {
+            Warning::Undrawable { at }
+            | Warning::UnusedValues { at, .. }
+            | Warning::OutsideScreen { at } => Some(*at),
+            Warning::UnusedVariable(_) => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ast::parse_file, check::Kind, token::StringTokenizer, util::test_helpers::*};
+
+    #[test]
+    fn test_constants_cannot_be_reassigned() {
+        let mut runtime = run_script("#const W 10\n#let h $W").unwrap();
+        assert_eq!(runtime.lookup("h"), Ok(scalar(10)));
+
+        assert_eq!(
+            run_script("#const W 10\n#let W 20").err(),
+            Some(Error::ConstantReassigned("W".into()))
+        );
+        assert_eq!(
+            run_script("#const W 10\n#const W 20").err(),
+            Some(Error::ConstantReassigned("W".into()))
+        );
+    }
+
+    #[test]
+    fn test_lists_draw_every_item() {
+        let source = "list (line (pnt2 0 0) (pnt2 1 1)) 5 (circle (pnt2 0 0) 1)";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(runtime.finish().commands.len(), 2);
+    }
+
+    #[test]
+    fn test_for_loop() {
+        let source = "#let ps (list (pnt2 0 0) (pnt2 5 5))\n#for p in $ps\ncircle $p 1\n#end";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(runtime.finish().commands.len(), 2);
+
+        assert_eq!(
+            run_script("#for p in 5\n#end").err(),
+            Some(Error::TypeError)
+        );
+    }
+
+    #[test]
+    fn test_scopes() {
+        let source = "#const x 1\n#scope\n#let x 2\n#let y $x\n#let z 3\n#end\n#let w $x";
+        let mut runtime = run_script(source).unwrap();
+        assert_eq!(runtime.lookup("w"), Ok(scalar(1)));
+        assert_eq!(
+            runtime.lookup("z"),
+            Err(Error::VariableNotFound {
+                name: "z".into(),
+                suggestion: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_suggestions() {
+        assert_eq!(
+            run_script("#let width 1\n#let height 2\n#let w $widht").err(),
+            Some(Error::VariableNotFound {
+                name: "widht".into(),
+                suggestion: Some("width".into()),
+            })
+        );
+        assert_eq!(
+            run_script("circel (pnt2 0 0) 1").err(),
+            Some(Error::FunctionNotFound {
+                name: "circel".into(),
+                suggestion: Some("circle".into()),
+            })
+        );
+        assert_eq!(
+            run_script("frobnicate 1").err(),
+            Some(Error::FunctionNotFound {
+                name: "frobnicate".into(),
+                suggestion: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_void_newline() {
+        let source = "#screen 10 10\ncircle (pnt2 1 1) 1!\n\
+            circle (pnt2 2 2) 1; circle (pnt2 3 3) 1!\npnt2 4 4 => circle 1!";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(runtime.warnings(), []);
+        let expected = run_script("#screen 10 10\ncircle (pnt2 2 2) 1").unwrap();
+        assert_eq!(runtime.finish().commands, expected.finish().commands);
+
+        // Values left with ! are still there for the next line
+        let program = parse_file(&mut StringTokenizer::new(&"pnt2 0 0!\ncircle 1")).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.set_stack_policy(StackPolicy::Keep);
+        runtime.execute(program).unwrap();
+        assert_eq!(runtime.finish().commands.len(), 1);
+    }
+
+    #[test]
+    fn test_options() {
+        fn scaled(stack: &mut Stack) -> Result<Value, Error> {
+            let by = match stack.option("by") {
+                Some(by) => Scalar::try_from(by)?,
+                None => 1.into(),
+            };
+            Ok(Value::Scalar(Scalar::try_from(stack.pop()?)? * by))
+        }
+        let run = |source: &str| {
+            let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+            let mut runtime = Runtime::<Recorder>::default();
+            runtime.define_fn("scaled", scaled);
+            runtime.define_stateful_fn("twice", |stack, context| {
+                let value = context.call("scaled", stack)?;
+                stack.push(value);
+                context.call("scaled", stack)
+            });
+            runtime.execute(program)?;
+            Ok(runtime.get_variable("x").cloned())
+        };
+
+        assert_eq!(run("#let x (scaled 2)"), Ok(Some(Value::Scalar(2.into()))));
+        // Inner calls take their own options first
+        assert_eq!(
+            run("#let x (scaled 2 by=(scaled 3 by=2))"),
+            Ok(Some(Value::Scalar(12.into())))
+        );
+        // Options aren't handed on to builtins called by builtins
+        assert_eq!(
+            run("#let x (twice 5 by=3)"),
+            Err(Error::UnknownOption {
+                function: "twice".into(),
+                option: "by".into(),
+            })
+        );
+        assert_eq!(
+            run("#let x (scaled 2 size=3)"),
+            Err(Error::UnknownOption {
+                function: "scaled".into(),
+                option: "size".into(),
+            })
+        );
+        assert_eq!(run("#let x (scaled 2 by=yes)"), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_warnings() {
+        let source = "#screen 10 10\n#let a 1\npnt2 1 2 => pnt2 $a 4\nline (pnt2 0 0) (pnt2 20 0)";
+        let runtime = run_script(source).unwrap();
+        let warnings = runtime.warnings();
+
+        assert_eq!(warnings.len(), 3, "{warnings:?}");
+        assert!(matches!(warnings[0], Warning::Undrawable { .. }));
+        assert!(matches!(
+            warnings[1],
+            Warning::UnusedValues { count: 1, .. }
+        ));
+        assert!(matches!(warnings[2], Warning::OutsideScreen { .. }));
+    }
+
+    #[test]
+    fn test_unused_variable_warning() {
+        let runtime = run_script("#let a 1\n#scope\n#let b 2\n#end").unwrap();
+        assert_eq!(
+            runtime.warnings(),
+            [
+                Warning::UnusedVariable("b".into()),
+                Warning::UnusedVariable("a".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_stack_policy() {
+        let source = "pnt2 0 0 => vec2 1 1 => nop\nline";
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn("nop", |_| Ok(Value::Void));
+        runtime.set_stack_policy(StackPolicy::Keep);
+        runtime.execute(program.clone()).unwrap();
+        assert_eq!(runtime.finish().commands.len(), 1);
+
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn("nop", |_| Ok(Value::Void));
+        assert_eq!(
+            runtime.execute(program),
+            Err(Error::NotEnoughArguments {
+                function: "line".into(),
+                needed: 2,
+                found: 0,
+            })
+        );
+    }
+
+    #[test]
+    fn test_infix_arithmetic() {
+        let mut runtime = run_script("#let x 2\n#let y ($x + 3 * 4 - -1)").unwrap();
+        assert_eq!(runtime.lookup("y"), Ok(scalar(15)));
+    }
+
+    #[test]
+    fn test_unit_literals() {
+        let mut runtime = run_script("#let a (1cm + 5mm)\n#let b 1in\n#let c 2.5").unwrap();
+        assert_eq!(runtime.lookup("a"), Ok(scalar(15)));
+        assert_eq!(runtime.lookup("b"), Ok(scalar(25.4)));
+        assert_eq!(runtime.lookup("c"), Ok(scalar(2.5)));
+    }
+
+    #[test]
+    fn test_angle_literals() {
+        let mut runtime = run_script("#let a 90deg\n#let b (sin $a)").unwrap();
+        assert_eq!(
+            runtime.lookup("a"),
+            Ok(Value::Angle(Angle::from_degrees(90.0)))
+        );
+        assert_eq!(runtime.lookup("b"), Ok(scalar(1.0)));
+    }
+
+    #[test]
+    #[cfg(feature = "serde")]
+    fn test_serde_draw_commands() {
+        let commands = run_script("line (pnt2 0 0) (vec2 1 1)\ncircle (pnt2 1 1) 2")
+            .unwrap()
+            .finish()
+            .commands;
+
+        let json = serde_json::to_string(&commands).unwrap();
+        let decoded: Vec<DrawCommand> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, commands);
+
+        let value = list_of([scalar(1), point(2.5, 3.0)]);
+        let json = serde_json::to_string(&value).unwrap();
+        assert_eq!(serde_json::from_str::<Value>(&json).unwrap(), value);
+    }
+
+    #[test]
+    fn test_host_functions() {
+        struct Table(Arc<Vec<f64>>);
+
+        impl Extension for Table {
+            fn register<Backend>(self, runtime: &mut Runtime<Backend>) {
+                let rows = self.0.clone();
+                runtime.define_fn("row", move |stack| {
+                    let Value::Scalar(index) = stack.pop()? else {
+                        return Err(Error::TypeError);
+                    };
+                    let index = index.as_integer().ok_or(Error::InvalidArgument)?;
+                    let row = usize::try_from(index)
+                        .ok()
+                        .and_then(|index| rows.get(index))
+                        .ok_or(Error::IndexOutOfBounds(index))?;
+                    Ok(Value::Scalar((*row).into()))
+                });
+                runtime.define_fn("rows", move |_| {
+                    Ok(Value::Scalar((self.0.len() as i64).into()))
+                });
+            }
+        }
+
+        let program = parse_file(&mut StringTokenizer::new(
+            &"#let a (row 1)\n#let n (rows)\ncount\ncount",
+        ))
+        .unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.install(Table(Arc::new(vec![1.5, 2.5])));
+        let mut calls = 0;
+        runtime.define_stateful_fn("count", move |_, context| {
+            calls += 1;
+            context.print(calls);
+            Ok(Value::Void)
+        });
+        runtime.execute(program).unwrap();
+
+        assert_eq!(runtime.lookup("a"), Ok(scalar(2.5)));
+        assert_eq!(runtime.lookup("n"), Ok(scalar(2)));
+        assert_eq!(runtime.output(), "1\n2\n");
+    }
+
+    #[test]
+    fn test_function_docs() {
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn_with_doc("answer", "answer -> Scalar", "The answer.", |_| {
+            Ok(scalar(42))
+        });
+        let doc = runtime.function_doc("answer").unwrap();
+        assert_eq!(doc.signature, "answer -> Scalar");
+        assert_eq!(doc.summary, "The answer.");
+        assert!(runtime.function_doc("circle").is_some());
+        assert_eq!(runtime.function_doc("nope"), None);
+
+        // Redefining a builtin forgets what the old one did
+        runtime.define_fn("circle", |_| Ok(Value::Void));
+        assert_eq!(runtime.function_doc("circle"), None);
+    }
+
+    #[test]
+    fn test_declared_effects() {
+        const HALF: Effect = Effect::Overloads(&[(&[Kind::Scalar], &[Kind::Scalar])]);
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn_with_effect("half", HALF, |stack| {
+            let Value::Scalar(value) = stack.pop()? else {
+                return Err(Error::TypeError);
+            };
+            Ok(Value::Scalar(value / 2.into()))
+        });
+        assert_eq!(runtime.function_effect("half"), Some(HALF));
+        assert_eq!(
+            runtime.function_doc("half").unwrap().signature,
+            "half Scalar -> Scalar"
+        );
+
+        let program = parse_file(&mut StringTokenizer::new(
+            &"#let a (half 3)
+half",
+        ))
+        .unwrap();
+        assert_eq!(
+            runtime.execute(program),
+            Err(Error::NotEnoughArguments {
+                function: "half".into(),
+                needed: 1,
+                found: 0,
+            })
+        );
+        assert_eq!(runtime.lookup("a"), Ok(scalar(ratio(3, 2))));
+    }
+
+    #[test]
+    fn test_value_reflection() {
+        let mut runtime = run_script("#let c (circle (pnt2 1 2) 3)\n#let p (pnt2 4 5)").unwrap();
+        let circle = runtime.lookup("c").unwrap();
+        assert_eq!(circle.kind(), ValueKind::Circle);
+        assert_eq!(
+            circle.as_circle(),
+            Some((
+                Point {
+                    x: 1.into(),
+                    y: 2.into()
+                },
+                3.into()
+            ))
+        );
+        assert_eq!(circle.as_point(), None);
+
+        let p = runtime.lookup("p").unwrap();
+        assert_eq!(p.as_point().map(|p| p.x), Some(4.into()));
+        assert_eq!(
+            Point::try_from(p.clone()),
+            Ok(Point {
+                x: 4.into(),
+                y: 5.into()
+            })
+        );
+        assert_eq!(Vector::try_from(p), Err(Error::TypeError));
+        assert_eq!(Value::from(Scalar::from(2)), scalar(2));
+        assert_eq!(Value::Void.kind().to_string(), "Void");
+    }
+
+    #[test]
+    fn test_truthiness() {
+        let mut runtime = run_script("#let z (gt 1 2)\n#let t (le 0.5 (div 1 2))").unwrap();
+        let mut truthy = |name| runtime.lookup(name).unwrap().is_truthy();
+        assert!(!truthy("z"));
+        assert!(truthy("t"));
+        assert!(!Value::Void.is_truthy());
+        assert!(!Value::Bool(false).is_truthy());
+        assert!(!Value::String("".into()).is_truthy());
+        assert!(!Value::List(Arc::new(Vec::new())).is_truthy());
+        assert!(scalar(-0.5).is_truthy());
+        assert!(point(0, 0).is_truthy());
+    }
+
+    #[test]
+    fn test_register_module() {
+        struct Paper;
+
+        impl StdlibModule for Paper {
+            fn name(&self) -> &str {
+                "paper"
+            }
+
+            fn register(&self, functions: &mut Functions) {
+                functions.define_fn("a4", |_| {
+                    Ok(Value::Vector(Vector {
+                        x: 210.into(),
+                        y: 297.into(),
+                    }))
+                });
+            }
+        }
+
+        let program = parse_file(&mut StringTokenizer::new(&"#let size (a4)")).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.register_module(&Paper);
+        runtime.execute(program).unwrap();
+        assert_eq!(runtime.lookup("size"), Ok(vector(210, 297)));
+    }
+
+    #[test]
+    fn test_host_variables() {
+        let program = parse_file(&mut StringTokenizer::new(
+            &"#const H 5\n#let area (mul $w $H)",
+        ))
+        .unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.set_variable("w", scalar(4)).unwrap();
+        runtime.execute(program).unwrap();
+
+        assert_eq!(runtime.get_variable("area"), Some(&scalar(20)));
+        assert_eq!(runtime.get_variable("missing"), None);
+        assert_eq!(
+            runtime.set_variable("H", scalar(1)),
+            Err(Error::ConstantReassigned("H".into()))
+        );
+        assert_eq!(runtime.warnings(), [Warning::UnusedVariable("area".into())]);
+    }
+
+    #[test]
+    fn test_params() {
+        let source = "#param r 1 10 2!\ncircle (pnt2 0 0) $r";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(
+            runtime.params(),
+            [Param {
+                name: "r".into(),
+                min: 1.into(),
+                max: 10.into(),
+                default: 2.into(),
+                value: 2.into(),
+            }]
+        );
+
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.set_variable("r", scalar(5.5)).unwrap();
+        runtime.execute(program).unwrap();
+        assert_eq!(runtime.params()[0].value, 5.5.into());
+        assert_eq!(
+            runtime.finish().commands,
+            [DrawCommand::Circle {
+                at: (Mm(0.0), Mm(0.0)),
+                radius: Mm(5.5),
+            }]
+        );
+
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.set_variable("r", scalar(11)).unwrap();
+        assert_eq!(
+            runtime.execute(program),
+            Err(Error::ParamOutOfRange("r".into()))
+        );
+        assert_eq!(
+            run_script("#param r 1 10 0").err(),
+            Some(Error::ParamOutOfRange("r".into()))
+        );
+        assert_eq!(
+            run_script("#param r 10 1 5").err(),
+            Some(Error::InvalidArgument)
+        );
+    }
+
+    #[test]
+    fn test_metadata() {
+        let commands = run_script("#title \"Plan\"\n#meta creator \"Ada\"")
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(
+            commands,
+            [
+                DrawCommand::Metadata {
+                    name: "title".into(),
+                    value: "Plan".into(),
+                },
+                DrawCommand::Metadata {
+                    name: "creator".into(),
+                    value: "Ada".into(),
+                },
+            ]
+        );
+
+        assert_eq!(
+            run_script("#meta author \"Ada\"").err(),
+            Some(Error::UnknownMetadata("author".into()))
+        );
+        assert_eq!(run_script("#desc 5").err(), Some(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn test_origin_and_ydir() {
+        let source = "#screen 100 50\n#origin bottom-left\n#ydir up\n\
+                      line (pnt2 10 10) (vec2 0 5)\n\
+                      #origin top-left\n\
+                      line (pnt2 10 10) (vec2 0 5)";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(
+            runtime.context.visible_area(),
+            Some((0.0, -50.0, 100.0, 50.0))
+        );
+        let commands = runtime.finish().commands;
+        assert_eq!(
+            commands[1..],
+            [
+                DrawCommand::Line {
+                    from: (Mm(10.0), Mm(40.0)),
+                    to: (Mm(10.0), Mm(35.0)),
+                },
+                DrawCommand::Line {
+                    from: (Mm(10.0), Mm(-10.0)),
+                    to: (Mm(10.0), Mm(-15.0)),
+                },
+            ]
+        );
+
+        assert_eq!(
+            run_script("#origin bottom-left").err(),
+            Some(Error::UnknownVisibleArea)
+        );
+    }
+
+    #[test]
+    fn test_config_limits() {
+        let run = |source: &str, config: RuntimeConfig| {
+            let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+            let mut runtime = Runtime::<Recorder>::default();
+            runtime.set_config(config);
+            runtime
+                .execute(program)
+                .map(|_| runtime.context.output().to_string())
+        };
+
+        let loop_ = "#for p in (list 1 2 3 4 5)\nprint $p\n#end";
+        let instructions = |max| RuntimeConfig {
+            max_instructions: Some(max),
+            ..Default::default()
+        };
+        assert!(run(loop_, instructions(6)).is_ok());
+        assert_eq!(
+            run(loop_, instructions(5)),
+            Err(Error::LimitExceeded(Limit::Instructions))
+        );
+
+        let output = RuntimeConfig {
+            max_output: Some(4),
+            ..Default::default()
+        };
+        assert_eq!(run("print 1\nprint 2", output.clone()), Ok("1\n2\n".into()));
+        assert_eq!(
+            run("print 1\nprint 2\nprint 3", output),
+            Err(Error::LimitExceeded(Limit::Output))
+        );
+    }
+
+    #[test]
+    fn test_deepest_nesting_runs() {
+        // Each of the parentheses holds a unary minus, which nests as well
+        let depth = crate::ast::MAX_NESTING / 2;
+        let source = format!(
+            "{}#let a {}1{}\n{}",
+            "#scope\n".repeat(depth),
+            "(- ".repeat(depth / 2),
+            ")".repeat(depth / 2),
+            "#end\n".repeat(depth)
+        );
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        assert!(crate::check::check_program(&program).is_empty());
+        crate::format::format_program(&program);
+        Runtime::<Recorder>::default().execute(program).unwrap();
+    }
+
+    #[test]
+    fn test_execution_limits() {
+        let run = |source: &str, config: RuntimeConfig| {
+            let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+            let mut runtime = Runtime::<Recorder>::default();
+            runtime.set_config(config);
+            runtime.define_stateful_fn("nested", |stack, context| context.call("sqrt", stack));
+            runtime.execute(program)
+        };
+        let loop_ = "#for r in (list 1 2 3)\ncircle (pnt2 0 0) $r\n#end";
+
+        let iterations = RuntimeConfig {
+            max_loop_iterations: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            run(loop_, iterations),
+            Err(Error::LimitExceeded(Limit::LoopIterations))
+        );
+
+        let draw = |max| RuntimeConfig {
+            max_draw_commands: Some(max),
+            ..Default::default()
+        };
+        assert_eq!(run(loop_, draw(3)), Ok(()));
+        assert_eq!(
+            run(loop_, draw(2)),
+            Err(Error::LimitExceeded(Limit::DrawCommands))
+        );
+
+        let depth = |max| RuntimeConfig {
+            max_call_depth: Some(max),
+            ..Default::default()
+        };
+        assert_eq!(run("#let a (nested 4)", depth(2)), Ok(()));
+        assert_eq!(
+            run("#let a (nested 4)", depth(1)),
+            Err(Error::LimitExceeded(Limit::CallDepth))
+        );
+
+        let stack = RuntimeConfig {
+            max_stack: Some(2),
+            ..Default::default()
+        };
+        assert_eq!(run("dup 1", stack.clone()), Ok(()));
+        assert_eq!(
+            run("dup (dup 1)", stack),
+            Err(Error::LimitExceeded(Limit::Stack))
+        );
+    }
+
+    #[test]
+    fn test_readable_paths() {
+        let mut runtime = Runtime::<Recorder>::default();
+        let root = std::path::Path::new(env!("CARGO_MANIFEST_DIR"));
+        assert!(runtime
+            .context
+            .readable_path(root.join("Cargo.toml"))
+            .is_ok());
+
+        runtime.set_config(RuntimeConfig {
+            readable_paths: Some(vec![root.join("src")]),
+            ..Default::default()
+        });
+        assert!(runtime
+            .context
+            .readable_path(root.join("src/lib.rs"))
+            .is_ok());
+        let denied = root.join("src/../Cargo.toml");
+        assert_eq!(
+            runtime.context.readable_path(&denied),
+            Err(Error::AccessDenied(denied))
+        );
+    }
+
+    #[test]
+    fn test_execute_with_hooks() {
+        #[derive(Default)]
+        struct Trace {
+            steps: Vec<String>,
+        }
+
+        fn stack(state: Snapshot) -> String {
+            let values = state.stack().iter().map(ToString::to_string);
+            values.collect::<Vec<_>>().join(" ")
+        }
+
+        impl Observer for Trace {
+            fn before(&mut self, expression: &Expression, state: Snapshot) {
+                let line = expression.position.line();
+                self.steps.push(format!("{line}: [{}]", stack(state)));
+            }
+
+            fn after(&mut self, _: Position, state: Snapshot) {
+                let n = state.variable("n").unwrap();
+                self.steps.push(format!("-> [{}] n={n}", stack(state)));
+            }
+        }
+
+        let program = parse_file(&mut StringTokenizer::new(
+            &"#let n 2\n#scope\n#let n 3\n1 => add $n\n#end",
+        ))
+        .unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        let mut trace = Trace::default();
+        runtime.execute_with_hooks(program, &mut trace).unwrap();
+
+        #[rustfmt::skip]
+        assert_eq!(
+            trace.steps,
+            [
+                "0: []", "-> [2] n=2",
+                "1: []",
+                    "2: []", "-> [3] n=3",
+                    "3: []", "-> [1] n=3",
+                    "3: [1]", "-> [4] n=3",
+                "-> [] n=2",
+            ]
+        );
+    }
 }
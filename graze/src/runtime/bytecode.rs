@@ -0,0 +1,1037 @@
+//! Programs are lowered to a flat list of [`Op`]s before they run, so that
+//! builtins and variables are looked up and literals converted once instead
+//! of every time a loop comes around.
+
+use std::{
+    collections::{HashMap, HashSet},
+    hash::Hash,
+    sync::Arc,
+};
+
+use smol_str::SmolStr;
+
+use super::{
+    scope::{Names, Scopes},
+    Error, Observer, Param, Runtime, Stack, StackPolicy, Value, Warning,
+};
+use crate::{
+    ast::{Argument, Expression, ExpressionContent, Instruction, Literal, Program},
+    check::Effect,
+    output::{DrawBuffer, DrawCommand, METADATA_NAMES},
+    plugin::Functions,
+    stdlib::{Angle, Scalar, ScalarKey},
+    token::{Position, Unit},
+};
+
+/// One step of a compiled program. Expressions leave their value in a
+/// result register, from where arguments are pushed onto the stack.
+#[derive(Debug)]
+pub(super) enum Op<'p> {
+    /// Start an instruction, starting at `start` if it isn't empty.
+    Instruction {
+        start: Option<Position>,
+    },
+    /// End the innermost instruction, clearing the stack if the policy says so.
+    EndInstruction,
+    /// Start one of the expressions of an instruction.
+    Expression(&'p Expression),
+    /// Push the result of the expression and draw it if it should be.
+    EndExpression(&'p Expression),
+    /// The instruction ended with `!`, so its last value is left on the
+    /// stack on purpose and not worth a warning.
+    Void,
+    /// Set the result to a literal, converted while compiling.
+    Value(Value),
+    /// Set the result to the variable whose name was interned at the index.
+    Load(usize),
+    /// Set the result to the value on top of the stack, removing it.
+    Pop,
+    /// Move the result onto the stack.
+    Push,
+    /// Move the result aside for a keyword taking several arguments.
+    Save,
+    /// Fail unless the result is a scalar.
+    Scalar,
+    /// Move the result aside as the option `name` of the next call.
+    Option(&'p SmolStr),
+    /// Remember where the arguments of a builtin collecting all of them,
+    /// like `list`, start on the stack.
+    Mark,
+    /// Call the builtin at `index` of the function table with the last
+    /// `options` options moved aside. One that `collects` only sees the
+    /// values pushed since the last [`Op::Mark`].
+    Call {
+        index: usize,
+        name: &'p str,
+        options: usize,
+        collects: bool,
+    },
+    /// Like [`Op::Call`] for a builtin known to be pure, reusing the
+    /// result of an earlier call from `site` with the same `arity`
+    /// arguments.
+    Memo {
+        index: usize,
+        name: &'p str,
+        arity: usize,
+        site: &'p ExpressionContent,
+    },
+    /// Fail with an error found while compiling, once the program gets here.
+    Fail(Error),
+    Bind {
+        index: usize,
+        constant: bool,
+    },
+    EnterScope,
+    /// Leave the innermost scope, setting the result to void.
+    ExitScope,
+    /// Start looping over the list in the result.
+    Loop,
+    /// Run the next iteration with the item bound to the variable at
+    /// `index`, or jump to `exit` once every item ran.
+    Next {
+        index: usize,
+        exit: usize,
+    },
+    /// End an iteration and jump back to the [`Op::Next`] at `next`.
+    Repeat {
+        next: usize,
+    },
+    /// Start defining the symbol `name`, drawing without a transform.
+    Symbol(&'p SmolStr),
+    /// Finish defining the symbol `name` and restore the transform.
+    EndSymbol(&'p SmolStr),
+    /// Bind the parameter at the index from the minimum, maximum and
+    /// default moved aside.
+    Param(usize),
+    Screen,
+    View {
+        flip_y: bool,
+    },
+    Metadata(SmolStr),
+    Page,
+    Origin {
+        bottom: bool,
+    },
+    YDir {
+        up: bool,
+    },
+}
+
+/// Lower one top-level instruction, resolving calls against `functions`
+/// and interning the names of variables in `names`. Unknown functions
+/// only fail once the program reaches them, see [`resolve`] to find them
+/// beforehand.
+pub(super) fn compile<'p>(
+    instruction: &'p Instruction,
+    functions: &Functions,
+    names: &mut Names,
+    ops: &mut Vec<Op<'p>>,
+) {
+    Compiler {
+        ops,
+        functions,
+        names,
+    }
+    .instruction(instruction);
+}
+
+/// Check that every builtin `program` calls exists and that every variable
+/// it reads is bound somewhere, by the program itself or in `scopes`
+/// before it runs. Returns the first problem and the expression it is in.
+pub(super) fn resolve<'p>(
+    program: &'p Program,
+    functions: &Functions,
+    scopes: &Scopes,
+) -> Result<(), (Error, &'p Expression)> {
+    let mut resolver = Resolver {
+        functions,
+        scopes,
+        bound: HashSet::new(),
+        checking: false,
+    };
+    resolver.block(&program.instructions)?;
+    resolver.checking = true;
+    resolver.block(&program.instructions)
+}
+
+/// Collects the names a program binds, then checks the names it uses.
+struct Resolver<'p, 'f> {
+    functions: &'f Functions,
+    scopes: &'f Scopes,
+    bound: HashSet<&'p str>,
+    checking: bool,
+}
+
+impl<'p> Resolver<'p, '_> {
+    fn block(&mut self, instructions: &'p [Instruction]) -> Result<(), (Error, &'p Expression)> {
+        for expression in instructions.iter().flat_map(|i| &i.expressions) {
+            self.expression(&expression.content)
+                .map_err(|err| (err, expression))?;
+            // Blocks report problems with the expression they are in
+            match &expression.content {
+                ExpressionContent::Scope(body)
+                | ExpressionContent::For { body, .. }
+                | ExpressionContent::Symbol { body, .. } => self.block(body)?,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn expression(&mut self, expression: &'p ExpressionContent) -> Result<(), Error> {
+        match expression {
+            ExpressionContent::Variable(name) => self.read(name),
+            ExpressionContent::FunctionCall {
+                name,
+                args,
+                options,
+            } => {
+                for arg in args.iter().chain(options.iter().map(|(_, value)| value)) {
+                    self.argument(arg)?;
+                }
+                match (self.checking, self.functions.index(name)) {
+                    (true, None) => Err(self.functions.not_found(name)),
+                    _ => Ok(()),
+                }
+            }
+            ExpressionContent::Let { name, init } | ExpressionContent::Const { name, init } => {
+                self.bound.insert(name);
+                init.iter().try_for_each(|init| self.argument(init))
+            }
+            ExpressionContent::For { name, items, .. } => {
+                self.bound.insert(name);
+                self.argument(items)
+            }
+            ExpressionContent::Param {
+                name,
+                min,
+                max,
+                default,
+            } => {
+                self.bound.insert(name);
+                [min, max, default]
+                    .into_iter()
+                    .try_for_each(|argument| self.argument(argument))
+            }
+            ExpressionContent::Screen(x, y) => [x, y]
+                .into_iter()
+                .try_for_each(|argument| self.argument(argument)),
+            ExpressionContent::View {
+                x,
+                y,
+                width,
+                height,
+                ..
+            } => [x, y, width, height]
+                .into_iter()
+                .try_for_each(|argument| self.argument(argument)),
+            ExpressionContent::Title(value)
+            | ExpressionContent::Desc(value)
+            | ExpressionContent::Meta { value, .. } => self.argument(value),
+            ExpressionContent::Literal(_)
+            | ExpressionContent::Scope(_)
+            | ExpressionContent::Symbol { .. }
+            | ExpressionContent::Page
+            | ExpressionContent::Origin { .. }
+            | ExpressionContent::YDir { .. } => Ok(()),
+        }
+    }
+
+    fn argument(&mut self, argument: &'p Argument) -> Result<(), Error> {
+        match argument {
+            Argument::Variable(name) => self.read(name),
+            Argument::Literal(_) => Ok(()),
+            Argument::Parenthesized(content) => self.expression(content),
+        }
+    }
+
+    fn read(&self, name: &str) -> Result<(), Error> {
+        if !self.checking || self.bound.contains(name) || self.scopes.find(name).is_some() {
+            return Ok(());
+        }
+        Err(self.scopes.not_found(name, self.bound.iter().copied()))
+    }
+}
+
+struct Compiler<'p, 'o, 'f> {
+    ops: &'o mut Vec<Op<'p>>,
+    functions: &'f Functions,
+    names: &'f mut Names,
+}
+
+impl<'p> Compiler<'p, '_, '_> {
+    fn block(&mut self, instructions: &'p [Instruction]) {
+        for instruction in instructions {
+            self.instruction(instruction);
+        }
+    }
+
+    fn instruction(&mut self, instruction: &'p Instruction) {
+        let start = instruction
+            .expressions
+            .first()
+            .map(|expression| expression.position);
+        self.ops.push(Op::Instruction { start });
+        for expression in &instruction.expressions {
+            self.ops.push(Op::Expression(expression));
+            self.expression(&expression.content, false);
+            self.ops.push(Op::EndExpression(expression));
+        }
+        if instruction
+            .expressions
+            .last()
+            .is_some_and(|last| !last.draw_result)
+        {
+            self.ops.push(Op::Void);
+        }
+        self.ops.push(Op::EndInstruction);
+    }
+
+    /// Lower `expression`, which is `nested` in the arguments of another.
+    fn expression(&mut self, expression: &'p ExpressionContent, nested: bool) {
+        match expression {
+            ExpressionContent::Literal(literal) => self.literal(literal),
+            ExpressionContent::Variable(name) => self.ops.push(Op::Load(self.names.intern(name))),
+            ExpressionContent::FunctionCall {
+                name,
+                args,
+                options,
+            } => {
+                // Builtins like `list` only collect their own arguments,
+                // or everything the earlier expressions left if they are
+                // called without any at the end of a pipeline
+                let collects = matches!(self.functions.effect(name), Some(Effect::TakeAll(_)))
+                    && (nested || !args.is_empty());
+                if collects {
+                    self.ops.push(Op::Mark);
+                }
+                for arg in args {
+                    self.argument(arg);
+                    self.ops.push(Op::Push);
+                }
+                for (option, value) in options {
+                    self.argument(value);
+                    self.ops.push(Op::Option(option));
+                }
+                self.ops.push(match self.functions.index(name) {
+                    Some(index) => Op::Call {
+                        index,
+                        name,
+                        options: options.len(),
+                        collects,
+                    },
+                    None => Op::Fail(self.functions.not_found(name)),
+                });
+            }
+            ExpressionContent::Let { name, init } | ExpressionContent::Const { name, init } => {
+                match init {
+                    Some(init) => self.argument(init),
+                    None => self.ops.push(Op::Pop),
+                }
+                self.ops.push(Op::Bind {
+                    index: self.names.intern(name),
+                    constant: matches!(expression, ExpressionContent::Const { .. }),
+                });
+            }
+            ExpressionContent::Scope(body) => {
+                self.ops.push(Op::EnterScope);
+                self.block(body);
+                self.ops.push(Op::ExitScope);
+            }
+            ExpressionContent::For { name, items, body } => {
+                self.argument(items);
+                self.ops.push(Op::Loop);
+                let (index, next) = (self.names.intern(name), self.ops.len());
+                self.ops.push(Op::Next { index, exit: next });
+                self.block(body);
+                self.ops.push(Op::Repeat { next });
+                let exit = self.ops.len();
+                self.ops[next] = Op::Next { index, exit };
+            }
+            ExpressionContent::Symbol { name, body } => {
+                self.ops.extend([Op::Symbol(name), Op::EnterScope]);
+                self.block(body);
+                self.ops.extend([Op::ExitScope, Op::EndSymbol(name)]);
+            }
+            ExpressionContent::Param {
+                name,
+                min,
+                max,
+                default,
+            } => {
+                for argument in [min, max, default] {
+                    self.argument(argument);
+                    self.ops.push(Op::Save);
+                }
+                self.ops.push(Op::Param(self.names.intern(name)));
+            }
+            ExpressionContent::Screen(x, y) => {
+                for argument in [x, y] {
+                    self.argument(argument);
+                    self.ops.push(Op::Save);
+                }
+                self.ops.push(Op::Screen);
+            }
+            ExpressionContent::View {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => {
+                for argument in [x, y, width, height] {
+                    self.argument(argument);
+                    self.ops.extend([Op::Scalar, Op::Save]);
+                }
+                self.ops.push(Op::View { flip_y: *flip_y });
+            }
+            ExpressionContent::Title(text) => self.metadata("title".into(), text),
+            ExpressionContent::Desc(text) => self.metadata("description".into(), text),
+            ExpressionContent::Meta { name, value } => {
+                if METADATA_NAMES.contains(&name.as_str()) {
+                    self.metadata(name.clone(), value);
+                } else {
+                    self.ops
+                        .push(Op::Fail(Error::UnknownMetadata(name.clone())));
+                }
+            }
+            ExpressionContent::Page => self.ops.push(Op::Page),
+            ExpressionContent::Origin { bottom } => self.ops.push(Op::Origin { bottom: *bottom }),
+            ExpressionContent::YDir { up } => self.ops.push(Op::YDir { up: *up }),
+        }
+    }
+
+    fn metadata(&mut self, name: SmolStr, value: &'p Argument) {
+        self.argument(value);
+        self.ops.push(Op::Metadata(name));
+    }
+
+    fn argument(&mut self, argument: &'p Argument) {
+        match argument {
+            Argument::Variable(name) => self.ops.push(Op::Load(self.names.intern(name))),
+            Argument::Literal(literal) => self.literal(literal),
+            Argument::Parenthesized(content) => {
+                self.expression(content, true);
+                // Loops often compute the same subexpression over and over
+                let arity = self.pure_arity(content);
+                if let (Some(arity), Some(op)) = (arity, self.ops.last_mut()) {
+                    if let Op::Call { index, name, .. } = *op {
+                        *op = Op::Memo {
+                            index,
+                            name,
+                            arity,
+                            site: content,
+                        };
+                    }
+                }
+            }
+        }
+    }
+
+    /// How many arguments `content` calls a builtin with if the result only
+    /// depends on them. That is the case for stateless builtins of the
+    /// standard library without options that take exactly the arguments
+    /// given and return a single value.
+    fn pure_arity(&self, content: &ExpressionContent) -> Option<usize> {
+        let ExpressionContent::FunctionCall {
+            name,
+            args,
+            options,
+        } = content
+        else {
+            return None;
+        };
+        if !options.is_empty() || !self.functions.is_parallel(name) {
+            return None;
+        }
+        let Some(Effect::Overloads(overloads)) = self.functions.effect(name) else {
+            return None;
+        };
+        let exact = overloads
+            .iter()
+            .all(|(inputs, outputs)| inputs.len() == args.len() && outputs.len() == 1);
+        (exact && !overloads.is_empty()).then_some(args.len())
+    }
+
+    fn literal(&mut self, literal: &Literal) {
+        self.ops.push(match evaluate_literal(literal) {
+            Ok(value) => Op::Value(value),
+            Err(err) => Op::Fail(err),
+        });
+    }
+}
+
+fn evaluate_literal(literal: &Literal) -> Result<Value, Error> {
+    match *literal {
+        Literal::Number(number) => Ok(Value::Scalar(number.try_into()?)),
+        Literal::Quantity(number, unit) => {
+            let value = Scalar::try_from(number)?;
+            match unit {
+                Unit::Degree => Ok(Value::Angle(Angle::from_degrees(value.into()))),
+                Unit::Radian => Ok(Value::Angle(Angle::from_radians(value.into()))),
+                length => {
+                    let factor = length.millimeters().ok_or(Error::InvalidArgument)?;
+                    Ok(Value::Scalar((value * Scalar::try_from(factor)?).finite()?))
+                }
+            }
+        }
+        Literal::String(ref string) => Ok(Value::String(string.clone())),
+    }
+}
+
+/// How many results of pure calls an instruction remembers at most.
+const MEMO_CAPACITY: usize = 4096;
+
+/// A call to a pure builtin, by where it is in the program, the grid
+/// points are snapped to and the arguments it got.
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct MemoKey {
+    site: usize,
+    snap: Option<ScalarKey>,
+    arguments: Vec<ArgumentKey>,
+}
+
+/// An argument of a memoized call, compared and hashed by how it is
+/// stored so that arguments giving different results never share a key.
+#[derive(Debug, PartialEq, Eq, Hash)]
+enum ArgumentKey {
+    Scalar(ScalarKey),
+    Bool(bool),
+    String(SmolStr),
+    Point(ScalarKey, ScalarKey),
+    Vector(ScalarKey, ScalarKey),
+    Angle(u64),
+}
+
+impl MemoKey {
+    /// `None` if an argument is too large to be worth comparing, like a
+    /// path or a list.
+    fn new(site: usize, snap: Option<Scalar>, arguments: &[Value]) -> Option<Self> {
+        let arguments = arguments
+            .iter()
+            .map(|argument| match argument {
+                Value::Scalar(scalar) => Some(ArgumentKey::Scalar(scalar.key())),
+                Value::Bool(bool) => Some(ArgumentKey::Bool(*bool)),
+                Value::String(string) => Some(ArgumentKey::String(string.clone())),
+                Value::Point(point) => Some(ArgumentKey::Point(point.x.key(), point.y.key())),
+                Value::Vector(vector) => Some(ArgumentKey::Vector(vector.x.key(), vector.y.key())),
+                Value::Angle(angle) => Some(ArgumentKey::Angle(angle.radians().to_bits())),
+                _ => None,
+            })
+            .collect::<Option<_>>()?;
+        Some(Self {
+            site,
+            snap: snap.map(Scalar::key),
+            arguments,
+        })
+    }
+}
+
+/// What the interpreter keeps track of besides the runtime itself.
+#[derive(Default)]
+struct Machine {
+    result: Value,
+    /// Arguments moved aside by [`Op::Save`].
+    saved: Vec<Value>,
+    /// Options moved aside by [`Op::Option`]. Those of nested calls are
+    /// taken before the outer call gets to its own.
+    options: Vec<(SmolStr, Value)>,
+    /// Stack heights recorded by [`Op::Mark`], innermost call last.
+    marks: Vec<usize>,
+    /// Start and number of drawn values of every running instruction,
+    /// innermost last.
+    instructions: Vec<(Position, usize)>,
+    /// Stacks of the instructions whose blocks are running, innermost last.
+    outer: Vec<Stack>,
+    /// Items of the running loops and how many of them ran, innermost last.
+    loops: Vec<(Arc<Vec<Value>>, usize)>,
+    /// Results of the pure calls made so far.
+    memo: HashMap<MemoKey, Value>,
+}
+
+impl Machine {
+    fn take(&mut self) -> Value {
+        std::mem::take(&mut self.result)
+    }
+
+    fn scalars<const N: usize>(&mut self) -> Result<[Scalar; N], Error> {
+        let start = self.saved.len().saturating_sub(N);
+        let scalars = self
+            .saved
+            .drain(start..)
+            .map(|value| match value {
+                Value::Scalar(scalar) => Ok(scalar),
+                _ => Err(Error::InvalidArgument),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        scalars.try_into().map_err(|_| Error::StackUnderflow)
+    }
+}
+
+impl<Backend> Runtime<Backend>
+where
+    Backend: DrawBuffer,
+{
+    pub(super) fn run(&mut self, ops: &[Op], observer: &mut dyn Observer) -> Result<(), Error> {
+        let mut machine = Machine::default();
+        let mut pc = 0;
+        let result = loop {
+            let Some(op) = ops.get(pc) else {
+                break Ok(());
+            };
+            pc += 1;
+            if let Err(err) = self.step(op, &mut pc, &mut machine, observer) {
+                break Err(err);
+            }
+        };
+
+        // Leave the blocks an error jumped out of
+        while let Some(outer) = machine.outer.pop() {
+            self.exit_scope(outer);
+        }
+        result
+    }
+
+    fn step(
+        &mut self,
+        op: &Op,
+        pc: &mut usize,
+        machine: &mut Machine,
+        observer: &mut dyn Observer,
+    ) -> Result<(), Error> {
+        match op {
+            Op::Instruction { start } => {
+                machine
+                    .instructions
+                    .push((start.unwrap_or(self.position), 0));
+                self.context.executed += 1;
+                self.context.check_limits(&self.stack)?;
+            }
+            Op::EndInstruction => {
+                let Some((start, drawn)) = machine.instructions.pop() else {
+                    return Ok(());
+                };
+                if self.stack_policy == StackPolicy::Clear {
+                    let count = self.stack.len().saturating_sub(drawn);
+                    if count > 0 {
+                        self.warn(Warning::UnusedValues { at: start, count });
+                    }
+                    self.stack.clear();
+                }
+            }
+            Op::Expression(expression) => {
+                self.position = expression.position;
+                self.span.clone_from(&expression.span);
+                observer.before(expression, self.snapshot());
+            }
+            Op::EndExpression(expression) => {
+                let value = machine.take();
+                self.stack.push(value.clone());
+                if expression.draw_result && value != Value::Void {
+                    if let Some((_, drawn)) = machine.instructions.last_mut() {
+                        *drawn += 1;
+                    }
+                    let computes_value = matches!(
+                        expression.content,
+                        ExpressionContent::Literal(_)
+                            | ExpressionContent::Variable(_)
+                            | ExpressionContent::FunctionCall { .. }
+                    );
+                    if computes_value && !value.is_drawable() {
+                        self.warn(Warning::Undrawable {
+                            at: expression.position,
+                        });
+                    }
+                    self.draw_value(value);
+                }
+                observer.after(expression.position, self.snapshot());
+                self.context.check_limits(&self.stack)?;
+            }
+            Op::Void => {
+                if let Some((_, drawn)) = machine.instructions.last_mut() {
+                    *drawn += 1;
+                }
+            }
+            Op::Value(value) => machine.result = value.clone(),
+            Op::Load(index) => machine.result = self.load(*index)?,
+            Op::Pop => machine.result = self.stack.pop()?,
+            Op::Push => self.stack.push(machine.take()),
+            Op::Save => {
+                let value = machine.take();
+                machine.saved.push(value);
+            }
+            Op::Scalar => {
+                if !matches!(machine.result, Value::Scalar(_)) {
+                    return Err(Error::InvalidArgument);
+                }
+            }
+            Op::Option(name) => {
+                let value = machine.take();
+                machine.options.push(((*name).clone(), value));
+            }
+            Op::Mark => machine.marks.push(self.stack.len()),
+            Op::Call {
+                index,
+                name,
+                options,
+                collects,
+            } => {
+                let start = machine.options.len() - options;
+                let options = machine.options.split_off(start);
+                let base = match collects {
+                    true => machine.marks.pop().unwrap_or_default(),
+                    false => self.stack.base,
+                };
+                let outer = std::mem::replace(&mut self.stack.base, base);
+                let result = self.call(*index, name, options);
+                self.stack.base = outer;
+                machine.result = result?;
+            }
+            Op::Memo {
+                index,
+                name,
+                arity,
+                site,
+            } => {
+                let values = self.stack.values();
+                let key = MemoKey::new(
+                    std::ptr::from_ref(*site) as usize,
+                    self.context.snap,
+                    &values[values.len().saturating_sub(*arity)..],
+                );
+                if let Some(result) = key.as_ref().and_then(|key| machine.memo.get(key)) {
+                    for _ in 0..*arity {
+                        self.stack.pop()?;
+                    }
+                    machine.result = result.clone();
+                    return Ok(());
+                }
+                machine.result = self.call(*index, name, Vec::new())?;
+                if let Some(key) = key {
+                    if machine.memo.len() >= MEMO_CAPACITY {
+                        machine.memo.clear();
+                    }
+                    machine.memo.insert(key, machine.result.clone());
+                }
+            }
+            Op::Fail(err) => return Err(err.clone()),
+            Op::Bind { index, constant } => {
+                self.bind_index(*index, machine.result.clone(), *constant)?;
+            }
+            Op::EnterScope => self.enter_scope(machine),
+            Op::ExitScope => {
+                if let Some(outer) = machine.outer.pop() {
+                    self.exit_scope(outer);
+                }
+                machine.result = Value::Void;
+            }
+            Op::Loop => {
+                let Value::List(items) = machine.take() else {
+                    return Err(Error::TypeError);
+                };
+                machine.loops.push((items, 0));
+            }
+            Op::Next { index, exit } => {
+                let item = machine.loops.last_mut().and_then(|(items, ran)| {
+                    let item = items.get(*ran)?.clone();
+                    *ran += 1;
+                    Some(item)
+                });
+                let Some(item) = item else {
+                    machine.loops.pop();
+                    machine.result = Value::Void;
+                    *pc = *exit;
+                    return Ok(());
+                };
+                self.context.iterations += 1;
+                self.context.check_limits(&self.stack)?;
+                self.enter_scope(machine);
+                self.bind_index(*index, item, false)?;
+            }
+            Op::Repeat { next } => {
+                if let Some(outer) = machine.outer.pop() {
+                    self.exit_scope(outer);
+                }
+                *pc = *next;
+            }
+            Op::Symbol(name) => {
+                if self.context.symbols.contains(*name) {
+                    return Err(Error::SymbolRedefined((*name).clone()));
+                }
+                let outside = std::mem::take(&mut self.context.transform);
+                self.context.defining.push(outside);
+                self.emit(DrawCommand::BeginSymbol {
+                    name: (*name).clone(),
+                });
+            }
+            Op::EndSymbol(name) => {
+                self.emit(DrawCommand::EndSymbol);
+                self.context.transform = self.context.defining.pop().unwrap_or_default();
+                if !self.context.symbols.insert((*name).clone()) {
+                    return Err(Error::SymbolRedefined((*name).clone()));
+                }
+            }
+            Op::Screen => {
+                let [x, y] = machine.scalars()?;
+                self.emit(DrawCommand::Resize {
+                    x: x.into(),
+                    y: y.into(),
+                });
+                machine.result = Value::Void;
+            }
+            Op::View { flip_y } => {
+                let [x, y, width, height] = machine.scalars()?;
+                self.emit(DrawCommand::SetView {
+                    x: x.into(),
+                    y: y.into(),
+                    width: width.into(),
+                    height: height.into(),
+                    flip_y: *flip_y,
+                });
+                machine.result = Value::Void;
+            }
+            Op::Metadata(name) => {
+                let Value::String(value) = machine.take() else {
+                    return Err(Error::InvalidArgument);
+                };
+                self.emit(DrawCommand::Metadata {
+                    name: name.clone(),
+                    value,
+                });
+            }
+            Op::Page => {
+                self.close_groups();
+                self.draw.new_page();
+                machine.result = Value::Void;
+            }
+            Op::Origin { bottom } => {
+                if self.context.visible.is_none() {
+                    return Err(Error::UnknownVisibleArea);
+                }
+                self.context.axes.bottom = Some(*bottom);
+                machine.result = Value::Void;
+            }
+            Op::YDir { up } => {
+                self.context.axes.up = *up;
+                machine.result = Value::Void;
+            }
+            Op::Param(index) => {
+                let name = self.context.scopes.names.name(*index).clone();
+                let [min, max, default] = machine.scalars()?;
+                if f64::from(min) > f64::from(max) {
+                    return Err(Error::InvalidArgument);
+                }
+                // The host may have set the parameter before running
+                let value = match self.context.scopes.get(*index).map(|param| &param.value) {
+                    Some(Value::Scalar(value)) => *value,
+                    Some(_) => return Err(Error::TypeError),
+                    None => default,
+                };
+                let range = f64::from(min)..=f64::from(max);
+                if !range.contains(&f64::from(default)) || !range.contains(&f64::from(value)) {
+                    return Err(Error::ParamOutOfRange(name));
+                }
+                self.bind_index(*index, Value::Scalar(value), false)?;
+                let param = Param {
+                    name,
+                    min,
+                    max,
+                    default,
+                    value,
+                };
+                match self
+                    .params
+                    .iter_mut()
+                    .find(|earlier| earlier.name == param.name)
+                {
+                    Some(earlier) => *earlier = param,
+                    None => self.params.push(param),
+                }
+                machine.result = Value::Scalar(value);
+            }
+        }
+        Ok(())
+    }
+
+    /// Call the builtin at `index` and draw what it queued.
+    fn call(
+        &mut self,
+        index: usize,
+        name: &str,
+        options: Vec<(SmolStr, Value)>,
+    ) -> Result<Value, Error> {
+        let result = self
+            .context
+            .call_index(index, name, &mut self.stack, options);
+
+        // Hand the queue back afterwards to keep its allocation
+        let mut pending = std::mem::take(&mut self.context.pending);
+        for command in pending.drain(..) {
+            self.emit(command);
+        }
+        self.context.pending = pending;
+        self.context.check_limits(&self.stack)?;
+        result
+    }
+
+    /// Run the following instructions in `scope`, leaving the stack of the
+    /// enclosing instruction untouched.
+    fn enter_scope(&mut self, machine: &mut Machine) {
+        machine.outer.push(std::mem::take(&mut self.stack));
+        self.context.scopes.enter();
+    }
+
+    fn exit_scope(&mut self, outer: Stack) {
+        self.warn_unused_variables();
+        self.context.scopes.exit();
+        self.stack = outer;
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ast::parse_file, testing::Recorder, token::StringTokenizer, util::test_helpers::*,
+    };
+
+    fn compiled(source: &str, check: impl FnOnce(&[Op])) {
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        let context = &mut runtime.context;
+        let mut ops = Vec::new();
+        for instruction in &program.instructions {
+            compile(
+                instruction,
+                &context.functions,
+                &mut context.scopes.names,
+                &mut ops,
+            );
+        }
+        check(&ops);
+    }
+
+    #[test]
+    fn test_calls_and_literals_are_resolved() {
+        compiled("circle (pnt2 1 2) 3mm\nnope", |ops| {
+            assert!(ops
+                .iter()
+                .any(|op| matches!(op, Op::Call { name: "circle", .. })));
+            assert!(ops
+                .iter()
+                .any(|op| matches!(op, Op::Memo { name: "pnt2", .. })));
+            assert!(ops
+                .iter()
+                .any(|op| matches!(op, Op::Value(value) if *value == scalar(3))));
+            assert!(ops.iter().any(
+                |op| matches!(op, Op::Fail(Error::FunctionNotFound { name, .. }) if name == "nope")
+            ));
+        });
+    }
+
+    #[test]
+    fn test_only_pure_subexpressions_are_memoized() {
+        compiled(
+            "mul 2 (add 1 2)\nadd 1 (rand)\nadd 1 (arrow $a end=dot)\nlist 1 (add 2)",
+            |ops| {
+                let memoized = ops
+                    .iter()
+                    .filter_map(|op| match op {
+                        Op::Memo { name, arity, .. } => Some((*name, *arity)),
+                        _ => None,
+                    })
+                    .collect::<Vec<_>>();
+                assert_eq!(memoized, [("add", 2)]);
+            },
+        );
+    }
+
+    #[test]
+    fn test_memoized_results() {
+        let source = "#for i in (list 1 2 1)
+    print (mul $i 10)
+#end
+#for i in (list 0 1)
+    snap_mode (mul $i 5)
+    print (pnt2 (add 1.2 0) 0)
+#end";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(
+            runtime.context.output().to_string(),
+            "10\n20\n10\n(1.2, 0)\n(0, 0)\n"
+        );
+
+        // Equal values stored differently may give different results
+        let source = "#for i in (list 0.0 (mul (sub 0 1.0) 0.0))\n    print (mul $i 1)\n#end";
+        let runtime = run_script(source).unwrap();
+        assert_eq!(runtime.context.output().to_string(), "0\n-0\n");
+    }
+
+    #[test]
+    fn test_memo_keys() {
+        let key = |value| MemoKey::new(0, None, &[value]).unwrap();
+        let mut memo = HashMap::new();
+        memo.insert(key(scalar(1)), scalar(2));
+        assert_eq!(memo.get(&key(scalar(1))), Some(&scalar(2)));
+        assert_eq!(memo.get(&key(scalar(1.0))), None);
+        assert_ne!(key(scalar(0.0)), key(scalar(-0.0)));
+        assert_eq!(key(point(1, 2)), key(point(1, 2)));
+        assert!(MemoKey::new(0, None, &[Value::List(Arc::new(Vec::new()))]).is_none());
+    }
+
+    #[test]
+    fn test_loops_jump_past_their_body() {
+        compiled("#for i in (list 1 2)\n#end\n#page", |ops| {
+            let next = ops
+                .iter()
+                .position(|op| matches!(op, Op::Next { .. }))
+                .unwrap();
+            let Op::Next { exit, .. } = ops[next] else {
+                unreachable!()
+            };
+            assert!(matches!(ops[exit - 1], Op::Repeat { next: n } if n == next));
+        });
+    }
+
+    #[test]
+    fn test_unknown_names_fail_before_running() {
+        fn run(source: &str) -> (Result<(), Error>, &str) {
+            let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+            let mut runtime = Runtime::<Recorder>::default();
+            let result = runtime.execute(program);
+            assert!(runtime.draw.commands.is_empty());
+            (result, &source[runtime.expression_span()])
+        }
+        let source = "circle (pnt2 0 0) 1\n#for i in (list 1)\nprint $widht\n#end\n#let width 2";
+        assert_eq!(
+            run(source),
+            (
+                Err(Error::VariableNotFound {
+                    name: "widht".into(),
+                    suggestion: Some("width".into()),
+                }),
+                "print $widht"
+            )
+        );
+        assert_eq!(
+            run("circle (pnt2 0 0) 1\n#scope\nnope $i\n#end\n#let i 1"),
+            (
+                Err(Error::FunctionNotFound {
+                    name: "nope".into(),
+                    suggestion: None,
+                }),
+                "nope $i"
+            )
+        );
+    }
+
+    #[test]
+    fn test_errors_leave_blocks() {
+        let program = "#let x 1\n#scope\n#for i in (list 1 2)\ncircle $i 1\n#end\n#end";
+        let program = parse_file(&mut StringTokenizer::new(&program)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        assert_eq!(runtime.execute(program), Err(Error::TypeError));
+        assert_eq!(runtime.context.scopes.len(), 1);
+        assert_eq!(runtime.lookup("x"), Ok(scalar(1)));
+    }
+}
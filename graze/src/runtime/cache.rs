@@ -0,0 +1,388 @@
+//! Remembering what top-level instructions did, so that running an edited
+//! program again only executes the instructions the edit affected.
+
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+};
+
+use smol_str::SmolStr;
+
+use super::{
+    bytecode,
+    parallel::{Access, Report, Worker},
+    Axes, Error, Runtime, StackPolicy, Value, Warning,
+};
+use crate::{
+    ast::{Argument, ExpressionContent, Instruction, Literal, Program},
+    output::DrawBuffer,
+    stdlib::{NumericPolicy, Scalar, Transform},
+    token::Number,
+};
+
+/// What top-level instructions did in earlier runs, to quickly run edited
+/// versions of a program, e.g. every time its file is saved.
+///
+/// An instruction is reused if its code, the global variables it reads,
+/// the transform, visible area and axes it draws with, the grid it snaps to
+/// and the numeric policy are the same as before.
+/// Instructions calling stateful builtins always run again.
+#[derive(Default)]
+pub struct Cache {
+    /// By the hash of the code. Copies of the same instruction with
+    /// different inputs share a bucket.
+    entries: HashMap<u64, Vec<Entry>>,
+    reused: usize,
+    executed: usize,
+}
+
+impl Cache {
+    /// How many instructions the last run reused.
+    pub fn reused(&self) -> usize {
+        self.reused
+    }
+
+    /// How many instructions the last run executed.
+    pub fn executed(&self) -> usize {
+        self.executed
+    }
+}
+
+/// The global variables an instruction read, `None` if they were not bound.
+type Inputs = Vec<(SmolStr, Option<Value>)>;
+
+struct Entry {
+    /// The line the instruction started on, to move warnings along with it.
+    line: usize,
+    inputs: Inputs,
+    transform: Transform,
+    visible: Option<(f64, f64, f64, f64)>,
+    axes: Axes,
+    snap: Option<Scalar>,
+    numeric_policy: NumericPolicy,
+    report: Report,
+}
+
+impl Entry {
+    fn matches(&self, inputs: &[(SmolStr, Option<Value>)], runtime: &Runtime<impl Sized>) -> bool {
+        self.inputs == inputs
+            && self.transform == runtime.context.transform
+            && self.visible == runtime.context.visible
+            && self.axes == runtime.context.axes
+            && self.snap == runtime.context.snap
+            && self.numeric_policy == runtime.context.config.numeric_policy
+    }
+
+    /// What the instruction did, as if it started on `line`.
+    fn report(&self, line: usize) -> Report {
+        let mut report = self.report.clone();
+        for warning in &mut report.warnings {
+            match warning {
+                Warning::Undrawable { at }
+                | Warning::UnusedValues { at, .. }
+                | Warning::OutsideScreen { at } => {
+                    *at = at.with_line(at.line() - self.line + line);
+                }
+                Warning::UnusedVariable(_) => {}
+            }
+        }
+        report
+    }
+}
+
+impl<Backend> Runtime<Backend>
+where
+    Backend: DrawBuffer,
+{
+    /// Like [`Runtime::execute`], but reuse what unchanged instructions did
+    /// in earlier runs with `cache`, and remember what the others do.
+    pub fn execute_cached(&mut self, program: Program, cache: &mut Cache) -> Result<(), Error> {
+        self.resolve(&program)?;
+        let mut previous = std::mem::take(&mut cache.entries);
+        (cache.reused, cache.executed) = (0, 0);
+        let mut worker = None;
+        let mut access = Access::default();
+
+        for instruction in &program.instructions {
+            let Some((key, line, inputs)) = self.cache_key(instruction, &mut access) else {
+                cache.executed += 1;
+                let mut ops = Vec::new();
+                let context = &mut self.context;
+                bytecode::compile(
+                    instruction,
+                    &context.functions,
+                    &mut context.scopes.names,
+                    &mut ops,
+                );
+                self.run(&ops, &mut ())?;
+                continue;
+            };
+
+            let earlier = previous.get_mut(&key).and_then(|bucket| {
+                let index = bucket
+                    .iter()
+                    .position(|entry| entry.matches(&inputs, self))?;
+                Some(bucket.swap_remove(index))
+            });
+            let hit = match earlier {
+                Some(entry) => Some(entry),
+                // The same instruction may come up more than once
+                None => cache.entries.get(&key).and_then(|bucket| {
+                    let entry = bucket.iter().find(|entry| entry.matches(&inputs, self))?;
+                    Some(Entry {
+                        line: entry.line,
+                        inputs: entry.inputs.clone(),
+                        report: entry.report.clone(),
+                        ..*entry
+                    })
+                }),
+            };
+            if let Some(entry) = hit {
+                cache.reused += 1;
+                self.merge(entry.report(line))?;
+                cache.entries.entry(key).or_default().push(entry);
+                continue;
+            }
+
+            cache.executed += 1;
+            let seed = self.seed(inputs.iter().map(|(name, _)| name.as_str()));
+            let report = worker
+                .get_or_insert_with(Worker::new)
+                .run(seed, std::slice::from_ref(instruction));
+            if report
+                .outcomes
+                .iter()
+                .all(|outcome| outcome.error.is_none())
+            {
+                cache.entries.entry(key).or_default().push(Entry {
+                    line,
+                    inputs,
+                    transform: self.context.transform,
+                    visible: self.context.visible,
+                    axes: self.context.axes,
+                    snap: self.context.snap,
+                    numeric_policy: self.context.config.numeric_policy,
+                    report: report.clone(),
+                });
+            }
+            self.merge(report)?;
+        }
+        self.warn_unused_variables();
+        Ok(())
+    }
+
+    /// The hash of the code of `instruction`, the line it starts on and
+    /// the global variables it reads, or `None` if it can't be cached.
+    fn cache_key<'p>(
+        &self,
+        instruction: &'p Instruction,
+        access: &mut Access<'p>,
+    ) -> Option<(u64, usize, Inputs)> {
+        let start = instruction.expressions.first()?.position;
+        let scopes = &self.context.scopes;
+        access.collect(instruction, &self.context.functions)?;
+        let cacheable = self.stack_policy == StackPolicy::Clear
+            && access
+                .writes
+                .iter()
+                .all(|name| !scopes.global(name).is_some_and(|global| global.constant));
+        if !cacheable {
+            return None;
+        }
+
+        let mut hasher = DefaultHasher::new();
+        start.column().hash(&mut hasher);
+        hash_instruction(instruction, &mut hasher);
+        let mut reads = access.reads.clone();
+        reads.sort_unstable();
+        reads.dedup();
+        let inputs = reads
+            .into_iter()
+            .map(|name| {
+                let value = scopes.global(name).map(|global| global.value.clone());
+                (SmolStr::new(name), value)
+            })
+            .collect();
+        Some((hasher.finish(), start.line(), inputs))
+    }
+}
+
+/// Hash the code of `instruction`, leaving out where it is.
+fn hash_instruction(instruction: &Instruction, state: &mut impl Hasher) {
+    instruction.expressions.len().hash(state);
+    for expression in &instruction.expressions {
+        expression.draw_result.hash(state);
+        hash_expression(&expression.content, state);
+    }
+}
+
+fn hash_expression(expression: &ExpressionContent, state: &mut impl Hasher) {
+    std::mem::discriminant(expression).hash(state);
+    match expression {
+        ExpressionContent::Literal(literal) => hash_literal(literal, state),
+        ExpressionContent::Variable(name) => name.hash(state),
+        ExpressionContent::FunctionCall {
+            name,
+            args,
+            options,
+        } => {
+            name.hash(state);
+            args.len().hash(state);
+            for arg in args {
+                hash_argument(arg, state);
+            }
+            options.len().hash(state);
+            for (option, value) in options {
+                option.hash(state);
+                hash_argument(value, state);
+            }
+        }
+        ExpressionContent::Let { name, init } | ExpressionContent::Const { name, init } => {
+            name.hash(state);
+            init.is_some().hash(state);
+            if let Some(init) = init {
+                hash_argument(init, state);
+            }
+        }
+        ExpressionContent::Param {
+            name,
+            min,
+            max,
+            default,
+        } => {
+            name.hash(state);
+            for argument in [min, max, default] {
+                hash_argument(argument, state);
+            }
+        }
+        ExpressionContent::Screen(x, y) => {
+            hash_argument(x, state);
+            hash_argument(y, state);
+        }
+        ExpressionContent::View {
+            x,
+            y,
+            width,
+            height,
+            flip_y,
+        } => {
+            for argument in [x, y, width, height] {
+                hash_argument(argument, state);
+            }
+            flip_y.hash(state);
+        }
+        ExpressionContent::Page => {}
+        ExpressionContent::Origin { bottom } => bottom.hash(state),
+        ExpressionContent::YDir { up } => up.hash(state),
+        ExpressionContent::Title(text) | ExpressionContent::Desc(text) => {
+            hash_argument(text, state);
+        }
+        ExpressionContent::Meta { name, value } => {
+            name.hash(state);
+            hash_argument(value, state);
+        }
+        ExpressionContent::Scope(body) => hash_block(body, state),
+        ExpressionContent::For { name, items, body } => {
+            name.hash(state);
+            hash_argument(items, state);
+            hash_block(body, state);
+        }
+        ExpressionContent::Symbol { name, body } => {
+            name.hash(state);
+            hash_block(body, state);
+        }
+    }
+}
+
+fn hash_block(body: &[Instruction], state: &mut impl Hasher) {
+    body.len().hash(state);
+    for instruction in body {
+        hash_instruction(instruction, state);
+    }
+}
+
+fn hash_argument(argument: &Argument, state: &mut impl Hasher) {
+    std::mem::discriminant(argument).hash(state);
+    match argument {
+        Argument::Variable(name) => name.hash(state),
+        Argument::Literal(literal) => hash_literal(literal, state),
+        Argument::Parenthesized(content) => hash_expression(content, state),
+    }
+}
+
+fn hash_literal(literal: &Literal, state: &mut impl Hasher) {
+    std::mem::discriminant(literal).hash(state);
+    let hash_number = |number: &Number, state: &mut _| match number {
+        Number::Integer(integer) => (0u8, integer).hash(state),
+        Number::Float(float) => (1u8, float.to_bits()).hash(state),
+    };
+    match literal {
+        Literal::Number(number) => hash_number(number, state),
+        Literal::Quantity(number, unit) => {
+            hash_number(number, state);
+            std::mem::discriminant(unit).hash(state);
+        }
+        Literal::String(string) => string.hash(state),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{ast::parse_file, testing::Recorder, token::StringTokenizer};
+
+    fn run(source: &str, cache: Option<&mut Cache>) -> Runtime<Recorder> {
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        match cache {
+            Some(cache) => runtime.execute_cached(program, cache).unwrap(),
+            None => runtime.execute(program).unwrap(),
+        }
+        runtime
+    }
+
+    /// Run `source` with `cache`, check that it draws and warns the same as
+    /// without it and return how many instructions were reused.
+    fn assert_same(source: &str, cache: &mut Cache) -> usize {
+        let cached = run(source, Some(cache));
+        let expected = run(source, None);
+        assert_eq!(cached.draw.commands, expected.draw.commands);
+        assert_eq!(cached.warnings(), expected.warnings());
+        cache.reused()
+    }
+
+    #[test]
+    fn test_only_changes_run_again() {
+        let mut cache = Cache::default();
+        let source = "#screen 10 10\n#let r 1\ncircle (pnt2 0 0) $r\n\
+            circle (pnt2 20 20) 2\n#let p (pnt2 1 1)\nline $p (vec2 1 1)\n5";
+        assert_eq!(assert_same(source, &mut cache), 0);
+        assert_eq!(cache.executed(), 7);
+        assert_eq!(assert_same(source, &mut cache), 6);
+        assert_eq!(cache.executed(), 1);
+
+        // Only the binding and the circle reading it
+        let edited = source.replace("#let r 1", "#let r 2");
+        assert_eq!(assert_same(&edited, &mut cache), 4);
+
+        // Warnings move along with the lines
+        let moved = format!("#let unused 0\n\n{edited}");
+        assert_eq!(assert_same(&moved, &mut cache), 6);
+    }
+
+    #[test]
+    fn test_transforms_and_errors() {
+        let mut cache = Cache::default();
+        let source = "circle (pnt2 1 1) 1\ncircle (pnt2 1 1) 1\n\
+            translate (vec2 1 0)\ncircle (pnt2 1 1) 1";
+        assert_eq!(assert_same(source, &mut cache), 1);
+        assert_eq!(assert_same(source, &mut cache), 3);
+        assert_eq!(cache.executed(), 1);
+
+        let program = parse_file(&mut StringTokenizer::new(&"#let a 1\ncircle $a 1")).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        let result = runtime.execute_cached(program, &mut cache);
+        assert_eq!(result, Err(Error::TypeError));
+        assert_eq!(runtime.lookup("a"), Ok(Value::Scalar(1.into())));
+    }
+}
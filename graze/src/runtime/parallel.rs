@@ -0,0 +1,566 @@
+//! Running independent instructions on several threads. A run of top-level
+//! instructions that don't touch each other's variables is split into
+//! chunks, each run by a runtime of its own that draws into a buffer. The
+//! buffers are then replayed to the real backend in program order, so the
+//! backend itself is only ever used from the calling thread.
+
+use std::collections::HashSet;
+
+use smol_str::SmolStr;
+
+use super::{bytecode, Axes, Error, Runtime, RuntimeConfig, Scopes, Value, Variable, Warning};
+use crate::{
+    ast::{Argument, ExpressionContent, Instruction, Program},
+    output::{DrawBuffer, DrawCommand},
+    plugin::Functions,
+    stdlib::{Scalar, Transform},
+    token::Span,
+};
+
+/// Instructions each thread gets at least, so that short runs aren't
+/// slowed down by starting threads.
+const MIN_CHUNK: usize = 64;
+
+/// The global variables an instruction reads and binds, in no particular
+/// order and possibly repeated.
+#[derive(Default)]
+pub(super) struct Access<'p> {
+    pub reads: Vec<&'p str>,
+    pub writes: Vec<&'p str>,
+}
+
+impl<'p> Access<'p> {
+    /// Find out what `instruction` does to global variables, failing if it
+    /// has to run on the calling thread, e.g. because it calls a stateful
+    /// builtin.
+    pub fn collect(&mut self, instruction: &'p Instruction, functions: &Functions) -> Option<()> {
+        self.reads.clear();
+        self.writes.clear();
+        for expression in &instruction.expressions {
+            self.expression(&expression.content, functions, true)?;
+        }
+        Some(())
+    }
+
+    /// Record what `expression` accesses. Bindings inside blocks are local,
+    /// but reads inside them count even if they are of local variables.
+    fn expression(
+        &mut self,
+        expression: &'p ExpressionContent,
+        functions: &Functions,
+        global: bool,
+    ) -> Option<()> {
+        match expression {
+            ExpressionContent::Literal(_) => {}
+            ExpressionContent::Variable(name) => {
+                self.reads.push(name);
+            }
+            ExpressionContent::FunctionCall {
+                name,
+                args,
+                options,
+            } => {
+                if !functions.is_parallel(name) {
+                    return None;
+                }
+                for arg in args.iter().chain(options.iter().map(|(_, value)| value)) {
+                    self.argument(arg, functions, global)?;
+                }
+            }
+            ExpressionContent::Let { name, init } | ExpressionContent::Const { name, init } => {
+                if let Some(init) = init {
+                    self.argument(init, functions, global)?;
+                }
+                if global {
+                    self.writes.push(name);
+                }
+            }
+            ExpressionContent::Scope(body) => self.block(body, functions)?,
+            ExpressionContent::For { items, body, .. } => {
+                self.argument(items, functions, global)?;
+                self.block(body, functions)?;
+            }
+            // Symbols have to be defined before anything places them
+            ExpressionContent::Symbol { .. } => return None,
+            // Parameters are collected in the order they are declared
+            ExpressionContent::Param { .. } => return None,
+            // These change the page or what is visible on it
+            ExpressionContent::Screen(..)
+            | ExpressionContent::View { .. }
+            | ExpressionContent::Title(_)
+            | ExpressionContent::Desc(_)
+            | ExpressionContent::Meta { .. }
+            | ExpressionContent::Page
+            | ExpressionContent::Origin { .. }
+            | ExpressionContent::YDir { .. } => return None,
+        }
+        Some(())
+    }
+
+    fn argument(
+        &mut self,
+        argument: &'p Argument,
+        functions: &Functions,
+        global: bool,
+    ) -> Option<()> {
+        match argument {
+            Argument::Variable(name) => {
+                self.reads.push(name);
+                Some(())
+            }
+            Argument::Literal(_) => Some(()),
+            Argument::Parenthesized(content) => self.expression(content, functions, global),
+        }
+    }
+
+    fn block(&mut self, body: &'p [Instruction], functions: &Functions) -> Option<()> {
+        for instruction in body {
+            for expression in &instruction.expressions {
+                self.expression(&expression.content, functions, false)?;
+            }
+        }
+        Some(())
+    }
+}
+
+/// What a worker starts from: the state of the runtime before the
+/// instructions it runs.
+#[derive(Clone)]
+pub(super) struct Seed {
+    /// The global variables read by the instructions.
+    pub variables: Vec<(SmolStr, Value)>,
+    pub transform: Transform,
+    pub visible: Option<(f64, f64, f64, f64)>,
+    pub axes: Axes,
+    pub snap: Option<Scalar>,
+    pub config: RuntimeConfig,
+}
+
+/// What one instruction did on a worker.
+#[derive(Clone)]
+pub(super) struct Outcome {
+    /// How many commands it drew.
+    commands: usize,
+    /// How many warnings it gave.
+    warnings: usize,
+    executed: usize,
+    iterations: usize,
+    pub error: Option<Error>,
+    /// The expression that ran last, the one that failed if there is an error.
+    span: Span,
+}
+
+/// What a worker did with its instructions.
+#[derive(Clone)]
+pub(super) struct Report {
+    /// One per instruction that ran, up to the first one that failed.
+    pub outcomes: Vec<Outcome>,
+    commands: Vec<DrawCommand>,
+    pub warnings: Vec<Warning>,
+    /// Global variables from before the instructions that were read.
+    read: Vec<SmolStr>,
+    bound: Vec<Binding>,
+}
+
+/// A global variable bound on a worker.
+#[derive(Clone)]
+struct Binding {
+    name: SmolStr,
+    value: Value,
+    constant: bool,
+    /// Whether it was read after being bound.
+    used: bool,
+}
+
+/// Keeps what a worker draws until it is replayed to the real backend.
+#[derive(Default)]
+pub(super) struct Buffer {
+    commands: Vec<DrawCommand>,
+}
+
+impl DrawBuffer for Buffer {
+    fn reset(&mut self) {
+        self.commands.clear();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// A runtime of its own that runs instructions away from the real backend
+/// and records what they did.
+pub(super) struct Worker {
+    runtime: Runtime<Buffer>,
+}
+
+impl Worker {
+    pub fn new() -> Self {
+        Self {
+            runtime: Runtime::default(),
+        }
+    }
+
+    /// Run `instructions` from `seed`, up to the first one that fails.
+    pub fn run(&mut self, seed: Seed, instructions: &[Instruction]) -> Report {
+        let runtime = &mut self.runtime;
+        let context = &mut runtime.context;
+        context.transform = seed.transform;
+        context.visible = seed.visible;
+        context.axes = seed.axes;
+        context.snap = seed.snap;
+        context.config = seed.config;
+        (context.executed, context.iterations, context.drawn) = (0, 0, 0);
+        runtime.stack.clear();
+
+        // The variables read go into a scope of their own below the
+        // global one, to tell them apart from the ones bound here
+        context.scopes = Scopes::default();
+        for (name, value) in seed.variables {
+            let index = context.scopes.names.intern(&name);
+            let variable = Variable {
+                value,
+                constant: false,
+                used: false,
+                host: false,
+            };
+            context.scopes.bind(index, variable);
+        }
+        context.scopes.enter();
+
+        let mut outcomes = Vec::with_capacity(instructions.len());
+        let mut ops = Vec::new();
+        for instruction in instructions {
+            let context = &mut runtime.context;
+            let before = (
+                runtime.draw.commands.len(),
+                runtime.warnings.len(),
+                context.executed,
+                context.iterations,
+            );
+            ops.clear();
+            bytecode::compile(
+                instruction,
+                &context.functions,
+                &mut context.scopes.names,
+                &mut ops,
+            );
+            let error = runtime.run(&ops, &mut ()).err();
+
+            let failed = error.is_some();
+            outcomes.push(Outcome {
+                commands: runtime.draw.commands.len() - before.0,
+                warnings: runtime.warnings.len() - before.1,
+                executed: runtime.context.executed - before.2,
+                iterations: runtime.context.iterations - before.3,
+                error,
+                span: runtime.span.clone(),
+            });
+            if failed {
+                break;
+            }
+        }
+
+        let scopes = &runtime.context.scopes;
+        Report {
+            outcomes,
+            commands: std::mem::take(&mut runtime.draw.commands),
+            warnings: std::mem::take(&mut runtime.warnings),
+            read: scopes
+                .scope(0)
+                .filter(|(_, variable)| variable.used)
+                .map(|(name, _)| name.clone())
+                .collect(),
+            bound: scopes
+                .scope(1)
+                .map(|(name, variable)| Binding {
+                    name: name.clone(),
+                    value: variable.value.clone(),
+                    constant: variable.constant,
+                    used: variable.used,
+                })
+                .collect(),
+        }
+    }
+}
+
+impl<Backend> Runtime<Backend>
+where
+    Backend: DrawBuffer,
+{
+    pub(super) fn execute_parallel(&mut self, program: Program) -> Result<(), Error> {
+        self.resolve(&program)?;
+        let mut rest = &program.instructions[..];
+        while !rest.is_empty() {
+            let (count, reads) = self.independent(rest);
+            let chunks = (count / MIN_CHUNK).min(self.threads);
+            if chunks < 2 {
+                // Too few to be worth a thread, or not independent at all
+                for instruction in &rest[..count.max(1)] {
+                    let mut ops = Vec::new();
+                    let context = &mut self.context;
+                    bytecode::compile(
+                        instruction,
+                        &context.functions,
+                        &mut context.scopes.names,
+                        &mut ops,
+                    );
+                    self.run(&ops, &mut ())?;
+                }
+                rest = &rest[count.max(1)..];
+            } else {
+                self.execute_batch(&rest[..count], chunks, reads)?;
+                rest = &rest[count..];
+            }
+        }
+        self.warn_unused_variables();
+        Ok(())
+    }
+
+    /// How many instructions from the start of `instructions` can run in
+    /// any order, and which global variables they read.
+    fn independent<'p>(&self, instructions: &'p [Instruction]) -> (usize, HashSet<&'p str>) {
+        let scopes = &self.context.scopes;
+        let constant = |name| scopes.global(name).is_some_and(|global| global.constant);
+        let (mut reads, mut writes) = (HashSet::new(), HashSet::new());
+        let mut access = Access::default();
+        for (count, instruction) in instructions.iter().enumerate() {
+            let independent = access
+                .collect(instruction, &self.context.functions)
+                .is_some()
+                && access.reads.iter().all(|name| !writes.contains(name))
+                && access
+                    .writes
+                    .iter()
+                    .all(|&name| !reads.contains(name) && !constant(name) && writes.insert(name));
+            if !independent {
+                return (count, reads);
+            }
+            reads.extend(access.reads.drain(..));
+        }
+        (instructions.len(), reads)
+    }
+
+    fn execute_batch(
+        &mut self,
+        batch: &[Instruction],
+        chunks: usize,
+        reads: HashSet<&str>,
+    ) -> Result<(), Error> {
+        let seed = self.seed(reads);
+        let reports = std::thread::scope(|scope| {
+            let threads = batch
+                .chunks(batch.len().div_ceil(chunks))
+                .map(|chunk| {
+                    let seed = seed.clone();
+                    scope.spawn(move || Worker::new().run(seed, chunk))
+                })
+                .collect::<Vec<_>>();
+            threads
+                .into_iter()
+                .map(|thread| {
+                    thread
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<Vec<_>>()
+        });
+
+        reports
+            .into_iter()
+            .try_for_each(|report| self.merge(report))
+    }
+
+    /// What a worker starts from to run instructions reading the global
+    /// variables `reads` next.
+    pub(super) fn seed<'n>(&self, reads: impl IntoIterator<Item = &'n str>) -> Seed {
+        let scopes = &self.context.scopes;
+        let config = &self.context.config;
+        // Workers may use what is left of the limits, the total is
+        // checked again once they are done
+        let left = |max: Option<usize>, used: usize| max.map(|max| max.saturating_sub(used));
+        Seed {
+            variables: reads
+                .into_iter()
+                .filter_map(|name| Some((name.into(), scopes.global(name)?.value.clone())))
+                .collect(),
+            transform: self.context.transform,
+            visible: self.context.visible,
+            axes: self.context.axes,
+            snap: self.context.snap,
+            config: RuntimeConfig {
+                max_instructions: left(config.max_instructions, self.context.executed),
+                max_loop_iterations: left(config.max_loop_iterations, self.context.iterations),
+                max_draw_commands: left(config.max_draw_commands, self.context.drawn),
+                ..config.clone()
+            },
+        }
+    }
+
+    /// Take over what a worker did, as if it had run here.
+    pub(super) fn merge(&mut self, report: Report) -> Result<(), Error> {
+        let result = self.replay(report.outcomes, report.commands, report.warnings);
+        let scopes = &mut self.context.scopes;
+        for name in report.read {
+            if let Some(global) = scopes.global_mut(&name) {
+                global.used = true;
+            }
+        }
+        for binding in report.bound {
+            self.bind(&binding.name, binding.value, binding.constant)?;
+            if let (true, Some(global)) =
+                (binding.used, self.context.scopes.global_mut(&binding.name))
+            {
+                global.used = true;
+            }
+        }
+        result
+    }
+
+    /// Hand what the instructions of a worker drew to the backend, stopping
+    /// at the first error.
+    fn replay(
+        &mut self,
+        outcomes: Vec<Outcome>,
+        commands: Vec<DrawCommand>,
+        warnings: Vec<Warning>,
+    ) -> Result<(), Error> {
+        let (mut commands, mut warnings) = (commands.into_iter(), warnings.into_iter());
+        for outcome in outcomes {
+            self.context.executed += outcome.executed;
+            self.context.iterations += outcome.iterations;
+            for command in commands.by_ref().take(outcome.commands) {
+                // Already transformed on the thread
+                self.context.drawn += 1;
+                self.draw.draw(command);
+            }
+            for warning in warnings.by_ref().take(outcome.warnings) {
+                self.warn(warning);
+            }
+            self.context.check_limits(&self.stack)?;
+            if let Some(err) = outcome.error {
+                self.span = outcome.span;
+                return Err(err);
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ast::parse_file, testing::Recorder, token::StringTokenizer, util::test_helpers::*,
+    };
+
+    fn run(source: &str, threads: usize) -> (Result<(), Error>, Runtime<Recorder>) {
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.set_threads(threads);
+        let result = runtime.execute(program);
+        (result, runtime)
+    }
+
+    /// Run `source` both in order and on four threads, and check that
+    /// both draw and warn the same.
+    fn assert_same(source: &str) -> Runtime<Recorder> {
+        let (expected, sequential) = run(source, 1);
+        let (actual, parallel) = run(source, 4);
+        assert_eq!(actual, expected);
+        assert_eq!(parallel.warnings(), sequential.warnings());
+        assert_eq!(parallel.draw.commands, sequential.draw.commands);
+        parallel
+    }
+
+    #[test]
+    fn test_access() {
+        let program = "#let a (add $b 1) => circle (pnt2 $a 0) 1\n\
+            #for i in $items\n#let local $i\n#end\n\
+            dim (pnt2 0 0) (pnt2 1 1)";
+        let program = parse_file(&mut StringTokenizer::new(&program)).unwrap();
+        let runtime = Runtime::<Recorder>::default();
+        let functions = &runtime.context.functions;
+
+        let mut access = Access::default();
+        access.collect(&program.instructions[0], functions).unwrap();
+        assert_eq!(access.reads, ["b", "a"]);
+        assert_eq!(access.writes, ["a"]);
+        access.collect(&program.instructions[1], functions).unwrap();
+        assert_eq!(access.reads, ["items", "i"]);
+        assert!(access.writes.is_empty());
+        assert!(access
+            .collect(&program.instructions[2], functions)
+            .is_none());
+    }
+
+    #[test]
+    fn test_batches_end_at_dependencies() {
+        let mut source = String::from("#screen 100 100\n#let y 3\n");
+        for i in 0..200 {
+            source.push_str(&format!("#let x{i} {i} => circle (pnt2 $x{i} $y) 1\n"));
+        }
+        source.push_str("#let z (add $x1 $x199)\n");
+        for i in 0..200 {
+            source.push_str(&format!("line (pnt2 0 $z) (vec2 {i} 1)\n"));
+        }
+        source.push_str("#let x3 (sub $x0 $z)\ncircle (pnt2 0 $x3) 200");
+
+        let mut runtime = assert_same(&source);
+        assert_eq!(runtime.lookup("x3"), Ok(scalar(-200)));
+        assert_eq!(runtime.draw.commands.len(), 402);
+
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let runtime = Runtime::<Recorder>::default();
+        assert_eq!(runtime.independent(&program.instructions[1..]).0, 1);
+        assert_eq!(runtime.independent(&program.instructions[2..]).0, 200);
+        assert_eq!(runtime.independent(&program.instructions[203..]).0, 201);
+    }
+
+    #[test]
+    fn test_same_as_in_order() {
+        let mut source = String::from("#let unused 1\n");
+        for i in 0..300 {
+            source.push_str(&format!(
+                "#let v{i} (pnt2 {i} 0) => circle $v{i} 1\n\
+                 #for p in (list 1 2)\nline (pnt2 $p 0) (vec2 1 1)\n#end\n"
+            ));
+        }
+        source.push_str("rotate 90deg\n");
+        for i in 0..300 {
+            source.push_str(&format!("line $v{i} (vec2 1 1)\n"));
+        }
+        let runtime = assert_same(&source);
+        assert!(runtime
+            .warnings()
+            .contains(&Warning::UnusedVariable("unused".into())));
+    }
+
+    #[test]
+    fn test_errors_and_limits() {
+        let mut source = String::new();
+        for i in 0..300 {
+            source.push_str(&format!("circle (pnt2 {i} 0) 1\n"));
+        }
+        assert_same(&format!("{source}circle (pnt2 0 0) (div 1 0)\n{source}"));
+        assert_same(&format!("{source}#const c 1\n{source}#let c 2\n{source}"));
+
+        let (result, runtime) = {
+            let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+            let mut runtime = Runtime::<Recorder>::default();
+            runtime.set_threads(4);
+            runtime.set_config(RuntimeConfig {
+                max_draw_commands: Some(100),
+                ..RuntimeConfig::default()
+            });
+            (runtime.execute(program), runtime)
+        };
+        assert_eq!(
+            result,
+            Err(Error::LimitExceeded(crate::Limit::DrawCommands))
+        );
+        assert!(runtime.draw.commands.len() <= 101);
+    }
+}
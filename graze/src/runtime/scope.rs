@@ -0,0 +1,241 @@
+//! Variables by the index their name was interned at. Compiled programs
+//! refer to variables by index, so reading one indexes into a table
+//! instead of hashing its name once for every enclosing scope.
+
+use std::collections::HashMap;
+
+use smol_str::SmolStr;
+
+use super::{Error, Value};
+use crate::util::closest;
+
+/// Names of variables, each interned once at a dense index.
+#[derive(Default)]
+pub(super) struct Names {
+    indices: HashMap<SmolStr, usize>,
+    names: Vec<SmolStr>,
+}
+
+impl Names {
+    /// The index of `name`, interning it if it is new.
+    pub fn intern(&mut self, name: &SmolStr) -> usize {
+        if let Some(&index) = self.indices.get(name) {
+            return index;
+        }
+        let index = self.names.len();
+        self.indices.insert(name.clone(), index);
+        self.names.push(name.clone());
+        index
+    }
+
+    /// The index of `name`, if it was interned.
+    pub fn index(&self, name: &str) -> Option<usize> {
+        self.indices.get(name).copied()
+    }
+
+    pub fn name(&self, index: usize) -> &SmolStr {
+        &self.names[index]
+    }
+}
+
+pub(super) struct Variable {
+    pub value: Value,
+    pub constant: bool,
+    /// Whether it was read since it was bound.
+    pub used: bool,
+    /// Whether the host bound it, leaving a `#let` of the script in the
+    /// same scope nothing but a default.
+    pub host: bool,
+}
+
+/// Every variable in scope. Each name keeps its bindings in a stack of its
+/// own, so that the innermost one is found without searching the scopes.
+pub(super) struct Scopes {
+    pub names: Names,
+    /// The bindings of each name along with the depth of the scope they
+    /// belong to, innermost last.
+    bindings: Vec<Vec<(usize, Variable)>>,
+    /// The names each scope bound, innermost scope last. The global scope
+    /// is never popped.
+    bound: Vec<Vec<usize>>,
+}
+
+impl Default for Scopes {
+    fn default() -> Self {
+        Self {
+            names: Names::default(),
+            bindings: Vec::new(),
+            bound: vec![Vec::new()],
+        }
+    }
+}
+
+impl Scopes {
+    /// How many scopes are open, counting the global one.
+    pub fn len(&self) -> usize {
+        self.bound.len()
+    }
+
+    pub fn enter(&mut self) {
+        self.bound.push(Vec::new());
+    }
+
+    /// Leave the innermost scope, dropping what it bound.
+    pub fn exit(&mut self) {
+        if self.bound.len() == 1 {
+            return;
+        }
+        for index in self.bound.pop().unwrap_or_default() {
+            self.bindings[index].pop();
+        }
+    }
+
+    /// The innermost binding of the name at `index`.
+    pub fn get(&self, index: usize) -> Option<&Variable> {
+        let (_, variable) = self.bindings.get(index)?.last()?;
+        Some(variable)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Variable> {
+        let (_, variable) = self.bindings.get_mut(index)?.last_mut()?;
+        Some(variable)
+    }
+
+    /// The innermost binding of `name`.
+    pub fn find(&self, name: &str) -> Option<&Variable> {
+        self.get(self.names.index(name)?)
+    }
+
+    /// The binding of the name at `index` in the scope at `depth`, the
+    /// global scope being at 0.
+    pub fn get_in(&mut self, depth: usize, index: usize) -> Option<&mut Variable> {
+        let bindings = self.bindings.get_mut(index)?;
+        let (_, variable) = bindings.iter_mut().rfind(|(at, _)| *at == depth)?;
+        Some(variable)
+    }
+
+    /// The binding of `name` in the global scope.
+    pub fn global(&self, name: &str) -> Option<&Variable> {
+        let bindings = self.bindings.get(self.names.index(name)?)?;
+        match bindings.first() {
+            Some((0, variable)) => Some(variable),
+            _ => None,
+        }
+    }
+
+    pub fn global_mut(&mut self, name: &str) -> Option<&mut Variable> {
+        let index = self.names.index(name)?;
+        self.get_in(0, index)
+    }
+
+    /// Bind the name at `index` in the scope at `depth`, replacing what
+    /// that scope bound it to before.
+    pub fn bind_in(&mut self, depth: usize, index: usize, variable: Variable) {
+        if self.bindings.len() <= index {
+            self.bindings.resize_with(index + 1, Vec::new);
+        }
+        let bindings = &mut self.bindings[index];
+        match bindings.iter().rposition(|(at, _)| *at <= depth) {
+            Some(position) if bindings[position].0 == depth => {
+                bindings[position].1 = variable;
+            }
+            position => {
+                let position = position.map_or(0, |position| position + 1);
+                bindings.insert(position, (depth, variable));
+                self.bound[depth].push(index);
+            }
+        }
+    }
+
+    /// Bind the name at `index` in the innermost scope.
+    pub fn bind(&mut self, index: usize, variable: Variable) {
+        self.bind_in(self.len() - 1, index, variable);
+    }
+
+    /// Everything the scope at `depth` bound, in no particular order.
+    pub fn scope(&self, depth: usize) -> impl Iterator<Item = (&SmolStr, &Variable)> {
+        self.bound[depth].iter().filter_map(move |&index| {
+            let (_, variable) = self.bindings[index].iter().rfind(|(at, _)| *at == depth)?;
+            Some((self.names.name(index), variable))
+        })
+    }
+
+    /// The innermost binding of every variable in scope, in no particular
+    /// order.
+    pub fn visible(&self) -> impl Iterator<Item = (&SmolStr, &Variable)> {
+        self.bindings
+            .iter()
+            .enumerate()
+            .filter_map(|(index, bindings)| {
+                let (_, variable) = bindings.last()?;
+                Some((self.names.name(index), variable))
+            })
+    }
+
+    /// Variables of the innermost scope that were never read, marking them
+    /// as read so that they are only reported once.
+    pub fn take_unused(&mut self) -> Vec<SmolStr> {
+        let depth = self.len() - 1;
+        let mut unused = Vec::new();
+        for &index in &self.bound[depth] {
+            if let Some((_, variable)) = self.bindings[index].last_mut() {
+                if !variable.used {
+                    variable.used = true;
+                    unused.push(self.names.name(index).clone());
+                }
+            }
+        }
+        unused
+    }
+
+    /// The error for reading `name`, suggesting a variable in scope or one
+    /// of `others` with a similar name.
+    pub fn not_found<'n>(&'n self, name: &str, others: impl IntoIterator<Item = &'n str>) -> Error {
+        let names = self.visible().map(|(name, _)| name.as_str()).chain(others);
+        Error::VariableNotFound {
+            name: name.into(),
+            suggestion: closest(name, names).map(SmolStr::new),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn variable(value: Value) -> Variable {
+        Variable {
+            value,
+            constant: false,
+            used: false,
+            host: false,
+        }
+    }
+
+    #[test]
+    fn test_shadowing() {
+        let mut scopes = Scopes::default();
+        let x = scopes.names.intern(&"x".into());
+        scopes.bind(x, variable(scalar(1)));
+        scopes.enter();
+        scopes.bind(x, variable(scalar(2)));
+        scopes.bind(x, variable(scalar(3)));
+        assert_eq!(scopes.get(x).map(|x| &x.value), Some(&scalar(3)));
+        assert_eq!(scopes.scope(1).count(), 1);
+
+        // Globals bound from inside a scope stay hidden until it ends
+        let y = scopes.names.intern(&"y".into());
+        scopes.bind(y, variable(scalar(4)));
+        scopes.bind_in(0, y, variable(scalar(5)));
+        assert_eq!(scopes.get(y).map(|y| &y.value), Some(&scalar(4)));
+        assert_eq!(scopes.take_unused().len(), 2);
+        assert!(scopes.take_unused().is_empty());
+
+        scopes.exit();
+        assert_eq!(scopes.find("x").map(|x| &x.value), Some(&scalar(1)));
+        assert_eq!(scopes.global("y").map(|y| &y.value), Some(&scalar(5)));
+        scopes.exit();
+        assert_eq!(scopes.len(), 1);
+    }
+}
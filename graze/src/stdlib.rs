@@ -1,10 +1,45 @@
+mod angle;
 mod basic;
+mod bounds;
+mod circle;
+mod clip;
+mod compare;
+mod corner;
+mod debug;
+mod dimension;
+mod docs;
+mod frame;
+mod grid;
+mod group;
+mod hatch;
+mod image;
+mod intersect;
+mod layer;
+mod list;
+mod logic;
+mod marker;
+mod offset;
+mod path;
+mod plot;
 mod point;
+mod random;
 mod scalar;
+mod shape;
+mod style;
+mod symbol;
+mod transform;
 mod vector;
 
+pub use angle::Angle;
+pub use docs::{builtin_doc, BuiltinDoc, DOCS};
+pub use group::Group;
+pub use path::Path;
+pub(crate) use point::snapped;
 pub use point::Point;
-pub use scalar::Scalar;
+pub use random::Random;
+pub(crate) use scalar::ScalarKey;
+pub use scalar::{NumericPolicy, Scalar};
+pub use transform::Transform;
 pub use vector::Vector;
 
 use crate::runtime::Runtime;
@@ -22,7 +57,33 @@ macro_rules! reverse_pop {
 
 pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
     basic::register(runtime);
+    angle::register(runtime);
     vector::register(runtime);
     point::register(runtime);
     scalar::register(runtime);
+    compare::register(runtime);
+    logic::register(runtime);
+    circle::register(runtime);
+    intersect::register(runtime);
+    corner::register(runtime);
+    transform::register(runtime);
+    layer::register(runtime);
+    list::register(runtime);
+    group::register(runtime);
+    symbol::register(runtime);
+    debug::register(runtime);
+    random::register(runtime);
+    grid::register(runtime);
+    dimension::register(runtime);
+    marker::register(runtime);
+    hatch::register(runtime);
+    shape::register(runtime);
+    offset::register(runtime);
+    bounds::register(runtime);
+    clip::register(runtime);
+    path::register(runtime);
+    style::register(runtime);
+    frame::register(runtime);
+    plot::register(runtime);
+    image::register(runtime);
 }
@@ -0,0 +1,193 @@
+use std::{
+    f64::consts::{PI, TAU},
+    ops::{Add, Mul, Sub},
+};
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::{Point, Scalar};
+
+/// An angle, stored in radians no matter how it was written.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Angle {
+    radians: f64,
+}
+
+impl Angle {
+    pub fn from_radians(radians: f64) -> Self {
+        Self { radians }
+    }
+
+    pub fn from_degrees(degrees: f64) -> Self {
+        Self {
+            radians: degrees.to_radians(),
+        }
+    }
+
+    pub fn radians(self) -> f64 {
+        self.radians
+    }
+
+    pub fn degrees(self) -> f64 {
+        self.radians.to_degrees()
+    }
+}
+
+impl std::fmt::Display for Angle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}deg", self.degrees())
+    }
+}
+
+impl Add for Angle {
+    type Output = Angle;
+
+    fn add(self, rhs: Angle) -> Self::Output {
+        Angle::from_radians(self.radians + rhs.radians)
+    }
+}
+
+impl Sub for Angle {
+    type Output = Angle;
+
+    fn sub(self, rhs: Angle) -> Self::Output {
+        Angle::from_radians(self.radians - rhs.radians)
+    }
+}
+
+impl Mul<Scalar> for Angle {
+    type Output = Angle;
+
+    fn mul(self, rhs: Scalar) -> Self::Output {
+        Angle::from_radians(self.radians * f64::from(rhs))
+    }
+}
+
+/// Turn a number of degrees into an angle.
+pub fn deg(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => x);
+    let Value::Scalar(degrees) = x else {
+        return Err(Error::TypeError);
+    };
+    Ok(Value::Angle(Angle::from_degrees(degrees.into())))
+}
+
+/// Turn a number of radians into an angle.
+pub fn rad(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => x);
+    let Value::Scalar(radians) = x else {
+        return Err(Error::TypeError);
+    };
+    Ok(Value::Angle(Angle::from_radians(radians.into())))
+}
+
+fn trig(stack: &mut Stack, function: fn(f64) -> f64) -> Result<Value, Error> {
+    reverse_pop!(stack => x);
+    let Value::Angle(angle) = x else {
+        return Err(Error::TypeError);
+    };
+    Ok(Value::Scalar(function(angle.radians()).into()))
+}
+
+pub fn sin(stack: &mut Stack) -> Result<Value, Error> {
+    trig(stack, f64::sin)
+}
+
+pub fn cos(stack: &mut Stack) -> Result<Value, Error> {
+    trig(stack, f64::cos)
+}
+
+pub fn tan(stack: &mut Stack) -> Result<Value, Error> {
+    trig(stack, f64::tan)
+}
+
+/// The direction of the ray from `vertex` through `a` and the turn from it
+/// to the ray through `b`, counterclockwise and at most half a turn either
+/// way, both in radians.
+pub(super) fn rays(vertex: Point, a: Point, b: Point) -> Result<(f64, f64), Error> {
+    let direction = |point: Point| {
+        let (dx, dy) = (
+            f64::from(point.x) - f64::from(vertex.x),
+            f64::from(point.y) - f64::from(vertex.y),
+        );
+        if dx.hypot(dy) < f64::EPSILON {
+            return Err(Error::InvalidArgument);
+        }
+        Ok(dy.atan2(dx))
+    };
+    let start = direction(a)?;
+    let mut turn = direction(b)? - start;
+    if turn > PI {
+        turn -= TAU;
+    } else if turn <= -PI {
+        turn += TAU;
+    }
+    Ok((start, turn))
+}
+
+/// The angle between the rays from a vertex through two points.
+pub fn angle_at(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => vertex, a, b);
+    let (Value::Point(vertex), Value::Point(a), Value::Point(b)) = (vertex, a, b) else {
+        return Err(Error::TypeError);
+    };
+    let (_, turn) = rays(vertex, a, b)?;
+    Ok(Value::Angle(Angle::from_radians(turn.abs())))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("angle_at", angle_at);
+    runtime.define_fn("deg", deg);
+    runtime.define_fn("rad", rad);
+    runtime.define_fn("sin", sin);
+    runtime.define_fn("cos", cos);
+    runtime.define_fn("tan", tan);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_conversions() {
+        let mut stack = dummy_stack([scalar(std::f64::consts::PI), scalar(180)]);
+
+        let Ok(Value::Angle(degrees)) = deg(&mut stack) else {
+            panic!("Expected an angle");
+        };
+        let Ok(Value::Angle(radians)) = rad(&mut stack) else {
+            panic!("Expected an angle");
+        };
+        assert!((degrees.radians() - radians.radians()).abs() < 1e-12);
+        assert!((radians.degrees() - 180.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_trig_requires_angle() {
+        let mut stack = dummy_stack([scalar(1), Value::Angle(Angle::from_degrees(90.0))]);
+
+        assert_values_eq(sin(&mut stack), scalar(1.0));
+        assert_eq!(cos(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_angle_at() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(1, 1), point(1, 1), point(2, 2),
+            point(0, 0), point(0, 1), point(-1, 0),
+            point(0, 0), point(1, 0), point(0, 1),
+        ]);
+        let right = Value::Angle(Angle::from_radians(PI / 2.0));
+
+        assert_values_eq(angle_at(&mut stack), right.clone());
+        // The smaller angle, whichever ray comes first
+        assert_values_eq(angle_at(&mut stack), right);
+        assert_eq!(angle_at(&mut stack), Err(Error::InvalidArgument));
+    }
+}
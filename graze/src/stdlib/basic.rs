@@ -1,3 +1,4 @@
+use super::scalar::NumericPolicy;
 use crate::{
     reverse_pop,
     runtime::{
@@ -6,57 +7,156 @@ use crate::{
     },
 };
 
+/// Whether every number in `value` is an integer. Angles never are.
+fn integral(value: &Value) -> bool {
+    match value {
+        Scalar(scalar) => scalar.is_integer(),
+        Vector(vec) => vec.x.is_integer() && vec.y.is_integer(),
+        Point(pnt) => pnt.x.is_integer() && pnt.y.is_integer(),
+        _ => false,
+    }
+}
+
+/// Arithmetic never wraps, but floats can still run off to infinity.
+/// `exact` says whether the operands were all integers, which the numeric
+/// policy of the runtime may insist the result is as well.
+fn finish(value: Value, exact: bool, policy: NumericPolicy) -> Result<Value, Error> {
+    match &value {
+        Scalar(scalar) => {
+            scalar.finite()?;
+        }
+        Vector(vec) => {
+            vec.x.finite()?;
+            vec.y.finite()?;
+        }
+        Point(pnt) => {
+            pnt.x.finite()?;
+            pnt.y.finite()?;
+        }
+        _ => {}
+    }
+    match policy {
+        NumericPolicy::Promote => Ok(value),
+        NumericPolicy::StrictInteger if exact && !integral(&value) => Err(Error::InexactInteger),
+        NumericPolicy::StrictInteger => Ok(value),
+        NumericPolicy::AlwaysFloat => Ok(match value {
+            Scalar(scalar) => Scalar(scalar.to_float()),
+            Vector(vec) => Vector(super::Vector {
+                x: vec.x.to_float(),
+                y: vec.y.to_float(),
+            }),
+            Point(pnt) => Point(super::Point {
+                x: pnt.x.to_float(),
+                y: pnt.y.to_float(),
+            }),
+            other => other,
+        }),
+    }
+}
+
 pub fn add(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => a, b);
-    match (a, b) {
-        (Scalar(a), Scalar(b)) => Ok(Scalar(a + b)),
-        (Vector(a), Vector(b)) => Ok(Vector(a + b)),
-        (Vector(vec), Point(pnt)) | (Point(pnt), Vector(vec)) => Ok(Point(pnt + vec)),
-        _ => Err(Error::TypeError),
-    }
+    let exact = integral(&a) && integral(&b);
+    let result = match (a, b) {
+        (Scalar(a), Scalar(b)) => Scalar(a + b),
+        (Angle(a), Angle(b)) => Angle(a + b),
+        (Vector(a), Vector(b)) => Vector(a + b),
+        (Vector(vec), Point(pnt)) | (Point(pnt), Vector(vec)) => Point(pnt + vec),
+        _ => return Err(Error::TypeError),
+    };
+    finish(result, exact, stack.numeric_policy())
 }
 
 pub fn sub(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => a, b);
-    match (a, b) {
-        (Scalar(a), Scalar(b)) => Ok(Scalar(a - b)),
-        (Vector(a), Vector(b)) => Ok(Vector(a - b)),
-        (Point(a), Point(b)) => Ok(Vector(a - b)),
-        (Point(pnt), Vector(vec)) => Ok(Point(pnt - vec)),
-        _ => Err(Error::TypeError),
-    }
+    let exact = integral(&a) && integral(&b);
+    let result = match (a, b) {
+        (Scalar(a), Scalar(b)) => Scalar(a - b),
+        (Angle(a), Angle(b)) => Angle(a - b),
+        (Vector(a), Vector(b)) => Vector(a - b),
+        (Point(a), Point(b)) => Vector(a - b),
+        (Point(pnt), Vector(vec)) => Point(pnt - vec),
+        _ => return Err(Error::TypeError),
+    };
+    finish(result, exact, stack.numeric_policy())
 }
 
 pub fn mul(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => a, b);
-    match (a, b) {
-        (Scalar(a), Scalar(b)) => Ok(Scalar(a * b)),
-        (Vector(vec), Scalar(r)) | (Scalar(r), Vector(vec)) => Ok(Vector(vec * r)),
-        _ => Err(Error::TypeError),
-    }
+    let exact = integral(&a) && integral(&b);
+    let result = match (a, b) {
+        (Scalar(a), Scalar(b)) => Scalar(a * b),
+        (Angle(angle), Scalar(r)) | (Scalar(r), Angle(angle)) => Angle(angle * r),
+        (Vector(vec), Scalar(r)) | (Scalar(r), Vector(vec)) => Vector(vec * r),
+        _ => return Err(Error::TypeError),
+    };
+    finish(result, exact, stack.numeric_policy())
 }
 
 pub fn div(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => a, b);
-    match (a, b) {
-        (Scalar(a), Scalar(b)) => Ok(Scalar(a / b)),
-        (Vector(vec), Scalar(r)) => Ok(Vector(vec / r)),
-        _ => Err(Error::TypeError),
-    }
+    let exact = integral(&a) && integral(&b);
+    let result = match (a, b) {
+        (_, Scalar(divisor)) if divisor.is_zero() => return Err(Error::DivisionByZero),
+        (Scalar(a), Scalar(b)) => Scalar(a / b),
+        (Vector(vec), Scalar(r)) => Vector(vec / r),
+        _ => return Err(Error::TypeError),
+    };
+    finish(result, exact, stack.numeric_policy())
 }
 
-pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+/// `a -> a a`
+pub fn dup(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a);
+    stack.push(a.clone());
+    Ok(a)
+}
 
+/// `a b -> b a`
+pub fn swap(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b);
+    stack.push(b);
+    Ok(a)
+}
+
+/// `a ->`
+pub fn drop(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => _a);
+    Ok(Void)
+}
+
+/// `a b -> a b a`
+pub fn over(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b);
+    stack.push(a.clone());
+    stack.push(b);
+    Ok(a)
+}
+
+/// `a b c -> b c a`
+pub fn rot(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b, c);
+    stack.push(b);
+    stack.push(c);
+    Ok(a)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
     runtime.define_fn("add", add);
     runtime.define_fn("sub", sub);
     runtime.define_fn("mul", mul);
     runtime.define_fn("div", div);
+    runtime.define_fn("dup", dup);
+    runtime.define_fn("swap", swap);
+    runtime.define_fn("drop", drop);
+    runtime.define_fn("over", over);
+    runtime.define_fn("rot", rot);
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::util::test_helpers::*;
+    use crate::{ast::parse_file, token::StringTokenizer, util::test_helpers::*, RuntimeConfig};
 
     #[test]
     fn test_add() {
@@ -122,8 +222,89 @@ mod test {
             ],
         );
 
-        assert_values_eq(div(&mut stack), scalar(0.5));
-        assert_values_eq(div(&mut stack), vector(1.0 / 3.0, 2.0 / 3.0));
+        assert_values_eq(div(&mut stack), scalar(ratio(1, 2)));
+        assert_values_eq(div(&mut stack), vector(ratio(1, 3), ratio(2, 3)));
         assert_eq!(div(&mut stack), Err(Error::TypeError));
     }
+
+    #[test]
+    fn test_division_by_zero() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            vector(1, 2), scalar(0.0),
+            scalar(1), scalar(0),
+        ]);
+
+        assert_eq!(div(&mut stack), Err(Error::DivisionByZero));
+        assert_eq!(div(&mut stack), Err(Error::DivisionByZero));
+    }
+
+    #[test]
+    fn test_overflow() {
+        let mut stack = dummy_stack([scalar(1.0e308), scalar(10)]);
+        assert_eq!(mul(&mut stack), Err(Error::Overflow));
+
+        let mut stack = dummy_stack([scalar(i64::MAX), scalar(1)]);
+        assert_values_eq(add(&mut stack), scalar(i64::MAX as f64 + 1.0));
+    }
+
+    #[test]
+    fn test_numeric_policies() {
+        let eval = |expression: &str, numeric_policy| {
+            let source = format!("#let x ({expression})");
+            let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+            let mut runtime = Runtime::<Recorder>::default();
+            runtime.set_config(RuntimeConfig {
+                numeric_policy,
+                ..Default::default()
+            });
+            runtime.execute(program)?;
+            Ok(runtime.get_variable("x").cloned().unwrap())
+        };
+
+        let strict = NumericPolicy::StrictInteger;
+        assert_eq!(eval("div 6 3", strict), Ok(scalar(2)));
+        assert_eq!(eval("div 6.0 4", strict), Ok(scalar(1.5)));
+        assert_eq!(eval("div 6 4", strict), Err(Error::InexactInteger));
+        assert_eq!(
+            eval("mul (vec2 9223372036854775807 1) 2", strict),
+            Err(Error::InexactInteger)
+        );
+
+        let float = NumericPolicy::AlwaysFloat;
+        assert_eq!(eval("add 1 2", float), Ok(scalar(3.0)));
+        assert_eq!(eval("mul 2 (vec2 1 2)", float), Ok(vector(2.0, 4.0)));
+        assert_eq!(
+            eval("div 6 4", NumericPolicy::Promote),
+            Ok(scalar(ratio(3, 2)))
+        );
+    }
+
+    #[test]
+    fn test_stack_words() {
+        let mut stack = dummy_stack([scalar(1), scalar(2), scalar(3)]);
+        assert_values_eq(rot(&mut stack), scalar(1));
+        assert_eq!(stack.values(), [scalar(2), scalar(3)]);
+
+        assert_values_eq(swap(&mut stack), scalar(2));
+        assert_eq!(stack.values(), [scalar(3)]);
+
+        stack.push(scalar(2));
+        assert_values_eq(over(&mut stack), scalar(3));
+        assert_eq!(stack.values(), [scalar(3), scalar(2)]);
+
+        assert_values_eq(dup(&mut stack), scalar(2));
+        assert_eq!(stack.values(), [scalar(3), scalar(2)]);
+
+        assert_values_eq(drop(&mut stack), Void);
+        assert_values_eq(drop(&mut stack), Void);
+        assert_eq!(drop(&mut stack), Err(Error::MissingArgument));
+    }
+
+    #[test]
+    fn test_stack_words_in_script() {
+        let runtime =
+            run_script("pnt2 0 0 => dup => drop => vec2 1 0 => swap => swap => line").unwrap();
+        assert_eq!(runtime.finish().commands.len(), 1);
+    }
 }
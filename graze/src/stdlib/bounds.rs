@@ -0,0 +1,173 @@
+use std::sync::Arc;
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::{shape::rectangle, Path, Point};
+
+fn cross(origin: Point, a: Point, b: Point) -> f64 {
+    let (ax, ay) = (f64::from(a.x - origin.x), f64::from(a.y - origin.y));
+    let (bx, by) = (f64::from(b.x - origin.x), f64::from(b.y - origin.y));
+    ax * by - ay * bx
+}
+
+/// The corners of a convex chain through `points`, turning left only.
+fn half(points: impl Iterator<Item = Point>) -> Vec<Point> {
+    let mut chain: Vec<Point> = Vec::new();
+    for point in points {
+        while let [.., a, b] = chain[..] {
+            if cross(a, b, point) > 0.0 {
+                break;
+            }
+            chain.pop();
+        }
+        chain.push(point);
+    }
+    chain
+}
+
+/// The smallest convex polygon around a list of points or a path,
+/// counterclockwise from its lowest leftmost corner.
+pub fn hull(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => points);
+    let mut points = match points {
+        Value::List(items) => items
+            .iter()
+            .map(|item| match item {
+                Value::Point(point) => Ok(*point),
+                _ => Err(Error::TypeError),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Value::Path(path) => path.points.clone(),
+        _ => return Err(Error::TypeError),
+    };
+    points.sort_by(|a, b| {
+        f64::from(a.x)
+            .total_cmp(&f64::from(b.x))
+            .then(f64::from(a.y).total_cmp(&f64::from(b.y)))
+    });
+    points.dedup();
+
+    // Andrew's monotone chain: the lower half left to right, then the
+    // upper half back, each ending where the other one starts
+    let mut corners = half(points.iter().copied());
+    corners.pop();
+    let mut upper = half(points.iter().rev().copied());
+    upper.pop();
+    corners.extend(upper);
+    if corners.len() < 3 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(Value::Path(Arc::new(Path::closed(corners))))
+}
+
+/// Grow `bounds` to include everything `value` draws.
+fn include(bounds: &mut Option<(f64, f64, f64, f64)>, value: &Value) -> Result<(), Error> {
+    let mut extend = |x: f64, y: f64, margin: f64| {
+        let (x0, y0, x1, y1) = bounds.get_or_insert((x, y, x, y));
+        *x0 = x0.min(x - margin);
+        *y0 = y0.min(y - margin);
+        *x1 = x1.max(x + margin);
+        *y1 = y1.max(y + margin);
+    };
+    match value {
+        Value::Point(point) => extend(point.x.into(), point.y.into(), 0.0),
+        Value::Line(start, direction) => {
+            let end = *start + *direction;
+            extend(start.x.into(), start.y.into(), 0.0);
+            extend(end.x.into(), end.y.into(), 0.0);
+        }
+        Value::Circle(center, radius) => {
+            extend(center.x.into(), center.y.into(), (*radius).into());
+        }
+        Value::Path(path) => {
+            for point in &path.points {
+                extend(point.x.into(), point.y.into(), 0.0);
+            }
+        }
+        Value::List(items) => {
+            for item in items.iter() {
+                include(bounds, item)?;
+            }
+        }
+        Value::Group(group) => {
+            for item in &group.items {
+                include(bounds, item)?;
+            }
+        }
+        _ => return Err(Error::TypeError),
+    }
+    Ok(())
+}
+
+/// The smallest rectangle along the axes around a value.
+pub fn bbox(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => value);
+    let mut bounds = None;
+    include(&mut bounds, &value)?;
+    let (x0, y0, x1, y1) = bounds.ok_or(Error::InvalidArgument)?;
+    Ok(rectangle(x0, y0, x1 - x0, y1 - y0))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("hull", hull);
+    runtime.define_fn("bbox", bbox);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn corners_of(value: Result<Value, Error>) -> Vec<(f64, f64)> {
+        let Ok(Value::Path(path)) = value else {
+            panic!("expected a path, got {value:?}");
+        };
+        assert!(path.closed);
+        path.points
+            .iter()
+            .map(|point| (point.x.into(), point.y.into()))
+            .collect()
+    }
+
+    #[test]
+    fn test_hull() {
+        let points = list_of([
+            point(0, 0),
+            point(2, 1),
+            point(4, 0),
+            point(2, 0),
+            point(4, 4),
+            point(0, 4),
+            point(0, 4),
+            point(1, 3),
+        ]);
+        let mut stack = dummy_stack([points]);
+        assert_eq!(
+            corners_of(hull(&mut stack)),
+            [(0.0, 0.0), (4.0, 0.0), (4.0, 4.0), (0.0, 4.0)]
+        );
+
+        let mut stack = dummy_stack([list_of([point(0, 0), point(1, 1), point(2, 2)])]);
+        assert_eq!(hull(&mut stack), Err(Error::InvalidArgument));
+        let mut stack = dummy_stack([list_of([point(0, 0), scalar(1)])]);
+        assert_eq!(hull(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_bbox() {
+        let shapes = list_of([circle_value(0, 0, 1), line(1, 1, 2, -4), point(-2, 0)]);
+        let mut stack = dummy_stack([shapes]);
+        assert_eq!(
+            corners_of(bbox(&mut stack)),
+            [(-2.0, -3.0), (3.0, -3.0), (3.0, 1.0), (-2.0, 1.0)]
+        );
+
+        let mut stack = dummy_stack([list_of([])]);
+        assert_eq!(bbox(&mut stack), Err(Error::InvalidArgument));
+        let mut stack = dummy_stack([scalar(1)]);
+        assert_eq!(bbox(&mut stack), Err(Error::TypeError));
+    }
+}
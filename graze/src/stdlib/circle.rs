@@ -0,0 +1,128 @@
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::Point;
+
+fn pnt(x: f64, y: f64) -> Point {
+    Point {
+        x: x.into(),
+        y: y.into(),
+    }
+}
+
+fn xy(value: Value) -> Result<(f64, f64), Error> {
+    let Value::Point(point) = value else {
+        return Err(Error::TypeError);
+    };
+    Ok((point.x.into(), point.y.into()))
+}
+
+/// Three corners of a triangle, failing if they are on one line.
+fn triangle(stack: &mut Stack) -> Result<[(f64, f64); 3], Error> {
+    reverse_pop!(stack => a, b, c);
+    let corners @ [a, b, c] = [xy(a)?, xy(b)?, xy(c)?];
+    let cross = (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0);
+    if cross.abs() < f64::EPSILON {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(corners)
+}
+
+pub fn circle(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => center, radius);
+    let (Value::Point(center), Value::Scalar(radius)) = (center, radius) else {
+        return Err(Error::TypeError);
+    };
+    if f64::from(radius) < 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(stack.snapped(Value::Circle(center, radius)))
+}
+
+/// A circle around a point, through another one.
+pub fn circle_thru(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => center, through);
+    let (Value::Point(center), (x, y)) = (center, xy(through)?) else {
+        return Err(Error::TypeError);
+    };
+    let radius = (x - f64::from(center.x)).hypot(y - f64::from(center.y));
+    Ok(Value::Circle(center, radius.into()))
+}
+
+/// The circle through the three corners of a triangle.
+pub fn circumcircle(stack: &mut Stack) -> Result<Value, Error> {
+    let [a, b, c] = triangle(stack)?;
+    let d = 2.0 * (a.0 * (b.1 - c.1) + b.0 * (c.1 - a.1) + c.0 * (a.1 - b.1));
+    let [la, lb, lc] = [a, b, c].map(|(x, y)| x * x + y * y);
+    let x = (la * (b.1 - c.1) + lb * (c.1 - a.1) + lc * (a.1 - b.1)) / d;
+    let y = (la * (c.0 - b.0) + lb * (a.0 - c.0) + lc * (b.0 - a.0)) / d;
+    let radius = (a.0 - x).hypot(a.1 - y);
+    Ok(Value::Circle(pnt(x, y), radius.into()))
+}
+
+/// The largest circle inside a triangle, touching all three sides.
+pub fn incircle(stack: &mut Stack) -> Result<Value, Error> {
+    let [a, b, c] = triangle(stack)?;
+    // Each corner weighs as much as the side across from it
+    let sides = [
+        (b.0 - c.0).hypot(b.1 - c.1),
+        (c.0 - a.0).hypot(c.1 - a.1),
+        (a.0 - b.0).hypot(a.1 - b.1),
+    ];
+    let perimeter: f64 = sides.iter().sum();
+    let x = (sides[0] * a.0 + sides[1] * b.0 + sides[2] * c.0) / perimeter;
+    let y = (sides[0] * a.1 + sides[1] * b.1 + sides[2] * c.1) / perimeter;
+    let area = ((b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)).abs() / 2.0;
+    Ok(Value::Circle(pnt(x, y), (2.0 * area / perimeter).into()))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("circle", circle);
+    runtime.define_fn("circle_thru", circle_thru);
+    runtime.define_fn("circumcircle", circumcircle);
+    runtime.define_fn("incircle", incircle);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_circle() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            scalar(1), scalar(2),
+            point(0, 0), scalar(-1),
+            point(1, 2), scalar(3),
+        ]);
+
+        assert_values_eq(circle(&mut stack), circle_value(1, 2, 3));
+        assert_eq!(circle(&mut stack), Err(Error::InvalidArgument));
+        assert_eq!(circle(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_circle_thru() {
+        let mut stack = dummy_stack([point(1, 1), scalar(2), point(1, 1), point(4, 5)]);
+
+        assert_values_eq(circle_thru(&mut stack), circle_value(1, 1, 5.0));
+        assert_eq!(circle_thru(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_triangle_circles() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(0, 0), point(1, 1), point(2, 2),
+            point(0, 0), point(4, 0), point(0, 3),
+            point(0, 0), point(4, 0), point(0, 3),
+        ]);
+
+        assert_values_eq(circumcircle(&mut stack), circle_value(2.0, 1.5, 2.5));
+        assert_values_eq(incircle(&mut stack), circle_value(1.0, 1.0, 1.0));
+        assert_eq!(incircle(&mut stack), Err(Error::InvalidArgument));
+    }
+}
@@ -0,0 +1,85 @@
+use std::f64::consts::TAU;
+
+use crate::{
+    output::Mm,
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::hatch::corners;
+
+/// Corners of the polygon standing in for a circular clip region.
+const CIRCLE_SEGMENTS: usize = 64;
+
+/// Only draw inside a circle, closed path or polygon given as a list of
+/// points until `endclip`.
+pub fn clip(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => shape);
+    let region = match shape {
+        Value::Circle(center, radius) => {
+            let (x, y, r) = (f64::from(center.x), f64::from(center.y), f64::from(radius));
+            (0..CIRCLE_SEGMENTS)
+                .map(|i| {
+                    let (sin, cos) = (TAU * i as f64 / CIRCLE_SEGMENTS as f64).sin_cos();
+                    (x + r * cos, y + r * sin)
+                })
+                .collect()
+        }
+        polygon => corners(polygon)?,
+    };
+    context.begin_clip(region.into_iter().map(|(x, y)| (Mm(x), Mm(y))).collect());
+    Ok(Value::Void)
+}
+
+pub fn endclip(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    context.end_clip()?;
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("clip", clip);
+    runtime.define_stateful_fn("endclip", endclip);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{output::DrawCommand, util::test_helpers::*};
+
+    #[test]
+    fn test_clip() {
+        let commands = run_script("clip (rect (pnt2 0 0) 2 2)\nlayer \"a\"\ncircle (pnt2 1 1) 3")
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(
+            commands[0],
+            DrawCommand::BeginClip {
+                region: vec![
+                    (Mm(0.0), Mm(0.0)),
+                    (Mm(2.0), Mm(0.0)),
+                    (Mm(2.0), Mm(2.0)),
+                    (Mm(0.0), Mm(2.0)),
+                ],
+            }
+        );
+        // Whatever is left open closes in reverse order
+        assert_eq!(
+            commands[commands.len() - 2..],
+            [DrawCommand::EndLayer, DrawCommand::EndClip]
+        );
+    }
+
+    #[test]
+    fn test_endclip() {
+        assert!(matches!(run_script("endclip"), Err(Error::ClipUnderflow)));
+        assert!(matches!(
+            run_script("clip (circle (pnt2 0 0) 1)\nlayer \"a\"\nendclip"),
+            Err(Error::ClipUnderflow)
+        ));
+        assert!(matches!(
+            run_script("clip (pnt2 0 0)"),
+            Err(Error::TypeError)
+        ));
+    }
+}
@@ -0,0 +1,142 @@
+use std::cmp::Ordering;
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::Scalar;
+
+/// How `lhs` and `rhs` are ordered, if they are scalars or angles.
+fn order(lhs: &Value, rhs: &Value) -> Result<Ordering, Error> {
+    match (lhs, rhs) {
+        (Value::Scalar(lhs), Value::Scalar(rhs)) => Ok(lhs.compare(*rhs)),
+        (Value::Angle(lhs), Value::Angle(rhs)) => Ok(lhs.radians().total_cmp(&rhs.radians())),
+        _ => Err(Error::TypeError),
+    }
+}
+
+/// Whether `holds` is true of how the two arguments are ordered.
+fn comparison(stack: &mut Stack, holds: fn(Ordering) -> bool) -> Result<Value, Error> {
+    reverse_pop!(stack => lhs, rhs);
+    Ok(Value::Bool(holds(order(&lhs, &rhs)?)))
+}
+
+pub fn lt(stack: &mut Stack) -> Result<Value, Error> {
+    comparison(stack, Ordering::is_lt)
+}
+
+pub fn le(stack: &mut Stack) -> Result<Value, Error> {
+    comparison(stack, Ordering::is_le)
+}
+
+pub fn gt(stack: &mut Stack) -> Result<Value, Error> {
+    comparison(stack, Ordering::is_gt)
+}
+
+pub fn ge(stack: &mut Stack) -> Result<Value, Error> {
+    comparison(stack, Ordering::is_ge)
+}
+
+/// Scalars are equal if their values are, whether they are integers or
+/// floats. Strings and booleans can be compared too.
+fn equal(stack: &mut Stack) -> Result<bool, Error> {
+    reverse_pop!(stack => lhs, rhs);
+    match (&lhs, &rhs) {
+        (Value::String(lhs), Value::String(rhs)) => Ok(lhs == rhs),
+        (Value::Bool(lhs), Value::Bool(rhs)) => Ok(lhs == rhs),
+        _ => Ok(order(&lhs, &rhs)?.is_eq()),
+    }
+}
+
+pub fn eq(stack: &mut Stack) -> Result<Value, Error> {
+    Ok(Value::Bool(equal(stack)?))
+}
+
+pub fn ne(stack: &mut Stack) -> Result<Value, Error> {
+    Ok(Value::Bool(!equal(stack)?))
+}
+
+pub fn min(lhs: Scalar, rhs: Scalar) -> Result<Scalar, Error> {
+    Ok(if rhs.compare(lhs).is_lt() { rhs } else { lhs })
+}
+
+pub fn max(lhs: Scalar, rhs: Scalar) -> Result<Scalar, Error> {
+    Ok(if rhs.compare(lhs).is_gt() { rhs } else { lhs })
+}
+
+/// Keep `x` between `low` and `high`.
+pub fn clamp(x: Scalar, low: Scalar, high: Scalar) -> Result<Scalar, Error> {
+    if low.compare(high).is_gt() {
+        return Err(Error::InvalidArgument);
+    }
+    max(x, low).and_then(|x| min(x, high))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("lt", lt);
+    runtime.define_fn("le", le);
+    runtime.define_fn("gt", gt);
+    runtime.define_fn("ge", ge);
+    runtime.define_fn("eq", eq);
+    runtime.define_fn("ne", ne);
+    runtime.define_native("min", min);
+    runtime.define_native("max", max);
+    runtime.define_native("clamp", clamp);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{native::NativeFn, stdlib::Angle, util::test_helpers::*};
+
+    #[test]
+    fn test_comparisons() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            scalar(2), scalar(2.0),
+            scalar(1), Value::Scalar(ratio(1, 3)),
+            Value::Angle(Angle::from_degrees(90.0)), Value::Angle(Angle::from_degrees(45.0)),
+            Value::String("a".into()), Value::String("b".into()),
+            Value::Bool(true), Value::Bool(true),
+            scalar(1), Value::String("1".into()),
+        ]);
+
+        assert_eq!(eq(&mut stack), Err(Error::TypeError));
+        assert_eq!(eq(&mut stack), Ok(Value::Bool(true)));
+        assert_eq!(ne(&mut stack), Ok(Value::Bool(true)));
+        assert_eq!(gt(&mut stack), Ok(Value::Bool(true)));
+        assert_eq!(lt(&mut stack), Ok(Value::Bool(false)));
+        assert_eq!(eq(&mut stack), Ok(Value::Bool(true)));
+    }
+
+    #[test]
+    fn test_exact_ordering() {
+        // Too close together to tell apart as floats
+        let mut stack = dummy_stack([scalar(i64::MAX), scalar(i64::MAX - 1)]);
+        assert_eq!(le(&mut stack), Ok(Value::Bool(false)));
+        assert_eq!(ratio(2, 3).compare(ratio(3, 5)), Ordering::Greater);
+        assert_eq!(
+            ratio(-1, 3).compare(scalar(-0.5).as_scalar().unwrap()),
+            Ordering::Greater
+        );
+    }
+
+    #[test]
+    fn test_min_max_clamp() {
+        let mut stack = dummy_stack([scalar(1), Value::Scalar(ratio(1, 2))]);
+        assert_values_eq(min.run(&mut stack), Value::Scalar(ratio(1, 2)));
+        let mut stack = dummy_stack([scalar(1), scalar(2.5)]);
+        assert_values_eq(max.run(&mut stack), scalar(2.5));
+
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            scalar(5), scalar(1), scalar(0),
+            scalar(-3), scalar(0), scalar(10),
+            scalar(12), scalar(0), scalar(10),
+        ]);
+        assert_values_eq(clamp.run(&mut stack), scalar(10));
+        assert_values_eq(clamp.run(&mut stack), scalar(0));
+        assert_eq!(clamp.run(&mut stack), Err(Error::InvalidArgument));
+    }
+}
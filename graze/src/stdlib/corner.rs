@@ -0,0 +1,211 @@
+use std::f64::consts::{PI, TAU};
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::{intersect::line_line, Path, Point, Vector};
+
+/// The largest angle covered by one segment of a fillet.
+const ARC_STEP: f64 = TAU / 32.0;
+
+type Xy = (f64, f64);
+
+fn xy(point: Point) -> Xy {
+    (point.x.into(), point.y.into())
+}
+
+fn pnt((x, y): Xy) -> Point {
+    Point {
+        x: x.into(),
+        y: y.into(),
+    }
+}
+
+fn vector((x, y): Xy) -> Vector {
+    Vector {
+        x: x.into(),
+        y: y.into(),
+    }
+}
+
+fn distance(a: Xy, b: Xy) -> f64 {
+    (b.0 - a.0).hypot(b.1 - a.1)
+}
+
+/// One of the two lines meeting at a corner.
+struct Leg {
+    /// The end away from the corner, which is kept.
+    far: Xy,
+    /// Whether `far` is where the line started.
+    far_is_start: bool,
+    /// Unit vector from the corner towards `far`.
+    direction: Xy,
+    length: f64,
+}
+
+impl Leg {
+    fn new(corner: Xy, start: Point, v: Vector) -> Self {
+        let start = xy(start);
+        let end = (start.0 + f64::from(v.x), start.1 + f64::from(v.y));
+        let far_is_start = distance(corner, start) >= distance(corner, end);
+        let far = if far_is_start { start } else { end };
+        let length = distance(corner, far);
+        let direction = ((far.0 - corner.0) / length, (far.1 - corner.1) / length);
+        Self {
+            far,
+            far_is_start,
+            direction,
+            length,
+        }
+    }
+
+    /// The point `offset` away from the corner, towards the far end.
+    fn at(&self, corner: Xy, offset: f64) -> Xy {
+        (
+            corner.0 + self.direction.0 * offset,
+            corner.1 + self.direction.1 * offset,
+        )
+    }
+
+    /// The line cut or lengthened to end at `cut`, pointing the same way
+    /// as before.
+    fn trimmed(&self, cut: Xy) -> Value {
+        let (from, to) = if self.far_is_start {
+            (self.far, cut)
+        } else {
+            (cut, self.far)
+        };
+        Value::Line(pnt(from), vector((to.0 - from.0, to.1 - from.1)))
+    }
+}
+
+/// The corner where two lines meet and both legs, checking that `size`
+/// fits on them.
+fn corner(a: Value, b: Value, size: Value) -> Result<(Xy, Leg, Leg, f64), Error> {
+    let (Value::Line(p1, v1), Value::Line(p2, v2), Value::Scalar(size)) = (a, b, size) else {
+        return Err(Error::TypeError);
+    };
+    let size = f64::from(size);
+    if size <= 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    let corner = xy(line_line(p1, v1, p2, v2)?);
+    let (first, second) = (Leg::new(corner, p1, v1), Leg::new(corner, p2, v2));
+    if first.length < f64::EPSILON || second.length < f64::EPSILON {
+        return Err(Error::InvalidArgument);
+    }
+    Ok((corner, first, second, size))
+}
+
+/// Round the corner where two lines meet with an arc of radius `r`,
+/// leaving both lines trimmed to the arc and the arc between them.
+pub fn fillet(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b, r);
+    let (corner, first, second, r) = corner(a, b, r)?;
+
+    let (u1, u2) = (first.direction, second.direction);
+    let cos = (u1.0 * u2.0 + u1.1 * u2.1).clamp(-1.0, 1.0);
+    // r / tan(angle / 2), without losing precision on right angles
+    let tangent = r * ((1.0 + cos) / (1.0 - cos)).sqrt();
+    if tangent > first.length || tangent > second.length {
+        return Err(Error::InvalidArgument);
+    }
+    let (t1, t2) = (first.at(corner, tangent), second.at(corner, tangent));
+
+    // Square to the first leg, towards the second
+    let normal = (u2.0 - u1.0 * cos, u2.1 - u1.1 * cos);
+    let reach = r / normal.0.hypot(normal.1);
+    let center = (t1.0 + normal.0 * reach, t1.1 + normal.1 * reach);
+    let start = (t1.1 - center.1).atan2(t1.0 - center.0);
+    let mut sweep = (t2.1 - center.1).atan2(t2.0 - center.0) - start;
+    if sweep > PI {
+        sweep -= TAU;
+    } else if sweep < -PI {
+        sweep += TAU;
+    }
+    let steps = (sweep.abs() / ARC_STEP).ceil().max(1.0) as usize;
+    let points = (0..=steps)
+        .map(|step| {
+            let (sin, cos) = (start + sweep * step as f64 / steps as f64).sin_cos();
+            pnt((center.0 + r * cos, center.1 + r * sin))
+        })
+        .collect();
+    let arc = Path {
+        points,
+        closed: false,
+    };
+
+    Ok(stack.push_results([
+        first.trimmed(t1),
+        Value::Path(arc.into()),
+        second.trimmed(t2),
+    ]))
+}
+
+/// Bevel the corner where two lines meet, cutting `d` off both, leaving
+/// both lines trimmed and the bevel between them.
+pub fn chamfer(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b, d);
+    let (corner, first, second, d) = corner(a, b, d)?;
+    if d > first.length || d > second.length {
+        return Err(Error::InvalidArgument);
+    }
+    let (c1, c2) = (first.at(corner, d), second.at(corner, d));
+
+    Ok(stack.push_results([
+        first.trimmed(c1),
+        Value::Line(pnt(c1), vector((c2.0 - c1.0, c2.1 - c1.1))),
+        second.trimmed(c2),
+    ]))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("fillet", fillet);
+    runtime.define_fn("chamfer", chamfer);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_fillet() {
+        let mut stack = dummy_stack([line(0, 0, 4, 0), line(4, 0, 0, 4), scalar(1)]);
+        assert_values_eq(fillet(&mut stack), line(4.0, 1.0, 0.0, 3.0));
+        let Ok(Value::Path(arc)) = stack.pop() else {
+            panic!("Expected the arc");
+        };
+        assert_values_eq(stack.pop(), line(0.0, 0.0, 3.0, 0.0));
+        assert!(!arc.closed);
+        assert!(distance(xy(arc.points[0]), (3.0, 0.0)) < 1e-9);
+        assert!(distance(xy(*arc.points.last().unwrap()), (4.0, 1.0)) < 1e-9);
+        for point in &arc.points {
+            assert!((distance(xy(*point), (3.0, 1.0)) - 1.0).abs() < 1e-9);
+        }
+
+        // Lines that stop short of the corner are lengthened
+        let mut stack = dummy_stack([line(0, 0, 2, 0), line(4, 4, 0, -2), scalar(1)]);
+        assert_values_eq(fillet(&mut stack), line(4.0, 4.0, 0.0, -3.0));
+
+        let mut stack = dummy_stack([line(0, 0, 4, 0), line(4, 0, 0, 4), scalar(5)]);
+        assert_eq!(fillet(&mut stack), Err(Error::InvalidArgument));
+        let mut stack = dummy_stack([line(0, 0, 4, 0), line(0, 1, 4, 0), scalar(1)]);
+        assert_eq!(fillet(&mut stack), Err(Error::NoIntersection));
+    }
+
+    #[test]
+    fn test_chamfer() {
+        let mut stack = dummy_stack([line(0, 0, 4, 0), line(4, 0, 0, 4), scalar(1)]);
+        assert_values_eq(chamfer(&mut stack), line(4.0, 1.0, 0.0, 3.0));
+        assert_eq!(stack.pop(), Ok(line(3.0, 0.0, 1.0, 1.0)));
+        assert_eq!(stack.pop(), Ok(line(0.0, 0.0, 3.0, 0.0)));
+
+        let mut stack = dummy_stack([line(0, 0, 4, 0), line(4, 0, 0, 4), scalar(0)]);
+        assert_eq!(chamfer(&mut stack), Err(Error::InvalidArgument));
+        let mut stack = dummy_stack([line(0, 0, 4, 0), point(4, 0), scalar(1)]);
+        assert_eq!(chamfer(&mut stack), Err(Error::TypeError));
+    }
+}
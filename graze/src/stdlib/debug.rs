@@ -0,0 +1,128 @@
+use crate::{
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+pub fn print(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => value);
+    context.print(value);
+    Ok(Value::Void)
+}
+
+/// Print a value along with the rest of the stack, then pass it on unchanged.
+pub fn dbg(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => value);
+    let rest = stack
+        .values()
+        .iter()
+        .map(ToString::to_string)
+        .collect::<Vec<_>>()
+        .join(", ");
+    context.print(format_args!("{value} (stack: [{rest}])"));
+    Ok(value)
+}
+
+/// Print the signature and summary of every builtin, alphabetically.
+pub fn help(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    let functions = context.functions();
+    let mut names = functions.names().collect::<Vec<_>>();
+    names.sort_unstable();
+    let entries = names
+        .into_iter()
+        .map(|name| match functions.doc(name) {
+            Some(doc) => (doc.signature.clone(), doc.summary.clone()),
+            None => (name.to_string(), String::new()),
+        })
+        .collect::<Vec<_>>();
+    let width = entries
+        .iter()
+        .map(|(signature, _)| signature.chars().count())
+        .max()
+        .unwrap_or(0);
+    for (signature, summary) in entries {
+        let line = format!("{signature:width$}  {summary}");
+        context.print(line.trim_end());
+    }
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("print", print);
+    runtime.define_stateful_fn("dbg", dbg);
+    runtime.define_stateful_fn("help", help);
+}
+
+#[cfg(test)]
+mod test {
+    use std::{cell::RefCell, io::Write, rc::Rc};
+
+    use super::*;
+    use crate::{ast::parse_file, token::StringTokenizer, util::test_helpers::*};
+
+    #[test]
+    fn test_print() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([list_of([scalar(1), Value::String("a".into())]), point(1, 2)]);
+
+        assert_eq!(print(&mut stack, &mut context), Ok(Value::Void));
+        assert_eq!(print(&mut stack, &mut context), Ok(Value::Void));
+        assert_eq!(print(&mut stack, &mut context), Err(Error::MissingArgument));
+        assert_eq!(context.output(), "(1, 2)\n[1, a]\n");
+    }
+
+    #[test]
+    fn test_print_line() {
+        let runtime = run_script("line (pnt2 0 0) (vec2 1 1) => print").unwrap();
+        assert_eq!(runtime.output(), "line (0, 0) <1, 1>\n");
+    }
+
+    #[test]
+    fn test_dbg() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([scalar(1), scalar(2.5)]);
+
+        assert_values_eq(dbg(&mut stack, &mut context), scalar(2.5));
+        assert_eq!(context.output(), "2.5 (stack: [1])\n");
+    }
+
+    #[test]
+    fn test_help() {
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn("zz_undocumented", |_| Ok(Value::Void));
+        runtime
+            .execute(parse_file(&mut StringTokenizer::new(&"help")).unwrap())
+            .unwrap();
+
+        let lines = runtime.output().lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), runtime.function_names().count());
+        assert!(lines[0].starts_with("add a b -> a + b "));
+        assert!(lines[0].ends_with("  Add scalars, vectors, angles, or a vector to a point."));
+        assert_eq!(lines.last(), Some(&"zz_undocumented"));
+    }
+
+    #[test]
+    fn test_log() {
+        #[derive(Clone, Default)]
+        struct SharedLog(Rc<RefCell<Vec<u8>>>);
+
+        impl Write for SharedLog {
+            fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+                self.0.borrow_mut().write(buf)
+            }
+
+            fn flush(&mut self) -> std::io::Result<()> {
+                Ok(())
+            }
+        }
+
+        let log = SharedLog::default();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.set_log(log.clone());
+        runtime
+            .execute(parse_file(&mut StringTokenizer::new(&"print 5")).unwrap())
+            .unwrap();
+
+        assert_eq!(runtime.output(), "");
+        assert_eq!(log.0.borrow().as_slice(), b"5\n");
+    }
+}
@@ -0,0 +1,234 @@
+use smol_str::SmolStr;
+
+use crate::{
+    output::{DrawCommand, Mm},
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::{angle::rays, grid::line, Scalar};
+
+/// Space between a measured point and its extension line.
+const GAP: f64 = 1.0;
+/// How far extension lines reach past the dimension line.
+const OVERSHOOT: f64 = 2.0;
+const ARROW_LENGTH: f64 = 3.0;
+/// Half the width of an arrowhead's base.
+const ARROW_WIDTH: f64 = 1.0;
+const TEXT_SIZE: f64 = 3.5;
+/// Radius of angle marks unless given with `radius=`.
+const MARK_RADIUS: f64 = 5.0;
+/// The largest angle covered by one segment of an angle mark.
+const MARK_STEP: f64 = std::f64::consts::TAU / 64.0;
+
+/// A distance with at most two decimals, e.g. `12.5`.
+fn label(distance: f64) -> SmolStr {
+    let label = format!("{distance:.2}");
+    label.trim_end_matches('0').trim_end_matches('.').into()
+}
+
+/// Dimension the distance between two points, `offset` to the left of
+/// the direction from the first to the second.
+pub fn dim(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => from, to, offset);
+    let (Value::Point(from), Value::Point(to), Value::Scalar(offset)) = (from, to, offset) else {
+        return Err(Error::TypeError);
+    };
+    let from = (f64::from(from.x), f64::from(from.y));
+    let to = (f64::from(to.x), f64::from(to.y));
+    let offset = f64::from(offset);
+
+    let distance = (to.0 - from.0).hypot(to.1 - from.1);
+    if distance < f64::EPSILON {
+        return Err(Error::InvalidArgument);
+    }
+    let along = ((to.0 - from.0) / distance, (to.1 - from.1) / distance);
+    let across = (-along.1, along.0);
+    let side = offset.signum();
+    // `along` units in the measured direction, then `aside` units to its left
+    let at = |(x, y): (f64, f64), forward: f64, aside: f64| {
+        (
+            x + along.0 * forward + across.0 * aside,
+            y + along.1 * forward + across.1 * aside,
+        )
+    };
+
+    for point in [from, to] {
+        let start = side * GAP.min(offset.abs());
+        line(
+            context,
+            at(point, 0.0, start),
+            at(point, 0.0, offset + side * OVERSHOOT),
+        );
+    }
+
+    let (start, end) = (at(from, 0.0, offset), at(to, 0.0, offset));
+    line(context, start, end);
+    for (tip, inwards) in [(start, 1.0), (end, -1.0)] {
+        for wing in [-1.0, 1.0] {
+            let base = at(tip, inwards * ARROW_LENGTH, wing * ARROW_WIDTH);
+            line(context, tip, base);
+        }
+    }
+
+    let middle = ((start.0 + end.0) / 2.0, (start.1 + end.1) / 2.0);
+    let (x, y) = at(middle, 0.0, side * TEXT_SIZE);
+    context.draw(DrawCommand::Text {
+        at: (Mm(x), Mm(y)),
+        text: label(distance),
+        size: Mm(TEXT_SIZE),
+    });
+    Ok(Value::Void)
+}
+
+/// Mark the angle between the rays from a vertex through two points with
+/// an arc, or a square for right angles. Takes `radius=` and a `label=`
+/// to write just outside the mark.
+pub fn mark_angle(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => vertex, a, b);
+    let (Value::Point(vertex), Value::Point(a), Value::Point(b)) = (vertex, a, b) else {
+        return Err(Error::TypeError);
+    };
+    let radius = match stack.option("radius") {
+        Some(radius) => f64::from(Scalar::try_from(radius)?),
+        None => MARK_RADIUS,
+    };
+    if radius <= 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    let label = stack.option("label").map(SmolStr::try_from).transpose()?;
+
+    let (start, turn) = rays(vertex, a, b)?;
+    let (x, y) = (f64::from(vertex.x), f64::from(vertex.y));
+    let at = |angle: f64, distance: f64| {
+        let (sin, cos) = angle.sin_cos();
+        (Mm(x + distance * cos), Mm(y + distance * sin))
+    };
+    let points = if (turn.abs() - std::f64::consts::FRAC_PI_2).abs() < 1e-9 {
+        let side = radius / 2.0;
+        let corner = start + turn / 2.0;
+        vec![
+            at(start, side),
+            at(corner, side * std::f64::consts::SQRT_2),
+            at(start + turn, side),
+        ]
+    } else {
+        let steps = (turn.abs() / MARK_STEP).ceil().max(1.0) as usize;
+        (0..=steps)
+            .map(|step| at(start + turn * step as f64 / steps as f64, radius))
+            .collect()
+    };
+    context.draw(DrawCommand::Path {
+        points,
+        closed: false,
+    });
+
+    if let Some(text) = label {
+        context.draw(DrawCommand::Text {
+            at: at(start + turn / 2.0, radius + TEXT_SIZE),
+            text,
+            size: Mm(TEXT_SIZE),
+        });
+    }
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("dim", dim);
+    runtime.define_stateful_fn("mark_angle", mark_angle);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_dim() {
+        let commands = run_script("dim (pnt2 0 0) (pnt2 10 0) 5")
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(commands.len(), 8);
+        assert_eq!(
+            commands[0],
+            DrawCommand::Line {
+                from: (Mm(0.0), Mm(1.0)),
+                to: (Mm(0.0), Mm(7.0)),
+            }
+        );
+        assert_eq!(
+            commands[2],
+            DrawCommand::Line {
+                from: (Mm(0.0), Mm(5.0)),
+                to: (Mm(10.0), Mm(5.0)),
+            }
+        );
+        assert_eq!(
+            commands[7],
+            DrawCommand::Text {
+                at: (Mm(5.0), Mm(8.5)),
+                text: "10".into(),
+                size: Mm(TEXT_SIZE),
+            }
+        );
+    }
+
+    #[test]
+    fn test_dim_errors() {
+        assert!(matches!(
+            run_script("dim (pnt2 1 1) (pnt2 1 1) 5"),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script("dim (pnt2 1 1) 5 (pnt2 1 1)"),
+            Err(Error::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_mark_angle() {
+        let commands = run_script("mark_angle (pnt2 0 0) (pnt2 1 0) (pnt2 1 1) label=\"a\"")
+            .unwrap()
+            .finish()
+            .commands;
+        let [DrawCommand::Path { points, closed }, DrawCommand::Text { at, text, .. }] =
+            &commands[..]
+        else {
+            panic!("Expected an arc and a label, got {commands:?}");
+        };
+        assert!(!closed);
+        assert_eq!(points[0], (Mm(5.0), Mm(0.0)));
+        let (Mm(x), Mm(y)) = points[points.len() - 1];
+        assert!((x - y).abs() < 1e-9 && (x.hypot(y) - 5.0).abs() < 1e-9);
+        let (Mm(x), Mm(y)) = *at;
+        assert!((x.hypot(y) - 8.5).abs() < 1e-9 && 0.0 < y && y < x);
+        assert_eq!(text, "a");
+
+        // Right angles get a square
+        let commands = run_script("mark_angle (pnt2 0 0) (pnt2 0 1) (pnt2 1 0) radius=4")
+            .unwrap()
+            .finish()
+            .commands;
+        let [DrawCommand::Path { points, .. }] = &commands[..] else {
+            panic!("Expected a square, got {commands:?}");
+        };
+        let points = points
+            .iter()
+            .map(|(x, y)| ((x.0 * 1e6).round() / 1e6, (y.0 * 1e6).round() / 1e6))
+            .collect::<Vec<_>>();
+        assert_eq!(points, [(0.0, 2.0), (2.0, 2.0), (2.0, 0.0)]);
+
+        assert!(matches!(
+            run_script("mark_angle (pnt2 0 0) (pnt2 0 1) (pnt2 1 0) radius=0"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_label() {
+        assert_eq!(label(10.0), "10");
+        assert_eq!(label(12.5), "12.5");
+        assert_eq!(label(2f64.sqrt()), "1.41");
+    }
+}
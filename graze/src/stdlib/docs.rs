@@ -0,0 +1,503 @@
+/// Reference documentation for a builtin, used by editor tooling.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuiltinDoc {
+    pub name: &'static str,
+    /// Arguments in stack order and the result, e.g. `pnt2 x y -> Point`.
+    pub signature: &'static str,
+    pub summary: &'static str,
+}
+
+const fn doc(name: &'static str, signature: &'static str, summary: &'static str) -> BuiltinDoc {
+    BuiltinDoc {
+        name,
+        signature,
+        summary,
+    }
+}
+
+/// Every builtin of the standard library, alphabetically.
+pub static DOCS: &[BuiltinDoc] = &[
+    doc(
+        "add",
+        "add a b -> a + b",
+        "Add scalars, vectors, angles, or a vector to a point.",
+    ),
+    doc(
+        "along",
+        "along path t -> Point",
+        "The point t along a path or line, measured from its start.",
+    ),
+    doc("and", "and a b -> Bool", "Whether both booleans are true."),
+    doc(
+        "angle_at",
+        "angle_at vertex a b -> Angle",
+        "The angle between the rays from a vertex through two points.",
+    ),
+    doc(
+        "apply",
+        "apply transform shape -> shape",
+        "Move a shape, or every shape in a list, by a transform value.",
+    ),
+    doc(
+        "arrow",
+        "arrow from to [start=marker] [end=marker] ->",
+        "Draw a line between two points with an arrowhead at the second, or the given markers.",
+    ),
+    doc(
+        "axes",
+        "axes ->",
+        "Draw the x and y axes across the visible area.",
+    ),
+    doc(
+        "bbox",
+        "bbox value -> Path",
+        "The smallest rectangle along the axes around a drawable value.",
+    ),
+    doc(
+        "chamfer",
+        "chamfer a b d -> Line Line Line",
+        "Bevel the corner where two lines meet, giving both trimmed lines and the bevel between them.",
+    ),
+    doc(
+        "circle",
+        "circle center radius -> Circle",
+        "A circle around a point.",
+    ),
+    doc(
+        "circle_thru",
+        "circle_thru center point -> Circle",
+        "A circle around a point, through another one.",
+    ),
+    doc(
+        "circumcircle",
+        "circumcircle a b c -> Circle",
+        "The circle through the three corners of a triangle.",
+    ),
+    doc("clamp", "clamp x low high -> Scalar", "Keep a scalar between two bounds."),
+    doc(
+        "clip",
+        "clip shape ->",
+        "Only draw inside a circle or polygon until endclip.",
+    ),
+    doc(
+        "compose",
+        "compose first second -> Transform",
+        "The transform doing the first, then the second.",
+    ),
+    doc("cos", "cos angle -> Scalar", "Cosine of an angle."),
+    doc(
+        "dbg",
+        "dbg value -> value",
+        "Print a value and the stack below it, then pass it on.",
+    ),
+    doc(
+        "deg",
+        "deg degrees -> Angle",
+        "An angle from a number of degrees.",
+    ),
+    doc(
+        "dim",
+        "dim from to offset ->",
+        "Dimension the distance between two points, offset to the left.",
+    ),
+    doc(
+        "dist",
+        "dist a b -> Scalar",
+        "Distance between two points, or a point and a line.",
+    ),
+    doc(
+        "div",
+        "div a b -> a / b",
+        "Divide a scalar or vector by a scalar. Integers give exact fractions.",
+    ),
+    doc("dot", "dot a b -> Scalar", "Dot product of two vectors."),
+    doc("drop", "drop a ->", "Discard the top of the stack."),
+    doc("dup", "dup a -> a a", "Duplicate the top of the stack."),
+    doc("endclip", "endclip ->", "Close the innermost clip region."),
+    doc(
+        "endframe",
+        "endframe ->",
+        "Go back to the coordinates from before the innermost in_frame.",
+    ),
+    doc("endlayer", "endlayer ->", "Close the innermost layer."),
+    doc("eq", "eq a b -> Bool", "Whether two scalars, angles, strings or booleans are equal."),
+    doc(
+        "extend_to",
+        "extend_to line target -> Line",
+        "Lengthen a line past its end until it meets a line or circle.",
+    ),
+    doc(
+        "fillet",
+        "fillet a b r -> Line Path Line",
+        "Round the corner where two lines meet with an arc of radius r, giving both trimmed lines and the arc.",
+    ),
+    doc(
+        "foot",
+        "foot point line -> Point",
+        "The point on a line closest to a point.",
+    ),
+    doc(
+        "frame",
+        "frame size orientation ->",
+        "Size the screen to a sheet like \"a4\" \"landscape\" and draw its border and title block.",
+    ),
+    doc(
+        "frame_at",
+        "frame_at origin xaxis -> Transform",
+        "A local coordinate frame with its origin at a point and its x axis along a vector, for in_frame and apply.",
+    ),
+    doc("ge", "ge a b -> Bool", "Whether a scalar or angle is at least another."),
+    doc(
+        "get",
+        "get list index -> Value",
+        "The item at a zero-based index.",
+    ),
+    doc(
+        "grid",
+        "grid spacing ->",
+        "Draw grid lines across the visible area at multiples of spacing.",
+    ),
+    doc(
+        "group",
+        "group shapes... style=? -> Group",
+        "Bundle the shapes given into one value that is transformed and drawn as a unit, optionally in its own line style.",
+    ),
+    doc("gt", "gt a b -> Bool", "Whether a scalar or angle is greater than another."),
+    doc(
+        "hatch",
+        "hatch shape angle spacing ->",
+        "Fill a circle or a list of polygon corners with parallel lines.",
+    ),
+    doc(
+        "help",
+        "help ->",
+        "Print every builtin with its signature and what it does.",
+    ),
+    doc(
+        "hull",
+        "hull points -> Path",
+        "The convex hull of a list of points or a path.",
+    ),
+    doc(
+        "image",
+        "image corner width height path ->",
+        "Place an image file in a rectangle, e.g. to trace over a photo.",
+    ),
+    doc(
+        "in_frame",
+        "in_frame frame ->",
+        "Draw everything up to endframe in a local frame from frame_at, mapped into the outer coordinates.",
+    ),
+    doc(
+        "incircle",
+        "incircle a b c -> Circle",
+        "The largest circle inside a triangle, touching all three sides.",
+    ),
+    doc(
+        "isect",
+        "isect line line -> Point",
+        "Intersection of two lines.",
+    ),
+    doc(
+        "isect_cc",
+        "isect_cc circle circle -> Point Point",
+        "Intersections of two circles.",
+    ),
+    doc(
+        "isect_cl",
+        "isect_cl circle line -> Point Point",
+        "Intersections of a circle and a line.",
+    ),
+    doc(
+        "jump",
+        "jump point dx dy -> Point",
+        "Move a point by an offset.",
+    ),
+    doc(
+        "layer",
+        "layer name ->",
+        "Open a named layer for everything drawn until endlayer.",
+    ),
+    doc("le", "le a b -> Bool", "Whether a scalar or angle is at most another."),
+    doc(
+        "len_list",
+        "len_list list -> Scalar",
+        "The number of items in a list.",
+    ),
+    doc(
+        "line",
+        "line start direction -> Line",
+        "A line segment from a point along a vector.",
+    ),
+    doc(
+        "linestyle",
+        "linestyle name ->",
+        "Draw \"continuous\", \"hidden\", \"center\" or \"phantom\" lines from now on.",
+    ),
+    doc(
+        "list",
+        "list items... -> List",
+        "Collect the items given into a list, or with none, what the earlier expressions of a pipeline left.",
+    ),
+    doc("lt", "lt a b -> Bool", "Whether a scalar or angle is less than another."),
+    doc(
+        "lvec",
+        "lvec point -> Vector",
+        "The vector from the origin to a point.",
+    ),
+    doc(
+        "mark_angle",
+        "mark_angle vertex a b [radius=r] [label=text] ->",
+        "Mark the angle at a vertex with an arc, or a square for right angles.",
+    ),
+    doc(
+        "markers",
+        "markers start end ->",
+        "Put \"arrow\", \"dot\", \"bar\" or \"none\" at the ends of lines drawn afterwards.",
+    ),
+    doc("max", "max a b -> Scalar", "The larger of two scalars."),
+    doc("min", "min a b -> Scalar", "The smaller of two scalars."),
+    doc(
+        "mirror",
+        "mirror value axis -> Value",
+        "Reflect a value across a line.",
+    ),
+    doc(
+        "mul",
+        "mul a b -> a * b",
+        "Multiply scalars, or scale a vector or angle.",
+    ),
+    doc("ne", "ne a b -> Bool", "Whether two scalars, angles, strings or booleans differ."),
+    doc(
+        "ngon",
+        "ngon center radius n -> Path",
+        "A regular polygon with n corners, the first along the x axis.",
+    ),
+    doc("not", "not a -> Bool", "The opposite of a boolean."),
+    doc(
+        "offset",
+        "offset shape distance -> Value",
+        "A parallel line or path, or a larger circle, with mitered corners.",
+    ),
+    doc(
+        "offset_round",
+        "offset_round shape distance -> Value",
+        "A parallel line or path with rounded outer corners, or a larger circle.",
+    ),
+    doc("or", "or a b -> Bool", "Whether either boolean is true."),
+    doc(
+        "over",
+        "over a b -> a b a",
+        "Copy the second value to the top of the stack.",
+    ),
+    doc(
+        "path_len",
+        "path_len path -> Scalar",
+        "The length of a path or line, including the way back to the start of a polygon.",
+    ),
+    doc(
+        "place",
+        "place name at rotate=? scale=? ->",
+        "Draw the symbol defined with #symbol name, its origin at a point, optionally turned and scaled around it.",
+    ),
+    doc(
+        "plot",
+        "plot points -> Path",
+        "A polyline through a list of points.",
+    ),
+    doc(
+        "plot_fn",
+        "plot_fn name from to samples -> Path",
+        "The graph of a function taking and returning a scalar, sampled evenly.",
+    ),
+    doc("pnt2", "pnt2 x y -> Point", "A point from its coordinates."),
+    doc(
+        "pop_tf",
+        "pop_tf ->",
+        "Restore the transform saved by push_tf.",
+    ),
+    doc("print", "print value ->", "Print a value."),
+    doc(
+        "push",
+        "push list value -> List",
+        "A copy of a list with a value appended.",
+    ),
+    doc("push_tf", "push_tf ->", "Save the current transform."),
+    doc(
+        "rad",
+        "rad radians -> Angle",
+        "An angle from a number of radians.",
+    ),
+    doc(
+        "rand",
+        "rand -> Scalar",
+        "A random number between 0 and 1, from the seeded generator.",
+    ),
+    doc(
+        "rand_range",
+        "rand_range low high -> Scalar",
+        "A random number between low and high.",
+    ),
+    doc(
+        "rect",
+        "rect corner width height -> Path",
+        "A rectangle from its lower left corner and size.",
+    ),
+    doc(
+        "rot",
+        "rot a b c -> b c a",
+        "Move the third value to the top of the stack.",
+    ),
+    doc(
+        "rotate",
+        "rotate angle ->",
+        "Rotate everything drawn afterwards around the origin.",
+    ),
+    doc(
+        "rrect",
+        "rrect corner width height radius -> Path",
+        "A rectangle with rounded corners.",
+    ),
+    doc(
+        "scale",
+        "scale factor ->",
+        "Scale everything drawn afterwards by a scalar or vector.",
+    ),
+    doc(
+        "scatter",
+        "scatter points radius -> List",
+        "A dot on each of a list of points.",
+    ),
+    doc(
+        "seed",
+        "seed n ->",
+        "Restart the random generator from an integer seed.",
+    ),
+    doc("sin", "sin angle -> Scalar", "Sine of an angle."),
+    doc(
+        "snap",
+        "snap point spacing -> Point",
+        "Move a point or vector to the nearest multiple of the spacing.",
+    ),
+    doc(
+        "snap_mode",
+        "snap_mode spacing ->",
+        "Snap geometry made by pnt2, vec2, line, circle and list to a grid, or stop with 0.",
+    ),
+    doc(
+        "sqrt",
+        "sqrt x -> Scalar",
+        "Square root of a non-negative scalar.",
+    ),
+    doc(
+        "star",
+        "star center outer inner n -> Path",
+        "A star with n points, alternating between two radii.",
+    ),
+    doc(
+        "sub",
+        "sub a b -> a - b",
+        "Subtract scalars, vectors, angles or points.",
+    ),
+    doc("swap", "swap a b -> b a", "Swap the top two values."),
+    doc("tan", "tan angle -> Scalar", "Tangent of an angle."),
+    doc(
+        "tf_rotate",
+        "tf_rotate angle -> Transform",
+        "A transform value rotating counterclockwise around the origin.",
+    ),
+    doc(
+        "tf_scale",
+        "tf_scale factor -> Transform",
+        "A transform value scaling by a factor or a vector of factors.",
+    ),
+    doc(
+        "tf_translate",
+        "tf_translate offset -> Transform",
+        "A transform value moving by a vector or x y.",
+    ),
+    doc(
+        "ticks",
+        "ticks spacing size ->",
+        "Draw tick marks of a given size along both axes.",
+    ),
+    doc(
+        "to_bool",
+        "to_bool x -> Bool",
+        "False for void, zero, false and empty strings, lists and groups, true otherwise.",
+    ),
+    doc(
+        "to_float",
+        "to_float x -> Scalar",
+        "The same scalar as a float, e.g. to divide integers inexactly.",
+    ),
+    doc(
+        "to_int",
+        "to_int x -> Scalar",
+        "Round a scalar to the nearest integer, or turn a boolean into 1 or 0.",
+    ),
+    doc(
+        "transform",
+        "transform transform ->",
+        "Apply a transform value to everything drawn afterwards.",
+    ),
+    doc(
+        "translate",
+        "translate offset ->",
+        "Move everything drawn afterwards by a vector or x y.",
+    ),
+    doc(
+        "trim",
+        "trim line cutter [keep=end] -> Line",
+        "Cut a line where it first meets a line or circle, or keep the part after the last cut.",
+    ),
+    doc(
+        "unpack",
+        "unpack list -> items...",
+        "Push every item of a list, first item first.",
+    ),
+    doc(
+        "vec2",
+        "vec2 x y -> Vector",
+        "A vector from its components.",
+    ),
+    doc(
+        "x",
+        "x value -> Scalar",
+        "The x coordinate of a point or vector.",
+    ),
+    doc(
+        "xy",
+        "xy value -> x y",
+        "Both coordinates of a point or vector.",
+    ),
+    doc(
+        "y",
+        "y value -> Scalar",
+        "The y coordinate of a point or vector.",
+    ),
+];
+
+/// Look up the documentation of a builtin by name.
+pub fn builtin_doc(name: &str) -> Option<&'static BuiltinDoc> {
+    DOCS.iter().find(|doc| doc.name == name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{runtime::Runtime, util::test_helpers::Recorder};
+
+    #[test]
+    fn test_every_builtin_is_documented() {
+        let runtime = Runtime::<Recorder>::default();
+        let mut names = runtime.function_names().collect::<Vec<_>>();
+        names.sort();
+        let documented = DOCS.iter().map(|doc| doc.name).collect::<Vec<_>>();
+        assert_eq!(names, documented);
+        for doc in DOCS {
+            let registered = runtime.function_doc(doc.name).unwrap();
+            assert_eq!(registered.signature, doc.signature);
+        }
+    }
+}
@@ -0,0 +1,146 @@
+use crate::{
+    output::{DrawCommand, Mm},
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::grid::line;
+
+/// Width of the filing margin on the left, per ISO 5457.
+const FILING_MARGIN: f64 = 20.0;
+const MARGIN: f64 = 10.0;
+/// The widest title block ISO 7200 allows.
+const TITLE_WIDTH: f64 = 180.0;
+const ROW_HEIGHT: f64 = 8.0;
+const TEXT_SIZE: f64 = 3.5;
+/// Variables shown in the title block, and their labels.
+const FIELDS: [(&str, &str); 4] = [
+    ("title", "Title"),
+    ("author", "Author"),
+    ("date", "Date"),
+    ("scale", "Scale"),
+];
+
+/// Width and height of a sheet in portrait orientation.
+fn sheet(name: &str) -> Option<(f64, f64)> {
+    match name {
+        "a0" => Some((841.0, 1189.0)),
+        "a1" => Some((594.0, 841.0)),
+        "a2" => Some((420.0, 594.0)),
+        "a3" => Some((297.0, 420.0)),
+        "a4" => Some((210.0, 297.0)),
+        "letter" => Some((215.9, 279.4)),
+        _ => None,
+    }
+}
+
+fn outline(context: &mut Context, (x0, y0): (f64, f64), (x1, y1): (f64, f64)) {
+    context.draw(DrawCommand::Path {
+        points: vec![
+            (Mm(x0), Mm(y0)),
+            (Mm(x1), Mm(y0)),
+            (Mm(x1), Mm(y1)),
+            (Mm(x0), Mm(y1)),
+        ],
+        closed: true,
+    });
+}
+
+/// Size the screen to a sheet of paper and draw its border, with a title
+/// block in the lower right corner showing the variables `title`,
+/// `author`, `date` and `scale` that are set.
+pub fn frame(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => size, orientation);
+    let (Value::String(size), Value::String(orientation)) = (size, orientation) else {
+        return Err(Error::TypeError);
+    };
+    let (short, long) = sheet(&size).ok_or(Error::InvalidArgument)?;
+    let (width, height) = match orientation.as_str() {
+        "portrait" => (short, long),
+        "landscape" => (long, short),
+        _ => return Err(Error::InvalidArgument),
+    };
+
+    context.draw(DrawCommand::Resize {
+        x: Mm(width),
+        y: Mm(height),
+    });
+    let (right, bottom) = (width - MARGIN, height - MARGIN);
+    outline(context, (FILING_MARGIN, MARGIN), (right, bottom));
+
+    let rows = FIELDS
+        .iter()
+        .filter_map(|&(name, label)| Some(format!("{label}: {}", context.variable(name)?)))
+        .collect::<Vec<_>>();
+    if rows.is_empty() {
+        return Ok(Value::Void);
+    }
+    let left = right - TITLE_WIDTH.min(right - FILING_MARGIN);
+    let top = bottom - ROW_HEIGHT * rows.len() as f64;
+    outline(context, (left, top), (right, bottom));
+    for (i, row) in rows.into_iter().enumerate() {
+        let y = top + ROW_HEIGHT * i as f64;
+        if i > 0 {
+            line(context, (left, y), (right, y));
+        }
+        context.draw(DrawCommand::Text {
+            at: (Mm((left + right) / 2.0), Mm(y + ROW_HEIGHT / 2.0)),
+            text: row.into(),
+            size: Mm(TEXT_SIZE),
+        });
+    }
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("frame", frame);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_frame() {
+        let runtime = run_script("frame \"a4\" \"landscape\"").unwrap();
+        let commands = runtime.finish().commands;
+        assert_eq!(commands.len(), 2);
+        assert_eq!(
+            commands[0],
+            DrawCommand::Resize {
+                x: Mm(297.0),
+                y: Mm(210.0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_title_block() {
+        let source = "#let title \"Bracket\"\n#let scale \"1:2\"\nframe \"a3\" \"portrait\"";
+        let runtime = run_script(source).unwrap();
+        assert!(runtime.warnings().is_empty());
+        let texts = runtime
+            .finish()
+            .commands
+            .into_iter()
+            .filter_map(|command| match command {
+                DrawCommand::Text { text, .. } => Some(text),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(texts, ["Title: Bracket", "Scale: 1:2"]);
+    }
+
+    #[test]
+    fn test_frame_errors() {
+        assert!(matches!(
+            run_script("frame \"b5\" \"portrait\""),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script("frame \"a4\" \"sideways\""),
+            Err(Error::InvalidArgument)
+        ));
+    }
+}
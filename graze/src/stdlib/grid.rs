@@ -0,0 +1,160 @@
+use crate::{
+    output::{DrawCommand, Mm},
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+/// More lines than this are almost certainly a typo in the spacing.
+const MAX_LINES: i64 = 10_000;
+
+pub(super) fn positive(value: Value) -> Result<f64, Error> {
+    let Value::Scalar(value) = value else {
+        return Err(Error::TypeError);
+    };
+    let value = f64::from(value);
+    if value > 0.0 {
+        Ok(value)
+    } else {
+        Err(Error::InvalidArgument)
+    }
+}
+
+/// Every multiple of `spacing` between `start` and `start + length`, so
+/// that grids line up with the coordinates instead of the screen edge.
+pub(super) fn multiples(
+    start: f64,
+    length: f64,
+    spacing: f64,
+) -> Result<impl Iterator<Item = f64>, Error> {
+    let first = (start / spacing).ceil() as i64;
+    let last = ((start + length) / spacing).floor() as i64;
+    // Casts saturate, so the difference may not fit in an i64
+    if last
+        .checked_sub(first)
+        .is_none_or(|count| count > MAX_LINES)
+    {
+        return Err(Error::InvalidArgument);
+    }
+    Ok((first..=last).map(move |i| i as f64 * spacing))
+}
+
+pub(super) fn line(context: &mut Context, (x1, y1): (f64, f64), (x2, y2): (f64, f64)) {
+    context.draw(DrawCommand::Line {
+        from: (Mm(x1), Mm(y1)),
+        to: (Mm(x2), Mm(y2)),
+    });
+}
+
+/// Lines across the visible area at every multiple of `spacing`.
+pub fn grid(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => spacing);
+    let spacing = positive(spacing)?;
+    let (x, y, width, height) = context.visible_area().ok_or(Error::UnknownVisibleArea)?;
+
+    for at in multiples(x, width, spacing)? {
+        line(context, (at, y), (at, y + height));
+    }
+    for at in multiples(y, height, spacing)? {
+        line(context, (x, at), (x + width, at));
+    }
+    Ok(Value::Void)
+}
+
+/// The x and y axes, where they cross the visible area.
+pub fn axes(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    let (x, y, width, height) = context.visible_area().ok_or(Error::UnknownVisibleArea)?;
+
+    if (x..=x + width).contains(&0.0) {
+        line(context, (0.0, y), (0.0, y + height));
+    }
+    if (y..=y + height).contains(&0.0) {
+        line(context, (x, 0.0), (x + width, 0.0));
+    }
+    Ok(Value::Void)
+}
+
+/// Marks of length `size` along both axes at every multiple of `spacing`.
+pub fn ticks(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => spacing, size);
+    let (spacing, half) = (positive(spacing)?, positive(size)? / 2.0);
+    let (x, y, width, height) = context.visible_area().ok_or(Error::UnknownVisibleArea)?;
+
+    if (y..=y + height).contains(&0.0) {
+        let (low, high) = ((-half).max(y), half.min(y + height));
+        for at in multiples(x, width, spacing)?.filter(|at| *at != 0.0) {
+            line(context, (at, low), (at, high));
+        }
+    }
+    if (x..=x + width).contains(&0.0) {
+        let (low, high) = ((-half).max(x), half.min(x + width));
+        for at in multiples(y, height, spacing)?.filter(|at| *at != 0.0) {
+            line(context, (low, at), (high, at));
+        }
+    }
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("grid", grid);
+    runtime.define_stateful_fn("axes", axes);
+    runtime.define_stateful_fn("ticks", ticks);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn lines(source: &str) -> Vec<((f64, f64), (f64, f64))> {
+        let recorder = run_script(source).unwrap().finish();
+        recorder
+            .commands
+            .into_iter()
+            .filter_map(|command| match command {
+                DrawCommand::Line { from, to } => {
+                    Some(((from.0 .0, from.1 .0), (to.0 .0, to.1 .0)))
+                }
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_grid() {
+        assert_eq!(
+            lines("#view 1 0 8 4\ngrid 4"),
+            [
+                ((4.0, 0.0), (4.0, 4.0)),
+                ((8.0, 0.0), (8.0, 4.0)),
+                ((1.0, 0.0), (9.0, 0.0)),
+                ((1.0, 4.0), (9.0, 4.0)),
+            ]
+        );
+        assert!(matches!(
+            run_script("grid 1"),
+            Err(Error::UnknownVisibleArea)
+        ));
+        assert!(matches!(
+            run_script("#screen 10 10\ngrid 0"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_axes_and_ticks() {
+        assert_eq!(
+            lines("#view (-4) (-2) 8 4\naxes"),
+            [((0.0, -2.0), (0.0, 2.0)), ((-4.0, 0.0), (4.0, 0.0))]
+        );
+        assert_eq!(
+            lines("#screen 4 4\nticks 2 1"),
+            [
+                ((2.0, 0.0), (2.0, 0.5)),
+                ((4.0, 0.0), (4.0, 0.5)),
+                ((0.0, 2.0), (0.5, 2.0)),
+                ((0.0, 4.0), (0.5, 4.0)),
+            ]
+        );
+        assert!(lines("#view 1 1 4 4\naxes").is_empty());
+    }
+}
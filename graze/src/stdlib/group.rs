@@ -0,0 +1,130 @@
+use std::sync::Arc;
+
+use crate::{
+    output::LineStyle,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+/// Shapes bundled into one value, drawn together in an optional line style.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Group {
+    pub items: Vec<Value>,
+    /// The style the items are drawn in, instead of the current one.
+    pub style: Option<LineStyle>,
+}
+
+impl Group {
+    /// The same group with `f` applied to each of its items.
+    pub fn map(&self, f: impl FnMut(&Value) -> Result<Value, Error>) -> Result<Self, Error> {
+        Ok(Self {
+            items: self.items.iter().map(f).collect::<Result<_, _>>()?,
+            style: self.style,
+        })
+    }
+}
+
+impl std::fmt::Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "group [")?;
+        for (i, item) in self.items.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{item}")?;
+        }
+        write!(f, "]")?;
+        if let Some(style) = self.style {
+            write!(f, " style={}", style.name())?;
+        }
+        Ok(())
+    }
+}
+
+/// Bundle the arguments into a group, bottom first. Lists are
+/// flattened into the group; anything else has to be drawable.
+pub fn group(stack: &mut Stack) -> Result<Value, Error> {
+    let style = match stack.option("style") {
+        Some(Value::String(name)) => {
+            Some(LineStyle::from_name(&name).ok_or(Error::InvalidArgument)?)
+        }
+        Some(_) => return Err(Error::TypeError),
+        None => None,
+    };
+    let mut items = Vec::new();
+    for value in stack.take_all() {
+        match value {
+            Value::List(list) => items.extend(list.iter().cloned()),
+            value => items.push(value),
+        }
+    }
+    if !items.iter().all(Value::is_drawable) {
+        return Err(Error::TypeError);
+    }
+    Ok(Value::Group(Arc::new(Group { items, style })))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("group", group);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        output::{DrawCommand, Mm},
+        util::test_helpers::*,
+    };
+
+    #[test]
+    fn test_group() {
+        let mut stack = dummy_stack([line(0, 0, 1, 0), list_of([circle_value(0, 0, 1)])]);
+        assert_eq!(
+            group(&mut stack),
+            Ok(Value::Group(Arc::new(Group {
+                items: vec![line(0, 0, 1, 0), circle_value(0, 0, 1)],
+                style: None,
+            })))
+        );
+
+        let mut stack = dummy_stack([line(0, 0, 1, 0), point(1, 1)]);
+        assert_eq!(group(&mut stack), Err(Error::TypeError));
+
+        // The point belongs to the outer call
+        assert!(run_script("print (pnt2 0 0) (group (circle (pnt2 0 0) 1))").is_ok());
+    }
+
+    #[test]
+    fn test_draw_group() {
+        let runtime = run_script(
+            "#let b (group (line (pnt2 0 0) (vec2 1 0)) (circle (pnt2 0 0) 1) style=hidden)!\n\
+             apply (tf_translate (vec2 2 0)) $b\n\
+             line (pnt2 0 0) (vec2 0 1)",
+        )
+        .unwrap();
+        let commands = runtime.finish().commands;
+        assert_eq!(
+            commands,
+            vec![
+                DrawCommand::SetLineStyle {
+                    style: LineStyle::Hidden
+                },
+                DrawCommand::Line {
+                    from: (Mm(2.0), Mm(0.0)),
+                    to: (Mm(3.0), Mm(0.0)),
+                },
+                DrawCommand::Circle {
+                    at: (Mm(2.0), Mm(0.0)),
+                    radius: Mm(1.0),
+                },
+                DrawCommand::SetLineStyle {
+                    style: LineStyle::Continuous
+                },
+                DrawCommand::Line {
+                    from: (Mm(0.0), Mm(0.0)),
+                    to: (Mm(0.0), Mm(1.0)),
+                },
+            ]
+        );
+    }
+}
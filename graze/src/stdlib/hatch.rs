@@ -0,0 +1,207 @@
+use crate::{
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::grid::{line, multiples, positive};
+
+/// The corners of a closed path or a list of points.
+pub(super) fn corners(polygon: Value) -> Result<Vec<(f64, f64)>, Error> {
+    let corners = match polygon {
+        Value::List(points) => points
+            .iter()
+            .map(|point| match point {
+                Value::Point(point) => Ok((f64::from(point.x), f64::from(point.y))),
+                _ => Err(Error::TypeError),
+            })
+            .collect::<Result<Vec<_>, _>>()?,
+        Value::Path(path) if path.closed => path
+            .points
+            .iter()
+            .map(|point| (f64::from(point.x), f64::from(point.y)))
+            .collect(),
+        Value::Path(_) => return Err(Error::InvalidArgument),
+        _ => return Err(Error::TypeError),
+    };
+    if corners.len() < 3 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(corners)
+}
+
+/// Fill a circle, or a polygon given as a closed path or a list of points, with parallel
+/// lines at `angle`, `spacing` apart.
+pub fn hatch(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => shape, angle, spacing);
+    let Value::Angle(angle) = angle else {
+        return Err(Error::TypeError);
+    };
+    let spacing = positive(spacing)?;
+
+    // Hatch lines run along `along`, and are numbered by their distance
+    // from the origin in the direction of `across`
+    let (sin, cos) = angle.radians().sin_cos();
+    let along = (cos, sin);
+    let across = (-sin, cos);
+    let project = |(x, y): (f64, f64), (dx, dy): (f64, f64)| x * dx + y * dy;
+    let at = |offset: f64, position: f64| {
+        (
+            across.0 * offset + along.0 * position,
+            across.1 * offset + along.1 * position,
+        )
+    };
+
+    match shape {
+        Value::Circle(center, radius) => {
+            let center = (f64::from(center.x), f64::from(center.y));
+            let radius = f64::from(radius);
+            let (middle, position) = (project(center, across), project(center, along));
+            for offset in multiples(middle - radius, 2.0 * radius, spacing)? {
+                let half = (radius.powi(2) - (offset - middle).powi(2)).sqrt();
+                if half > 0.0 {
+                    line(
+                        context,
+                        at(offset, position - half),
+                        at(offset, position + half),
+                    );
+                }
+            }
+        }
+        polygon @ (Value::List(_) | Value::Path(_)) => {
+            let corners = corners(polygon)?;
+
+            let offsets = corners.iter().map(|&corner| project(corner, across));
+            let low = offsets.clone().fold(f64::INFINITY, f64::min);
+            let high = offsets.fold(f64::NEG_INFINITY, f64::max);
+            for offset in multiples(low, high - low, spacing)? {
+                let mut crossings = Vec::new();
+                for (i, &start) in corners.iter().enumerate() {
+                    let end = corners[(i + 1) % corners.len()];
+                    let (before, after) = (
+                        project(start, across) - offset,
+                        project(end, across) - offset,
+                    );
+                    // Count corners on the line as above it, so that they
+                    // aren't crossed twice
+                    if (before < 0.0) != (after < 0.0) {
+                        let t = before / (before - after);
+                        let crossing = (
+                            start.0 + (end.0 - start.0) * t,
+                            start.1 + (end.1 - start.1) * t,
+                        );
+                        crossings.push(project(crossing, along));
+                    }
+                }
+                crossings.sort_by(f64::total_cmp);
+                // Inside and outside alternate along the line
+                for pair in crossings.chunks_exact(2) {
+                    // Lines touching a corner cross it twice at once
+                    if pair[1] - pair[0] > 1e-9 {
+                        line(context, at(offset, pair[0]), at(offset, pair[1]));
+                    }
+                }
+            }
+        }
+        _ => return Err(Error::TypeError),
+    }
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("hatch", hatch);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        output::{DrawCommand, Mm},
+        util::test_helpers::*,
+    };
+
+    fn lines(source: &str) -> Vec<((f64, f64), (f64, f64))> {
+        run_script(source)
+            .unwrap()
+            .finish()
+            .commands
+            .into_iter()
+            .map(|command| match command {
+                DrawCommand::Line { from, to } => {
+                    // Adding zero turns -0.0 into 0.0
+                    let round = |(x, y): (Mm, Mm)| {
+                        (
+                            (x.0 * 1e6).round() / 1e6 + 0.0,
+                            (y.0 * 1e6).round() / 1e6 + 0.0,
+                        )
+                    };
+                    (round(from), round(to))
+                }
+                other => panic!("unexpected {other:?}"),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_hatch_polygon() {
+        let square = "(list (pnt2 0 0) (pnt2 10 0) (pnt2 10 10) (pnt2 0 10))";
+        assert_eq!(
+            lines(&format!("#let s {square}\nhatch $s 0deg 3")),
+            [
+                ((0.0, 3.0), (10.0, 3.0)),
+                ((0.0, 6.0), (10.0, 6.0)),
+                ((0.0, 9.0), (10.0, 9.0)),
+            ]
+        );
+
+        // A concave U shape splits each line in two
+        let u = "(list (pnt2 0 0) (pnt2 9 0) (pnt2 9 9) (pnt2 6 9) (pnt2 6 3) (pnt2 3 3) (pnt2 3 9) (pnt2 0 9))";
+        assert_eq!(
+            lines(&format!("#let s {u}\nhatch $s 0deg 4")),
+            [
+                ((0.0, 4.0), (3.0, 4.0)),
+                ((6.0, 4.0), (9.0, 4.0)),
+                ((0.0, 8.0), (3.0, 8.0)),
+                ((6.0, 8.0), (9.0, 8.0)),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hatch_path() {
+        assert_eq!(
+            lines("hatch (ngon (pnt2 0 0) 2 4) 0deg 1"),
+            [
+                ((-1.0, -1.0), (1.0, -1.0)),
+                ((-2.0, 0.0), (2.0, 0.0)),
+                ((-1.0, 1.0), (1.0, 1.0))
+            ]
+        );
+    }
+
+    #[test]
+    fn test_hatch_circle() {
+        let lines = lines("hatch (circle (pnt2 0 0) 5) 90deg 2");
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[2], ((0.0, -5.0), (0.0, 5.0)));
+    }
+
+    #[test]
+    fn test_hatch_errors() {
+        assert!(matches!(
+            run_script("hatch (circle (pnt2 0 0) 5) 90deg 0"),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script("#let s (list (pnt2 0 0) (pnt2 1 1))\nhatch $s 0deg 1"),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script("hatch (pnt2 0 0) 0deg 1"),
+            Err(Error::TypeError)
+        ));
+        assert!(matches!(
+            run_script("hatch (circle (pnt2 0 0) 5) 0deg 0.000000000000000000001"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+}
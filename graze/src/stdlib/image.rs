@@ -0,0 +1,101 @@
+use crate::{
+    output::{DrawCommand, Mm},
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+/// Place the image file at `path` in the rectangle from `corner`, `width`
+/// wide and `height` tall. Only files the runtime config allows are read.
+pub fn image(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => corner, width, height, path);
+    let (Value::Point(corner), Value::Scalar(width), Value::Scalar(height), Value::String(path)) =
+        (corner, width, height, path)
+    else {
+        return Err(Error::TypeError);
+    };
+    let (width, height) = (f64::from(width), f64::from(height));
+    if width <= 0.0 || height <= 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    let path = context.readable_path(path.as_str())?;
+    if !path.is_file() {
+        return Err(Error::InvalidArgument);
+    }
+
+    context.draw(DrawCommand::Image {
+        at: (Mm(corner.x.into()), Mm(corner.y.into())),
+        width: Mm(width),
+        height: Mm(height),
+        path: path.to_string_lossy().into(),
+    });
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("image", image);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    const CARGO_TOML: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+
+    #[test]
+    fn test_image() {
+        let commands = run_script(&format!("image (pnt2 1 2) 30 20 \"{CARGO_TOML}\""))
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(
+            commands,
+            [DrawCommand::Image {
+                at: (Mm(1.0), Mm(2.0)),
+                width: Mm(30.0),
+                height: Mm(20.0),
+                path: CARGO_TOML.into(),
+            }]
+        );
+    }
+
+    #[test]
+    fn test_transformed_image() {
+        let image = |transform: &str| {
+            let source = format!("{transform}\nimage (pnt2 10 10) 30 20 \"{CARGO_TOML}\"");
+            let commands = run_script(&source).unwrap().finish().commands;
+            match commands.last() {
+                Some(DrawCommand::Image {
+                    at, width, height, ..
+                }) => (
+                    at.0 .0.round(),
+                    at.1 .0.round(),
+                    width.0.round(),
+                    height.0.round(),
+                ),
+                other => panic!("expected an image, got {other:?}"),
+            }
+        };
+
+        assert_eq!(image("rotate 180deg"), (-40.0, -30.0, 30.0, 20.0));
+        assert_eq!(image("rotate 90deg"), (-30.0, 10.0, 20.0, 30.0));
+        assert_eq!(image("scale (vec2 (sub 0 1) 1)"), (-40.0, 10.0, 30.0, 20.0));
+    }
+
+    #[test]
+    fn test_image_errors() {
+        let missing = concat!(env!("CARGO_MANIFEST_DIR"), "/missing.png");
+        assert!(matches!(
+            run_script(&format!("image (pnt2 0 0) 10 10 \"{missing}\"")),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script(&format!("image (pnt2 0 0) 0 10 \"{CARGO_TOML}\"")),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script("image (pnt2 0 0) 10 10 5"),
+            Err(Error::TypeError)
+        ));
+    }
+}
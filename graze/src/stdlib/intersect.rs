@@ -0,0 +1,320 @@
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::{Point, Scalar, Vector};
+
+/// Either one or two intersection points.
+enum Hits {
+    One(Point),
+    Two(Point, Point),
+}
+
+impl Hits {
+    /// Leave all but the last hit on the stack and return the last one,
+    /// so that both points of a secant are available to the next expression.
+    fn into_value(self, stack: &mut Stack) -> Value {
+        match self {
+            Hits::One(p) => Value::Point(p),
+            Hits::Two(p1, p2) => stack.push_results([Value::Point(p1), Value::Point(p2)]),
+        }
+    }
+}
+
+fn pnt(x: f64, y: f64) -> Point {
+    Point {
+        x: x.into(),
+        y: y.into(),
+    }
+}
+
+/// Intersect the infinite lines through `(p1, v1)` and `(p2, v2)`.
+pub(super) fn line_line(p1: Point, v1: Vector, p2: Point, v2: Vector) -> Result<Point, Error> {
+    let (x1, y1) = (f64::from(p1.x), f64::from(p1.y));
+    let (dx1, dy1) = (f64::from(v1.x), f64::from(v1.y));
+    let (x2, y2) = (f64::from(p2.x), f64::from(p2.y));
+    let (dx2, dy2) = (f64::from(v2.x), f64::from(v2.y));
+
+    let cross = dx1 * dy2 - dy1 * dx2;
+    if cross.abs() < f64::EPSILON {
+        return Err(Error::NoIntersection);
+    }
+
+    let t = ((x2 - x1) * dy2 - (y2 - y1) * dx2) / cross;
+    Ok(pnt(x1 + dx1 * t, y1 + dy1 * t))
+}
+
+/// Intersect a circle with the infinite line through `(p, v)`.
+/// Points are ordered along the direction of `v`.
+fn circle_line(c: Point, r: Scalar, p: Point, v: Vector) -> Result<Hits, Error> {
+    let (cx, cy, r) = (f64::from(c.x), f64::from(c.y), f64::from(r));
+    let (px, py) = (f64::from(p.x) - cx, f64::from(p.y) - cy);
+    let (dx, dy) = (f64::from(v.x), f64::from(v.y));
+
+    let a = dx * dx + dy * dy;
+    if a < f64::EPSILON {
+        return Err(Error::NoIntersection);
+    }
+    let b = 2.0 * (px * dx + py * dy);
+    let c = px * px + py * py - r * r;
+
+    let discriminant = b * b - 4.0 * a * c;
+    let at = |t: f64| pnt(cx + px + dx * t, cy + py + dy * t);
+    if discriminant.abs() < f64::EPSILON {
+        Ok(Hits::One(at(-b / (2.0 * a))))
+    } else if discriminant < 0.0 {
+        Err(Error::NoIntersection)
+    } else {
+        let root = discriminant.sqrt();
+        Ok(Hits::Two(
+            at((-b - root) / (2.0 * a)),
+            at((-b + root) / (2.0 * a)),
+        ))
+    }
+}
+
+/// Intersect two circles. Seen from the first center towards the second,
+/// the left intersection point comes first.
+fn circle_circle(c1: Point, r1: Scalar, c2: Point, r2: Scalar) -> Result<Hits, Error> {
+    let (x1, y1, r1) = (f64::from(c1.x), f64::from(c1.y), f64::from(r1));
+    let (x2, y2, r2) = (f64::from(c2.x), f64::from(c2.y), f64::from(r2));
+    let (dx, dy) = (x2 - x1, y2 - y1);
+
+    let d = (dx * dx + dy * dy).sqrt();
+    if d < f64::EPSILON || d > r1 + r2 + f64::EPSILON || d < (r1 - r2).abs() - f64::EPSILON {
+        return Err(Error::NoIntersection);
+    }
+
+    let a = (r1 * r1 - r2 * r2 + d * d) / (2.0 * d);
+    let h = (r1 * r1 - a * a).max(0.0).sqrt();
+    let (bx, by) = (x1 + a * dx / d, y1 + a * dy / d);
+    if h < f64::EPSILON {
+        return Ok(Hits::One(pnt(bx, by)));
+    }
+
+    let (ox, oy) = (-dy * h / d, dx * h / d);
+    Ok(Hits::Two(pnt(bx + ox, by + oy), pnt(bx - ox, by - oy)))
+}
+
+/// Where the infinite line through `(p, v)` meets `target`, a line or a
+/// circle, as multiples of `v` from `p` in ascending order.
+fn crossings(p: Point, v: Vector, target: &Value) -> Result<Vec<f64>, Error> {
+    let (px, py) = (f64::from(p.x), f64::from(p.y));
+    let (dx, dy) = (f64::from(v.x), f64::from(v.y));
+    match *target {
+        Value::Line(q, w) => {
+            let (wx, wy) = (f64::from(w.x), f64::from(w.y));
+            let cross = dx * wy - dy * wx;
+            if cross.abs() < f64::EPSILON {
+                return Ok(vec![]);
+            }
+            let (qx, qy) = (f64::from(q.x) - px, f64::from(q.y) - py);
+            Ok(vec![(qx * wy - qy * wx) / cross])
+        }
+        Value::Circle(c, r) => {
+            let (cx, cy, r) = (px - f64::from(c.x), py - f64::from(c.y), f64::from(r));
+            let a = dx * dx + dy * dy;
+            if a < f64::EPSILON {
+                return Ok(vec![]);
+            }
+            let b = 2.0 * (cx * dx + cy * dy);
+            let discriminant = b * b - 4.0 * a * (cx * cx + cy * cy - r * r);
+            if discriminant < 0.0 {
+                return Ok(vec![]);
+            }
+            let root = discriminant.sqrt();
+            Ok(vec![(-b - root) / (2.0 * a), (-b + root) / (2.0 * a)])
+        }
+        _ => Err(Error::TypeError),
+    }
+}
+
+/// The segment from `p` along `v`, scaled by `from` and `to`.
+fn part(p: Point, v: Vector, from: f64, to: f64) -> Value {
+    let (px, py) = (f64::from(p.x), f64::from(p.y));
+    let (dx, dy) = (f64::from(v.x), f64::from(v.y));
+    Value::Line(
+        pnt(px + dx * from, py + dy * from),
+        Vector {
+            x: (dx * (to - from)).into(),
+            y: (dy * (to - from)).into(),
+        },
+    )
+}
+
+/// Cut a segment where it first meets a line or circle, keeping the part
+/// before the cut, or after the last cut with `keep=end`.
+pub fn trim(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => segment, cutter);
+    let Value::Line(p, v) = segment else {
+        return Err(Error::TypeError);
+    };
+    let keep_end = match stack.option("keep") {
+        None => false,
+        Some(Value::String(keep)) => match keep.as_str() {
+            "start" => false,
+            "end" => true,
+            _ => return Err(Error::InvalidArgument),
+        },
+        Some(_) => return Err(Error::TypeError),
+    };
+
+    let cuts = crossings(p, v, &cutter)?
+        .into_iter()
+        .filter(|t| *t > f64::EPSILON && *t < 1.0 - f64::EPSILON)
+        .collect::<Vec<_>>();
+    match (cuts.first(), cuts.last()) {
+        (Some(_), Some(&last)) if keep_end => Ok(part(p, v, last, 1.0)),
+        (Some(&first), _) => Ok(part(p, v, 0.0, first)),
+        _ => Err(Error::NoIntersection),
+    }
+}
+
+/// Lengthen a segment past its end until it meets a line or circle.
+pub fn extend_to(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => segment, target);
+    let Value::Line(p, v) = segment else {
+        return Err(Error::TypeError);
+    };
+
+    crossings(p, v, &target)?
+        .into_iter()
+        .find(|t| *t >= 1.0 - f64::EPSILON)
+        .map(|t| part(p, v, 0.0, t))
+        .ok_or(Error::NoIntersection)
+}
+
+pub fn isect(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b);
+    let (Value::Line(p1, v1), Value::Line(p2, v2)) = (a, b) else {
+        return Err(Error::TypeError);
+    };
+
+    Ok(Value::Point(line_line(p1, v1, p2, v2)?))
+}
+
+pub fn isect_cl(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b);
+    let hits = match (a, b) {
+        (Value::Circle(c, r), Value::Line(p, v)) | (Value::Line(p, v), Value::Circle(c, r)) => {
+            circle_line(c, r, p, v)?
+        }
+        _ => return Err(Error::TypeError),
+    };
+
+    Ok(hits.into_value(stack))
+}
+
+pub fn isect_cc(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b);
+    let (Value::Circle(c1, r1), Value::Circle(c2, r2)) = (a, b) else {
+        return Err(Error::TypeError);
+    };
+
+    Ok(circle_circle(c1, r1, c2, r2)?.into_value(stack))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("isect", isect);
+    runtime.define_fn("isect_cl", isect_cl);
+    runtime.define_fn("isect_cc", isect_cc);
+    runtime.define_fn("trim", trim);
+    runtime.define_fn("extend_to", extend_to);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_isect() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(0, 0), line(0, 0, 1, 1),
+            line(0, 0, 1, 0), line(0, 1, 2, 0),
+            line(0, 0, 2, 2), line(0, 2, 2, -2),
+        ]);
+
+        assert_values_eq(isect(&mut stack), point(1.0, 1.0));
+        assert_eq!(isect(&mut stack), Err(Error::NoIntersection));
+        assert_eq!(isect(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_isect_cl() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            circle_value(0, 0, 1), line(0, 2, 1, 0),
+            line(-2, 1, 1, 0), circle_value(0, 0, 1),
+            circle_value(0, 0, 2), line(-4, 0, 1, 0),
+        ]);
+
+        assert_values_eq(isect_cl(&mut stack), point(2.0, 0.0));
+        assert_eq!(stack.pop(), Ok(point(-2.0, 0.0)));
+        assert_values_eq(isect_cl(&mut stack), point(0.0, 1.0));
+        assert_eq!(isect_cl(&mut stack), Err(Error::NoIntersection));
+    }
+
+    #[test]
+    fn test_isect_cc() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            circle_value(0, 0, 1), circle_value(5, 0, 1),
+            circle_value(0, 0, 1), circle_value(2, 0, 1),
+            circle_value(0, 0, 5), circle_value(8, 0, 5),
+        ]);
+
+        assert_values_eq(isect_cc(&mut stack), point(4.0, -3.0));
+        assert_eq!(stack.pop(), Ok(point(4.0, 3.0)));
+        assert_values_eq(isect_cc(&mut stack), point(1.0, 0.0));
+        assert_eq!(isect_cc(&mut stack), Err(Error::NoIntersection));
+    }
+
+    #[test]
+    fn test_trim() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            line(0, 0, 4, 0), circle_value(4, 0, 1),
+            line(0, 0, 4, 0), line(1, 0, 0, 1),
+            line(0, 0, 4, 0), circle_value(2, 0, 1),
+            line(0, 0, 4, 0), line(0, 1, 1, 0),
+        ]);
+
+        assert_eq!(trim(&mut stack), Err(Error::NoIntersection));
+        assert_values_eq(trim(&mut stack), line(0.0, 0.0, 1.0, 0.0));
+        assert_values_eq(trim(&mut stack), line(0.0, 0.0, 1.0, 0.0));
+        assert_values_eq(trim(&mut stack), line(0.0, 0.0, 3.0, 0.0));
+        assert!(stack.is_empty());
+
+        let source = "#screen 10 10\n#let s (trim (line (pnt2 0 0) (vec2 4 0)) \
+            (circle (pnt2 2 0) 1) keep=end)";
+        let runtime = run_script(source).unwrap();
+        assert_values_eq(
+            Ok(runtime.get_variable("s").unwrap().clone()),
+            line(3.0, 0.0, 1.0, 0.0),
+        );
+        assert!(matches!(
+            run_script("trim (line (pnt2 0 0) (vec2 4 0)) (circle (pnt2 2 0) 1) keep=middle"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_extend_to() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            line(0, 0, 1, 0), line(0, 1, 1, 0),
+            line(0, 0, 1, 0), line(-1, 0, 0, 1),
+            line(0, 0, 1, 1), circle_value(0, 0, 2),
+            line(0, 0, 1, 0), line(3, 0, 0, 1),
+        ]);
+
+        assert_values_eq(extend_to(&mut stack), line(0.0, 0.0, 3.0, 0.0));
+        let root = 2.0f64.sqrt();
+        assert_values_eq(extend_to(&mut stack), line(0.0, 0.0, root, root));
+        assert_eq!(extend_to(&mut stack), Err(Error::NoIntersection));
+        assert_eq!(extend_to(&mut stack), Err(Error::NoIntersection));
+    }
+}
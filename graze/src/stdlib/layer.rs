@@ -0,0 +1,43 @@
+use crate::{
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+pub fn layer(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => name);
+    let Value::String(name) = name else {
+        return Err(Error::TypeError);
+    };
+    context.begin_layer(name);
+    Ok(Value::Void)
+}
+
+pub fn endlayer(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    context.end_layer()?;
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("layer", layer);
+    runtime.define_stateful_fn("endlayer", endlayer);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_layers() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([scalar(1), Value::String("outline".into())]);
+
+        assert_eq!(layer(&mut stack, &mut context), Ok(Value::Void));
+        assert_eq!(layer(&mut stack, &mut context), Err(Error::TypeError));
+        assert_eq!(endlayer(&mut stack, &mut context), Ok(Value::Void));
+        assert_eq!(
+            endlayer(&mut stack, &mut context),
+            Err(Error::LayerUnderflow)
+        );
+    }
+}
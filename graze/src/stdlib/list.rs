@@ -0,0 +1,127 @@
+use std::sync::Arc;
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+/// Collect the arguments into a list, bottom first. See
+/// [`Stack::take_all`] for what they are.
+pub fn list(stack: &mut Stack) -> Result<Value, Error> {
+    let items = Value::List(Arc::new(stack.take_all()));
+    Ok(stack.snapped(items))
+}
+
+pub fn get(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => list, index);
+    let (Value::List(items), Value::Scalar(index)) = (list, index) else {
+        return Err(Error::TypeError);
+    };
+    let index = index.as_integer().ok_or(Error::InvalidArgument)?;
+
+    usize::try_from(index)
+        .ok()
+        .and_then(|i| items.get(i))
+        .cloned()
+        .ok_or(Error::IndexOutOfBounds(index))
+}
+
+/// Returns a copy of the list with `value` appended.
+pub fn push(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => list, value);
+    let Value::List(mut items) = list else {
+        return Err(Error::TypeError);
+    };
+    Arc::make_mut(&mut items).push(value);
+    Ok(Value::List(items))
+}
+
+pub fn len_list(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => list);
+    let Value::List(items) = list else {
+        return Err(Error::TypeError);
+    };
+    Ok(Value::Scalar((items.len() as i64).into()))
+}
+
+/// Push every item of a list in order, returning the last one.
+pub fn unpack(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => list);
+    let Value::List(items) = list else {
+        return Err(Error::TypeError);
+    };
+    Ok(stack.push_results(items.iter().cloned()))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("list", list);
+    runtime.define_fn("get", get);
+    runtime.define_fn("push", push);
+    runtime.define_fn("len_list", len_list);
+    runtime.define_fn("unpack", unpack);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_list() {
+        let mut stack = dummy_stack([scalar(1), point(2, 3)]);
+
+        assert_values_eq(list(&mut stack), list_of([scalar(1), point(2, 3)]));
+        assert_values_eq(list(&mut stack), list_of([]));
+    }
+
+    #[test]
+    fn test_nested_list() {
+        let runtime = run_script("print (pnt2 0 0) (list 1 2)\n1; 2; list => print").unwrap();
+        assert_eq!(runtime.output(), "[1, 2]\n[1, 2]\n");
+
+        let runtime = run_script("circle (pnt2 0 0) (get (list 1 2) 0)").unwrap();
+        assert_eq!(runtime.finish().commands.len(), 1);
+        let runtime = run_script("#let l (push (list) (len_list (list 1 2)))").unwrap();
+        assert_eq!(runtime.get_variable("l"), Some(&list_of([scalar(2)])));
+    }
+
+    #[test]
+    fn test_get() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            scalar(1), scalar(0),
+            list_of([scalar(1)]), scalar(0.5),
+            list_of([scalar(1)]), scalar(1),
+            list_of([scalar(1), scalar(2)]), scalar(1),
+        ]);
+
+        assert_values_eq(get(&mut stack), scalar(2));
+        assert_eq!(get(&mut stack), Err(Error::IndexOutOfBounds(1)));
+        assert_eq!(get(&mut stack), Err(Error::InvalidArgument));
+        assert_eq!(get(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_unpack() {
+        let mut stack = dummy_stack([list_of([]), list_of([scalar(1), point(2, 3), scalar(4)])]);
+
+        assert_values_eq(unpack(&mut stack), scalar(4));
+        assert_eq!(stack.values()[1..], [scalar(1), point(2, 3)]);
+        stack.take_all();
+
+        stack.push(list_of([]));
+        assert_values_eq(unpack(&mut stack), Value::Void);
+        assert!(stack.is_empty());
+    }
+
+    #[test]
+    fn test_push_and_len() {
+        let mut stack = dummy_stack([list_of([scalar(1)]), point(0, 0)]);
+
+        let pushed = push(&mut stack).unwrap();
+        assert_eq!(pushed, list_of([scalar(1), point(0, 0)]));
+
+        stack.push(pushed);
+        assert_values_eq(len_list(&mut stack), scalar(2));
+    }
+}
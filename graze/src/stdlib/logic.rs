@@ -0,0 +1,63 @@
+use crate::runtime::{Error, Runtime, Value};
+
+pub fn and(lhs: bool, rhs: bool) -> Result<bool, Error> {
+    Ok(lhs && rhs)
+}
+
+pub fn or(lhs: bool, rhs: bool) -> Result<bool, Error> {
+    Ok(lhs || rhs)
+}
+
+pub fn not(x: bool) -> Result<bool, Error> {
+    Ok(!x)
+}
+
+/// Whether a value is true, see [`Value::is_truthy`]. Booleans are never
+/// made from other values implicitly, so that using a scalar where a
+/// boolean is expected stays a type error.
+pub fn to_bool(x: Value) -> Result<bool, Error> {
+    Ok(x.is_truthy())
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_native("and", and);
+    runtime.define_native("or", or);
+    runtime.define_native("not", not);
+    runtime.define_native("to_bool", to_bool);
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::{native::NativeFn, util::test_helpers::*};
+
+    #[test]
+    fn test_logic() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            Value::Bool(false), Value::Bool(true),
+            Value::Bool(true), Value::Bool(false),
+            Value::Bool(true),
+        ]);
+        assert_eq!(not.run(&mut stack), Ok(Value::Bool(false)));
+        assert_eq!(and.run(&mut stack), Ok(Value::Bool(false)));
+        assert_eq!(or.run(&mut stack), Ok(Value::Bool(true)));
+
+        let mut stack = dummy_stack([scalar(1), Value::Bool(true)]);
+        assert_eq!(and.run(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_to_bool() {
+        let mut stack = dummy_stack([
+            scalar(0),
+            Value::String("no".into()),
+            Value::List(Arc::new(Vec::new())),
+        ]);
+        assert_eq!(to_bool.run(&mut stack), Ok(Value::Bool(false)));
+        assert_eq!(to_bool.run(&mut stack), Ok(Value::Bool(true)));
+        assert_eq!(to_bool.run(&mut stack), Ok(Value::Bool(false)));
+    }
+}
@@ -0,0 +1,139 @@
+use crate::{
+    output::{DrawCommand, Marker},
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+/// A marker by name, where `none` means no marker.
+fn marker(value: Value) -> Result<Option<Marker>, Error> {
+    let Value::String(name) = value else {
+        return Err(Error::TypeError);
+    };
+    match name.as_str() {
+        "none" => Ok(None),
+        name => Marker::from_name(name)
+            .map(Some)
+            .ok_or(Error::InvalidArgument),
+    }
+}
+
+pub fn markers(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => start, end);
+    let (start, end) = (marker(start)?, marker(end)?);
+    context.set_markers(start, end);
+    Ok(Value::Void)
+}
+
+/// Draw a line between two points with an arrowhead at the second, or the
+/// markers named by the `start=` and `end=` options.
+pub fn arrow(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => from, to);
+    let (Value::Point(from), Value::Point(to)) = (from, to) else {
+        return Err(Error::TypeError);
+    };
+    let head = match stack.option("start") {
+        Some(name) => marker(name)?,
+        None => None,
+    };
+    let tail = match stack.option("end") {
+        Some(name) => marker(name)?,
+        None => Some(Marker::Arrow),
+    };
+
+    let (start, end) = context.markers();
+    context.set_markers(head, tail);
+    context.draw(DrawCommand::Line {
+        from: (from.x.into(), from.y.into()),
+        to: (to.x.into(), to.y.into()),
+    });
+    context.set_markers(start, end);
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("markers", markers);
+    runtime.define_stateful_fn("arrow", arrow);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{output::Mm, util::test_helpers::*};
+
+    #[test]
+    fn test_markers() {
+        let commands = run_script("markers \"dot\" \"none\"\nline (pnt2 0 0) (vec2 1 1)")
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(
+            commands[0],
+            DrawCommand::SetMarkers {
+                start: Some(Marker::Dot),
+                end: None,
+            }
+        );
+        assert!(matches!(commands[1], DrawCommand::Line { .. }));
+
+        assert!(matches!(
+            run_script("markers \"dot\" \"star\""),
+            Err(Error::InvalidArgument)
+        ));
+        assert!(matches!(
+            run_script("markers \"dot\" 1"),
+            Err(Error::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_arrow_restores_markers() {
+        let commands = run_script("markers \"bar\" \"bar\"\narrow (pnt2 0 0) (pnt2 1 0)")
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(
+            commands[1..],
+            [
+                DrawCommand::SetMarkers {
+                    start: None,
+                    end: Some(Marker::Arrow),
+                },
+                DrawCommand::Line {
+                    from: (Mm(0.0), Mm(0.0)),
+                    to: (Mm(1.0), Mm(0.0)),
+                },
+                DrawCommand::SetMarkers {
+                    start: Some(Marker::Bar),
+                    end: Some(Marker::Bar),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_arrow_options() {
+        let commands = run_script("arrow (pnt2 0 0) (pnt2 1 0) start=dot end=\"none\"")
+            .unwrap()
+            .finish()
+            .commands;
+        assert_eq!(
+            commands[0],
+            DrawCommand::SetMarkers {
+                start: Some(Marker::Dot),
+                end: None,
+            }
+        );
+
+        assert_eq!(
+            run_script("arrow (pnt2 0 0) (pnt2 1 0) size=2").err(),
+            Some(Error::UnknownOption {
+                function: "arrow".into(),
+                option: "size".into(),
+            })
+        );
+        assert!(matches!(
+            run_script("arrow (pnt2 0 0) (pnt2 1 0) end=star"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+}
@@ -0,0 +1,304 @@
+use std::{f64::consts::TAU, sync::Arc};
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::{Path, Point, Scalar, Vector};
+
+/// Sharp corners whose miter would reach further than this many times the
+/// distance are cut off instead.
+const MITER_LIMIT: f64 = 4.0;
+/// The largest angle covered by one segment of a round join.
+const ARC_STEP: f64 = TAU / 32.0;
+
+type Xy = (f64, f64);
+
+fn add(a: Xy, b: Xy) -> Xy {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+fn sub(a: Xy, b: Xy) -> Xy {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn scale(a: Xy, factor: f64) -> Xy {
+    (a.0 * factor, a.1 * factor)
+}
+
+fn cross(a: Xy, b: Xy) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn dot(a: Xy, b: Xy) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+/// The unit vector to the left of `direction`.
+fn normal(direction: Xy) -> Xy {
+    (-direction.1, direction.0)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Join {
+    Miter,
+    Round,
+}
+
+/// Connect the offset segments before and after `corner`.
+fn join(points: &mut Vec<Xy>, corner: Xy, incoming: Xy, outgoing: Xy, distance: f64, join: Join) {
+    let start = add(corner, scale(normal(incoming), distance));
+    let end = add(corner, scale(normal(outgoing), distance));
+    let turn = cross(incoming, outgoing);
+
+    let straight = turn.abs() < 1e-12;
+    if straight && dot(incoming, outgoing) > 0.0 {
+        points.push(start);
+        return;
+    }
+    // The offset side is on the outside of the corner, leaving a gap
+    // between the two segments instead of an overlap
+    let outside = straight || turn * distance < 0.0;
+
+    if outside && join == Join::Round {
+        let sweep = if straight {
+            -TAU / 2.0 * distance.signum()
+        } else {
+            cross(normal(incoming), normal(outgoing)).atan2(dot(normal(incoming), normal(outgoing)))
+        };
+        let (from_x, from_y) = sub(start, corner);
+        let first = from_y.atan2(from_x);
+        let steps = (sweep.abs() / ARC_STEP).ceil().max(1.0) as usize;
+        for step in 0..=steps {
+            let (sin, cos) = (first + sweep * step as f64 / steps as f64).sin_cos();
+            points.push(add(corner, scale((cos, sin), distance.abs())));
+        }
+    } else if straight {
+        points.extend([start, end]);
+    } else {
+        // Where the two offset segments, extended, meet
+        let along = cross(sub(end, start), outgoing) / turn;
+        let miter = add(start, scale(incoming, along));
+        let reach = sub(miter, corner);
+        if outside && dot(reach, reach).sqrt() > MITER_LIMIT * distance.abs() {
+            points.extend([start, end]);
+        } else {
+            points.push(miter);
+        }
+    }
+}
+
+/// The polyline `distance` to the left of `corners`.
+fn offset_path(corners: &[Xy], closed: bool, distance: f64, kind: Join) -> Vec<Xy> {
+    let count = corners.len();
+    let segments = if closed { count } else { count - 1 };
+    let directions = (0..segments)
+        .map(|i| {
+            let delta = sub(corners[(i + 1) % count], corners[i]);
+            scale(delta, 1.0 / dot(delta, delta).sqrt())
+        })
+        .collect::<Vec<_>>();
+
+    let mut points = Vec::new();
+    if closed {
+        for (i, &corner) in corners.iter().enumerate() {
+            let incoming = directions[(i + segments - 1) % segments];
+            join(&mut points, corner, incoming, directions[i], distance, kind);
+        }
+    } else {
+        points.push(add(corners[0], scale(normal(directions[0]), distance)));
+        for i in 1..segments {
+            let (incoming, outgoing) = (directions[i - 1], directions[i]);
+            join(&mut points, corners[i], incoming, outgoing, distance, kind);
+        }
+        let last = directions[segments - 1];
+        points.push(add(corners[count - 1], scale(normal(last), distance)));
+    }
+    points
+}
+
+fn offset_value(shape: Value, distance: Value, kind: Join) -> Result<Value, Error> {
+    let Value::Scalar(distance) = distance else {
+        return Err(Error::TypeError);
+    };
+    let distance = f64::from(distance);
+
+    match shape {
+        Value::Circle(center, radius) => {
+            let radius = f64::from(radius) + distance;
+            if radius < 0.0 {
+                return Err(Error::InvalidArgument);
+            }
+            Ok(Value::Circle(center, radius.into()))
+        }
+        Value::Line(start, direction) => {
+            let (dx, dy) = (f64::from(direction.x), f64::from(direction.y));
+            let length = dx.hypot(dy);
+            if length == 0.0 {
+                return Err(Error::InvalidArgument);
+            }
+            let shift = Vector {
+                x: Scalar::from(-dy / length * distance),
+                y: Scalar::from(dx / length * distance),
+            };
+            Ok(Value::Line(start + shift, direction))
+        }
+        Value::Path(path) => {
+            let mut corners = path
+                .points
+                .iter()
+                .map(|point| (f64::from(point.x), f64::from(point.y)))
+                .collect::<Vec<_>>();
+            corners.dedup();
+            if path.closed && corners.len() > 1 && corners.first() == corners.last() {
+                corners.pop();
+            }
+            if corners.len() < if path.closed { 3 } else { 2 } {
+                return Err(Error::InvalidArgument);
+            }
+
+            // Grow closed paths outwards whichever way they wind
+            let mut distance = distance;
+            if path.closed {
+                let area = (0..corners.len())
+                    .map(|i| cross(corners[i], corners[(i + 1) % corners.len()]))
+                    .sum::<f64>();
+                if area > 0.0 {
+                    distance = -distance;
+                }
+            }
+
+            let points = offset_path(&corners, path.closed, distance, kind)
+                .into_iter()
+                .map(|(x, y)| Point {
+                    x: x.into(),
+                    y: y.into(),
+                })
+                .collect();
+            Ok(Value::Path(Arc::new(Path {
+                points,
+                closed: path.closed,
+            })))
+        }
+        _ => Err(Error::TypeError),
+    }
+}
+
+/// A parallel copy of a line, circle or path. Closed paths and circles
+/// grow for positive distances, anything else moves to the left.
+pub fn offset(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => shape, distance);
+    offset_value(shape, distance, Join::Miter)
+}
+
+/// Like `offset`, but with rounded corners on the outside of turns.
+pub fn offset_round(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => shape, distance);
+    offset_value(shape, distance, Join::Round)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("offset", offset);
+    runtime.define_fn("offset_round", offset_round);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn path(points: &[(f64, f64)], closed: bool) -> Value {
+        let points = points
+            .iter()
+            .map(|&(x, y)| Point {
+                x: x.into(),
+                y: y.into(),
+            })
+            .collect();
+        Value::Path(Arc::new(Path { points, closed }))
+    }
+
+    fn corners_of(value: Result<Value, Error>) -> Vec<(f64, f64)> {
+        let Ok(Value::Path(path)) = value else {
+            panic!("expected a path, got {value:?}");
+        };
+        path.points
+            .iter()
+            .map(|point| {
+                let round = |value: f64| (value * 1e6).round() / 1e6 + 0.0;
+                (round(point.x.into()), round(point.y.into()))
+            })
+            .collect()
+    }
+
+    const SQUARE: [(f64, f64); 4] = [(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 10.0)];
+
+    #[test]
+    fn test_offset_polygon() {
+        let grown = [(-1.0, -1.0), (11.0, -1.0), (11.0, 11.0), (-1.0, 11.0)];
+        let mut stack = dummy_stack([path(&SQUARE, true), scalar(1)]);
+        assert_eq!(corners_of(offset(&mut stack)), grown);
+
+        // Clockwise corners still grow outwards
+        let mut reversed = SQUARE;
+        reversed.reverse();
+        let mut stack = dummy_stack([path(&reversed, true), scalar(1)]);
+        let mut expected = grown;
+        expected.reverse();
+        assert_eq!(corners_of(offset(&mut stack)), expected);
+
+        let mut stack = dummy_stack([path(&SQUARE, true), scalar(-1)]);
+        assert_eq!(
+            corners_of(offset(&mut stack)),
+            [(1.0, 1.0), (9.0, 1.0), (9.0, 9.0), (1.0, 9.0)]
+        );
+    }
+
+    #[test]
+    fn test_offset_polyline() {
+        let mut stack = dummy_stack([path(&SQUARE[..3], false), scalar(1)]);
+        assert_eq!(
+            corners_of(offset(&mut stack)),
+            [(0.0, 1.0), (9.0, 1.0), (9.0, 10.0)]
+        );
+
+        // A sharp spike is cut off instead of reaching far out
+        let spike = [(0.0, 0.0), (10.0, 0.0), (0.0, 0.5)];
+        let mut stack = dummy_stack([path(&spike, false), scalar(-1)]);
+        assert_eq!(corners_of(offset(&mut stack)).len(), 4);
+
+        let mut stack = dummy_stack([path(&[(1.0, 1.0), (1.0, 1.0)], false), scalar(1)]);
+        assert_eq!(offset(&mut stack), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn test_offset_round() {
+        let mut stack = dummy_stack([path(&SQUARE, true), scalar(1)]);
+        let corners = corners_of(offset_round(&mut stack));
+        assert_eq!(corners.len(), 4 * 9);
+        assert_eq!(corners[0], (-1.0, 0.0));
+        assert_eq!(corners[8], (0.0, -1.0));
+        assert!(corners[..9]
+            .iter()
+            .all(|&(x, y)| (x.hypot(y) - 1.0).abs() < 1e-6));
+    }
+
+    #[test]
+    fn test_offset_line_and_circle() {
+        let mut stack = dummy_stack([line(0, 0, 2, 0), scalar(1)]);
+        let shifted = Point {
+            x: 0.0.into(),
+            y: 1.0.into(),
+        };
+        let Ok(Value::Line(start, _)) = offset(&mut stack) else {
+            panic!("expected a line");
+        };
+        assert_eq!(start, shifted);
+
+        let mut stack = dummy_stack([circle_value(0, 0, 2), scalar(-1)]);
+        assert_values_eq(offset(&mut stack), circle_value(0, 0, 1.0));
+        let mut stack = dummy_stack([circle_value(0, 0, 2), scalar(-3)]);
+        assert_eq!(offset(&mut stack), Err(Error::InvalidArgument));
+    }
+}
@@ -0,0 +1,164 @@
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::Point;
+
+/// A polyline through a list of points, or a polygon if `closed`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Path {
+    pub points: Vec<Point>,
+    /// Whether the last point connects back to the first.
+    pub closed: bool,
+}
+
+impl Path {
+    pub fn closed(points: Vec<Point>) -> Self {
+        Self {
+            points,
+            closed: true,
+        }
+    }
+
+    /// The straight pieces of the path in order, including the one back
+    /// to the start of a polygon.
+    pub fn segments(&self) -> impl Iterator<Item = (Point, Point)> + '_ {
+        let closing = match (self.closed, self.points.as_slice()) {
+            (true, [first, .., last]) => Some((*last, *first)),
+            _ => None,
+        };
+        self.points
+            .windows(2)
+            .map(|pair| (pair[0], pair[1]))
+            .chain(closing)
+    }
+
+    pub fn length(&self) -> f64 {
+        self.segments().map(|(from, to)| distance(from, to)).sum()
+    }
+}
+
+fn distance(from: Point, to: Point) -> f64 {
+    f64::from(to.x - from.x).hypot(f64::from(to.y - from.y))
+}
+
+/// A path or a line, which is a path with a single segment.
+fn as_path(value: Value) -> Result<Path, Error> {
+    match value {
+        Value::Path(path) => Ok(Path::clone(&path)),
+        Value::Line(p, v) => Ok(Path {
+            points: vec![p, p + v],
+            closed: false,
+        }),
+        _ => Err(Error::TypeError),
+    }
+}
+
+/// The length of a path or line, including the way back to the start of a
+/// polygon.
+pub fn path_len(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => path);
+    Ok(Value::Scalar(as_path(path)?.length().into()))
+}
+
+/// The point `t` along a path or line, measured from its start.
+pub fn along(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => path, t);
+    let (path, Value::Scalar(t)) = (as_path(path)?, t) else {
+        return Err(Error::TypeError);
+    };
+    let mut left = f64::from(t);
+    if left < 0.0 || left > path.length() + f64::EPSILON {
+        return Err(Error::InvalidArgument);
+    }
+
+    for (from, to) in path.segments() {
+        let length = distance(from, to);
+        if left <= length && length > 0.0 {
+            let share = left / length;
+            return Ok(Value::Point(Point {
+                x: (f64::from(from.x) + f64::from(to.x - from.x) * share).into(),
+                y: (f64::from(from.y) + f64::from(to.y - from.y) * share).into(),
+            }));
+        }
+        left -= length;
+    }
+    // Rounding left a bit past the end, or the path has no length
+    let end = match path.closed {
+        true => path.points.first(),
+        false => path.points.last(),
+    };
+    end.map(|end| Value::Point(*end))
+        .ok_or(Error::InvalidArgument)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("path_len", path_len);
+    runtime.define_fn("along", along);
+}
+
+impl std::fmt::Display for Path {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [", if self.closed { "polygon" } else { "path" })?;
+        for (i, point) in self.points.iter().enumerate() {
+            if i > 0 {
+                write!(f, ", ")?;
+            }
+            write!(f, "{point}")?;
+        }
+        write!(f, "]")
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn path(points: &[(i64, i64)], closed: bool) -> Value {
+        let points = points
+            .iter()
+            .map(|&(x, y)| Point {
+                x: x.into(),
+                y: y.into(),
+            })
+            .collect();
+        Value::Path(Arc::new(Path { points, closed }))
+    }
+
+    #[test]
+    fn test_path_len() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(0, 0),
+            line(0, 0, 3, 4),
+            path(&[(0, 0), (1, 0), (1, 1), (0, 1)], true),
+            path(&[(0, 0), (3, 0), (3, 4)], false),
+        ]);
+
+        assert_values_eq(path_len(&mut stack), scalar(7.0));
+        assert_values_eq(path_len(&mut stack), scalar(4.0));
+        assert_values_eq(path_len(&mut stack), scalar(5.0));
+        assert_eq!(path_len(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_along() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            line(0, 0, 3, 4), scalar(6),
+            path(&[(0, 0), (1, 0), (1, 1), (0, 1)], true), scalar(4),
+            path(&[(0, 0), (1, 0), (1, 1), (0, 1)], true), scalar(3.5),
+            path(&[(0, 0), (3, 0), (3, 4)], false), scalar(5),
+        ]);
+
+        assert_values_eq(along(&mut stack), point(3.0, 2.0));
+        assert_values_eq(along(&mut stack), point(0.0, 0.5));
+        assert_values_eq(along(&mut stack), point(0.0, 0.0));
+        assert_eq!(along(&mut stack), Err(Error::InvalidArgument));
+    }
+}
@@ -0,0 +1,161 @@
+use std::sync::Arc;
+
+use crate::{
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::{Path, Point};
+
+/// More samples than this are almost certainly a typo.
+const MAX_SAMPLES: i64 = 100_000;
+
+fn points(list: Value) -> Result<Vec<Point>, Error> {
+    let Value::List(items) = list else {
+        return Err(Error::TypeError);
+    };
+    items
+        .iter()
+        .map(|item| match item {
+            Value::Point(point) => Ok(*point),
+            _ => Err(Error::TypeError),
+        })
+        .collect()
+}
+
+/// The graph of the builtin `name` between `from` and `to`, evaluated at
+/// `samples` evenly spaced points.
+pub fn plot_fn(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => name, from, to, samples);
+    let (Value::String(name), Value::Scalar(from), Value::Scalar(to), Value::Scalar(samples)) =
+        (name, from, to, samples)
+    else {
+        return Err(Error::TypeError);
+    };
+    let samples = match samples.as_integer() {
+        Some(samples) if (2..=MAX_SAMPLES).contains(&samples) => samples,
+        _ => return Err(Error::InvalidArgument),
+    };
+    let (from, to) = (f64::from(from), f64::from(to));
+
+    let points = (0..samples)
+        .map(|i| {
+            let x = from + (to - from) * i as f64 / (samples - 1) as f64;
+            let mut arguments = Stack::default();
+            arguments.push(Value::Scalar(x.into()));
+            match context.call(&name, &mut arguments)? {
+                Value::Scalar(y) => Ok(Point { x: x.into(), y }),
+                _ => Err(Error::TypeError),
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Value::Path(Arc::new(Path {
+        points,
+        closed: false,
+    })))
+}
+
+/// A polyline through a list of points.
+pub fn plot(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => list);
+    let points = points(list)?;
+    if points.len() < 2 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(Value::Path(Arc::new(Path {
+        points,
+        closed: false,
+    })))
+}
+
+/// A dot of radius `size` on each of a list of points.
+pub fn scatter(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => list, size);
+    let Value::Scalar(size) = size else {
+        return Err(Error::TypeError);
+    };
+    if f64::from(size) < 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    let dots = points(list)?
+        .into_iter()
+        .map(|point| Value::Circle(point, size))
+        .collect();
+    Ok(Value::List(Arc::new(dots)))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("plot_fn", plot_fn);
+    runtime.define_fn("plot", plot);
+    runtime.define_fn("scatter", scatter);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{
+        ast::parse_file, output::DrawCommand, token::StringTokenizer, util::test_helpers::*,
+    };
+
+    #[test]
+    fn test_plot_fn() {
+        let program = parse_file(&mut StringTokenizer::new(
+            &"#let graph (plot_fn \"square\" (-1) 1 3)",
+        ))
+        .unwrap();
+        let mut runtime = Runtime::<Recorder>::default();
+        runtime.define_fn("square", |stack| {
+            let Value::Scalar(x) = stack.pop()? else {
+                return Err(Error::TypeError);
+            };
+            Ok(Value::Scalar(x * x))
+        });
+        runtime.execute(program).unwrap();
+
+        let Some(Value::Path(graph)) = runtime.get_variable("graph") else {
+            panic!("expected a path");
+        };
+        let points = graph
+            .points
+            .iter()
+            .map(|point| (f64::from(point.x), f64::from(point.y)))
+            .collect::<Vec<_>>();
+        assert_eq!(points, [(-1.0, 1.0), (0.0, 0.0), (1.0, 1.0)]);
+
+        assert_eq!(
+            run_script("plot_fn \"nope\" 0 1 3").err(),
+            Some(Error::FunctionNotFound {
+                name: "nope".into(),
+                suggestion: None,
+            })
+        );
+    }
+
+    #[test]
+    fn test_plot_builtin() {
+        let runtime = run_script("plot_fn \"sqrt\" 0 4 5").unwrap();
+        let commands = runtime.finish().commands;
+        assert!(matches!(
+            &commands[..],
+            [DrawCommand::Path { points, closed: false }] if points.len() == 5
+        ));
+        assert!(matches!(
+            run_script("plot_fn \"sqrt\" 0 4 1"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_plot_and_scatter() {
+        let mut stack = dummy_stack([list_of([point(0, 0), point(1, 2)])]);
+        assert!(matches!(plot(&mut stack), Ok(Value::Path(path)) if path.points.len() == 2));
+        let mut stack = dummy_stack([list_of([point(0, 0)])]);
+        assert_eq!(plot(&mut stack), Err(Error::InvalidArgument));
+
+        let mut stack = dummy_stack([list_of([point(0, 0), point(1, 2)]), scalar(1)]);
+        assert_values_eq(
+            scatter(&mut stack),
+            list_of([circle_value(0, 0, 1), circle_value(1, 2, 1)]),
+        );
+    }
+}
@@ -1,16 +1,25 @@
+use std::sync::Arc;
+
 use crate::{
     reverse_pop,
-    runtime::{Error, Runtime, Stack, Value},
+    runtime::{Context, Error, Runtime, Stack, Value},
 };
 
 use super::{Scalar, Vector};
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     pub x: Scalar,
     pub y: Scalar,
 }
 
+impl std::fmt::Display for Point {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "({}, {})", self.x, self.y)
+    }
+}
+
 impl std::ops::Add<Vector> for Point {
     type Output = Point;
 
@@ -55,7 +64,7 @@ pub fn pnt2(stack: &mut Stack) -> Result<Value, Error> {
     let (Value::Scalar(x), Value::Scalar(y)) = (x, y) else {
         return Err(Error::TypeError);
     };
-    Ok(Value::Point(Point { x, y }))
+    Ok(stack.snapped(Value::Point(Point { x, y })))
 }
 
 pub fn lvec(stack: &mut Stack) -> Result<Value, Error> {
@@ -84,16 +93,114 @@ pub fn y(stack: &mut Stack) -> Result<Value, Error> {
     }
 }
 
-pub fn jump(stack: &mut Stack) -> Result<Value, Error> {
-    let Value::Vector(vec) = super::vector::vec2(stack)? else {
-        unreachable!()
+/// Leave the x coordinate on the stack and return the y coordinate.
+pub fn xy(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => value);
+    let (x, y) = match value {
+        Value::Point(pnt) => (pnt.x, pnt.y),
+        Value::Vector(vec) => (vec.x, vec.y),
+        _ => return Err(Error::TypeError),
     };
-    reverse_pop!(stack => previous);
-    let Value::Point(previous) = previous else {
+    Ok(stack.push_results([Value::Scalar(x), Value::Scalar(y)]))
+}
+
+fn spacing(value: Value) -> Result<Scalar, Error> {
+    let Value::Scalar(spacing) = value else {
         return Err(Error::TypeError);
     };
+    if f64::from(spacing) <= 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(spacing)
+}
 
-    Ok(Value::Point(previous + vec))
+/// Round the coordinates of points and vectors to multiples of `spacing`,
+/// including the start and direction of lines, the centers of circles and
+/// the items of lists, leaving other values alone.
+pub(crate) fn snapped(value: Value, spacing: Scalar) -> Value {
+    let point = |pnt: Point| Point {
+        x: pnt.x.snap(spacing),
+        y: pnt.y.snap(spacing),
+    };
+    let vector = |vec: Vector| Vector {
+        x: vec.x.snap(spacing),
+        y: vec.y.snap(spacing),
+    };
+    match value {
+        Value::Point(pnt) => Value::Point(point(pnt)),
+        Value::Vector(vec) => Value::Vector(vector(vec)),
+        Value::Line(start, direction) => Value::Line(point(start), vector(direction)),
+        Value::Circle(center, radius) => Value::Circle(point(center), radius),
+        Value::List(items) => Value::List(Arc::new(
+            items
+                .iter()
+                .map(|item| snapped(item.clone(), spacing))
+                .collect(),
+        )),
+        other => other,
+    }
+}
+
+/// Move a point or vector to the nearest multiple of the spacing.
+pub fn snap(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => value, grid);
+    let grid = spacing(grid)?;
+    match value {
+        Value::Point(_) | Value::Vector(_) => Ok(snapped(value, grid)),
+        _ => Err(Error::TypeError),
+    }
+}
+
+/// Snap the geometry made with `pnt2`, `vec2`, `line`, `circle` and `list`
+/// from now on, or stop snapping with a spacing of 0.
+pub fn snap_mode(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => grid);
+    match grid {
+        Value::Scalar(off) if off.is_zero() => context.set_snap(None),
+        grid => context.set_snap(Some(spacing(grid)?)),
+    }
+    Ok(Value::Void)
+}
+
+pub fn jump(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => previous, x, y);
+    let (Value::Point(previous), Value::Scalar(x), Value::Scalar(y)) = (previous, x, y) else {
+        return Err(Error::TypeError);
+    };
+
+    Ok(Value::Point(previous + Vector { x, y }))
+}
+
+/// Project `pnt` onto the infinite line through `(origin, dir)`.
+pub(super) fn project(pnt: Point, origin: Point, dir: Vector) -> Result<Point, Error> {
+    let len2 = f64::from(dir.x * dir.x + dir.y * dir.y);
+    if len2 < f64::EPSILON {
+        return Err(Error::InvalidArgument);
+    }
+    let offset = pnt - origin;
+    let t = f64::from(offset.x * dir.x + offset.y * dir.y) / len2;
+    Ok(origin + dir * Scalar::from(t))
+}
+
+pub fn dist(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => a, b);
+    let offset = match (a, b) {
+        (Value::Point(a), Value::Point(b)) => b - a,
+        (Value::Point(pnt), Value::Line(origin, dir))
+        | (Value::Line(origin, dir), Value::Point(pnt)) => pnt - project(pnt, origin, dir)?,
+        _ => return Err(Error::TypeError),
+    };
+    Ok(Value::Scalar(
+        (offset.x * offset.x + offset.y * offset.y).sqrt(),
+    ))
+}
+
+pub fn foot(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => pnt, line);
+    let (Value::Point(pnt), Value::Line(origin, dir)) = (pnt, line) else {
+        return Err(Error::TypeError);
+    };
+    Ok(Value::Point(project(pnt, origin, dir)?))
 }
 
 pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
@@ -101,7 +208,12 @@ pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
     runtime.define_fn("lvec", lvec);
     runtime.define_fn("x", x);
     runtime.define_fn("y", y);
+    runtime.define_fn("xy", xy);
     runtime.define_fn("jump", jump);
+    runtime.define_fn("snap", snap);
+    runtime.define_stateful_fn("snap_mode", snap_mode);
+    runtime.define_fn("dist", dist);
+    runtime.define_fn("foot", foot);
 }
 
 #[cfg(test)]
@@ -153,6 +265,72 @@ mod test {
         assert_eq!(x(&mut stack), Err(Error::TypeError))
     }
 
+    #[test]
+    fn test_snap() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(1, 2), scalar(0),
+            vector(-3, 7), scalar(5),
+            point(1.2, 2.6), scalar(0.5),
+            point(7, 8), scalar(5),
+        ]);
+
+        assert_values_eq(snap(&mut stack), point(5, 10));
+        assert_values_eq(snap(&mut stack), point(1.0, 2.5));
+        assert_values_eq(snap(&mut stack), vector(-5, 5));
+        assert_eq!(snap(&mut stack), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn test_snap_mode() {
+        let source = "snap_mode 5\n#let p (pnt2 7 8)\n#let v (vec2 2 3)\n\
+            #let l (jump $p 1 1)\nsnap_mode 0\n#let q (pnt2 7 8)";
+        let runtime = run_script(source).unwrap();
+        let get = |name| runtime.get_variable(name).cloned().unwrap();
+        assert_eq!(get("p"), point(5, 10));
+        assert_eq!(get("v"), vector(0, 5));
+        // Only the constructors snap
+        assert_eq!(get("l"), point(6, 11));
+        assert_eq!(get("q"), point(7, 8));
+
+        assert!(matches!(
+            run_script("snap_mode (-1)"),
+            Err(Error::InvalidArgument)
+        ));
+    }
+
+    #[test]
+    fn test_snap_mode_constructors() {
+        let source = "#let p (jump (pnt2 0 0) 2 8)\n#let v (add (vec2 0 0) (vec2 1 1))\n\
+            snap_mode 5\n#let a (line $p (pnt2 9 9))\n#let b (line $p $v)\n\
+            #let c (circle $p 3)\n#let d (list $p 7 $v)";
+        let runtime = run_script(source).unwrap();
+        let get = |name| runtime.get_variable(name).cloned().unwrap();
+        assert_eq!(get("a"), line(0, 10, 10, 0));
+        assert_eq!(get("b"), line(0, 10, 0, 0));
+        assert_eq!(
+            get("c"),
+            Value::Circle(point(0, 10).as_point().unwrap(), 3.into())
+        );
+        assert_eq!(
+            get("d"),
+            Value::List(Arc::new(vec![point(0, 10), scalar(7), vector(0, 0)]))
+        );
+    }
+
+    #[test]
+    fn test_xy() {
+        let mut stack = dummy_stack([vector(3, 4), point(1, 2)]);
+
+        assert_values_eq(xy(&mut stack), scalar(2));
+        assert_eq!(stack.pop(), Ok(scalar(1)));
+        assert_values_eq(xy(&mut stack), scalar(4));
+        assert_eq!(stack.values(), [scalar(3)]);
+
+        let runtime = run_script("#let p (pnt2 1 2)\nxy $p => add => #let s").unwrap();
+        assert_eq!(runtime.get_variable("s"), Some(&scalar(3)));
+    }
+
     #[test]
     fn test_y() {
         #[rustfmt::skip]
@@ -168,4 +346,50 @@ mod test {
         assert_values_eq(y(&mut stack), scalar(4));
         assert_eq!(y(&mut stack), Err(Error::TypeError))
     }
+
+    #[test]
+    fn test_dist() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack(
+            [
+                point(0, 0), vector(1, 1),
+                point(0, 0), line(1, 1, 0, 0),
+                line(0, 0, 2, 0), point(5, -3),
+                point(0, 0), point(3, 4),
+            ]
+        );
+
+        assert_values_eq(dist(&mut stack), scalar(5.0));
+        assert_values_eq(dist(&mut stack), scalar(3.0));
+        assert_eq!(dist(&mut stack), Err(Error::InvalidArgument));
+        assert_eq!(dist(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_foot() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack(
+            [
+                line(0, 0, 1, 1), point(1, 1),
+                point(3, 1), line(0, 0, 1, 1),
+            ]
+        );
+
+        assert_values_eq(foot(&mut stack), point(2.0, 2.0));
+        assert_eq!(foot(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_jump() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack(
+            [
+                point(1, 2), scalar(3), scalar(4),
+                point(1, 2), point(3, 4), scalar(5),
+            ]
+        );
+
+        assert_eq!(jump(&mut stack), Err(Error::TypeError));
+        assert_values_eq(jump(&mut stack), point(4, 6));
+    }
 }
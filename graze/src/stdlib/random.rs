@@ -0,0 +1,109 @@
+use crate::{
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+/// A small seedable generator (SplitMix64). Every run starts from the
+/// same seed, so drawings only change when the script asks for it.
+#[derive(Debug, Clone)]
+pub struct Random {
+    state: u64,
+}
+
+impl Default for Random {
+    fn default() -> Self {
+        Self::new(0)
+    }
+}
+
+impl Random {
+    pub fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in `[0, 1)`.
+    pub fn next_f64(&mut self) -> f64 {
+        // The top 53 bits fill the mantissa exactly
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
+
+pub fn seed(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => seed);
+    let Value::Scalar(seed) = seed else {
+        return Err(Error::TypeError);
+    };
+    let seed = seed.as_integer().ok_or(Error::InvalidArgument)?;
+    *context.random() = Random::new(seed as u64);
+    Ok(Value::Void)
+}
+
+pub fn rand(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    Ok(Value::Scalar(context.random().next_f64().into()))
+}
+
+pub fn rand_range(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => low, high);
+    let (Value::Scalar(low), Value::Scalar(high)) = (low, high) else {
+        return Err(Error::TypeError);
+    };
+    let (low, high) = (f64::from(low), f64::from(high));
+    if low > high {
+        return Err(Error::InvalidArgument);
+    }
+    let t = context.random().next_f64();
+    Ok(Value::Scalar((low + (high - low) * t).into()))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("seed", seed);
+    runtime.define_stateful_fn("rand", rand);
+    runtime.define_stateful_fn("rand_range", rand_range);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_seeded_sequence() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([]);
+        let first = rand(&mut stack, &mut context).unwrap();
+        rand(&mut stack, &mut context).unwrap();
+
+        let mut stack = dummy_stack([scalar(0)]);
+        seed(&mut stack, &mut context).unwrap();
+        assert_eq!(rand(&mut stack, &mut context), Ok(first));
+
+        let mut stack = dummy_stack([scalar(1.5)]);
+        assert_eq!(seed(&mut stack, &mut context), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn test_rand_range() {
+        let mut context = Context::default();
+        for _ in 0..100 {
+            let mut stack = dummy_stack([scalar(-2), scalar(3)]);
+            let Ok(Value::Scalar(x)) = rand_range(&mut stack, &mut context) else {
+                panic!("rand_range returns a scalar");
+            };
+            assert!((-2.0..3.0).contains(&f64::from(x)));
+        }
+
+        let mut stack = dummy_stack([scalar(3), scalar(-2)]);
+        assert_eq!(
+            rand_range(&mut stack, &mut context),
+            Err(Error::InvalidArgument)
+        );
+    }
+}
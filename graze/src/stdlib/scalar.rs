@@ -1,4 +1,4 @@
-use std::ops::*;
+use std::{cmp::Ordering, fmt::Display, ops::*};
 
 use crate::{
     reverse_pop,
@@ -8,30 +8,98 @@ use crate::{
 
 #[repr(transparent)]
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Scalar(ScalarInner);
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 enum ScalarInner {
     Integer(i64),
+    /// An exact fraction in lowest terms, with a denominator above 1.
+    Rational(i64, i64),
     Float(f64),
 }
 
+/// What `add`, `sub`, `mul` and `div` do with integers whose result isn't
+/// an integer, see [`RuntimeConfig`](crate::RuntimeConfig).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NumericPolicy {
+    /// Keep them exact as fractions, e.g. when dividing `1 3`, and turn
+    /// them into floats when they overflow. Arithmetic involving a float
+    /// always gives a float.
+    #[default]
+    Promote,
+    /// Fail instead, for drawings that need exact integer grids.
+    StrictInteger,
+    /// Give floats for every result, even exact ones.
+    AlwaysFloat,
+}
+
+/// The fraction `n / d` in lowest terms, an integer if it is one, or
+/// `None` if it doesn't fit.
+fn fraction(n: i128, d: i128) -> Option<Scalar> {
+    let (mut a, mut b) = (n.unsigned_abs(), d.unsigned_abs());
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    let divisor = i128::try_from(a).ok()?.max(1) * d.signum();
+    let (n, d) = (
+        i64::try_from(n / divisor).ok()?,
+        i64::try_from(d / divisor).ok()?,
+    );
+    Some(Scalar(match d {
+        1 => ScalarInner::Integer(n),
+        _ => ScalarInner::Rational(n, d),
+    }))
+}
+
+/// A scalar exactly as it is stored, telling integers apart from floats of
+/// the same value and 0 from -0, to look up results computed from it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum ScalarKey {
+    Integer(i64),
+    Rational(i64, i64),
+    Float(u64),
+}
+
+impl ScalarInner {
+    /// Numerator and denominator of exact values.
+    fn ratio(self) -> Option<(i128, i128)> {
+        match self {
+            ScalarInner::Integer(i) => Some((i.into(), 1)),
+            ScalarInner::Rational(n, d) => Some((n.into(), d.into())),
+            ScalarInner::Float(_) => None,
+        }
+    }
+}
+
+impl Scalar {
+    /// Combine two scalars exactly if both are, or as floats otherwise.
+    /// Exact results too large to store become floats rather than
+    /// wrapping around.
+    fn combine(
+        self,
+        rhs: Scalar,
+        exact: impl FnOnce((i128, i128), (i128, i128)) -> Option<(i128, i128)>,
+        float: impl FnOnce(f64, f64) -> f64,
+    ) -> Scalar {
+        let result = match (self.0.ratio(), rhs.0.ratio()) {
+            (Some(a), Some(b)) => exact(a, b).and_then(|(n, d)| fraction(n, d)),
+            _ => None,
+        };
+        result.unwrap_or_else(|| Scalar(ScalarInner::Float(float(self.into(), rhs.into()))))
+    }
+}
+
 impl Add<Scalar> for Scalar {
     type Output = Scalar;
 
     fn add(self, rhs: Scalar) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ScalarInner::Integer(a), ScalarInner::Integer(b)) => {
-                Scalar(ScalarInner::Integer(a + b))
-            }
-            (ScalarInner::Float(a), ScalarInner::Float(b)) => Scalar(ScalarInner::Float(a + b)),
-            (ScalarInner::Integer(a), ScalarInner::Float(b)) => {
-                Scalar(ScalarInner::Float(a as f64 + b))
-            }
-            (ScalarInner::Float(a), ScalarInner::Integer(b)) => {
-                Scalar(ScalarInner::Float(a + b as f64))
-            }
-        }
+        self.combine(
+            rhs,
+            |(a, b), (c, d)| Some((a * d + c * b, b * d)),
+            |a, b| a + b,
+        )
     }
 }
 
@@ -39,18 +107,11 @@ impl Sub<Scalar> for Scalar {
     type Output = Scalar;
 
     fn sub(self, rhs: Scalar) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ScalarInner::Integer(a), ScalarInner::Integer(b)) => {
-                Scalar(ScalarInner::Integer(a - b))
-            }
-            (ScalarInner::Float(a), ScalarInner::Float(b)) => Scalar(ScalarInner::Float(a - b)),
-            (ScalarInner::Integer(a), ScalarInner::Float(b)) => {
-                Scalar(ScalarInner::Float(a as f64 - b))
-            }
-            (ScalarInner::Float(a), ScalarInner::Integer(b)) => {
-                Scalar(ScalarInner::Float(a - b as f64))
-            }
-        }
+        self.combine(
+            rhs,
+            |(a, b), (c, d)| Some((a * d - c * b, b * d)),
+            |a, b| a - b,
+        )
     }
 }
 
@@ -58,18 +119,7 @@ impl Mul<Scalar> for Scalar {
     type Output = Scalar;
 
     fn mul(self, rhs: Scalar) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ScalarInner::Integer(a), ScalarInner::Integer(b)) => {
-                Scalar(ScalarInner::Integer(a * b))
-            }
-            (ScalarInner::Float(a), ScalarInner::Float(b)) => Scalar(ScalarInner::Float(a * b)),
-            (ScalarInner::Integer(a), ScalarInner::Float(b)) => {
-                Scalar(ScalarInner::Float(a as f64 * b))
-            }
-            (ScalarInner::Float(a), ScalarInner::Integer(b)) => {
-                Scalar(ScalarInner::Float(a * b as f64))
-            }
-        }
+        self.combine(rhs, |(a, b), (c, d)| Some((a * c, b * d)), |a, b| a * b)
     }
 }
 
@@ -77,14 +127,20 @@ impl Div<Scalar> for Scalar {
     type Output = Scalar;
 
     fn div(self, rhs: Scalar) -> Self::Output {
-        match (self.0, rhs.0) {
-            (ScalarInner::Integer(a), ScalarInner::Integer(b)) if a % b == 0 => {
-                Scalar(ScalarInner::Integer(a / b))
-            }
-            _ => {
-                let (a, b) = (f64::from(self), f64::from(rhs));
-                Scalar(ScalarInner::Float(a / b))
-            }
+        self.combine(
+            rhs,
+            |(a, b), (c, d)| (c != 0).then_some((a * d, b * c)),
+            |a, b| a / b,
+        )
+    }
+}
+
+impl Display for Scalar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            ScalarInner::Integer(i) => write!(f, "{i}"),
+            ScalarInner::Rational(..) => write!(f, "{}", f64::from(*self)),
+            ScalarInner::Float(x) => write!(f, "{x}"),
         }
     }
 }
@@ -105,6 +161,7 @@ impl From<Scalar> for f64 {
     fn from(value: Scalar) -> f64 {
         match value.0 {
             ScalarInner::Integer(i) => i as f64,
+            ScalarInner::Rational(n, d) => n as f64 / d as f64,
             ScalarInner::Float(f) => f,
         }
     }
@@ -114,6 +171,7 @@ impl From<Scalar> for i64 {
     fn from(value: Scalar) -> i64 {
         match value.0 {
             ScalarInner::Integer(i) => i,
+            ScalarInner::Rational(n, d) => n / d,
             ScalarInner::Float(f) => f as i64,
         }
     }
@@ -137,10 +195,108 @@ impl TryFrom<Number> for Scalar {
 }
 
 impl Scalar {
+    /// The exact fraction `numerator / denominator`, in lowest terms.
+    pub fn rational(numerator: i64, denominator: i64) -> Result<Self, Error> {
+        if denominator == 0 {
+            return Err(Error::DivisionByZero);
+        }
+        fraction(numerator.into(), denominator.into()).ok_or(Error::Overflow)
+    }
+
     pub fn sqrt(self) -> Self {
+        Scalar(ScalarInner::Float(f64::from(self).sqrt()))
+    }
+
+    pub fn is_zero(self) -> bool {
+        f64::from(self) == 0.0
+    }
+
+    /// Reject infinite and NaN results so they can't leak into the drawing.
+    pub fn finite(self) -> Result<Self, Error> {
+        if f64::from(self).is_finite() {
+            Ok(self)
+        } else {
+            Err(Error::Overflow)
+        }
+    }
+
+    /// The nearest multiple of `spacing`, staying exact if both are and
+    /// the spacing is an integer.
+    pub fn snap(self, spacing: Scalar) -> Self {
+        let steps = (f64::from(self) / f64::from(spacing)).round();
+        match (self.0, spacing.0) {
+            (
+                ScalarInner::Integer(_) | ScalarInner::Rational(..),
+                ScalarInner::Integer(spacing),
+            ) => match (steps as i64).checked_mul(spacing) {
+                Some(snapped) => Scalar(ScalarInner::Integer(snapped)),
+                None => Scalar(ScalarInner::Float(steps * spacing as f64)),
+            },
+            _ => Scalar(ScalarInner::Float(steps * f64::from(spacing))),
+        }
+    }
+
+    /// Whether this is an integer, as opposed to a fraction or a float that
+    /// may happen to have an integer value.
+    pub fn is_integer(self) -> bool {
+        matches!(self.0, ScalarInner::Integer(_))
+    }
+
+    /// The same value as a float.
+    pub fn to_float(self) -> Self {
+        Scalar(ScalarInner::Float(self.into()))
+    }
+
+    /// The nearest integer, halfway cases away from zero, failing if it is
+    /// too large for one.
+    pub fn round(self) -> Result<Self, Error> {
         match self.0 {
-            ScalarInner::Integer(i) => Scalar(ScalarInner::Float((i as f64).sqrt())),
-            ScalarInner::Float(f) => Scalar(ScalarInner::Float(f.sqrt())),
+            ScalarInner::Integer(_) => Ok(self),
+            ScalarInner::Rational(n, d) => {
+                let (n, d) = (i128::from(n), i128::from(d));
+                // Denominators are positive, so the remainder has the sign of n
+                let (quotient, remainder) = (n / d, n % d);
+                let away = 2 * remainder.abs() >= d;
+                let rounded = quotient + if away { n.signum() } else { 0 };
+                i64::try_from(rounded)
+                    .map(Scalar::from)
+                    .map_err(|_| Error::Overflow)
+            }
+            ScalarInner::Float(f) => {
+                let rounded = f.round();
+                // i64::MAX rounds up to 2^63 as a float, which doesn't fit
+                if rounded >= -(i64::MIN as f64) || rounded < i64::MIN as f64 {
+                    return Err(Error::Overflow);
+                }
+                Ok(Scalar(ScalarInner::Integer(rounded as i64)))
+            }
+        }
+    }
+
+    /// Order two scalars, exactly if both are. Scalars are never NaN, so
+    /// any two of them are ordered.
+    pub fn compare(self, other: Scalar) -> Ordering {
+        match (self.0.ratio(), other.0.ratio()) {
+            // Denominators are positive, so cross-multiplying keeps the order
+            (Some((a, b)), Some((c, d))) => (a * d).cmp(&(c * b)),
+            _ => f64::from(self).total_cmp(&f64::from(other)),
+        }
+    }
+
+    pub(crate) fn key(self) -> ScalarKey {
+        match self.0 {
+            ScalarInner::Integer(i) => ScalarKey::Integer(i),
+            ScalarInner::Rational(n, d) => ScalarKey::Rational(n, d),
+            ScalarInner::Float(f) => ScalarKey::Float(f.to_bits()),
+        }
+    }
+
+    /// The exact integer value, if there is one.
+    pub fn as_integer(self) -> Option<i64> {
+        match self.0 {
+            ScalarInner::Integer(i) => Some(i),
+            ScalarInner::Float(f) if f.fract() == 0.0 => Some(f as i64),
+            ScalarInner::Rational(..) | ScalarInner::Float(_) => None,
         }
     }
 }
@@ -149,7 +305,7 @@ pub fn sqrt(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => x);
     match x {
         Value::Scalar(scalar) => {
-            if dbg!(f64::from(scalar)) >= 0.0 {
+            if f64::from(scalar) >= 0.0 {
                 Ok(Value::Scalar(scalar.sqrt()))
             } else {
                 Err(Error::NonRealResult)
@@ -158,8 +314,28 @@ pub fn sqrt(stack: &mut Stack) -> Result<Value, Error> {
         _ => Err(Error::TypeError),
     }
 }
+/// Round a scalar to the nearest integer, or turn a boolean into 1 or 0.
+pub fn to_int(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => x);
+    match x {
+        Value::Scalar(scalar) => Ok(Value::Scalar(scalar.round()?)),
+        Value::Bool(bool) => Ok(Value::Scalar(i64::from(bool).into())),
+        _ => Err(Error::TypeError),
+    }
+}
+
+pub fn to_float(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => x);
+    match x {
+        Value::Scalar(scalar) => Ok(Value::Scalar(scalar.to_float())),
+        _ => Err(Error::TypeError),
+    }
+}
+
 pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
-    runtime.define_fn("sqrt", sqrt)
+    runtime.define_fn("sqrt", sqrt);
+    runtime.define_fn("to_int", to_int);
+    runtime.define_fn("to_float", to_float);
 }
 
 #[cfg(test)]
@@ -180,4 +356,55 @@ mod test {
         assert_values_eq(sqrt(&mut stack), scalar(f64::sqrt(8.0)));
         assert_eq!(sqrt(&mut stack), Err(Error::NonRealResult));
     }
+
+    #[test]
+    fn test_conversions() {
+        let mut stack = dummy_stack([scalar(1e19), scalar(-2.5), scalar(3), scalar(2.4)]);
+        assert_eq!(to_int(&mut stack), Ok(scalar(2)));
+        assert_eq!(to_float(&mut stack), Ok(scalar(3.0)));
+        assert_eq!(to_int(&mut stack), Ok(scalar(-3)));
+        assert_eq!(to_int(&mut stack), Err(Error::Overflow));
+        assert_eq!(Scalar::from(i64::MAX as f64).round(), Err(Error::Overflow));
+    }
+
+    #[test]
+    fn test_rationals() {
+        let third = Scalar::from(10) / Scalar::from(3);
+        assert_eq!(third, ratio(10, 3));
+        assert_eq!(third + third + third, Scalar::from(10));
+        assert_eq!(third * Scalar::from(-3) / Scalar::from(5), Scalar::from(-2));
+        assert_eq!(third - Scalar::from(0.5), Scalar::from(10.0 / 3.0 - 0.5));
+        assert_eq!(Scalar::rational(4, -6), Ok(ratio(-2, 3)));
+        assert_eq!(Scalar::rational(1, 0), Err(Error::DivisionByZero));
+        assert_eq!(ratio(-5, 2).round(), Ok(Scalar::from(-3)));
+        assert_eq!(ratio(7, 3).round(), Ok(Scalar::from(2)));
+        assert_eq!(ratio(2, 3).to_string(), (2.0 / 3.0).to_string());
+        assert!(!ratio(1, 2).is_integer());
+
+        // Denominators too large to store fall back to floats
+        let tiny = ratio(1, i64::MAX);
+        assert_eq!(tiny * tiny, Scalar::from(f64::from(tiny) * f64::from(tiny)));
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Scalar::from(3).to_string(), "3");
+        assert_eq!(Scalar::from(3.0).to_string(), "3");
+        assert_eq!(Scalar::from(-0.25).to_string(), "-0.25");
+    }
+
+    #[test]
+    fn test_integer_overflow_promotes_to_float() {
+        let max = Scalar::from(i64::MAX);
+        assert_eq!(max + Scalar::from(1), Scalar::from(i64::MAX as f64 + 1.0));
+        assert_eq!(max * Scalar::from(2), Scalar::from(i64::MAX as f64 * 2.0));
+        assert_eq!(
+            Scalar::from(i64::MIN) / Scalar::from(-1),
+            Scalar::from(-(i64::MIN as f64))
+        );
+        assert_eq!(
+            (Scalar::from(1.0e308) * Scalar::from(10)).finite(),
+            Err(Error::Overflow)
+        );
+    }
 }
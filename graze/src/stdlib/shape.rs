@@ -0,0 +1,211 @@
+use std::f64::consts::TAU;
+
+use crate::{
+    reverse_pop,
+    runtime::{Error, Runtime, Stack, Value},
+};
+
+use super::{Path, Point};
+
+/// More corners than this are almost certainly a mistake.
+const MAX_CORNERS: i64 = 10_000;
+/// Segments approximating each rounded corner of `rrect`.
+const CORNER_SEGMENTS: usize = 8;
+
+fn corners(value: Value) -> Result<i64, Error> {
+    let Value::Scalar(n) = value else {
+        return Err(Error::TypeError);
+    };
+    match n.as_integer() {
+        Some(n) if (3..=MAX_CORNERS).contains(&n) => Ok(n),
+        _ => Err(Error::InvalidArgument),
+    }
+}
+
+fn length(value: Value) -> Result<f64, Error> {
+    let Value::Scalar(length) = value else {
+        return Err(Error::TypeError);
+    };
+    let length = f64::from(length);
+    if length < 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    Ok(length)
+}
+
+/// A closed path through `count` points around `center`, the first one
+/// in the direction of the x axis.
+fn around(center: Point, count: i64, radius: impl Fn(i64) -> f64) -> Value {
+    let (x, y) = (f64::from(center.x), f64::from(center.y));
+    let points = (0..count)
+        .map(|i| {
+            let (sin, cos) = (TAU * i as f64 / count as f64).sin_cos();
+            pnt(x + radius(i) * cos, y + radius(i) * sin)
+        })
+        .collect();
+    Value::Path(Path::closed(points).into())
+}
+
+/// A regular polygon with `n` corners on a circle.
+pub fn ngon(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => center, r, n);
+    let Value::Point(center) = center else {
+        return Err(Error::TypeError);
+    };
+    let (r, n) = (length(r)?, corners(n)?);
+    Ok(around(center, n, |_| r))
+}
+
+/// A star with `n` points on the outer radius and `n` dents on the inner.
+pub fn star(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => center, outer, inner, n);
+    let Value::Point(center) = center else {
+        return Err(Error::TypeError);
+    };
+    let (outer, inner, n) = (length(outer)?, length(inner)?, corners(n)?);
+    Ok(around(center, 2 * n, |i| {
+        if i % 2 == 0 {
+            outer
+        } else {
+            inner
+        }
+    }))
+}
+
+fn pnt(x: f64, y: f64) -> Point {
+    Point {
+        x: x.into(),
+        y: y.into(),
+    }
+}
+
+pub(super) fn rectangle(x: f64, y: f64, w: f64, h: f64) -> Value {
+    let points = vec![pnt(x, y), pnt(x + w, y), pnt(x + w, y + h), pnt(x, y + h)];
+    Value::Path(Path::closed(points).into())
+}
+
+/// A rectangle from its lower left corner and size.
+pub fn rect(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => corner, width, height);
+    let Value::Point(corner) = corner else {
+        return Err(Error::TypeError);
+    };
+    let (w, h) = (length(width)?, length(height)?);
+    Ok(rectangle(corner.x.into(), corner.y.into(), w, h))
+}
+
+/// A rectangle with corners rounded to radius `r`, at most half its size.
+pub fn rrect(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => corner, width, height, r);
+    let Value::Point(corner) = corner else {
+        return Err(Error::TypeError);
+    };
+    let (x, y) = (f64::from(corner.x), f64::from(corner.y));
+    let (w, h) = (length(width)?, length(height)?);
+    let r = length(r)?.min(w / 2.0).min(h / 2.0);
+    if r == 0.0 {
+        return Ok(rectangle(x, y, w, h));
+    }
+
+    // Quarter circles around the inner corners, counterclockwise from the
+    // lower right
+    let centers = [
+        (x + w - r, y + r),
+        (x + w - r, y + h - r),
+        (x + r, y + h - r),
+        (x + r, y + r),
+    ];
+    let points = centers
+        .into_iter()
+        .enumerate()
+        .flat_map(|(quarter, (cx, cy))| {
+            (0..=CORNER_SEGMENTS).map(move |step| {
+                let turn = (quarter as f64 - 1.0 + step as f64 / CORNER_SEGMENTS as f64) / 4.0;
+                let (sin, cos) = (TAU * turn).sin_cos();
+                pnt(cx + r * cos, cy + r * sin)
+            })
+        })
+        .collect();
+    Ok(Value::Path(Path::closed(points).into()))
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("ngon", ngon);
+    runtime.define_fn("star", star);
+    runtime.define_fn("rect", rect);
+    runtime.define_fn("rrect", rrect);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    fn corners_of(value: Result<Value, Error>) -> Vec<(f64, f64)> {
+        let Ok(Value::Path(path)) = value else {
+            panic!("expected a path, got {value:?}");
+        };
+        assert!(path.closed);
+        path.points
+            .iter()
+            .map(|point| {
+                let round = |value: f64| (value * 1e6).round() / 1e6 + 0.0;
+                (round(point.x.into()), round(point.y.into()))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_ngon() {
+        let mut stack = dummy_stack([point(1, 1), scalar(2), scalar(4)]);
+        assert_eq!(
+            corners_of(ngon(&mut stack)),
+            [(3.0, 1.0), (1.0, 3.0), (-1.0, 1.0), (1.0, -1.0)]
+        );
+
+        let mut stack = dummy_stack([point(0, 0), scalar(1), scalar(2)]);
+        assert_eq!(ngon(&mut stack), Err(Error::InvalidArgument));
+        let mut stack = dummy_stack([point(0, 0), scalar(1), scalar(3.5)]);
+        assert_eq!(ngon(&mut stack), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn test_star() {
+        let mut stack = dummy_stack([point(0, 0), scalar(2), scalar(1), scalar(5)]);
+        let corners = corners_of(star(&mut stack));
+        assert_eq!(corners.len(), 10);
+        assert_eq!(corners[0], (2.0, 0.0));
+        assert_eq!(corners[5], (-1.0, 0.0));
+
+        let mut stack = dummy_stack([point(0, 0), scalar(-2), scalar(1), scalar(5)]);
+        assert_eq!(star(&mut stack), Err(Error::InvalidArgument));
+    }
+    #[test]
+    fn test_rect() {
+        let mut stack = dummy_stack([point(1, 2), scalar(3), scalar(4)]);
+        assert_eq!(
+            corners_of(rect(&mut stack)),
+            [(1.0, 2.0), (4.0, 2.0), (4.0, 6.0), (1.0, 6.0)]
+        );
+
+        let mut stack = dummy_stack([point(1, 2), scalar(-3), scalar(4)]);
+        assert_eq!(rect(&mut stack), Err(Error::InvalidArgument));
+    }
+
+    #[test]
+    fn test_rrect() {
+        let mut stack = dummy_stack([point(0, 0), scalar(10), scalar(4), scalar(1)]);
+        let corners = corners_of(rrect(&mut stack));
+        assert_eq!(corners.len(), 4 * (CORNER_SEGMENTS + 1));
+        assert_eq!(corners[0], (9.0, 0.0));
+        assert_eq!(corners[CORNER_SEGMENTS], (10.0, 1.0));
+        assert_eq!(corners[CORNER_SEGMENTS / 2], (9.707107, 0.292893));
+
+        // The radius is limited to half the shorter side
+        let mut stack = dummy_stack([point(0, 0), scalar(10), scalar(4), scalar(5)]);
+        assert_eq!(corners_of(rrect(&mut stack))[0], (8.0, 0.0));
+
+        let mut stack = dummy_stack([point(0, 0), scalar(10), scalar(4), scalar(0)]);
+        assert_eq!(corners_of(rrect(&mut stack)).len(), 4);
+    }
+}
@@ -0,0 +1,43 @@
+use crate::{
+    output::LineStyle,
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+pub fn linestyle(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => name);
+    let Value::String(name) = name else {
+        return Err(Error::TypeError);
+    };
+    let style = LineStyle::from_name(&name).ok_or(Error::InvalidArgument)?;
+    context.set_line_style(style);
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("linestyle", linestyle);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_linestyle() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([
+            Value::String("dotted".into()),
+            scalar(1),
+            Value::String("center".into()),
+        ]);
+
+        assert_eq!(linestyle(&mut stack, &mut context), Ok(Value::Void));
+        assert_eq!(context.line_style(), LineStyle::Center);
+        assert_eq!(linestyle(&mut stack, &mut context), Err(Error::TypeError));
+        assert_eq!(
+            linestyle(&mut stack, &mut context),
+            Err(Error::InvalidArgument)
+        );
+    }
+}
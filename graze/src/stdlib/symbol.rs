@@ -0,0 +1,94 @@
+use crate::{
+    output::DrawCommand,
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::Transform;
+
+/// Draw a symbol defined with `#symbol`, its origin at a point. The
+/// options `rotate=` and `scale=` turn it around its origin and resize it.
+pub fn place(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => name, at);
+    let (Value::String(name), Value::Point(at)) = (name, at) else {
+        return Err(Error::TypeError);
+    };
+    let angle = match stack.option("rotate") {
+        Some(Value::Angle(angle)) => angle.radians(),
+        Some(_) => return Err(Error::TypeError),
+        None => 0.0,
+    };
+    let factor = match stack.option("scale") {
+        Some(Value::Scalar(factor)) => f64::from(factor),
+        Some(_) => return Err(Error::TypeError),
+        None => 1.0,
+    };
+    if factor == 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    if !context.has_symbol(&name) {
+        return Err(Error::UnknownSymbol(name));
+    }
+
+    let transform = Transform::translate(at.x.into(), at.y.into())
+        .then(Transform::rotate(angle))
+        .then(Transform::scale(factor, factor));
+    context.draw(DrawCommand::PlaceSymbol { name, transform });
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_stateful_fn("place", place);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::{output::Mm, util::test_helpers::*};
+
+    #[test]
+    fn test_place() {
+        let source = "translate 10 0\n\
+                      #symbol bolt\n\
+                      circle (pnt2 0 0) 2\n\
+                      #end\n\
+                      place \"bolt\" (pnt2 1 2) rotate=90deg scale=2";
+        let commands = run_script(source).unwrap().finish().commands;
+        assert_eq!(
+            commands[..3],
+            [
+                DrawCommand::BeginSymbol {
+                    name: "bolt".into()
+                },
+                DrawCommand::Circle {
+                    at: (Mm(0.0), Mm(0.0)),
+                    radius: Mm(2.0),
+                },
+                DrawCommand::EndSymbol,
+            ]
+        );
+        let DrawCommand::PlaceSymbol { name, transform } = &commands[3] else {
+            panic!("Expected the symbol to be placed");
+        };
+        assert_eq!(name, "bolt");
+        let (x, y) = transform.apply_point(1.0, 0.0);
+        assert!((x - 11.0).abs() < 1e-9 && (y - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_place_errors() {
+        assert!(matches!(
+            run_script("place \"bolt\" (pnt2 0 0)"),
+            Err(Error::UnknownSymbol(name)) if name == "bolt"
+        ));
+        assert!(matches!(
+            run_script("#symbol a\n#end\n#symbol a\n#end"),
+            Err(Error::SymbolRedefined(name)) if name == "a"
+        ));
+        // A symbol can't place itself before it is complete
+        assert!(matches!(
+            run_script("#symbol a\nplace \"a\" (pnt2 0 0)\n#end"),
+            Err(Error::UnknownSymbol(_))
+        ));
+    }
+}
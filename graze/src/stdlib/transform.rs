@@ -0,0 +1,556 @@
+use std::sync::Arc;
+
+use crate::{
+    output::{DrawCommand, Mm},
+    reverse_pop,
+    runtime::{Context, Error, Runtime, Stack, Value},
+};
+
+use super::{point::project, Angle, Path, Point, Vector};
+
+/// An affine transform `(x, y) -> (a*x + c*y + e, b*x + d*y + f)`,
+/// using the same coefficient layout as SVG's `matrix()`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Transform {
+    pub a: f64,
+    pub b: f64,
+    pub c: f64,
+    pub d: f64,
+    pub e: f64,
+    pub f: f64,
+}
+
+impl Default for Transform {
+    fn default() -> Self {
+        Self::IDENTITY
+    }
+}
+
+impl std::fmt::Display for Transform {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let Self {
+            a,
+            b,
+            c,
+            d,
+            e,
+            f: g,
+        } = self;
+        write!(f, "matrix({a}, {b}, {c}, {d}, {e}, {g})")
+    }
+}
+
+impl Transform {
+    pub const IDENTITY: Self = Self {
+        a: 1.0,
+        b: 0.0,
+        c: 0.0,
+        d: 1.0,
+        e: 0.0,
+        f: 0.0,
+    };
+
+    pub fn translate(x: f64, y: f64) -> Self {
+        Self {
+            e: x,
+            f: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// Counter-clockwise rotation by `angle` radians around the origin.
+    pub fn rotate(angle: f64) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            a: cos,
+            b: sin,
+            c: -sin,
+            d: cos,
+            ..Self::IDENTITY
+        }
+    }
+
+    pub fn scale(x: f64, y: f64) -> Self {
+        Self {
+            a: x,
+            d: y,
+            ..Self::IDENTITY
+        }
+    }
+
+    /// The transform that applies `inner` first and `self` second.
+    pub fn then(self, inner: Transform) -> Self {
+        Self {
+            a: self.a * inner.a + self.c * inner.b,
+            b: self.b * inner.a + self.d * inner.b,
+            c: self.a * inner.c + self.c * inner.d,
+            d: self.b * inner.c + self.d * inner.d,
+            e: self.a * inner.e + self.c * inner.f + self.e,
+            f: self.b * inner.e + self.d * inner.f + self.f,
+        }
+    }
+
+    pub fn apply_point(&self, x: f64, y: f64) -> (f64, f64) {
+        (
+            self.a * x + self.c * y + self.e,
+            self.b * x + self.d * y + self.f,
+        )
+    }
+
+    /// Like [`Transform::apply_point`], but leaving out the translation.
+    pub fn apply_vector(&self, x: f64, y: f64) -> (f64, f64) {
+        (self.a * x + self.c * y, self.b * x + self.d * y)
+    }
+
+    /// Scale a length by the transform's average stretch. Exact for
+    /// similarity transforms; circles under non-uniform scaling stay circles.
+    pub fn apply_length(&self, length: f64) -> f64 {
+        length * (self.a * self.d - self.b * self.c).abs().sqrt()
+    }
+
+    pub fn apply(&self, command: DrawCommand) -> DrawCommand {
+        let point = |(x, y): (Mm, Mm)| {
+            let (x, y) = self.apply_point(x.0, y.0);
+            (Mm(x), Mm(y))
+        };
+
+        match command {
+            DrawCommand::Line { from, to } => DrawCommand::Line {
+                from: point(from),
+                to: point(to),
+            },
+            DrawCommand::Circle { at, radius } => DrawCommand::Circle {
+                at: point(at),
+                radius: Mm(self.apply_length(radius.0)),
+            },
+            DrawCommand::Path { points, closed } => DrawCommand::Path {
+                points: points.into_iter().map(point).collect(),
+                closed,
+            },
+            DrawCommand::BeginClip { region } => DrawCommand::BeginClip {
+                region: region.into_iter().map(point).collect(),
+            },
+            DrawCommand::Text { at, text, size } => DrawCommand::Text {
+                at: point(at),
+                text,
+                size: Mm(self.apply_length(size.0)),
+            },
+            DrawCommand::Image {
+                at,
+                width,
+                height,
+                path,
+            } => {
+                // Images stay upright, over the bounding box of their moved corners
+                let ((x, y), (w, h)) = ((at.0 .0, at.1 .0), (width.0, height.0));
+                let corners = [(x, y), (x + w, y), (x, y + h), (x + w, y + h)];
+                let mut min = (f64::INFINITY, f64::INFINITY);
+                let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+                for (x, y) in corners.map(|(x, y)| self.apply_point(x, y)) {
+                    min = (min.0.min(x), min.1.min(y));
+                    max = (max.0.max(x), max.1.max(y));
+                }
+                DrawCommand::Image {
+                    at: (Mm(min.0), Mm(min.1)),
+                    width: Mm(max.0 - min.0),
+                    height: Mm(max.1 - min.1),
+                    path,
+                }
+            }
+            DrawCommand::PlaceSymbol { name, transform } => DrawCommand::PlaceSymbol {
+                name,
+                transform: self.then(transform),
+            },
+            other => other,
+        }
+    }
+}
+
+/// Move `value` and everything in it by `transform`.
+fn transformed(transform: &Transform, value: Value) -> Result<Value, Error> {
+    let point = |pnt: Point| {
+        let (x, y) = transform.apply_point(pnt.x.into(), pnt.y.into());
+        Point {
+            x: x.into(),
+            y: y.into(),
+        }
+    };
+    let vector = |vec: Vector| {
+        let (x, y) = transform.apply_vector(vec.x.into(), vec.y.into());
+        Vector {
+            x: x.into(),
+            y: y.into(),
+        }
+    };
+
+    let result = match value {
+        Value::Point(pnt) => Value::Point(point(pnt)),
+        Value::Vector(vec) => Value::Vector(vector(vec)),
+        Value::Line(start, vec) => Value::Line(point(start), vector(vec)),
+        Value::Circle(center, radius) => {
+            Value::Circle(point(center), transform.apply_length(radius.into()).into())
+        }
+        Value::Path(path) => Value::Path(Arc::new(Path {
+            points: path.points.iter().map(|&pnt| point(pnt)).collect(),
+            closed: path.closed,
+        })),
+        Value::List(items) => Value::List(Arc::new(
+            items
+                .iter()
+                .map(|item| transformed(transform, item.clone()))
+                .collect::<Result<_, _>>()?,
+        )),
+        Value::Group(group) => Value::Group(Arc::new(
+            group.map(|item| transformed(transform, item.clone()))?,
+        )),
+        _ => return Err(Error::TypeError),
+    };
+    Ok(result)
+}
+
+/// Reflect `value` across the infinite line through `(origin, dir)`.
+fn reflect(value: Value, origin: Point, dir: Vector) -> Result<Value, Error> {
+    let point = |pnt: Point| -> Result<Point, Error> {
+        let foot = project(pnt, origin, dir)?;
+        Ok(foot + (foot - pnt))
+    };
+    let vector = |vec: Vector| -> Result<Vector, Error> {
+        let tip = point(origin + vec)?;
+        Ok(tip - point(origin)?)
+    };
+
+    let result = match value {
+        Value::Point(pnt) => Value::Point(point(pnt)?),
+        Value::Vector(vec) => Value::Vector(vector(vec)?),
+        Value::Line(start, vec) => Value::Line(point(start)?, vector(vec)?),
+        Value::Circle(center, radius) => Value::Circle(point(center)?, radius),
+        Value::Path(path) => Value::Path(Arc::new(Path {
+            points: path
+                .points
+                .iter()
+                .map(|&pnt| point(pnt))
+                .collect::<Result<_, _>>()?,
+            closed: path.closed,
+        })),
+        Value::List(items) => Value::List(Arc::new(
+            items
+                .iter()
+                .map(|item| reflect(item.clone(), origin, dir))
+                .collect::<Result<_, _>>()?,
+        )),
+        Value::Group(group) => Value::Group(Arc::new(
+            group.map(|item| reflect(item.clone(), origin, dir))?,
+        )),
+        _ => return Err(Error::TypeError),
+    };
+    Ok(result)
+}
+
+pub fn mirror(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => value, axis);
+    let Value::Line(origin, dir) = axis else {
+        return Err(Error::TypeError);
+    };
+    reflect(value, origin, dir)
+}
+
+/// A vector, or x and y scalars, from the top of the stack.
+fn offset(stack: &mut Stack) -> Result<Vector, Error> {
+    reverse_pop!(stack => offset);
+    match offset {
+        Value::Vector(vec) => Ok(vec),
+        Value::Scalar(y) => {
+            reverse_pop!(stack => x);
+            let Value::Scalar(x) = x else {
+                return Err(Error::TypeError);
+            };
+            Ok(Vector { x, y })
+        }
+        _ => Err(Error::TypeError),
+    }
+}
+
+/// A uniform factor or a vector of factors along x and y.
+fn factors(value: Value) -> Result<(f64, f64), Error> {
+    match value {
+        Value::Scalar(s) => Ok((s.into(), s.into())),
+        Value::Vector(vec) => Ok((vec.x.into(), vec.y.into())),
+        _ => Err(Error::TypeError),
+    }
+}
+
+pub fn tf_translate(stack: &mut Stack) -> Result<Value, Error> {
+    let offset = offset(stack)?;
+    Ok(Value::Transform(Transform::translate(
+        offset.x.into(),
+        offset.y.into(),
+    )))
+}
+
+pub fn tf_rotate(angle: Angle) -> Result<Transform, Error> {
+    Ok(Transform::rotate(angle.radians()))
+}
+
+pub fn tf_scale(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => factor);
+    let (x, y) = factors(factor)?;
+    Ok(Value::Transform(Transform::scale(x, y)))
+}
+
+/// The transform doing `first`, then `second`.
+pub fn compose(first: Transform, second: Transform) -> Result<Transform, Error> {
+    Ok(second.then(first))
+}
+
+/// A local coordinate frame with its origin at `origin` and its x axis
+/// along `xaxis`, as the transform from local to outer coordinates. Only
+/// the direction of `xaxis` matters; the y axis is a quarter turn
+/// counterclockwise from it.
+pub fn frame_at(origin: Point, xaxis: Vector) -> Result<Transform, Error> {
+    let (x, y) = (f64::from(xaxis.x), f64::from(xaxis.y));
+    let length = x.hypot(y);
+    if length == 0.0 {
+        return Err(Error::InvalidArgument);
+    }
+    let (cos, sin) = (x / length, y / length);
+    Ok(Transform {
+        a: cos,
+        b: sin,
+        c: -sin,
+        d: cos,
+        e: origin.x.into(),
+        f: origin.y.into(),
+    })
+}
+
+/// Draw everything up to the matching `endframe` in a local frame.
+pub fn in_frame(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => frame);
+    let Value::Transform(frame) = frame else {
+        return Err(Error::TypeError);
+    };
+    context.begin_frame(frame);
+    Ok(Value::Void)
+}
+
+pub fn endframe(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    context.end_frame()?;
+    Ok(Value::Void)
+}
+
+/// Move a shape, or every shape in a list, by a transform value.
+pub fn apply(stack: &mut Stack) -> Result<Value, Error> {
+    reverse_pop!(stack => transform, value);
+    let Value::Transform(transform) = transform else {
+        return Err(Error::TypeError);
+    };
+    transformed(&transform, value)
+}
+
+/// Apply a transform value to everything drawn from now on, like the
+/// builtins it was made with would.
+pub fn transform(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => transform);
+    let Value::Transform(transform) = transform else {
+        return Err(Error::TypeError);
+    };
+    context.compose_transform(transform);
+    Ok(Value::Void)
+}
+
+pub fn push_tf(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    context.push_transform();
+    Ok(Value::Void)
+}
+
+pub fn pop_tf(_: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    context.pop_transform()?;
+    Ok(Value::Void)
+}
+
+pub fn translate(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    let offset = offset(stack)?;
+    context.compose_transform(Transform::translate(offset.x.into(), offset.y.into()));
+    Ok(Value::Void)
+}
+
+pub fn rotate(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => angle);
+    let Value::Angle(angle) = angle else {
+        return Err(Error::TypeError);
+    };
+    context.compose_transform(Transform::rotate(angle.radians()));
+    Ok(Value::Void)
+}
+
+pub fn scale(stack: &mut Stack, context: &mut Context) -> Result<Value, Error> {
+    reverse_pop!(stack => factor);
+    let (x, y) = factors(factor)?;
+    context.compose_transform(Transform::scale(x, y));
+    Ok(Value::Void)
+}
+
+pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
+    runtime.define_fn("mirror", mirror);
+    runtime.define_stateful_fn("push_tf", push_tf);
+    runtime.define_stateful_fn("pop_tf", pop_tf);
+    runtime.define_stateful_fn("translate", translate);
+    runtime.define_stateful_fn("rotate", rotate);
+    runtime.define_stateful_fn("scale", scale);
+    runtime.define_fn("tf_translate", tf_translate);
+    runtime.define_native("tf_rotate", tf_rotate);
+    runtime.define_fn("tf_scale", tf_scale);
+    runtime.define_native("compose", compose);
+    runtime.define_fn("apply", apply);
+    runtime.define_stateful_fn("transform", transform);
+    runtime.define_native("frame_at", frame_at);
+    runtime.define_stateful_fn("in_frame", in_frame);
+    runtime.define_stateful_fn("endframe", endframe);
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::util::test_helpers::*;
+
+    #[test]
+    fn test_mirror() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(1, 1), point(0, 0),
+            circle_value(2, 0, 1), line(0, 0, 1, 1),
+            line(1, 0, 1, 0), line(0, 0, 0, 1),
+            vector(1, 2), line(3, 3, 1, 0),
+            point(2, 3), line(0, 0, 1, 0),
+        ]);
+
+        assert_values_eq(mirror(&mut stack), point(2.0, -3.0));
+        assert_values_eq(mirror(&mut stack), vector(1.0, -2.0));
+        assert_values_eq(mirror(&mut stack), line(-1.0, 0.0, -1.0, 0.0));
+        assert_values_eq(mirror(&mut stack), circle_value(0.0, 2.0, 1));
+        assert_eq!(mirror(&mut stack), Err(Error::TypeError));
+    }
+
+    #[test]
+    fn test_mirror_list() {
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            list_of([point(1, 1), list_of([point(2, 2)])]), line(0, 0, 1, 0),
+        ]);
+
+        assert_values_eq(
+            mirror(&mut stack),
+            list_of([point(1.0, -1.0), list_of([point(2.0, -2.0)])]),
+        );
+    }
+
+    #[test]
+    fn test_transform_stack() {
+        let mut context = Context::default();
+        #[rustfmt::skip]
+        let mut stack = dummy_stack([
+            point(0, 0),
+            vector(2, 1),
+            scalar(3), scalar(4),
+        ]);
+
+        assert_eq!(push_tf(&mut stack, &mut context), Ok(Value::Void));
+        translate(&mut stack, &mut context).unwrap();
+        scale(&mut stack, &mut context).unwrap();
+        assert_eq!(context.transform().apply_point(1.0, 1.0), (5.0, 5.0));
+        assert_eq!(translate(&mut stack, &mut context), Err(Error::TypeError));
+
+        pop_tf(&mut stack, &mut context).unwrap();
+        assert_eq!(context.transform(), Transform::IDENTITY);
+        assert_eq!(
+            pop_tf(&mut stack, &mut context),
+            Err(Error::TransformStackUnderflow)
+        );
+    }
+
+    #[test]
+    fn test_transform_values() {
+        let source = "#let t (compose (tf_scale 2) (tf_translate 1 0))\n\
+            #let p (apply $t (pnt2 1 1))\n#let v (apply $t (vec2 1 1))\n\
+            #let c (apply $t (circle (pnt2 0 0) 1))\n\
+            #let xs (list (pnt2 0 0))\n#let l (apply $t $xs)\n\
+            #let r (apply (tf_rotate 90deg) (pnt2 1 0))";
+        let runtime = run_script(source).unwrap();
+        let get = |name| runtime.get_variable(name).cloned().unwrap();
+        assert_eq!(
+            get("t"),
+            Value::Transform(Transform {
+                a: 2.0,
+                d: 2.0,
+                e: 1.0,
+                ..Transform::IDENTITY
+            })
+        );
+        assert_eq!(get("p"), point(3.0, 2.0));
+        assert_eq!(get("v"), vector(2.0, 2.0));
+        assert_eq!(get("c"), circle_value(1.0, 0.0, 2.0));
+        assert_eq!(get("l"), list_of([point(1.0, 0.0)]));
+        let rotated = get("r").as_point().unwrap();
+        assert!(f64::from(rotated.x).abs() < 1e-9 && f64::from(rotated.y) > 0.999);
+
+        assert!(matches!(
+            run_script("apply (pnt2 1 1) (tf_scale 2)"),
+            Err(Error::TypeError)
+        ));
+    }
+
+    #[test]
+    fn test_transform() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([
+            Value::Transform(Transform::translate(1.0, 2.0)),
+            point(0, 0),
+        ]);
+
+        assert_eq!(transform(&mut stack, &mut context), Err(Error::TypeError));
+        transform(&mut stack, &mut context).unwrap();
+        assert_eq!(context.transform().apply_point(1.0, 1.0), (2.0, 3.0));
+    }
+
+    #[test]
+    fn test_frames() {
+        let at = |x, y| point(x, y).as_point().unwrap();
+        let along = |x, y| vector(x, y).as_vector().unwrap();
+        let frame = frame_at(at(10, 0), along(0, 3)).unwrap();
+        let (x, y) = frame.apply_point(1.0, 2.0);
+        assert!((x - 8.0).abs() < 1e-9 && (y - 1.0).abs() < 1e-9);
+        assert_eq!(frame_at(at(0, 0), along(0, 0)), Err(Error::InvalidArgument));
+
+        let source = "translate 0 5
+                      in_frame (frame_at (pnt2 10 0) (vec2 0 1))
+                      line (pnt2 0 0) (vec2 2 0)
+                      endframe
+                      line (pnt2 0 0) (vec2 2 0)";
+        let commands = run_script(source).unwrap().finish().commands;
+        let ends = commands
+            .iter()
+            .map(|command| match command {
+                DrawCommand::Line { to, .. } => (to.0 .0.round(), to.1 .0.round()),
+                _ => panic!("Expected only lines"),
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(ends, [(10.0, 7.0), (2.0, 5.0)]);
+
+        assert!(matches!(run_script("endframe"), Err(Error::FrameUnderflow)));
+    }
+
+    #[test]
+    fn test_rotate() {
+        let mut context = Context::default();
+        let mut stack = dummy_stack([
+            scalar(std::f64::consts::FRAC_PI_2),
+            Value::Angle(Angle::from_degrees(90.0)),
+        ]);
+
+        rotate(&mut stack, &mut context).unwrap();
+        assert_eq!(rotate(&mut stack, &mut context), Err(Error::TypeError));
+        let (x, y) = context.transform().apply_point(1.0, 0.0);
+        assert!(x.abs() < 1e-9 && (y - 1.0).abs() < 1e-9);
+    }
+}
@@ -8,11 +8,18 @@ use crate::{
 use super::Scalar;
 
 #[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Vector {
     pub x: Scalar,
     pub y: Scalar,
 }
 
+impl std::fmt::Display for Vector {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<{}, {}>", self.x, self.y)
+    }
+}
+
 impl Add for Vector {
     type Output = Vector;
 
@@ -57,30 +64,22 @@ impl Div<Scalar> for Vector {
     }
 }
 
-pub fn dot(stack: &mut Stack) -> Result<Value, Error> {
-    reverse_pop!(stack => lhs, rhs);
-
-    let (Value::Vector(lhs), Value::Vector(rhs)) = (lhs, rhs) else {
-        return Err(Error::TypeError);
-    };
-
-    Ok(Value::Scalar(lhs.x * rhs.x + lhs.y * rhs.y))
+pub fn dot(lhs: Vector, rhs: Vector) -> Result<Scalar, Error> {
+    Ok(lhs.x * rhs.x + lhs.y * rhs.y)
 }
 
 pub fn vec2(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => x, y);
-    let result = match (x, y) {
-        (Value::Scalar(x), Value::Scalar(y)) => Value::Vector(Vector { x, y }),
-
-        _ => return Err(Error::TypeError),
+    let (Value::Scalar(x), Value::Scalar(y)) = (x, y) else {
+        return Err(Error::TypeError);
     };
-
-    Ok(result)
+    Ok(stack.snapped(Value::Vector(Vector { x, y })))
 }
 
 pub fn line(stack: &mut Stack) -> Result<Value, Error> {
     reverse_pop!(stack => p1, p2);
-    let result = match (p1, p2) {
+    // Snapping the ends rather than the line keeps its end on the grid
+    let result = match (stack.snapped(p1), stack.snapped(p2)) {
         (Value::Point(p1), Value::Vector(v)) => Value::Line(p1, v),
         (Value::Point(p1), Value::Point(p2)) => Value::Line(p1, p2 - p1),
 
@@ -91,7 +90,7 @@ pub fn line(stack: &mut Stack) -> Result<Value, Error> {
 }
 
 pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
-    runtime.define_fn("dot", dot);
+    runtime.define_native("dot", dot);
     runtime.define_fn("vec2", vec2);
     runtime.define_fn("line", line);
 }
@@ -99,7 +98,7 @@ pub fn register<Backend>(runtime: &mut Runtime<Backend>) {
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::util::test_helpers::*;
+    use crate::{native::NativeFn, util::test_helpers::*};
 
     #[test]
     fn test_dot() {
@@ -109,7 +108,7 @@ mod test {
             vector(1, 2), vector(3, 4),
         ]);
 
-        assert_values_eq(dot(&mut stack), scalar(11));
+        assert_values_eq(dot.run(&mut stack), scalar(11));
     }
 
     #[test]
@@ -0,0 +1,201 @@
+//! Golden tests for scripts: run a script, then compare what it drew
+//! against a file checked in next to the test.
+//!
+//! Set `GRAZE_UPDATE_GOLDEN=1` to write the current output to the golden
+//! files instead of comparing, then review the changes before committing.
+
+use std::{fmt::Write, path::Path};
+
+use thiserror::Error;
+
+use crate::{
+    ast::{parse_file, Error as ParseError},
+    output::{DrawBuffer, DrawCommand},
+    runtime::{Error, Runtime},
+    token::StringTokenizer,
+};
+
+/// Environment variable that makes [`assert_golden`] update golden files.
+pub const UPDATE_VARIABLE: &str = "GRAZE_UPDATE_GOLDEN";
+/// Unchanged lines shown around each change in a diff.
+const CONTEXT: usize = 3;
+
+/// A backend that keeps every command it receives.
+#[derive(Debug, Default)]
+pub struct Recorder {
+    pub commands: Vec<DrawCommand>,
+    pub pages: usize,
+}
+
+impl DrawBuffer for Recorder {
+    fn reset(&mut self) {
+        self.commands.clear();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        self.commands.push(command);
+    }
+
+    fn new_page(&mut self) {
+        self.pages += 1;
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// Writes each command on its own line, and `NewPage` between pages.
+#[derive(Default)]
+struct Transcript(String);
+
+impl DrawBuffer for Transcript {
+    fn reset(&mut self) {
+        self.0.clear();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        writeln!(self.0, "{command:?}").expect("Writing to a String never fails");
+    }
+
+    fn new_page(&mut self) {
+        self.0.push_str("NewPage\n");
+    }
+
+    fn flush(&mut self) {}
+}
+
+#[derive(Debug, Error)]
+pub enum ScriptError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Runtime(#[from] Error),
+}
+
+/// Run `source` and list the commands it draws, one per line.
+pub fn transcript(source: &str) -> Result<String, ScriptError> {
+    let program = parse_file(&mut StringTokenizer::new(&source))?;
+    let mut runtime = Runtime::<Transcript>::default();
+    runtime.execute(program)?;
+    Ok(runtime.finish().0)
+}
+
+/// Compare the lines of `expected` and `actual`, marking lines only in
+/// `expected` with `-` and lines only in `actual` with `+`. Long runs of
+/// unchanged lines are cut short.
+pub fn diff(expected: &str, actual: &str) -> String {
+    let (old, new) = (
+        expected.lines().collect::<Vec<_>>(),
+        actual.lines().collect::<Vec<_>>(),
+    );
+
+    // common[i][j] is the length of the longest common subsequence of
+    // old[i..] and new[j..]
+    let mut common = vec![vec![0; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            common[i][j] = if old[i] == new[j] {
+                common[i + 1][j + 1] + 1
+            } else {
+                common[i + 1][j].max(common[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push((' ', old[i]));
+            (i, j) = (i + 1, j + 1);
+        } else if i < old.len() && (j == new.len() || common[i + 1][j] >= common[i][j + 1]) {
+            lines.push(('-', old[i]));
+            i += 1;
+        } else {
+            lines.push(('+', new[j]));
+            j += 1;
+        }
+    }
+
+    let changed = |index: usize| {
+        let nearby = index.saturating_sub(CONTEXT)..(index + CONTEXT + 1).min(lines.len());
+        lines[nearby].iter().any(|(mark, _)| *mark != ' ')
+    };
+    let mut out = String::new();
+    let mut skipping = false;
+    for (index, (mark, line)) in lines.iter().enumerate() {
+        if changed(index) {
+            writeln!(out, "{mark} {line}").expect("Writing to a String never fails");
+            skipping = false;
+        } else if !skipping {
+            out.push_str("  ...\n");
+            skipping = true;
+        }
+    }
+    out
+}
+
+/// Check that `actual` matches the golden file at `path`, or overwrite the
+/// file if [`UPDATE_VARIABLE`] is set.
+#[track_caller]
+pub fn assert_golden(actual: &str, path: impl AsRef<Path>) {
+    let path = path.as_ref();
+    if std::env::var_os(UPDATE_VARIABLE).is_some() {
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent).expect("Could not create the golden directory");
+        }
+        std::fs::write(path, actual).expect("Could not write the golden file");
+        return;
+    }
+
+    let expected = std::fs::read_to_string(path).unwrap_or_else(|err| {
+        panic!(
+            "Could not read golden file {}: {err}\nRun with {UPDATE_VARIABLE}=1 to create it",
+            path.display()
+        )
+    });
+    if expected != actual {
+        panic!(
+            "Output differs from golden file {}:\n{}Run with {UPDATE_VARIABLE}=1 to accept it",
+            path.display(),
+            diff(&expected, actual)
+        );
+    }
+}
+
+/// Check the commands `source` draws against the golden file at `path`.
+#[track_caller]
+pub fn assert_commands_golden(source: &str, path: impl AsRef<Path>) {
+    match transcript(source) {
+        Ok(transcript) => assert_golden(&transcript, path),
+        Err(err) => panic!("Script failed: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_transcript() {
+        let commands = transcript("circle (pnt2 1 2) 3\n#page\n#screen 10 10").unwrap();
+        assert_eq!(
+            commands,
+            "Circle { at: (Mm(1.0), Mm(2.0)), radius: Mm(3.0) }
+NewPage
+Resize { x: Mm(10.0), y: Mm(10.0) }
+"
+        );
+        assert!(matches!(transcript("nope"), Err(ScriptError::Runtime(_))));
+    }
+
+    #[test]
+    fn test_diff() {
+        let expected = "a\nb\nc\nd\ne\nf\ng\nh\ni\n";
+        let actual = "a\nb\nc\nd\ne\nf\nG\nh\ni\nj\n";
+        assert_eq!(
+            diff(expected, actual),
+            "  ...\n  d\n  e\n  f\n- g\n+ G\n  h\n  i\n+ j\n"
+        );
+        assert_eq!(diff("same\n", "same\n"), "  ...\n");
+    }
+}
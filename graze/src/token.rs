@@ -1,17 +1,58 @@
-use std::{iter::Peekable, str::Chars};
+use std::{iter::Peekable, ops::Range, str::Chars};
 
 use smol_str::{SmolStr, SmolStrBuilder};
 use thiserror::Error;
 
 pub trait TokenSource {
     fn read_token(&mut self) -> Result<Token, Error>;
-    fn peek_token(&self) -> Result<Token, Error>;
+    /// Look at the next token without consuming it.
+    fn peek_token(&mut self) -> Result<&Token, Error>;
+    /// The end of the last token that was read.
     fn position(&self) -> Position;
+    /// Byte offset of [`TokenSource::position`].
+    fn offset(&self) -> usize;
+    /// Comments and blank lines skipped so far, in source order.
+    fn take_trivia(&mut self) -> Vec<Trivia> {
+        Vec::new()
+    }
+    /// Roughly how many lines are left, to make room for their instructions.
+    fn lines_hint(&self) -> usize {
+        0
+    }
+}
+
+/// Source text that doesn't change what a program does, but that a
+/// formatter has to reproduce.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Trivia {
+    /// A `//` comment running to the end of `line`. Trailing comments
+    /// follow code on the same line.
+    Comment {
+        line: usize,
+        text: SmolStr,
+        trailing: bool,
+    },
+    /// A line that is empty or only whitespace.
+    BlankLine(usize),
 }
 
+impl Trivia {
+    /// Zero-based line number.
+    pub fn line(&self) -> usize {
+        match self {
+            Trivia::Comment { line, .. } | Trivia::BlankLine(line) => *line,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
 pub struct Token {
     pub payload: Payload,
+    /// Where the token ends.
     pub position: Position,
+    /// The bytes of the source the token was read from.
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, PartialEq)]
@@ -20,14 +61,22 @@ pub enum Payload {
     Name(SmolStr),
     /// Name prefixed with $ for variable access
     Variable(SmolStr),
+    /// Name directly followed by =, naming an option, e.g. `size=`
+    Key(SmolStr),
     /// Builtins
     Keyword(Keyword),
     /// Number literal
     LitNumber(Number),
+    /// Number literal with a unit suffix, e.g. `2.5cm`
+    LitQuantity(Number, Unit),
+    /// Double-quoted string literal
+    LitString(SmolStr),
     /// =>
     Pipe,
     /// ;
     Concat,
+    /// + - * /
+    Operator(Operator),
     /// (
     ParenL,
     /// )
@@ -42,20 +91,178 @@ pub enum Payload {
 #[derive(Debug, Clone, Copy, PartialEq)]
 pub enum Keyword {
     Let,
+    Const,
+    Param,
     Screen,
+    View,
+    Page,
+    Origin,
+    YDir,
+    Title,
+    Desc,
+    Meta,
+    Scope,
+    For,
+    Symbol,
+    End,
+    If,
+    Repeat,
+    Def,
+}
+
+impl Keyword {
+    /// Every keyword along with the name it is written as after a `#`.
+    pub const ALL: &'static [(&'static str, Keyword)] = &[
+        ("let", Keyword::Let),
+        ("const", Keyword::Const),
+        ("param", Keyword::Param),
+        ("screen", Keyword::Screen),
+        ("view", Keyword::View),
+        ("page", Keyword::Page),
+        ("origin", Keyword::Origin),
+        ("ydir", Keyword::YDir),
+        ("title", Keyword::Title),
+        ("desc", Keyword::Desc),
+        ("meta", Keyword::Meta),
+        ("scope", Keyword::Scope),
+        ("for", Keyword::For),
+        ("symbol", Keyword::Symbol),
+        ("end", Keyword::End),
+        ("if", Keyword::If),
+        ("repeat", Keyword::Repeat),
+        ("def", Keyword::Def),
+    ];
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .find(|(keyword, _)| *keyword == name)
+            .map(|(_, keyword)| *keyword)
+    }
+
+    pub fn name(self) -> &'static str {
+        Self::ALL
+            .iter()
+            .find(|(_, keyword)| *keyword == self)
+            .map(|(name, _)| *name)
+            .expect("Every keyword is listed in Keyword::ALL")
+    }
+
+    /// Reserved for future use, the parser rejects these for now.
+    pub fn is_reserved(self) -> bool {
+        matches!(self, Keyword::If | Keyword::Repeat | Keyword::Def)
+    }
 }
 
+impl std::fmt::Display for Keyword {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "#{}", self.name())
+    }
+}
+
+/// Infix arithmetic, only allowed inside parentheses.
 #[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Operator {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+impl Operator {
+    pub const ALL: [Operator; 4] = [Operator::Add, Operator::Sub, Operator::Mul, Operator::Div];
+
+    /// Higher binds tighter.
+    pub fn precedence(self) -> u8 {
+        match self {
+            Operator::Add | Operator::Sub => 1,
+            Operator::Mul | Operator::Div => 2,
+        }
+    }
+
+    /// The builtin this operator is sugar for.
+    pub fn function(self) -> &'static str {
+        match self {
+            Operator::Add => "add",
+            Operator::Sub => "sub",
+            Operator::Mul => "mul",
+            Operator::Div => "div",
+        }
+    }
+
+    pub fn symbol(self) -> char {
+        match self {
+            Operator::Add => '+',
+            Operator::Sub => '-',
+            Operator::Mul => '*',
+            Operator::Div => '/',
+        }
+    }
+}
+
+/// A unit that can follow a number literal.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Unit {
+    Millimeter,
+    Centimeter,
+    Inch,
+    Point,
+    Degree,
+    Radian,
+}
+
+impl Unit {
+    pub fn from_suffix(suffix: &str) -> Option<Self> {
+        match suffix {
+            "mm" => Some(Unit::Millimeter),
+            "cm" => Some(Unit::Centimeter),
+            "in" => Some(Unit::Inch),
+            "pt" => Some(Unit::Point),
+            "deg" => Some(Unit::Degree),
+            "rad" => Some(Unit::Radian),
+            _ => None,
+        }
+    }
+
+    pub fn suffix(self) -> &'static str {
+        match self {
+            Unit::Millimeter => "mm",
+            Unit::Centimeter => "cm",
+            Unit::Inch => "in",
+            Unit::Point => "pt",
+            Unit::Degree => "deg",
+            Unit::Radian => "rad",
+        }
+    }
+
+    /// How long one of this unit is in millimeters, the unit drawings use.
+    /// `None` for angles.
+    pub fn millimeters(self) -> Option<Number> {
+        match self {
+            Unit::Millimeter => Some(Number::Integer(1)),
+            Unit::Centimeter => Some(Number::Integer(10)),
+            Unit::Inch => Some(Number::Float(25.4)),
+            Unit::Point => Some(Number::Float(25.4 / 72.0)),
+            Unit::Degree | Unit::Radian => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Number {
     Integer(u64),
     Float(f64),
 }
 
-#[derive(Debug, Clone, Copy, Error)]
+#[derive(Debug, Clone, Error)]
 #[error("Error tokenizing input at {at}: {kind:?}")]
 pub struct Error {
     pub kind: ErrorKind,
     pub at: Position,
+    /// The text read while lexing the invalid token.
+    pub span: Span,
 }
 
 #[derive(Debug, Clone, Copy, Error, PartialEq, Eq)]
@@ -70,51 +277,113 @@ pub enum ErrorKind {
     InvalidPipe,
     #[error("Expected a newline after a '!' to make it a 'void' token.")]
     ExpectedNewlineAfterBang,
-    #[error("'let' must not be qualified with a $ or #.")]
+    #[error("Unknown keyword after '#'")]
     InvalidKeyword,
+    #[error("String literal is missing its closing '\"'")]
+    UnterminatedString,
+    #[error("Unknown escape sequence in string literal")]
+    InvalidEscape,
+    #[error("Unknown unit, expected one of mm, cm, in, pt, deg or rad")]
+    UnknownUnit,
+}
+
+impl ErrorKind {
+    /// A stable identifier for this kind of error, e.g. to look it up in
+    /// the documentation. Lexer errors are numbered from E0001.
+    pub fn code(self) -> &'static str {
+        match self {
+            ErrorKind::InvalidCRLFSequence => "E0001",
+            ErrorKind::ExpectedIdentifier => "E0002",
+            ErrorKind::InvalidLiteral => "E0003",
+            ErrorKind::InvalidPipe => "E0004",
+            ErrorKind::ExpectedNewlineAfterBang => "E0005",
+            ErrorKind::InvalidKeyword => "E0006",
+            ErrorKind::UnterminatedString => "E0007",
+            ErrorKind::InvalidEscape => "E0008",
+            ErrorKind::UnknownUnit => "E0009",
+        }
+    }
 }
 
 #[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Position {
     line: usize,
     column: usize,
 }
 
+impl Position {
+    /// Zero-based line number.
+    pub fn line(&self) -> usize {
+        self.line
+    }
+
+    /// Zero-based column, counted in characters.
+    pub fn column(&self) -> usize {
+        self.column
+    }
+
+    pub fn is_line_start(&self) -> bool {
+        self.column == 0
+    }
+
+    /// The same column on another line.
+    pub(crate) fn with_line(self, line: usize) -> Self {
+        Self { line, ..self }
+    }
+}
+
 impl std::fmt::Display for Position {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "({},{})", self.line, self.column)
     }
 }
 
-#[derive(Clone)]
 pub struct StringTokenizer<'s> {
+    source: &'s str,
     chars: Peekable<Chars<'s>>,
     position: Position,
+    /// Byte offset of `position`.
+    offset: usize,
+    /// Byte offset where the token being lexed starts.
+    token_start: usize,
+    /// A token that was peeked but not read yet, along with the
+    /// position and offset before it.
+    lookahead: Option<(Position, usize, Result<Token, Error>)>,
+    trivia: Vec<Trivia>,
+    /// Whether anything but whitespace was seen on the current line.
+    line_has_content: bool,
 }
 
 impl<'s> StringTokenizer<'s> {
     #[must_use]
     pub fn new(slice: &'s impl AsRef<str>) -> Self {
+        let source = slice.as_ref();
         Self {
-            chars: slice.as_ref().chars().peekable(),
+            source,
+            chars: source.chars().peekable(),
             position: Default::default(),
+            offset: 0,
+            token_start: 0,
+            lookahead: None,
+            trivia: Vec::new(),
+            line_has_content: false,
         }
     }
 
     /// Consume characters while the condition is true.
     /// Use this instead of `Iterator::take_while` because it
     /// doesn't consume the next character. By contrast,
-    fn take_while(&mut self, mut condition: impl FnMut(&char) -> bool) -> SmolStr {
-        let mut result = SmolStrBuilder::new();
+    fn take_while(&mut self, mut condition: impl FnMut(&char) -> bool) -> &'s str {
+        let start = self.offset;
         while let Some(next) = self.chars.peek().copied() {
             if condition(&next) {
-                result.push(next);
                 self.advance();
             } else {
                 break;
             }
         }
-        result.finish()
+        &self.source[start..self.offset]
     }
 
     fn advance(&mut self) -> Option<char> {
@@ -130,15 +399,18 @@ impl<'s> StringTokenizer<'s> {
             }
             None => {}
         }
+        self.offset += next.map_or(0, char::len_utf8);
 
         next
     }
 
-    fn parse_name(&mut self) -> Option<SmolStr> {
+    fn parse_name(&mut self) -> Option<&'s str> {
         let mut first = true;
         let name = self.take_while(|c| {
-            let valid =
-                !matches!(c, ';' | '(' | ')' | '\'' | '$' | '=' | '!') && !c.is_whitespace();
+            let valid = !matches!(
+                c,
+                ';' | '(' | ')' | '\'' | '"' | '$' | '=' | '!' | '+' | '-' | '*' | '/'
+            ) && !c.is_whitespace();
             if first {
                 first = false;
                 valid && !c.is_numeric()
@@ -154,7 +426,25 @@ impl<'s> StringTokenizer<'s> {
         }
     }
 
-    fn parse_integer(&mut self) -> Option<SmolStr> {
+    /// Parse a string literal, starting at its opening quote.
+    fn parse_string(&mut self) -> Result<SmolStr, Error> {
+        self.advance();
+        let mut result = SmolStrBuilder::new();
+        loop {
+            match self.advance() {
+                Some('"') => return Ok(result.finish()),
+                Some('\\') => match self.advance() {
+                    Some('n') => result.push('\n'),
+                    Some(c @ ('"' | '\\')) => result.push(c),
+                    _ => return Err(self.error(ErrorKind::InvalidEscape)),
+                },
+                Some('\n') | None => return Err(self.error(ErrorKind::UnterminatedString)),
+                Some(c) => result.push(c),
+            }
+        }
+    }
+
+    fn parse_integer(&mut self) -> Option<&'s str> {
         let digits = self.take_while(char::is_ascii_digit);
 
         if digits.is_empty() {
@@ -174,6 +464,7 @@ impl<'s> StringTokenizer<'s> {
         Token {
             payload,
             position: self.position,
+            span: self.token_start..self.offset,
         }
     }
 
@@ -182,13 +473,53 @@ impl<'s> StringTokenizer<'s> {
         Error {
             kind,
             at: self.position,
+            span: self.token_start..self.offset,
         }
     }
 }
 
 impl<'s> TokenSource for StringTokenizer<'s> {
     fn read_token(&mut self) -> Result<Token, Error> {
+        match self.lookahead.take() {
+            Some((_, _, token)) => token,
+            None => self.lex(),
+        }
+    }
+
+    fn peek_token(&mut self) -> Result<&Token, Error> {
+        let lookahead = match self.lookahead.take() {
+            Some(lookahead) => lookahead,
+            None => (self.position, self.offset, self.lex()),
+        };
+        let (_, _, token) = self.lookahead.insert(lookahead);
+        token.as_ref().map_err(Error::clone)
+    }
+
+    fn position(&self) -> Position {
+        self.lookahead
+            .as_ref()
+            .map_or(self.position, |(before, _, _)| *before)
+    }
+
+    fn offset(&self) -> usize {
+        self.lookahead
+            .as_ref()
+            .map_or(self.offset, |(_, before, _)| *before)
+    }
+
+    fn take_trivia(&mut self) -> Vec<Trivia> {
+        std::mem::take(&mut self.trivia)
+    }
+
+    fn lines_hint(&self) -> usize {
+        self.source[self.offset..].matches('\n').count() + 1
+    }
+}
+
+impl<'s> StringTokenizer<'s> {
+    fn lex(&mut self) -> Result<Token, Error> {
         let first = loop {
+            self.token_start = self.offset;
             let Some(next) = self.current() else {
                 return Ok(self.token(Payload::Eof));
             };
@@ -196,28 +527,50 @@ impl<'s> TokenSource for StringTokenizer<'s> {
                 '\n' => Payload::Newline,
                 '\r' => {
                     self.advance();
-                    let Some('\n') = self.advance() else {
+                    let Some('\n') = self.current() else {
                         return Err(self.error(ErrorKind::InvalidCRLFSequence));
                     };
                     Payload::Newline
                 }
                 '!' => {
                     self.advance();
-                    let Some('\n') = self.advance() else {
-                        return Err(self.error(ErrorKind::ExpectedNewlineAfterBang));
-                    };
+                    match self.current() {
+                        // The last line may end without a newline
+                        Some('\n') | None => {}
+                        Some('\r') if self.chars.clone().nth(1) == Some('\n') => {
+                            self.advance();
+                        }
+                        _ => return Err(self.error(ErrorKind::ExpectedNewlineAfterBang)),
+                    }
                     Payload::VoidNewline
                 }
                 ';' => Payload::Concat,
                 '=' => {
                     self.advance();
-                    let Some('>') = self.advance() else {
+                    let Some('>') = self.current() else {
                         return Err(self.error(ErrorKind::InvalidPipe));
                     };
                     Payload::Pipe
                 }
                 '(' => Payload::ParenL,
                 ')' => Payload::ParenR,
+                '/' if self.chars.clone().nth(1) == Some('/') => {
+                    let line = self.position.line;
+                    self.advance();
+                    self.advance();
+                    let text = self.take_while(|c| !matches!(c, '\r' | '\n'));
+                    self.trivia.push(Trivia::Comment {
+                        line,
+                        text: text.trim_end().into(),
+                        trailing: self.line_has_content,
+                    });
+                    self.line_has_content = true;
+                    continue;
+                }
+                '+' => Payload::Operator(Operator::Add),
+                '-' => Payload::Operator(Operator::Sub),
+                '*' => Payload::Operator(Operator::Mul),
+                '/' => Payload::Operator(Operator::Div),
                 other => {
                     if other.is_whitespace() {
                         self.advance();
@@ -228,31 +581,60 @@ impl<'s> TokenSource for StringTokenizer<'s> {
                 }
             };
 
+            let line = self.position.line;
             self.advance();
+            if let Payload::Newline | Payload::VoidNewline = single {
+                if !self.line_has_content {
+                    self.trivia.push(Trivia::BlankLine(line));
+                }
+                self.line_has_content = false;
+            } else {
+                self.line_has_content = true;
+            }
             return Ok(self.token(single));
         };
+        self.line_has_content = true;
 
         if first.is_ascii_digit() {
             let lit = self
                 .parse_integer()
-                .expect("At least 1 digit is confirmed available");
+                .ok_or_else(|| self.error(ErrorKind::InvalidLiteral))?;
 
-            let Ok(value) = lit.parse::<u64>() else {
-                return Err(self.error(ErrorKind::InvalidLiteral));
+            let number = if self.current() == Some('.') {
+                self.advance();
+                self.parse_integer()
+                    .ok_or_else(|| self.error(ErrorKind::InvalidLiteral))?;
+                let digits = &self.source[self.token_start..self.offset];
+                let Ok(value) = digits.parse::<f64>() else {
+                    return Err(self.error(ErrorKind::InvalidLiteral));
+                };
+                Number::Float(value)
+            } else {
+                let Ok(value) = lit.parse::<u64>() else {
+                    return Err(self.error(ErrorKind::InvalidLiteral));
+                };
+                Number::Integer(value)
             };
 
-            Ok(self.token(Payload::LitNumber(Number::Integer(value))))
+            let suffix = self.take_while(char::is_ascii_alphabetic);
+            if suffix.is_empty() {
+                Ok(self.token(Payload::LitNumber(number)))
+            } else {
+                let unit =
+                    Unit::from_suffix(suffix).ok_or_else(|| self.error(ErrorKind::UnknownUnit))?;
+                Ok(self.token(Payload::LitQuantity(number, unit)))
+            }
+        } else if let '"' = first {
+            let value = self.parse_string()?;
+            Ok(self.token(Payload::LitString(value)))
         } else if let '#' = first {
             self.advance();
             let name = self
                 .parse_name()
                 .ok_or_else(|| self.error(ErrorKind::ExpectedIdentifier))?;
 
-            let keyword = match name.as_str() {
-                "let" => Keyword::Let,
-                "screen" => Keyword::Screen,
-                _ => return Err(self.error(ErrorKind::InvalidKeyword)),
-            };
+            let keyword =
+                Keyword::from_name(name).ok_or_else(|| self.error(ErrorKind::InvalidKeyword))?;
             Ok(self.token(Payload::Keyword(keyword)))
         } else {
             let make_payload = match first {
@@ -263,21 +645,95 @@ impl<'s> TokenSource for StringTokenizer<'s> {
 
                 _ => Payload::Name,
             };
-            let name = self
-                .parse_name()
-                .ok_or_else(|| self.error(ErrorKind::ExpectedIdentifier))?;
+            let Some(name) = self.parse_name() else {
+                let error = self.error(ErrorKind::ExpectedIdentifier);
+                // Skip the offending character so the next read makes progress
+                if first != '$' {
+                    self.advance();
+                }
+                return Err(error);
+            };
+            let name: SmolStr = name.into();
+            if first != '$' && self.current() == Some('=') && self.chars.clone().nth(1) != Some('>')
+            {
+                self.advance();
+                return Ok(self.token(Payload::Key(name)));
+            }
             Ok(self.token(make_payload(name)))
         }
     }
+}
+
+/// A byte range into the source text.
+pub type Span = Range<usize>;
+
+/// What a token is, for syntax highlighting.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenClass {
+    Keyword,
+    Name,
+    Variable,
+    /// Numbers, with or without a unit.
+    Number,
+    String,
+    /// Pipes, semicolons, parentheses, operators and `!`.
+    Punctuation,
+    Comment,
+}
 
-    fn peek_token(&self) -> Result<Token, Error> {
-        let mut copy = self.clone();
-        copy.read_token()
+/// Classify every token and comment in `source`, in order. Unlike the
+/// parser this never fails: text the lexer rejects is left out.
+pub fn tokenize_with_spans(source: &str) -> Vec<(Span, TokenClass)> {
+    let mut tokenizer = StringTokenizer::new(&source);
+    let mut spans = vec![];
+    loop {
+        let before = tokenizer.offset;
+        let class = match tokenizer.lex().map(|token| token.payload) {
+            Ok(Payload::Eof) => break,
+            Ok(Payload::Newline) => continue,
+            // Only the bang, not the newline after it
+            Ok(Payload::VoidNewline) => {
+                let start = tokenizer.token_start;
+                spans.push((start..start + 1, TokenClass::Punctuation));
+                continue;
+            }
+            // The name, then the equals sign
+            Ok(Payload::Key(_)) => {
+                let end = tokenizer.offset;
+                spans.push((tokenizer.token_start..end - 1, TokenClass::Name));
+                spans.push((end - 1..end, TokenClass::Punctuation));
+                continue;
+            }
+            Ok(Payload::Keyword(_)) => TokenClass::Keyword,
+            Ok(Payload::Name(_)) => TokenClass::Name,
+            Ok(Payload::Variable(_)) => TokenClass::Variable,
+            Ok(Payload::LitNumber(_) | Payload::LitQuantity(..)) => TokenClass::Number,
+            Ok(Payload::LitString(_)) => TokenClass::String,
+            Ok(_) => TokenClass::Punctuation,
+            Err(_) if tokenizer.offset == before => break,
+            Err(_) => continue,
+        };
+        spans.push((tokenizer.token_start..tokenizer.offset, class));
     }
 
-    fn position(&self) -> Position {
-        self.position
+    // Comments run to the end of their line, minus trailing whitespace
+    let mut line_ends = source
+        .split_inclusive('\n')
+        .scan(0, |start, line| {
+            let end = *start + line.trim_end().len();
+            *start += line.len();
+            Some(end)
+        })
+        .collect::<Vec<_>>();
+    line_ends.push(source.len());
+    for trivia in tokenizer.take_trivia() {
+        if let Trivia::Comment { line, text, .. } = trivia {
+            let end = line_ends[line];
+            spans.push((end - text.len() - 2..end, TokenClass::Comment));
+        }
     }
+    spans.sort_by_key(|(span, _)| span.start);
+    spans
 }
 
 #[cfg(test)]
@@ -308,6 +764,24 @@ mod test {
         assert_eq!(token3.position, Position { line: 1, column: 5 });
     }
 
+    #[test]
+    fn test_token_spans() {
+        let input = "fünf  $x\n\"é\" ==";
+        let mut tokenizer = StringTokenizer::new(&input);
+        let mut spans = vec![];
+        while let Ok(token) = tokenizer.read_token() {
+            if token.payload == Payload::Eof {
+                break;
+            }
+            spans.push(&input[token.span]);
+        }
+        assert_eq!(spans, ["fünf", "$x", "\n", "\"é\""]);
+
+        let error = tokenizer.read_token().unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidPipe);
+        assert_eq!(&input[error.span], "=");
+    }
+
     #[test]
     fn test_correct_identification_of_tokens() {
         let input = "func1 $var1 123 ; ( ) =>";
@@ -332,6 +806,37 @@ mod test {
         assert_payload!(tokenizer equals Payload::LitNumber(Number::Integer(12345678901234567890)));
     }
 
+    #[test]
+    fn test_float_and_unit_literals() {
+        let input = "2.5 10mm 2.5cm 1in 12pt 45deg 0.5rad 3px";
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_payload!(tokenizer equals Payload::LitNumber(Number::Float(2.5)));
+        assert_payload!(tokenizer equals Payload::LitQuantity(Number::Integer(10), Unit::Millimeter));
+        assert_payload!(tokenizer equals Payload::LitQuantity(Number::Float(2.5), Unit::Centimeter));
+        assert_payload!(tokenizer equals Payload::LitQuantity(Number::Integer(1), Unit::Inch));
+        assert_payload!(tokenizer equals Payload::LitQuantity(Number::Integer(12), Unit::Point));
+        assert_payload!(tokenizer equals Payload::LitQuantity(Number::Integer(45), Unit::Degree));
+        assert_payload!(tokenizer equals Payload::LitQuantity(Number::Float(0.5), Unit::Radian));
+        assert_eq!(
+            tokenizer.read_token().err().map(|err| err.kind),
+            Some(ErrorKind::UnknownUnit)
+        );
+    }
+
+    #[test]
+    fn test_string_literals() {
+        let input = r#""outline" "say \"hi\"" "open"#;
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_payload!(tokenizer equals Payload::LitString("outline".into()));
+        assert_payload!(tokenizer equals Payload::LitString("say \"hi\"".into()));
+        assert_eq!(
+            tokenizer.read_token().err().map(|err| err.kind),
+            Some(ErrorKind::UnterminatedString)
+        );
+    }
+
     #[test]
     fn test_newlines() {
         let input = "func1\r\n   $var1\n  123";
@@ -343,4 +848,150 @@ mod test {
         assert_payload!(tokenizer equals Payload::Newline);
         assert_payload!(tokenizer equals Payload::LitNumber(Number::Integer(123)));
     }
+
+    #[test]
+    fn test_void_newlines() {
+        let input = "a!\nb!\r\nc!";
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_payload!(tokenizer equals Payload::Name("a".into()));
+        assert_payload!(tokenizer equals Payload::VoidNewline);
+        assert_payload!(tokenizer equals Payload::Name("b".into()));
+        assert_payload!(tokenizer equals Payload::VoidNewline);
+        assert_eq!(tokenizer.position().line(), 2);
+        assert_payload!(tokenizer equals Payload::Name("c".into()));
+        assert_payload!(tokenizer equals Payload::VoidNewline);
+        assert_payload!(tokenizer equals Payload::Eof);
+
+        let mut tokenizer = StringTokenizer::new(&"a! b");
+        tokenizer.read_token().unwrap();
+        let error = tokenizer.read_token().unwrap_err();
+        assert_eq!(error.kind, ErrorKind::ExpectedNewlineAfterBang);
+    }
+
+    #[test]
+    fn test_keys() {
+        let input = "text size=4 a=>b $c=1";
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_payload!(tokenizer equals Payload::Name("text".into()));
+        assert_payload!(tokenizer equals Payload::Key("size".into()));
+        assert_payload!(tokenizer equals Payload::LitNumber(Number::Integer(4)));
+        assert_payload!(tokenizer equals Payload::Name("a".into()));
+        assert_payload!(tokenizer equals Payload::Pipe);
+        assert_payload!(tokenizer equals Payload::Name("b".into()));
+        assert_payload!(tokenizer equals Payload::Variable("c".into()));
+        let error = tokenizer.read_token().unwrap_err();
+        assert_eq!(error.kind, ErrorKind::InvalidPipe);
+    }
+
+    #[test]
+    fn test_keywords() {
+        for (name, keyword) in Keyword::ALL {
+            let input = format!("#{name}");
+            let mut tokenizer = StringTokenizer::new(&input);
+            assert_payload!(tokenizer equals Payload::Keyword(*keyword));
+            assert_eq!(keyword.to_string(), input);
+        }
+
+        let mut tokenizer = StringTokenizer::new(&"#nope");
+        assert_eq!(
+            tokenizer.read_token().err().map(|err| err.kind),
+            Some(ErrorKind::InvalidKeyword)
+        );
+    }
+
+    #[test]
+    fn test_operators() {
+        let input = "a+b - *c/";
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_payload!(tokenizer equals Payload::Name("a".into()));
+        assert_payload!(tokenizer equals Payload::Operator(Operator::Add));
+        assert_payload!(tokenizer equals Payload::Name("b".into()));
+        assert_payload!(tokenizer equals Payload::Operator(Operator::Sub));
+        assert_payload!(tokenizer equals Payload::Operator(Operator::Mul));
+        assert_payload!(tokenizer equals Payload::Name("c".into()));
+        assert_payload!(tokenizer equals Payload::Operator(Operator::Div));
+    }
+
+    #[test]
+    fn test_peek_does_not_consume() {
+        let input = "func1 $var1";
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_eq!(
+            tokenizer.peek_token().unwrap().payload,
+            Payload::Name("func1".into())
+        );
+        assert_eq!(tokenizer.position(), Position { line: 0, column: 0 });
+        assert_payload!(tokenizer equals Payload::Name("func1".into()));
+        assert_eq!(tokenizer.position(), Position { line: 0, column: 5 });
+        tokenizer.peek_token().unwrap();
+        assert_payload!(tokenizer equals Payload::Variable("var1".into()));
+        assert_payload!(tokenizer equals Payload::Eof);
+    }
+
+    #[test]
+    fn test_comments_and_blank_lines() {
+        let input = "// head\na=>b // tail\n\n  \nc";
+        let mut tokenizer = StringTokenizer::new(&input);
+
+        assert_payload!(tokenizer equals Payload::Newline);
+        assert_payload!(tokenizer equals Payload::Name("a".into()));
+        assert_payload!(tokenizer equals Payload::Pipe);
+        assert_payload!(tokenizer equals Payload::Name("b".into()));
+        assert_payload!(tokenizer equals Payload::Newline);
+        assert_payload!(tokenizer equals Payload::Newline);
+        assert_payload!(tokenizer equals Payload::Newline);
+        assert_payload!(tokenizer equals Payload::Name("c".into()));
+        assert_payload!(tokenizer equals Payload::Eof);
+        assert_eq!(
+            tokenizer.take_trivia(),
+            [
+                Trivia::Comment {
+                    line: 0,
+                    text: " head".into(),
+                    trailing: false,
+                },
+                Trivia::Comment {
+                    line: 1,
+                    text: " tail".into(),
+                    trailing: true,
+                },
+                Trivia::BlankLine(2),
+                Trivia::BlankLine(3),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_tokenize_with_spans() {
+        let input = "#let é (2cm+$x) // note\r\nprint \"hi\" at=1!\n";
+        let spans = tokenize_with_spans(input)
+            .into_iter()
+            .map(|(span, class)| (&input[span], class))
+            .collect::<Vec<_>>();
+
+        use TokenClass::*;
+        assert_eq!(
+            spans,
+            [
+                ("#let", Keyword),
+                ("é", Name),
+                ("(", Punctuation),
+                ("2cm", Number),
+                ("+", Punctuation),
+                ("$x", Variable),
+                (")", Punctuation),
+                ("// note", Comment),
+                ("print", Name),
+                ("\"hi\"", String),
+                ("at", Name),
+                ("=", Punctuation),
+                ("1", Number),
+                ("!", Punctuation),
+            ]
+        );
+    }
 }
@@ -1,10 +1,59 @@
+/// The candidate spelled most like `name`, if any is close enough to be a
+/// typo of it. Names shorter than three characters have no typos, since
+/// any other short name would be one.
+pub(crate) fn closest<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Option<&'a str> {
+    let allowed = (name.chars().count() / 3).min(3);
+    candidates
+        .into_iter()
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|&(distance, _)| distance <= allowed)
+        .min()
+        .map(|(_, candidate)| candidate)
+}
+
+/// How many characters have to be inserted, removed, replaced or swapped
+/// with their neighbour to turn `a` into `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut before: Vec<usize> = Vec::new();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for i in 0..a.len() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for j in 0..b.len() {
+            let replace = previous[j] + usize::from(a[i] != b[j]);
+            current[j + 1] = replace.min(previous[j + 1] + 1).min(current[j] + 1);
+            if i > 0 && j > 0 && a[i] == b[j - 1] && a[i - 1] == b[j] {
+                current[j + 1] = current[j + 1].min(before[j - 1] + 1);
+            }
+        }
+        before = std::mem::replace(&mut previous, current);
+    }
+    previous[b.len()]
+}
+
 #[cfg(test)]
 pub mod test_helpers {
     use crate::{
-        runtime::{Error, Stack, Value},
+        ast::parse_file,
+        runtime::{Error, Runtime, Stack, Value},
         stdlib::{Point, Scalar, Vector},
+        token::StringTokenizer,
     };
 
+    pub use crate::testing::Recorder;
+
+    /// Parse and execute `source`, panicking on syntax errors.
+    pub fn run_script(source: &str) -> Result<Runtime<Recorder>, Error> {
+        let program = parse_file(&mut StringTokenizer::new(&source)).expect("Invalid test script");
+        let mut runtime = Runtime::default();
+        runtime.execute(program)?;
+        Ok(runtime)
+    }
+
     #[track_caller]
     pub fn assert_values_eq(actual: Result<Value, Error>, expected: Value) {
         assert_eq!(actual, Ok(expected));
@@ -18,6 +67,11 @@ pub mod test_helpers {
         stack
     }
 
+    /// The exact fraction `numerator / denominator`.
+    pub fn ratio(numerator: i64, denominator: i64) -> Scalar {
+        Scalar::rational(numerator, denominator).unwrap()
+    }
+
     pub fn scalar<T>(value: T) -> Value
     where
         T: Into<Scalar>,
@@ -44,4 +98,54 @@ pub mod test_helpers {
             y: y.into(),
         })
     }
+
+    pub fn line<T>(x: T, y: T, dx: T, dy: T) -> Value
+    where
+        T: Into<Scalar>,
+    {
+        Value::Line(
+            Point {
+                x: x.into(),
+                y: y.into(),
+            },
+            Vector {
+                x: dx.into(),
+                y: dy.into(),
+            },
+        )
+    }
+
+    pub fn circle_value<T, R>(x: T, y: T, radius: R) -> Value
+    where
+        T: Into<Scalar>,
+        R: Into<Scalar>,
+    {
+        Value::Circle(
+            Point {
+                x: x.into(),
+                y: y.into(),
+            },
+            radius.into(),
+        )
+    }
+
+    pub fn list_of<const N: usize>(items: [Value; N]) -> Value {
+        Value::List(items.to_vec().into())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_closest() {
+        let names = ["circle", "pnt2", "pnt3", "line"];
+        assert_eq!(closest("circel", names), Some("circle"));
+        assert_eq!(closest("pnt", names), Some("pnt2"));
+        assert_eq!(closest("frobnicate", names), None);
+        assert_eq!(closest("x", ["y"]), None);
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("widht", "width"), 1);
+    }
 }
@@ -0,0 +1,149 @@
+use graze::{
+    builtin_doc, check::check_program, parse_file_tolerant, Keyword, Payload, Position, Span,
+    StringTokenizer, TokenSource, BUILTIN_DOCS,
+};
+
+#[derive(Debug, PartialEq)]
+pub struct Diagnostic {
+    pub at: Position,
+    /// The bytes of the source to underline.
+    pub span: Span,
+    pub message: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CompletionKind {
+    Function,
+    Variable,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    pub detail: Option<String>,
+}
+
+/// Every syntax error in `source`, not just the first, and the type
+/// errors of the lines that parsed.
+pub fn diagnostics(source: &str) -> Vec<Diagnostic> {
+    let (program, errors) = parse_file_tolerant(&mut StringTokenizer::new(&source));
+    let syntax = errors.into_iter().map(|err| Diagnostic {
+        at: err.position(),
+        span: err.span(),
+        message: err.kind().to_string(),
+    });
+    let types = check_program(&program).into_iter().map(|err| Diagnostic {
+        at: err.position(),
+        span: err.span(),
+        message: err.kind().to_string(),
+    });
+    syntax.chain(types).collect()
+}
+
+fn is_name_char(c: char) -> bool {
+    !c.is_whitespace() && !"();\"$=!+-*/#".contains(c)
+}
+
+/// The name under the cursor, if any.
+fn word_at(source: &str, line: usize, column: usize) -> Option<&str> {
+    let text = source.lines().nth(line)?;
+    let offset = |column: usize| {
+        text.char_indices()
+            .nth(column)
+            .map_or(text.len(), |(offset, _)| offset)
+    };
+    let cursor = offset(column);
+
+    let start = text[..cursor]
+        .rfind(|c| !is_name_char(c))
+        .map_or(0, |i| i + 1);
+    let end = text[cursor..]
+        .find(|c| !is_name_char(c))
+        .map_or(text.len(), |i| cursor + i);
+    (start < end).then(|| &text[start..end])
+}
+
+/// Markdown describing the builtin under the cursor.
+pub fn hover(source: &str, line: usize, column: usize) -> Option<String> {
+    let doc = builtin_doc(word_at(source, line, column)?)?;
+    Some(format!("```\n{}\n```\n{}", doc.signature, doc.summary))
+}
+
+/// Builtins, plus every variable the script binds or reads.
+pub fn completions(source: &str) -> Vec<Completion> {
+    let mut items: Vec<_> = BUILTIN_DOCS
+        .iter()
+        .map(|doc| Completion {
+            label: doc.name.to_string(),
+            kind: CompletionKind::Function,
+            detail: Some(doc.signature.to_string()),
+        })
+        .collect();
+
+    let mut variables = Vec::new();
+    let mut tokens = StringTokenizer::new(&source);
+    let mut binding = false;
+    while let Ok(token) = tokens.read_token() {
+        match token.payload {
+            Payload::Eof => break,
+            Payload::Keyword(keyword) => {
+                binding = matches!(
+                    keyword,
+                    Keyword::Let | Keyword::Const | Keyword::Param | Keyword::For
+                );
+                continue;
+            }
+            Payload::Name(name) if binding => variables.push(name),
+            Payload::Variable(name) => variables.push(name),
+            _ => {}
+        }
+        binding = false;
+    }
+    variables.sort();
+    variables.dedup();
+
+    items.extend(variables.into_iter().map(|name| Completion {
+        label: name.to_string(),
+        kind: CompletionKind::Variable,
+        detail: None,
+    }));
+    items
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_diagnostics() {
+        let found = diagnostics("pnt2 1 2\n(circle\nline )\ncircle 1 2\n");
+        assert_eq!(found.len(), 3);
+        assert_eq!(found[0].at.line(), 1);
+        assert_eq!(found[2].span, 24..34);
+        assert!(found[2].message.starts_with("circle expects"));
+    }
+
+    #[test]
+    fn test_hover() {
+        let source = "#let c (circle (pnt2 1 2) 3)";
+        assert!(hover(source, 0, 10)
+            .unwrap()
+            .contains("circle center radius"));
+        assert!(hover(source, 0, 17).unwrap().contains("pnt2 x y"));
+        assert_eq!(hover(source, 0, 2), None);
+        assert_eq!(hover(source, 3, 0), None);
+    }
+
+    #[test]
+    fn test_completions() {
+        let items = completions("#let width 10\n#for p in $points\n#end");
+        let variables = items
+            .iter()
+            .filter(|item| item.kind == CompletionKind::Variable)
+            .map(|item| item.label.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(variables, ["p", "points", "width"]);
+        assert!(items.iter().any(|item| item.label == "pnt2"));
+    }
+}
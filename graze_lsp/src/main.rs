@@ -0,0 +1,26 @@
+//! A language server for graze scripts, speaking LSP over stdin/stdout.
+
+mod analysis;
+mod server;
+mod transport;
+
+use std::io::{self, BufReader, Write};
+
+use server::Server;
+
+fn main() -> io::Result<()> {
+    let mut input = BufReader::new(io::stdin().lock());
+    let mut output = io::stdout().lock();
+    let mut server = Server::default();
+
+    while let Some(message) = transport::read_message(&mut input)? {
+        for reply in server.handle(message) {
+            transport::write_message(&mut output, &reply)?;
+        }
+        output.flush()?;
+        if server.exited() {
+            break;
+        }
+    }
+    Ok(())
+}
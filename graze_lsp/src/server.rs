@@ -0,0 +1,204 @@
+use std::collections::HashMap;
+
+use serde_json::{json, Value};
+
+use crate::analysis::{self, CompletionKind};
+
+/// Open documents and the protocol state of one editor session.
+#[derive(Default)]
+pub struct Server {
+    documents: HashMap<String, String>,
+    exited: bool,
+}
+
+impl Server {
+    pub fn exited(&self) -> bool {
+        self.exited
+    }
+
+    /// React to one incoming message, returning every message to send back.
+    pub fn handle(&mut self, message: Value) -> Vec<Value> {
+        let method = message["method"].as_str().unwrap_or_default();
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "hoverProvider": true,
+                    "completionProvider": { "triggerCharacters": ["$"] },
+                },
+                "serverInfo": { "name": "graze-lsp" },
+            }),
+            "shutdown" => Value::Null,
+            "exit" => {
+                self.exited = true;
+                return vec![];
+            }
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                return self.update(&document["uri"], &document["text"]);
+            }
+            "textDocument/didChange" => {
+                // Only full syncs are advertised, so the last change is the whole text
+                let text = params["contentChanges"]
+                    .as_array()
+                    .and_then(|changes| changes.last())
+                    .map_or(&Value::Null, |change| &change["text"]);
+                return self.update(&params["textDocument"]["uri"], text);
+            }
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                return vec![publish(uri, vec![])];
+            }
+            "textDocument/hover" => self
+                .position(params)
+                .and_then(|(source, line, column)| analysis::hover(source, line, column))
+                .map_or(
+                    Value::Null,
+                    |markdown| json!({ "contents": { "kind": "markdown", "value": markdown } }),
+                ),
+            "textDocument/completion" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                let source = self.documents.get(uri).map_or("", String::as_str);
+                let items = analysis::completions(source)
+                    .into_iter()
+                    .map(|item| {
+                        let kind = match item.kind {
+                            CompletionKind::Function => 3,
+                            CompletionKind::Variable => 6,
+                        };
+                        json!({ "label": item.label, "kind": kind, "detail": item.detail })
+                    })
+                    .collect::<Vec<_>>();
+                Value::Array(items)
+            }
+            _ => {
+                return match id {
+                    // Requests need an answer, notifications can be ignored
+                    Some(id) => vec![json!({
+                        "jsonrpc": "2.0",
+                        "id": id,
+                        "error": { "code": -32601, "message": format!("Unsupported method {method}") },
+                    })],
+                    None => vec![],
+                };
+            }
+        };
+
+        match id {
+            Some(id) => vec![json!({ "jsonrpc": "2.0", "id": id, "result": result })],
+            None => vec![],
+        }
+    }
+
+    fn update(&mut self, uri: &Value, text: &Value) -> Vec<Value> {
+        let (Some(uri), Some(text)) = (uri.as_str(), text.as_str()) else {
+            return vec![];
+        };
+        let diagnostics = analysis::diagnostics(text)
+            .into_iter()
+            .map(|diagnostic| {
+                let start = location(text, diagnostic.span.start);
+                let end = location(text, diagnostic.span.end);
+                json!({
+                    "range": { "start": start, "end": end },
+                    "severity": 1,
+                    "source": "graze",
+                    "message": diagnostic.message,
+                })
+            })
+            .collect();
+        self.documents.insert(uri.to_owned(), text.to_owned());
+        vec![publish(uri, diagnostics)]
+    }
+
+    /// The document and cursor of a `TextDocumentPositionParams`.
+    fn position<'a>(&'a self, params: &Value) -> Option<(&'a str, usize, usize)> {
+        let source = self
+            .documents
+            .get(params["textDocument"]["uri"].as_str()?)?;
+        let line = params["position"]["line"].as_u64()?;
+        let column = params["position"]["character"].as_u64()?;
+        Some((source, line as usize, column as usize))
+    }
+}
+
+/// The line and character of the byte `offset` into `source`.
+fn location(source: &str, offset: usize) -> Value {
+    let before = &source[..offset];
+    let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+    json!({
+        "line": before.matches('\n').count(),
+        "character": before[line_start..].chars().count(),
+    })
+}
+
+fn publish(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn open(server: &mut Server, text: &str) -> Vec<Value> {
+        server.handle(json!({
+            "jsonrpc": "2.0",
+            "method": "textDocument/didOpen",
+            "params": { "textDocument": { "uri": "file:///a.grz", "text": text } },
+        }))
+    }
+
+    #[test]
+    fn test_diagnostics_on_open() {
+        let mut server = Server::default();
+        let replies = open(&mut server, "(circle\n");
+        assert_eq!(replies.len(), 1);
+        let diagnostics = replies[0]["params"]["diagnostics"].as_array().unwrap();
+        assert_eq!(diagnostics.len(), 1);
+
+        let replies = open(&mut server, "pnt2 1 2\ncircle 1 2");
+        assert_eq!(
+            replies[0]["params"]["diagnostics"][0]["range"],
+            json!({
+                "start": { "line": 1, "character": 0 },
+                "end": { "line": 1, "character": 10 },
+            })
+        );
+    }
+
+    #[test]
+    fn test_hover_request() {
+        let mut server = Server::default();
+        open(&mut server, "circle (pnt2 0 0) 1");
+        let replies = server.handle(json!({
+            "jsonrpc": "2.0",
+            "id": 7,
+            "method": "textDocument/hover",
+            "params": {
+                "textDocument": { "uri": "file:///a.grz" },
+                "position": { "line": 0, "character": 2 },
+            },
+        }));
+        assert_eq!(replies[0]["id"], 7);
+        let markdown = replies[0]["result"]["contents"]["value"].as_str().unwrap();
+        assert!(markdown.contains("circle center radius"));
+    }
+
+    #[test]
+    fn test_unknown_request() {
+        let mut server = Server::default();
+        let replies = server.handle(json!({ "jsonrpc": "2.0", "id": 1, "method": "nope" }));
+        assert_eq!(replies[0]["error"]["code"], -32601);
+        assert!(server
+            .handle(json!({ "jsonrpc": "2.0", "method": "nope" }))
+            .is_empty());
+    }
+}
@@ -0,0 +1,453 @@
+//! Animated GIFs and PNGs of [`Canvas`]es, for `graze anim`. Both formats
+//! store the gray pixels as they are: GIFs with a palette of all 256 grays,
+//! APNGs as 8 bit grayscale. Every frame has to be the same size.
+
+use std::collections::HashMap;
+
+use crate::raster::Canvas;
+
+/// The file formats animations can be written as.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    Gif,
+    /// An animated PNG, showing its first frame where APNG isn't supported.
+    Png,
+}
+
+impl Format {
+    /// The format a file extension like `gif` stands for.
+    pub fn from_extension(extension: &str) -> Option<Self> {
+        match extension.to_ascii_lowercase().as_str() {
+            "gif" => Some(Format::Gif),
+            "png" | "apng" => Some(Format::Png),
+            _ => None,
+        }
+    }
+
+    /// Encode `frames` shown at `fps` frames per second, repeating forever.
+    pub fn encode(self, frames: &[Canvas], fps: f64) -> Vec<u8> {
+        match self {
+            Format::Gif => gif(frames, fps),
+            Format::Png => apng(frames, fps),
+        }
+    }
+}
+
+/// How long each of `count` frames is shown at `fps`, in hundredths of a
+/// second, the only unit GIFs know. Rounding the time each frame starts at
+/// instead of its length keeps the animation from drifting.
+fn delays(count: usize, fps: f64) -> impl Iterator<Item = u16> {
+    let start = move |frame: usize| (frame as f64 * 100.0 / fps).round();
+    (0..count).map(move |frame| (start(frame + 1) - start(frame)).clamp(1.0, 65535.0) as u16)
+}
+
+/// The width and height of the frames, checked to be the same for all.
+fn size(frames: &[Canvas]) -> (usize, usize) {
+    let size = frames
+        .first()
+        .map_or((1, 1), |frame| (frame.width(), frame.height()));
+    assert!(
+        frames
+            .iter()
+            .all(|frame| (frame.width(), frame.height()) == size),
+        "every frame has to be the same size"
+    );
+    size
+}
+
+/// A GIF showing `frames` at `fps`. Panics if they are wider or taller
+/// than the 65535 pixels GIFs allow.
+pub fn gif(frames: &[Canvas], fps: f64) -> Vec<u8> {
+    let (width, height) = size(frames);
+    let width = u16::try_from(width).expect("GIFs are at most 65535 pixels wide");
+    let height = u16::try_from(height).expect("GIFs are at most 65535 pixels tall");
+
+    let mut gif = b"GIF89a".to_vec();
+    gif.extend(width.to_le_bytes());
+    gif.extend(height.to_le_bytes());
+    // A global palette of 256 colors, with 8 bits per primary
+    gif.extend([0xf7, 0, 0]);
+    gif.extend((0..=255).flat_map(|gray| [gray; 3]));
+    // Repeat forever
+    gif.extend(b"\x21\xff\x0bNETSCAPE2.0\x03\x01\x00\x00\x00");
+
+    for (frame, delay) in frames.iter().zip(delays(frames.len(), fps)) {
+        gif.extend([0x21, 0xf9, 4, 0]);
+        gif.extend(delay.to_le_bytes());
+        gif.extend([0, 0]);
+
+        gif.push(0x2c);
+        gif.extend([0; 4]);
+        gif.extend(width.to_le_bytes());
+        gif.extend(height.to_le_bytes());
+        gif.push(0);
+
+        gif.push(8);
+        for block in lzw(frame.pixels()).chunks(255) {
+            gif.push(block.len() as u8);
+            gif.extend(block);
+        }
+        gif.push(0);
+    }
+    gif.push(0x3b);
+    gif
+}
+
+/// Writes codes of a growing number of bits, least significant first.
+#[derive(Default)]
+struct Bits {
+    bytes: Vec<u8>,
+    pending: u32,
+    count: u32,
+}
+
+impl Bits {
+    fn write(&mut self, value: u32, bits: u32) {
+        self.pending |= value << self.count;
+        self.count += bits;
+        while self.count >= 8 {
+            self.bytes.push(self.pending as u8);
+            self.pending >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    /// Write the bits of a Huffman code, which go most significant first.
+    fn write_reversed(&mut self, code: u32, bits: u32) {
+        self.write(code.reverse_bits() >> (32 - bits), bits);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.pending as u8);
+        }
+        self.bytes
+    }
+}
+
+/// The LZW codes of 8 bit `pixels` the way GIFs pack them. The table
+/// starts over once it is full.
+fn lzw(pixels: &[u8]) -> Vec<u8> {
+    const CLEAR: u32 = 256;
+    const END: u32 = 257;
+    const FULL: u32 = 4096;
+
+    let mut bits = Bits::default();
+    let mut table = HashMap::new();
+    let mut next = END + 1;
+    let mut width = 9;
+    bits.write(CLEAR, width);
+
+    let Some((&first, rest)) = pixels.split_first() else {
+        bits.write(END, width);
+        return bits.finish();
+    };
+    let mut prefix = u32::from(first);
+    for &pixel in rest {
+        if let Some(&code) = table.get(&(prefix, pixel)) {
+            prefix = code;
+            continue;
+        }
+        bits.write(prefix, width);
+        if next == FULL {
+            bits.write(CLEAR, width);
+            table.clear();
+            next = END + 1;
+            width = 9;
+        } else {
+            // Decoders only learn the code after reading the next one, so
+            // they widen a code later than it is added
+            if next == 1 << width {
+                width += 1;
+            }
+            table.insert((prefix, pixel), next);
+            next += 1;
+        }
+        prefix = u32::from(pixel);
+    }
+    bits.write(prefix, width);
+    if next == 1 << width && next < FULL {
+        width += 1;
+    }
+    bits.write(END, width);
+    bits.finish()
+}
+
+/// An animated PNG showing `frames` at `fps`.
+pub fn apng(frames: &[Canvas], fps: f64) -> Vec<u8> {
+    let (width, height) = size(frames);
+    let (width, height) = (width as u32, height as u32);
+
+    let mut png = b"\x89PNG\r\n\x1a\n".to_vec();
+    let mut header = Vec::new();
+    header.extend(width.to_be_bytes());
+    header.extend(height.to_be_bytes());
+    // 8 bit grayscale, not interlaced
+    header.extend([8, 0, 0, 0, 0]);
+    chunk(&mut png, b"IHDR", &header);
+
+    let mut control = Vec::new();
+    control.extend((frames.len() as u32).to_be_bytes());
+    // Repeat forever
+    control.extend(0_u32.to_be_bytes());
+    chunk(&mut png, b"acTL", &control);
+
+    // The frame controls and the data of all but the first frame share
+    // their sequence numbers
+    let mut sequence = 0_u32;
+    for (index, (frame, delay)) in frames.iter().zip(delays(frames.len(), fps)).enumerate() {
+        let mut control = Vec::new();
+        control.extend(sequence.to_be_bytes());
+        control.extend(width.to_be_bytes());
+        control.extend(height.to_be_bytes());
+        control.extend([0; 8]);
+        control.extend(delay.to_be_bytes());
+        control.extend(100_u16.to_be_bytes());
+        // Leave the frame when it is done, and replace what was there
+        control.extend([0, 0]);
+        chunk(&mut png, b"fcTL", &control);
+        sequence += 1;
+
+        let data = zlib(&scanlines(frame));
+        if index == 0 {
+            chunk(&mut png, b"IDAT", &data);
+        } else {
+            let mut numbered = sequence.to_be_bytes().to_vec();
+            numbered.extend(data);
+            chunk(&mut png, b"fdAT", &numbered);
+            sequence += 1;
+        }
+    }
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+/// Append a PNG chunk.
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+/// The rows of `canvas`, each filtered to its difference from the row
+/// above, which leaves long runs of zeros wherever a drawing is plain.
+fn scanlines(canvas: &Canvas) -> Vec<u8> {
+    const UP: u8 = 2;
+
+    let width = canvas.width();
+    let mut scanlines = Vec::with_capacity((width + 1) * canvas.height());
+    let mut above: &[u8] = &vec![0; width];
+    for row in canvas.pixels().chunks(width) {
+        scanlines.push(UP);
+        scanlines.extend(
+            row.iter()
+                .zip(above)
+                .map(|(&pixel, &up)| pixel.wrapping_sub(up)),
+        );
+        above = row;
+    }
+    scanlines
+}
+
+/// `data` compressed the way PNGs want it, with runs of the same byte as
+/// the only repetition looked for.
+fn zlib(data: &[u8]) -> Vec<u8> {
+    /// The shortest length of each length code, from 257 on.
+    const LENGTHS: [u32; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115,
+        131, 163, 195, 227, 258,
+    ];
+    /// The extra bits after each length code.
+    const EXTRA: [u32; 29] = [
+        0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3, 4, 4, 4, 4, 5, 5, 5, 5, 0,
+    ];
+
+    // The fixed Huffman codes of deflate
+    let symbol = |bits: &mut Bits, symbol: u32| match symbol {
+        0..=143 => bits.write_reversed(0x30 + symbol, 8),
+        144..=255 => bits.write_reversed(0x190 + symbol - 144, 9),
+        256..=279 => bits.write_reversed(symbol - 256, 7),
+        _ => bits.write_reversed(0xc0 + symbol - 280, 8),
+    };
+
+    let mut bits = Bits::default();
+    // A single block, the last one, with the fixed codes
+    bits.write(1, 1);
+    bits.write(1, 2);
+    let mut at = 0;
+    while at < data.len() {
+        let run = match at.checked_sub(1) {
+            Some(before) => data[at..]
+                .iter()
+                .take(258)
+                .take_while(|&&byte| byte == data[before])
+                .count(),
+            None => 0,
+        };
+        if run < 3 {
+            symbol(&mut bits, u32::from(data[at]));
+            at += 1;
+            continue;
+        }
+        let length = run as u32;
+        let code = LENGTHS
+            .iter()
+            .rposition(|&shortest| shortest <= length)
+            .unwrap_or(0);
+        symbol(&mut bits, 257 + code as u32);
+        bits.write(length - LENGTHS[code], EXTRA[code]);
+        // The distance of 1, with the distance code 0
+        bits.write_reversed(0, 5);
+        at += run;
+    }
+    symbol(&mut bits, 256);
+
+    let mut zlib = vec![0x78, 0x01];
+    zlib.extend(bits.finish());
+    zlib.extend(adler32(data).to_be_bytes());
+    zlib
+}
+
+fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
+
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1, 0), |(a, b), &byte| {
+        let a = (a + u32::from(byte)) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A canvas with a dark square in the middle.
+    fn square(width: usize, height: usize) -> Canvas {
+        let mut canvas = Canvas::new(width, height);
+        canvas.fill((width as f64 / 4.0, height as f64 / 4.0), (4.0, 4.0), 0);
+        canvas
+    }
+
+    /// Undo [`lzw`], for GIFs with 8 bit pixels.
+    fn unlzw(data: &[u8]) -> Vec<u8> {
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let mut width = 9;
+        let mut previous: Option<Vec<u8>> = None;
+        let (mut pending, mut count, mut bytes) = (0_u32, 0, data.iter());
+        let mut pixels = Vec::new();
+        loop {
+            while count < width {
+                pending |= u32::from(*bytes.next().unwrap()) << count;
+                count += 8;
+            }
+            let code = (pending & ((1 << width) - 1)) as usize;
+            pending >>= width;
+            count -= width;
+            match code {
+                256 => {
+                    table = (0..=255).map(|pixel| vec![pixel]).collect();
+                    table.extend([Vec::new(), Vec::new()]);
+                    width = 9;
+                    previous = None;
+                    continue;
+                }
+                257 => return pixels,
+                _ => {}
+            }
+            let entry = match (table.get(code), &previous) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(previous)) => [&previous[..], &previous[..1]].concat(),
+                (None, None) => panic!("unknown code {code}"),
+            };
+            if let Some(previous) = previous {
+                if table.len() < 4096 {
+                    table.push([&previous[..], &entry[..1]].concat());
+                }
+            }
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+            pixels.extend(&entry);
+            previous = Some(entry);
+        }
+    }
+
+    #[test]
+    fn test_lzw() {
+        assert!(unlzw(&lzw(&[])).is_empty());
+        assert_eq!(unlzw(&lzw(&[7, 7, 7, 7, 7])), [7, 7, 7, 7, 7]);
+
+        // Enough different pixels to fill the table a few times over
+        let pixels: Vec<u8> = (0..20_000_u32)
+            .map(|i| (i.wrapping_mul(2_654_435_761) >> 13) as u8)
+            .collect();
+        assert_eq!(unlzw(&lzw(&pixels)), pixels);
+        let canvas = square(40, 30);
+        assert_eq!(unlzw(&lzw(canvas.pixels())), canvas.pixels());
+    }
+
+    #[test]
+    fn test_delays() {
+        assert!(delays(4, 25.0).all(|delay| delay == 4));
+        // 3 1/3 hundredths each, without drifting
+        assert_eq!(delays(3, 30.0).collect::<Vec<_>>(), [3, 4, 3]);
+    }
+
+    #[test]
+    fn test_gif() {
+        let frames = [square(40, 30), Canvas::new(40, 30)];
+        let gif = gif(&frames, 10.0);
+        assert!(gif.starts_with(b"GIF89a\x28\x00\x1e\x00\xf7"));
+        assert_eq!(gif.last(), Some(&0x3b));
+        // A graphic control with a tenth of a second for each frame
+        let controls = gif.windows(6).filter(|w| w == b"\x21\xf9\x04\x00\x0a\x00");
+        assert_eq!(controls.count(), 2);
+    }
+
+    #[test]
+    fn test_apng() {
+        let frames = [square(40, 30), Canvas::new(40, 30), square(40, 30)];
+        let png = apng(&frames, 30.0);
+        assert!(png.starts_with(b"\x89PNG\r\n\x1a\n"));
+
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let length = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + length]);
+            let crc = u32::from_be_bytes(rest[8 + length..12 + length].try_into().unwrap());
+            assert_eq!(crc, crc32(&rest[4..8 + length]));
+            chunks.push((String::from_utf8(kind.to_vec()).unwrap(), data.to_vec()));
+            rest = &rest[12 + length..];
+        }
+        let kinds: Vec<_> = chunks.iter().map(|(kind, _)| kind.as_str()).collect();
+        assert_eq!(
+            kinds,
+            ["IHDR", "acTL", "fcTL", "IDAT", "fcTL", "fdAT", "fcTL", "fdAT", "IEND"]
+        );
+        // Numbered one after the other, with the first frame's data left out
+        let sequence: Vec<_> = chunks
+            .iter()
+            .filter(|(kind, _)| kind == "fcTL" || kind == "fdAT")
+            .map(|(_, data)| u32::from_be_bytes(data[..4].try_into().unwrap()))
+            .collect();
+        assert_eq!(sequence, [0, 1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn test_checksums() {
+        assert_eq!(crc32(b"IEND"), 0xae42_6082);
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+        assert_eq!(adler32(b"Wikipedia"), 0x11e6_0398);
+    }
+}
@@ -0,0 +1,140 @@
+//! A C interface for embedding graze in other languages.
+//!
+//! ```c
+//! GrazeResult *result = graze_render("circle (pnt2 5 5) 2");
+//! if (graze_result_is_ok(result)) {
+//!     for (size_t i = 0; i < graze_result_page_count(result); i++)
+//!         puts(graze_result_page(result, i));
+//! } else {
+//!     fputs(graze_result_error(result), stderr);
+//! }
+//! graze_result_free(result);
+//! ```
+
+use std::{
+    ffi::{c_char, CStr, CString},
+    ptr,
+};
+
+/// The outcome of [`graze_render`]. Owned by the caller until passed
+/// to [`graze_result_free`].
+pub struct GrazeResult(Result<Vec<CString>, CString>);
+
+fn c_string(text: String) -> CString {
+    // Interior NULs can't cross the boundary, drop them rather than failing
+    CString::new(text.replace('\0', "")).expect("All NUL bytes were removed")
+}
+
+/// Render a NUL-terminated source string. Never returns null, a null
+/// `source` gives a failed result.
+///
+/// # Safety
+/// `source` must be null or point to a NUL-terminated string that stays
+/// valid and unchanged until this returns.
+#[no_mangle]
+pub unsafe extern "C" fn graze_render(source: *const c_char) -> *mut GrazeResult {
+    if source.is_null() {
+        let error = c_string("Source is null".into());
+        return Box::into_raw(Box::new(GrazeResult(Err(error))));
+    }
+    let result = match unsafe { CStr::from_ptr(source) }.to_str() {
+        Ok(source) => crate::render(source)
+            .map(|pages| pages.into_iter().map(c_string).collect())
+            .map_err(|err| c_string(err.to_string())),
+        Err(err) => Err(c_string(format!("Source is not valid UTF-8: {err}"))),
+    };
+    Box::into_raw(Box::new(GrazeResult(result)))
+}
+
+/// # Safety
+/// `result` must come from [`graze_render`] and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn graze_result_is_ok(result: *const GrazeResult) -> bool {
+    unsafe { &*result }.0.is_ok()
+}
+
+/// The number of rendered pages, 0 if rendering failed.
+///
+/// # Safety
+/// `result` must come from [`graze_render`] and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn graze_result_page_count(result: *const GrazeResult) -> usize {
+    unsafe { &*result }.0.as_ref().map_or(0, Vec::len)
+}
+
+/// The SVG document for page `index`, or null if there is no such page.
+/// The string lives as long as `result`.
+///
+/// # Safety
+/// `result` must come from [`graze_render`] and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn graze_result_page(
+    result: *const GrazeResult,
+    index: usize,
+) -> *const c_char {
+    match &unsafe { &*result }.0 {
+        Ok(pages) => pages.get(index).map_or(ptr::null(), |page| page.as_ptr()),
+        Err(_) => ptr::null(),
+    }
+}
+
+/// The error message, or null if rendering succeeded.
+/// The string lives as long as `result`.
+///
+/// # Safety
+/// `result` must come from [`graze_render`] and not have been freed.
+#[no_mangle]
+pub unsafe extern "C" fn graze_result_error(result: *const GrazeResult) -> *const c_char {
+    match &unsafe { &*result }.0 {
+        Ok(_) => ptr::null(),
+        Err(message) => message.as_ptr(),
+    }
+}
+
+/// # Safety
+/// `result` must come from [`graze_render`] or be null, and must not be
+/// used afterwards.
+#[no_mangle]
+pub unsafe extern "C" fn graze_result_free(result: *mut GrazeResult) {
+    if !result.is_null() {
+        drop(unsafe { Box::from_raw(result) });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(text: *const c_char) -> String {
+        unsafe { CStr::from_ptr(text) }.to_str().unwrap().to_owned()
+    }
+
+    #[test]
+    fn test_render_pages() {
+        unsafe {
+            let result = graze_render(c"circle (pnt2 5 5) 2\n#page".as_ptr());
+            assert!(graze_result_is_ok(result));
+            assert_eq!(graze_result_page_count(result), 2);
+            assert!(read(graze_result_page(result, 0)).contains("<circle"));
+            assert!(graze_result_page(result, 2).is_null());
+            assert!(graze_result_error(result).is_null());
+            graze_result_free(result);
+        }
+    }
+
+    #[test]
+    fn test_render_error() {
+        unsafe {
+            let result = graze_render(c"nope".as_ptr());
+            assert!(!graze_result_is_ok(result));
+            assert_eq!(graze_result_page_count(result), 0);
+            assert!(read(graze_result_error(result)).contains("nope"));
+            graze_result_free(result);
+
+            let result = graze_render(ptr::null());
+            assert!(!graze_result_is_ok(result));
+            assert_eq!(read(graze_result_error(result)), "Source is null");
+            graze_result_free(result);
+        }
+    }
+}
@@ -1,14 +1,1004 @@
-pub fn add(left: u64, right: u64) -> u64 {
-    left + right
+use std::fmt::Display;
+
+use graze::{
+    parse_file, DrawBuffer, DrawCommand, LineStyle, Marker, Mm, ParseError, Runtime,
+    StringTokenizer, Transform,
+};
+use thiserror::Error;
+
+use writer::{indent, XmlWriter};
+
+pub mod anim;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "preview")]
+pub mod preview;
+#[cfg(feature = "python")]
+pub mod python;
+pub mod raster;
+pub mod testing;
+mod writer;
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+const RDF_NAMESPACE: &str = "http://www.w3.org/1999/02/22-rdf-syntax-ns#";
+const DC_NAMESPACE: &str = "http://purl.org/dc/elements/1.1/";
+const DEFAULT_DPI: f64 = 96.0;
+const MM_PER_INCH: f64 = 25.4;
+
+/// Decimals kept in coordinates by default, a fraction of a micrometer.
+const DEFAULT_PRECISION: usize = 3;
+
+/// The units SvgOutput writes lengths and coordinates in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Units {
+    /// Pixels at a resolution in dots per inch. Browsers and most tools
+    /// assume 96, at which drawings print at true scale.
+    Px { dpi: f64 },
+    /// Millimeters, for the size of the page as well as everything on it.
+    Mm,
+}
+
+impl Default for Units {
+    fn default() -> Self {
+        Units::Px { dpi: DEFAULT_DPI }
+    }
+}
+
+/// `value` rounded to `decimals` decimals, without trailing zeros.
+fn format_number(value: f64, decimals: usize) -> String {
+    let formatted = format!("{value:.decimals$}");
+    let trimmed = if formatted.contains('.') {
+        formatted.trim_end_matches('0').trim_end_matches('.')
+    } else {
+        &formatted
+    };
+    match trimmed {
+        "-0" => "0".into(),
+        trimmed => trimmed.into(),
+    }
+}
+
+/// Whether `points` are the corners of a rectangle along the axes, which
+/// SVG can draw as a `<rect>`.
+fn is_rectangle(points: &[(Mm, Mm)]) -> bool {
+    let [a, b, c, d] = points else {
+        return false;
+    };
+    let (x, y) = (|point: &(Mm, Mm)| point.0 .0, |point: &(Mm, Mm)| point.1 .0);
+    (x(a) == x(b) && y(b) == y(c) && x(c) == x(d) && y(d) == y(a))
+        || (y(a) == y(b) && x(b) == x(c) && y(c) == y(d) && x(d) == x(a))
+}
+
+/// Standard base64 with padding, for `data:` URLs.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, &byte)| {
+            group | u32::from(byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let index = (group >> (18 - 6 * i)) & 0x3f;
+                encoded.push(ALPHABET[index as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// The `href` of an `<image>`: the file itself as a `data:` URL if
+/// `embed`, otherwise a link to it. Files that can't be read are linked.
+fn image_href(path: &str, embed: bool) -> String {
+    let extension = std::path::Path::new(path)
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(str::to_ascii_lowercase);
+    let mime = match extension.as_deref() {
+        Some("png") => "image/png",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("svg") => "image/svg+xml",
+        _ => "application/octet-stream",
+    };
+    match std::fs::read(path) {
+        Ok(bytes) if embed => format!("data:{mime};base64,{}", base64(&bytes)),
+        _ => path.to_string(),
+    }
+}
+
+/// Renders draw commands into an SVG document, one per page.
+pub struct SvgOutput {
+    /// Attributes of the current page's root `<svg>` element.
+    root: Vec<(&'static str, String)>,
+    /// The content of the current page's `<defs>`.
+    defs: XmlWriter,
+    /// Everything drawn on the current page; open elements are `<g>`s.
+    body: XmlWriter,
+    /// All pages before the current one.
+    finished: Vec<String>,
+    /// Names and values of the document's metadata, in the order first set.
+    metadata: Vec<(String, String)>,
+    flipped: bool,
+    /// Markers at the start and end of lines drawn from now on.
+    markers: (Option<Marker>, Option<Marker>),
+    line_style: LineStyle,
+    /// The markers in the `<defs>` of the current page.
+    defined: Vec<Marker>,
+    /// How many `<clipPath>`s the current page has, to number their ids.
+    clips: usize,
+    /// The `<g>` of every symbol defined so far, which every page defines.
+    symbols: Vec<String>,
+    /// What was drawn before the symbols being defined, innermost last.
+    outside: Vec<XmlWriter>,
+    units: Units,
+    /// Whether the `viewBox` came from [`DrawCommand::SetView`].
+    has_view: bool,
+    /// Decimals kept in coordinates and lengths, or `None` for all.
+    precision: Option<usize>,
+    /// Whether images are copied into the document instead of linked.
+    embed_images: bool,
+    /// Whether pages are indented instead of written on one line.
+    pretty: bool,
+    /// Whether connected lines are merged into one `<path>`.
+    merge_lines: bool,
+    /// Corners of the connected lines waiting to be merged.
+    run: Vec<(Mm, Mm)>,
+}
+
+impl SvgOutput {
+    pub fn new() -> Self {
+        Self {
+            root: vec![("xmlns", SVG_NAMESPACE.into())],
+            defs: XmlWriter::default(),
+            body: XmlWriter::default(),
+            finished: Vec::new(),
+            metadata: Vec::new(),
+            flipped: false,
+            markers: (None, None),
+            line_style: LineStyle::default(),
+            defined: Vec::new(),
+            clips: 0,
+            symbols: Vec::new(),
+            outside: Vec::new(),
+            units: Units::default(),
+            has_view: false,
+            precision: Some(DEFAULT_PRECISION),
+            embed_images: true,
+            pretty: false,
+            merge_lines: false,
+            run: Vec::new(),
+        }
+    }
+
+    /// Put each element on its own line, indented by how deeply it is
+    /// nested, which is easier to read and diff. By default, each page is
+    /// written on a single line.
+    pub fn set_pretty(&mut self, pretty: bool) {
+        self.pretty = pretty;
+    }
+
+    /// Merge lines that continue where the previous one ended into a single
+    /// `<path>`, which keeps curves and hatching made of many short lines
+    /// small. Lines with markers are always drawn on their own. The last
+    /// merged lines are only written once the output is flushed.
+    pub fn set_merge_lines(&mut self, merge: bool) {
+        self.merge_lines = merge;
+    }
+
+    /// Write lengths and coordinates in `units` from now on. Set this before
+    /// drawing anything, as earlier elements keep their units.
+    pub fn set_units(&mut self, units: Units) {
+        self.units = units;
+        if units == Units::Mm {
+            // One pixel, like the default stroke in px
+            let width = self.length(Mm(MM_PER_INCH / DEFAULT_DPI));
+            self.set_root_attribute("stroke-width", width);
+        }
+    }
+
+    /// Round coordinates and lengths to `decimals` decimals, or write them
+    /// with full precision if `None`. Three decimals are kept by default.
+    pub fn set_precision(&mut self, decimals: Option<usize>) {
+        self.precision = decimals;
+    }
+
+    /// Link to image files instead of copying them into the document,
+    /// which keeps it small but only works next to the files.
+    pub fn set_embed_images(&mut self, embed: bool) {
+        self.embed_images = embed;
+    }
+
+    /// Render every page, including the current one.
+    pub fn pages(&self) -> Vec<String> {
+        let mut pages = self.finished.clone();
+        pages.push(self.to_string());
+        pages
+    }
+
+    /// A length or coordinate in the units of the document.
+    fn length(&self, mm: Mm) -> String {
+        let length = match self.units {
+            Units::Px { dpi } => mm.0 * dpi / MM_PER_INCH,
+            Units::Mm => mm.0,
+        };
+        match self.precision {
+            Some(decimals) => format_number(length, decimals),
+            None => length.to_string(),
+        }
+    }
+
+    /// The `transform` attribute of a `<use>`. The factors keep three more
+    /// decimals than lengths, since they multiply them.
+    fn matrix(&self, transform: Transform) -> String {
+        let factor = |value: f64| match self.precision {
+            Some(decimals) => format_number(value, decimals + 3),
+            None => value.to_string(),
+        };
+        let Transform { a, b, c, d, e, f } = transform;
+        format!(
+            "matrix({},{},{},{},{},{})",
+            factor(a),
+            factor(b),
+            factor(c),
+            factor(d),
+            self.length(Mm(e)),
+            self.length(Mm(f))
+        )
+    }
+
+    /// The `points` attribute of a `<polyline>` or `<polygon>`.
+    fn points_attribute(&self, points: &[(Mm, Mm)]) -> String {
+        points
+            .iter()
+            .map(|&(x, y)| format!("{},{}", self.length(x), self.length(y)))
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    /// The `<title>`, `<desc>` and RDF `<metadata>` of every page.
+    fn metadata_elements(&self) -> XmlWriter {
+        let mut writer = XmlWriter::default();
+        if self.metadata.is_empty() {
+            return writer;
+        }
+        for (name, element) in [("title", "title"), ("description", "desc")] {
+            if let Some((_, value)) = self.metadata.iter().find(|(key, _)| key == name) {
+                writer.text(element, &[], value);
+            }
+        }
+        writer.open("metadata", &[]);
+        let namespaces = [
+            ("xmlns:rdf", RDF_NAMESPACE.into()),
+            ("xmlns:dc", DC_NAMESPACE.into()),
+        ];
+        writer.open("rdf:RDF", &namespaces);
+        writer.open("rdf:Description", &[("rdf:about", String::new())]);
+        for (name, value) in &self.metadata {
+            writer.text(&format!("dc:{name}"), &[], value);
+        }
+        writer.close_all();
+        writer
+    }
+
+    fn set_root_attribute(&mut self, name: &'static str, value: String) {
+        match self.root.iter_mut().find(|(existing, _)| *existing == name) {
+            Some(attribute) => attribute.1 = value,
+            None => self.root.push((name, value)),
+        }
+    }
+
+    /// A reference to the definition of `marker`, adding it to the page
+    /// if needed.
+    fn marker_url(&mut self, marker: Marker) -> String {
+        let id = format!("marker-{}", marker.name());
+        if !self.defined.contains(&marker) {
+            let (view_box, reference, size, (shape, attributes)) = match marker {
+                Marker::Arrow => (
+                    "0 0 10 10",
+                    (10, 5),
+                    6,
+                    ("path", vec![("d", "M 0 0 L 10 5 L 0 10 z".into())]),
+                ),
+                Marker::Dot => (
+                    "0 0 10 10",
+                    (5, 5),
+                    4,
+                    (
+                        "circle",
+                        vec![("cx", "5".into()), ("cy", "5".into()), ("r", "5".into())],
+                    ),
+                ),
+                Marker::Bar => (
+                    "0 0 2 10",
+                    (1, 5),
+                    8,
+                    ("rect", vec![("width", "2".into()), ("height", "10".into())]),
+                ),
+            };
+            self.defs.open(
+                "marker",
+                &[
+                    ("id", id.clone()),
+                    ("viewBox", view_box.into()),
+                    ("refX", reference.0.to_string()),
+                    ("refY", reference.1.to_string()),
+                    ("markerWidth", size.to_string()),
+                    ("markerHeight", size.to_string()),
+                    ("orient", "auto-start-reverse".into()),
+                ],
+            );
+            self.defs.empty(shape, &attributes);
+            self.defs.close();
+            self.defined.push(marker);
+        }
+        format!("url(#{id})")
+    }
+
+    /// The style of outlines in the current line style.
+    fn stroke(&self) -> String {
+        let dashes = self.line_style.dashes();
+        if dashes.is_empty() {
+            return "fill:none;stroke:black".into();
+        }
+        let dashes = dashes
+            .iter()
+            .map(|&length| self.length(Mm(length)))
+            .collect::<Vec<_>>()
+            .join(",");
+        format!("fill:none;stroke:black;stroke-dasharray:{dashes}")
+    }
+
+    /// Draw the lines collected in `run`, as a `<path>` if there are several.
+    fn end_run(&mut self) {
+        let run = std::mem::take(&mut self.run);
+        match run[..] {
+            [] | [_] => {}
+            [from, to] => {
+                let attributes = [
+                    ("x1", self.length(from.0)),
+                    ("y1", self.length(from.1)),
+                    ("x2", self.length(to.0)),
+                    ("y2", self.length(to.1)),
+                    ("style", self.stroke()),
+                ];
+                self.body.empty("line", &attributes);
+            }
+            [first, ..] => {
+                let data = format!(
+                    "M{},{} L{}",
+                    self.length(first.0),
+                    self.length(first.1),
+                    self.points_attribute(&run[1..])
+                );
+                let attributes = [("d", data), ("style", self.stroke())];
+                self.body.empty("path", &attributes);
+            }
+        }
+    }
+
+    /// Attributes putting the current markers on the ends of a line.
+    fn marker_attributes(&mut self) -> Vec<(&'static str, String)> {
+        let (start, end) = self.markers;
+        let mut attributes = Vec::new();
+        if let Some(start) = start {
+            attributes.push(("marker-start", self.marker_url(start)));
+        }
+        if let Some(end) = end {
+            attributes.push(("marker-end", self.marker_url(end)));
+        }
+        attributes
+    }
+}
+
+impl Default for SvgOutput {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl DrawBuffer for SvgOutput {
+    fn reset(&mut self) {
+        *self = Self::new();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        if let DrawCommand::Line { from, to } = command {
+            if self.merge_lines && self.markers == (None, None) {
+                if self.run.last() != Some(&from) {
+                    self.end_run();
+                    self.run.push(from);
+                }
+                self.run.push(to);
+                return;
+            }
+        }
+        self.end_run();
+
+        match command {
+            DrawCommand::Line { from, to } => {
+                let mut attributes = vec![
+                    ("x1", self.length(from.0)),
+                    ("y1", self.length(from.1)),
+                    ("x2", self.length(to.0)),
+                    ("y2", self.length(to.1)),
+                    ("style", self.stroke()),
+                ];
+                attributes.extend(self.marker_attributes());
+                self.body.empty("line", &attributes);
+            }
+            DrawCommand::Circle { at, radius } => {
+                let attributes = [
+                    ("cx", self.length(at.0)),
+                    ("cy", self.length(at.1)),
+                    ("r", self.length(radius)),
+                    ("style", self.stroke()),
+                ];
+                self.body.empty("circle", &attributes);
+            }
+            DrawCommand::Path { points, closed } if closed && is_rectangle(&points) => {
+                let xs = points.iter().map(|point| point.0 .0);
+                let ys = points.iter().map(|point| point.1 .0);
+                let (x, y) = (
+                    xs.clone().fold(f64::INFINITY, f64::min),
+                    ys.clone().fold(f64::INFINITY, f64::min),
+                );
+                let (width, height) = (
+                    xs.fold(f64::NEG_INFINITY, f64::max) - x,
+                    ys.fold(f64::NEG_INFINITY, f64::max) - y,
+                );
+                let attributes = [
+                    ("x", self.length(Mm(x))),
+                    ("y", self.length(Mm(y))),
+                    ("width", self.length(Mm(width))),
+                    ("height", self.length(Mm(height))),
+                    ("style", self.stroke()),
+                ];
+                self.body.empty("rect", &attributes);
+            }
+            DrawCommand::Path { points, closed } => {
+                let mut attributes = vec![
+                    ("points", self.points_attribute(&points)),
+                    ("style", self.stroke()),
+                ];
+                if !closed {
+                    attributes.extend(self.marker_attributes());
+                }
+                let name = if closed { "polygon" } else { "polyline" };
+                self.body.empty(name, &attributes);
+            }
+            DrawCommand::Resize { x, y } => match self.units {
+                Units::Px { .. } => {
+                    self.set_root_attribute("width", self.length(x));
+                    self.set_root_attribute("height", self.length(y));
+                }
+                Units::Mm => {
+                    self.set_root_attribute("width", format!("{}mm", self.length(x)));
+                    self.set_root_attribute("height", format!("{}mm", self.length(y)));
+                    // Without a viewBox, one unit inside would be a pixel
+                    if !self.has_view {
+                        let view_box = format!("0 0 {} {}", self.length(x), self.length(y));
+                        self.set_root_attribute("viewBox", view_box);
+                    }
+                }
+            },
+            DrawCommand::SetView {
+                x,
+                y,
+                width,
+                height,
+                flip_y,
+            } => {
+                // Flipping mirrors everything drawn afterwards around the
+                // x axis, so the visible window moves to negative y.
+                let top = if flip_y { Mm(-(y.0 + height.0)) } else { y };
+                let view_box = [x, top, width, height]
+                    .map(|length| self.length(length))
+                    .join(" ");
+                self.set_root_attribute("viewBox", view_box);
+                self.has_view = true;
+
+                if flip_y {
+                    self.flipped = true;
+                    self.body.open("g", &[("transform", "scale(1,-1)".into())]);
+                }
+            }
+            DrawCommand::BeginLayer { name } => {
+                self.body.open("g", &[("id", name.to_string())]);
+            }
+            DrawCommand::BeginClip { region } => {
+                self.clips += 1;
+                let id = format!("clip-{}", self.clips);
+                self.defs.open("clipPath", &[("id", id.clone())]);
+                self.defs
+                    .empty("polygon", &[("points", self.points_attribute(&region))]);
+                self.defs.close();
+                self.body.open("g", &[("clip-path", format!("url(#{id})"))]);
+            }
+            DrawCommand::EndLayer | DrawCommand::EndClip => {
+                self.body.close();
+            }
+            DrawCommand::BeginSymbol { name } => {
+                self.outside.push(std::mem::take(&mut self.body));
+                self.body.open("g", &[("id", format!("symbol-{name}"))]);
+            }
+            DrawCommand::EndSymbol => {
+                if let Some(outside) = self.outside.pop() {
+                    let symbol = std::mem::replace(&mut self.body, outside);
+                    self.symbols.push(symbol.to_closed());
+                }
+            }
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let attributes = [
+                    ("href", format!("#symbol-{name}")),
+                    ("transform", self.matrix(transform)),
+                ];
+                self.body.empty("use", &attributes);
+            }
+            DrawCommand::Text { at, text, size } => {
+                let mut attributes = vec![
+                    ("x", self.length(at.0)),
+                    ("y", self.length(at.1)),
+                    ("font-size", self.length(size)),
+                    ("text-anchor", "middle".into()),
+                    ("dominant-baseline", "central".into()),
+                    ("style", "font-family:sans-serif".into()),
+                ];
+                if self.flipped {
+                    // Flip the glyphs back so they stay upright
+                    attributes[1].1 = self.length(Mm(-at.1 .0));
+                    attributes.push(("transform", "scale(1,-1)".into()));
+                }
+                self.body.text("text", &attributes, &text);
+            }
+            DrawCommand::Image {
+                at,
+                width,
+                height,
+                path,
+            } => {
+                let mut attributes = vec![
+                    ("x", self.length(at.0)),
+                    ("y", self.length(at.1)),
+                    ("width", self.length(width)),
+                    ("height", self.length(height)),
+                    ("preserveAspectRatio", "none".into()),
+                    ("href", image_href(&path, self.embed_images)),
+                ];
+                if self.flipped {
+                    // Flip the image back so it stays upright
+                    attributes[1].1 = self.length(Mm(-(at.1 .0 + height.0)));
+                    attributes.push(("transform", "scale(1,-1)".into()));
+                }
+                self.body.empty("image", &attributes);
+            }
+            DrawCommand::SetMarkers { start, end } => self.markers = (start, end),
+            DrawCommand::SetLineStyle { style } => self.line_style = style,
+            DrawCommand::Metadata { name, value } => {
+                match self.metadata.iter_mut().find(|(key, _)| *key == name) {
+                    Some(entry) => entry.1 = value.to_string(),
+                    None => self.metadata.push((name.to_string(), value.to_string())),
+                }
+            }
+        }
+    }
+
+    /// Start a blank page with the same size and view as the current one.
+    fn new_page(&mut self) {
+        self.end_run();
+        let mut next = Self::new();
+        next.units = self.units;
+        next.has_view = self.has_view;
+        for (name, value) in &self.root {
+            if ["width", "height", "viewBox", "stroke-width"].contains(name) {
+                next.set_root_attribute(name, value.clone());
+            }
+        }
+        if self.flipped {
+            next.flipped = true;
+            next.body.open("g", &[("transform", "scale(1,-1)".into())]);
+        }
+        next.metadata = self.metadata.clone();
+        next.symbols = self.symbols.clone();
+        next.markers = self.markers;
+        next.line_style = self.line_style;
+        next.precision = self.precision;
+        next.embed_images = self.embed_images;
+        next.merge_lines = self.merge_lines;
+        next.pretty = self.pretty;
+
+        next.finished = std::mem::take(&mut self.finished);
+        next.finished.push(self.to_string());
+        *self = next;
+    }
+
+    fn flush(&mut self) {
+        self.end_run();
+        self.body.close_all();
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum RenderError {
+    #[error(transparent)]
+    Parse(#[from] ParseError),
+    #[error(transparent)]
+    Runtime(#[from] graze::Error),
+}
+
+/// Parse and run `source`, returning one SVG document per page.
+pub fn render(source: &str) -> Result<Vec<String>, RenderError> {
+    let program = parse_file(&mut StringTokenizer::new(&source))?;
+    let mut runtime = Runtime::<SvgOutput>::default();
+    runtime.execute(program)?;
+    Ok(runtime.finish().pages())
+}
+
+impl Display for SvgOutput {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let mut svg = String::from("<svg");
+        for (name, value) in &self.root {
+            svg.push_str(&format!(r#" {name}="{value}""#));
+        }
+        svg.push('>');
+        svg.push_str(&self.metadata_elements().to_closed());
+        if !self.defs.is_empty() || !self.symbols.is_empty() {
+            let symbols = self.symbols.concat();
+            svg.push_str(&format!("<defs>{}{symbols}</defs>", self.defs.to_closed()));
+        }
+        svg.push_str(&self.body.to_closed());
+        svg.push_str("</svg>");
+
+        if self.pretty {
+            write!(f, "{}", indent(&svg))
+        } else {
+            write!(f, "{svg}")
+        }
+    }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    fn px(mm: f64) -> String {
+        SvgOutput::new().length(Mm(mm))
+    }
+
+    #[test]
+    fn test_line_is_converted_to_px() {
+        let mut output = SvgOutput::new();
+        output.draw(DrawCommand::Line {
+            from: (Mm(0.0), Mm(0.0)),
+            to: (Mm(10.0), Mm(0.0)),
+        });
+        let svg = output.to_string();
+
+        assert!(svg.starts_with("<svg"));
+        assert!(svg.contains(r#"x2="37.795""#));
+    }
+
+    #[test]
+    fn test_metadata() {
+        let pages = render(
+            "#title \"Plan & section\"
+#meta creator \"Ada\"
+#page
+#title \"Plan\"",
+        )
+        .unwrap();
+
+        assert!(pages[0].contains("<title>Plan &amp; section</title>"));
+        assert!(!pages[0].contains("<desc>"));
+        assert!(pages[0].contains("<dc:creator>Ada</dc:creator>"));
+        assert!(pages[1].contains("<title>Plan</title>"));
+        assert!(pages[1].contains("<dc:title>Plan</dc:title><dc:creator>Ada</dc:creator>"));
+        assert!(pages[1].contains(r#"xmlns:dc="http://purl.org/dc/elements/1.1/""#));
+    }
+
+    #[test]
+    fn test_pretty() {
+        let mut output = SvgOutput::new();
+        output.set_pretty(true);
+        output.draw(DrawCommand::BeginLayer {
+            name: "outline".into(),
+        });
+        output.draw(DrawCommand::Circle {
+            at: (Mm(0.0), Mm(0.0)),
+            radius: Mm(1.0),
+        });
+        let svg = output.to_string();
+
+        let lines = svg.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[1], r#"  <g id="outline">"#);
+        assert!(lines[2].starts_with("    <circle"));
+        assert_eq!(lines[3..], ["  </g>", "</svg>"]);
+    }
+
+    #[test]
+    fn test_units() {
+        let mut output = SvgOutput::new();
+        output.set_units(Units::Mm);
+        output.draw(DrawCommand::Resize {
+            x: Mm(210.0),
+            y: Mm(297.0),
+        });
+        output.draw(DrawCommand::Circle {
+            at: (Mm(10.0), Mm(20.0)),
+            radius: Mm(5.0),
+        });
+        let svg = output.to_string();
+        assert!(svg.contains(r#"width="210mm" height="297mm""#));
+        assert!(svg.contains(r#"viewBox="0 0 210 297""#));
+        assert!(svg.contains(r#"cx="10" cy="20" r="5""#));
+
+        let mut output = SvgOutput::new();
+        output.set_units(Units::Px { dpi: 254.0 });
+        output.draw(DrawCommand::Resize {
+            x: Mm(10.0),
+            y: Mm(20.0),
+        });
+        assert!(output.to_string().contains(r#"width="100" height="200""#));
+    }
+
+    #[test]
+    fn test_precision() {
+        assert_eq!(format_number(37.795275, 3), "37.795");
+        assert_eq!(format_number(3.7800001, 3), "3.78");
+        assert_eq!(format_number(2.0, 3), "2");
+        assert_eq!(format_number(-0.0001, 3), "0");
+        assert_eq!(format_number(1234.6, 0), "1235");
+
+        let mut output = SvgOutput::new();
+        output.set_precision(None);
+        output.draw(DrawCommand::Circle {
+            at: (Mm(10.0), Mm(0.0)),
+            radius: Mm(1.0),
+        });
+        assert!(output.to_string().contains(r#"cx="37.795275590551185""#));
+    }
+
+    #[test]
+    fn test_layers_become_groups() {
+        let mut output = SvgOutput::new();
+        output.draw(DrawCommand::BeginLayer {
+            name: "outline".into(),
+        });
+        output.draw(DrawCommand::Circle {
+            at: (Mm(1.0), Mm(1.0)),
+            radius: Mm(1.0),
+        });
+        output.draw(DrawCommand::EndLayer);
+        output.draw(DrawCommand::Line {
+            from: (Mm(0.0), Mm(0.0)),
+            to: (Mm(1.0), Mm(1.0)),
+        });
+        let svg = output.to_string();
+
+        let group = svg.find(r#"<g id="outline">"#).unwrap();
+        let circle = svg.find("<circle").unwrap();
+        let group_end = svg.find("</g>").unwrap();
+        let line = svg.find("<line").unwrap();
+        assert!(group < circle && circle < group_end && group_end < line);
+    }
+
+    #[test]
+    fn test_new_page_keeps_size() {
+        let mut output = SvgOutput::new();
+        output.draw(DrawCommand::Resize {
+            x: Mm(10.0),
+            y: Mm(10.0),
+        });
+        output.draw(DrawCommand::Circle {
+            at: (Mm(1.0), Mm(1.0)),
+            radius: Mm(1.0),
+        });
+        output.new_page();
+        let pages = output.pages();
+
+        assert_eq!(pages.len(), 2);
+        assert!(pages[0].contains("<circle"));
+        assert!(!pages[1].contains("<circle"));
+        assert!(pages[1].contains(&format!(r#"width="{}""#, px(10.0))));
+    }
+
+    #[test]
+    fn test_render() {
+        let pages = render("#screen 10 10\ncircle (pnt2 5 5) 2").unwrap();
+        assert_eq!(pages.len(), 1);
+        assert!(pages[0].contains("<circle"));
+
+        assert!(matches!(render("nope"), Err(RenderError::Runtime(_))));
+        assert!(matches!(render("(circle"), Err(RenderError::Parse(_))));
+    }
+
+    #[test]
+    fn test_flipped_view() {
+        let mut output = SvgOutput::new();
+        output.draw(DrawCommand::SetView {
+            x: Mm(0.0),
+            y: Mm(0.0),
+            width: Mm(10.0),
+            height: Mm(10.0),
+            flip_y: true,
+        });
+        let svg = output.to_string();
+
+        let ten = px(10.0);
+        assert!(svg.contains(&format!(r#"viewBox="0 -{ten} {ten} {ten}""#)));
+        assert!(svg.contains(r#"<g transform="scale(1,-1)">"#));
+    }
+    #[test]
+    fn test_text() {
+        let mut output = SvgOutput::new();
+        output.draw(DrawCommand::Text {
+            at: (Mm(5.0), Mm(2.0)),
+            text: "12.5".into(),
+            size: Mm(3.5),
+        });
+        let svg = output.to_string();
+
+        assert!(svg.contains(">12.5</text>"));
+        assert!(svg.contains(&format!(r#"y="{}""#, px(2.0))));
+    }
+
+    #[test]
+    fn test_merge_lines() {
+        let mut output = SvgOutput::new();
+        output.set_merge_lines(true);
+        let corners = [(0.0, 0.0), (1.0, 0.0), (1.0, 1.0), (0.0, 1.0)];
+        for pair in corners.windows(2) {
+            output.draw(DrawCommand::Line {
+                from: (Mm(pair[0].0), Mm(pair[0].1)),
+                to: (Mm(pair[1].0), Mm(pair[1].1)),
+            });
+        }
+        output.draw(DrawCommand::Line {
+            from: (Mm(5.0), Mm(5.0)),
+            to: (Mm(6.0), Mm(6.0)),
+        });
+        output.flush();
+        let svg = output.to_string();
+
+        let one = px(1.0);
+        assert!(svg.contains(&format!(r#"d="M0,0 L{one},0 {one},{one} 0,{one}""#)));
+        assert_eq!(svg.matches("<path").count(), 1);
+        assert_eq!(svg.matches("<line").count(), 1);
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foobar"), "Zm9vYmFy");
+    }
+
+    #[test]
+    fn test_images() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/Cargo.toml");
+        let image = DrawCommand::Image {
+            at: (Mm(1.0), Mm(2.0)),
+            width: Mm(30.0),
+            height: Mm(20.0),
+            path: path.into(),
+        };
+
+        let mut output = SvgOutput::new();
+        output.draw(image.clone());
+        let embedded = base64(&std::fs::read(path).unwrap());
+        let href = format!(r#"href="data:application/octet-stream;base64,{embedded}""#);
+        assert!(output.to_string().contains(&href));
+
+        let mut output = SvgOutput::new();
+        output.set_embed_images(false);
+        output.draw(image);
+        let svg = output.to_string();
+        assert!(svg.contains(&format!(r#"href="{path}""#)));
+        assert!(svg.contains(&format!(r#"width="{}""#, px(30.0))));
+    }
+    #[test]
+    fn test_markers_are_defined_once() {
+        let mut output = SvgOutput::new();
+        output.draw(DrawCommand::SetMarkers {
+            start: Some(Marker::Dot),
+            end: Some(Marker::Arrow),
+        });
+        for _ in 0..2 {
+            output.draw(DrawCommand::Line {
+                from: (Mm(0.0), Mm(0.0)),
+                to: (Mm(1.0), Mm(1.0)),
+            });
+        }
+        output.draw(DrawCommand::SetMarkers {
+            start: None,
+            end: None,
+        });
+        output.draw(DrawCommand::Line {
+            from: (Mm(0.0), Mm(0.0)),
+            to: (Mm(1.0), Mm(1.0)),
+        });
+        let svg = output.to_string();
+
+        assert_eq!(svg.matches(r#"id="marker-arrow""#).count(), 1);
+        assert_eq!(svg.matches(r#"id="marker-dot""#).count(), 1);
+        assert_eq!(svg.matches(r#"marker-end="url(#marker-arrow)""#).count(), 2);
+        assert_eq!(svg.matches(r#"marker-start="url(#marker-dot)""#).count(), 2);
+        assert_eq!(svg.matches("<line").count(), 3);
+    }
+    #[test]
+    fn test_paths() {
+        let pages = render("ngon (pnt2 0 0) 10 3").unwrap();
+        assert_eq!(pages[0].matches("<polygon").count(), 1);
+        assert!(pages[0].contains(&format!(r#"points="{},0"#, px(10.0))));
+    }
+    #[test]
+    fn test_rectangles() {
+        let pages = render(
+            "rect (pnt2 1 2) 3 4
+rotate 45deg
+rect (pnt2 1 2) 3 4",
+        )
+        .unwrap();
+        assert_eq!(pages[0].matches("<rect").count(), 1);
+        assert_eq!(pages[0].matches("<polygon").count(), 1);
+        assert!(pages[0].contains(&format!(r#"height="{}""#, px(4.0))));
+    }
+    #[test]
+    fn test_clip_paths() {
+        let pages = render(
+            "clip (rect (pnt2 0 0) 5 5)
+circle (pnt2 5 5) 3
+endclip
+circle (pnt2 5 5) 1",
+        )
+        .unwrap();
+        let svg = &pages[0];
+
+        assert!(svg.contains(r#"<clipPath id="clip-1"><polygon"#));
+        let group = svg.find(r#"<g clip-path="url(#clip-1)">"#).unwrap();
+        let first = svg.find("<circle").unwrap();
+        let group_end = svg.find("</g>").unwrap();
+        let second = svg.rfind("<circle").unwrap();
+        assert!(group < first && first < group_end && group_end < second);
+    }
+
     #[test]
-    fn it_works() {
-        let result = add(2, 2);
-        assert_eq!(result, 4);
+    fn test_line_styles() {
+        let pages = render("linestyle \"hidden\"\ncircle (pnt2 5 5) 1\nlinestyle \"continuous\"\ncircle (pnt2 5 5) 2").unwrap();
+        let dashes = format!("stroke-dasharray:{},{}", px(3.0), px(0.75));
+        assert_eq!(pages[0].matches(&dashes).count(), 1);
+        assert_eq!(pages[0].matches("<circle").count(), 2);
+    }
+
+    #[test]
+    fn test_symbols() {
+        let pages = render(
+            "#symbol bolt
+circle (pnt2 0 0) 1
+#end
+place \"bolt\" (pnt2 10 0)
+place \"bolt\" (pnt2 0 10) rotate=90deg
+#page
+place \"bolt\" (pnt2 5 5) scale=2",
+        )
+        .unwrap();
+
+        let definition = r#"<defs><g id="symbol-bolt"><circle"#;
+        assert!(pages[0].contains(definition));
+        assert_eq!(pages[0].matches("<circle").count(), 1);
+        let offset = px(10.0);
+        assert!(pages[0].contains(&format!(
+            r##"<use href="#symbol-bolt" transform="matrix(1,0,0,1,{offset},0)"/>"##
+        )));
+        assert!(pages[0].contains(&format!(r#"transform="matrix(0,1,-1,0,0,{offset})"/>"#)));
+        // Later pages can place symbols defined earlier
+        assert!(pages[1].contains(definition));
+        assert!(pages[1].contains(r#"transform="matrix(2,0,0,2,"#));
     }
 }
@@ -0,0 +1,887 @@
+use std::{
+    fs,
+    path::{Path, PathBuf},
+    process::ExitCode,
+    thread,
+    time::Duration,
+};
+
+use graze::{
+    check::check_program,
+    diagnostic::Diagnostic,
+    format::{format_expression, format_program},
+    parse_file, Cache, DrawBuffer, DrawCommand, Expression, Observer, Param, Position, Program,
+    Runtime, Scalar, Snapshot, StackPolicy, StringTokenizer, Value,
+};
+use graze_svg::{
+    anim::Format as AnimFormat,
+    raster::{Canvas, Recording, View, Window},
+    SvgOutput, Units,
+};
+use serde_json::json;
+
+const USAGE: &str =
+    "usage: graze <script> [-o <output.svg>] [-D <name>=<value>]... [--keep-stack] [--trace]
+             [--merge-lines] [--pretty] [--precision <decimals>] [--dpi <dpi> | --mm]
+             [-j <threads>] [--watch] [--preview] [--diagnostics human|json]
+             [--params <manifest.json>]
+       graze anim <script> --frames <count> [--fps <fps>] [-o <output.gif|.png>]
+             [-D <name>=<value>]... [--dpi <dpi>] [-j <threads>] [--diagnostics human|json]
+       graze fmt <script>...
+       graze doc <builtin>";
+
+/// How problems with the script are printed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Format {
+    /// With the source they are about, on stderr.
+    Human,
+    /// One JSON object per line on stdout, for editors and CI tools.
+    Json,
+}
+
+/// Why a command failed.
+#[derive(Debug)]
+enum Failure {
+    /// Problems with the script, already reported.
+    Diagnosed,
+    Other(String),
+}
+
+impl From<String> for Failure {
+    fn from(message: String) -> Self {
+        Failure::Other(message)
+    }
+}
+
+/// How often `--watch` checks whether the script changed.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+
+struct Args {
+    input: PathBuf,
+    output: PathBuf,
+    keep_stack: bool,
+    trace: bool,
+    merge_lines: bool,
+    pretty: bool,
+    /// Decimals kept in coordinates, if not the backend's default.
+    precision: Option<usize>,
+    /// Units to write the SVG in, if not the backend's default.
+    units: Option<Units>,
+    /// Variables to define before running the script.
+    defines: Vec<(String, Value)>,
+    /// Threads to run independent instructions on.
+    threads: usize,
+    /// Render again whenever the script changes.
+    watch: bool,
+    /// Show every rendering in a window while watching.
+    preview: bool,
+    diagnostics: Format,
+    /// Where to write the `#param`s the script declared, as JSON.
+    params: Option<PathBuf>,
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Args, String> {
+    let mut input = None;
+    let mut output = None;
+    let mut keep_stack = false;
+    let mut trace = false;
+    let mut merge_lines = false;
+    let mut pretty = false;
+    let mut precision = None;
+    let mut units = None;
+    let mut defines = Vec::new();
+    let mut threads = 1;
+    let mut watch = false;
+    let mut preview = false;
+    let mut diagnostics = Format::Human;
+    let mut params = None;
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-o" | "--output" => {
+                let path = args.next().ok_or("-o expects a file name")?;
+                output = Some(PathBuf::from(path));
+            }
+            "-D" | "--define" => {
+                let define = args.next().ok_or("-D expects name=value")?;
+                defines.push(parse_define(&define)?);
+            }
+            "--keep-stack" => keep_stack = true,
+            "--trace" => trace = true,
+            "--merge-lines" => merge_lines = true,
+            "--pretty" => pretty = true,
+            "--precision" => {
+                let decimals = args.next().ok_or("--precision expects a number")?;
+                let decimals = decimals
+                    .parse()
+                    .map_err(|_| format!("invalid precision '{decimals}'"))?;
+                precision = Some(decimals);
+            }
+            "--dpi" => {
+                let dpi = args.next().ok_or("--dpi expects a number")?;
+                let dpi = dpi
+                    .parse()
+                    .ok()
+                    .filter(|&dpi: &f64| dpi > 0.0)
+                    .ok_or_else(|| format!("invalid resolution '{dpi}'"))?;
+                units = Some(Units::Px { dpi });
+            }
+            "--mm" => units = Some(Units::Mm),
+            "-j" | "--jobs" => {
+                let count = args.next().ok_or("-j expects a number of threads")?;
+                threads = count
+                    .parse()
+                    .ok()
+                    .filter(|&threads| threads > 0)
+                    .ok_or_else(|| format!("invalid number of threads '{count}'"))?;
+            }
+            "--watch" => watch = true,
+            "--preview" => {
+                preview = true;
+                // There is nothing to preview between renderings otherwise
+                watch = true;
+            }
+            "--diagnostics" => {
+                let format = args.next().ok_or("--diagnostics expects human or json")?;
+                diagnostics = match format.as_str() {
+                    "human" => Format::Human,
+                    "json" => Format::Json,
+                    _ => return Err(format!("unknown diagnostics format '{format}'")),
+                };
+            }
+            "--params" => {
+                let path = args.next().ok_or("--params expects a file name")?;
+                params = Some(PathBuf::from(path));
+            }
+            _ if input.is_none() => input = Some(PathBuf::from(arg)),
+            _ => return Err(format!("unexpected argument '{arg}'")),
+        }
+    }
+
+    let input = input.ok_or("no input script given")?;
+    let output = output.unwrap_or_else(|| input.with_extension("svg"));
+    Ok(Args {
+        input,
+        output,
+        keep_stack,
+        trace,
+        merge_lines,
+        pretty,
+        precision,
+        units,
+        defines,
+        threads,
+        watch,
+        preview,
+        diagnostics,
+        params,
+    })
+}
+
+/// `graze anim`, which renders the script once per frame with `frame` set
+/// to the number of the frame and `t` to the seconds since the first.
+struct Anim {
+    args: Args,
+    frames: usize,
+    fps: f64,
+}
+
+/// The frames per second of animations unless `--fps` says otherwise.
+const DEFAULT_FPS: f64 = 30.0;
+
+const MM_PER_INCH: f64 = 25.4;
+
+/// Parse the arguments of `graze anim`, which are those of a rendering
+/// plus the frames to render.
+fn parse_anim_args(mut args: impl Iterator<Item = String>) -> Result<Anim, String> {
+    let mut frames = None;
+    let mut fps = DEFAULT_FPS;
+    let mut rest = Vec::new();
+
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => {
+                let count = args.next().ok_or("--frames expects a number")?;
+                let count = count
+                    .parse()
+                    .ok()
+                    .filter(|&count| count > 0)
+                    .ok_or_else(|| format!("invalid number of frames '{count}'"))?;
+                frames = Some(count);
+            }
+            "--fps" => {
+                let rate = args.next().ok_or("--fps expects a number")?;
+                fps = rate
+                    .parse()
+                    .ok()
+                    .filter(|&fps: &f64| fps > 0.0 && fps.is_finite())
+                    .ok_or_else(|| format!("invalid frame rate '{rate}'"))?;
+            }
+            _ => rest.push(arg),
+        }
+    }
+
+    let named_output = rest.iter().any(|arg| arg == "-o" || arg == "--output");
+    let mut args = parse_args(rest.into_iter())?;
+    if !named_output {
+        args.output = args.input.with_extension("gif");
+    }
+    let frames = frames.ok_or("anim expects --frames")?;
+    Ok(Anim { args, frames, fps })
+}
+
+/// Split `name=value`. Values that look like numbers become scalars,
+/// anything else is passed as a string.
+fn parse_define(define: &str) -> Result<(String, Value), String> {
+    let (name, value) = define
+        .split_once('=')
+        .ok_or_else(|| format!("expected name=value, got '{define}'"))?;
+    if name.is_empty() {
+        return Err(format!("missing variable name in '{define}'"));
+    }
+
+    let value = if let Ok(integer) = value.parse::<i64>() {
+        Value::Scalar(Scalar::from(integer))
+    } else if let Ok(float) = value.parse::<f64>() {
+        Value::Scalar(Scalar::from(float))
+    } else {
+        Value::String(value.into())
+    };
+    Ok((name.to_owned(), value))
+}
+
+/// `out.svg` for a single page, `out-1.svg`, `out-2.svg`, ... otherwise.
+fn page_paths(output: &Path, count: usize) -> Vec<PathBuf> {
+    if count == 1 {
+        return vec![output.to_owned()];
+    }
+
+    let stem = output.file_stem().unwrap_or_default().to_string_lossy();
+    let extension = output.extension().unwrap_or_default().to_string_lossy();
+    (1..=count)
+        .map(|page| output.with_file_name(format!("{stem}-{page}.{extension}")))
+        .collect()
+}
+
+/// Explains on stderr how each expression changes the stack.
+#[derive(Default)]
+struct Tracer {
+    /// The source and stack before each expression that is still running.
+    running: Vec<(String, Vec<Value>)>,
+}
+
+fn show_stack(values: &[Value]) -> String {
+    let values = values.iter().map(ToString::to_string).collect::<Vec<_>>();
+    format!("[{}]", values.join(", "))
+}
+
+impl Observer for Tracer {
+    fn before(&mut self, expression: &Expression, state: Snapshot) {
+        let source = format_expression(&expression.content);
+        let first_line = source.lines().next().unwrap_or_default().to_owned();
+        self.running.push((first_line, state.stack().to_vec()));
+    }
+
+    fn after(&mut self, position: Position, state: Snapshot) {
+        let Some((source, before)) = self.running.pop() else {
+            return;
+        };
+        eprintln!(
+            "trace: line {}: {source}: {} -> {}",
+            position.line() + 1,
+            show_stack(&before),
+            show_stack(state.stack()),
+        );
+    }
+}
+
+/// `diagnostic` as a JSON object. Lines and columns count from one.
+fn to_json(diagnostic: &Diagnostic, name: &str, source: &str) -> serde_json::Value {
+    let location = diagnostic.location(source);
+    json!({
+        "severity": diagnostic.severity.name(),
+        "code": diagnostic.code,
+        "message": diagnostic.message,
+        "hint": diagnostic.hint,
+        "file": name,
+        "span": diagnostic.span.as_ref().map(|span| json!({ "start": span.start, "end": span.end })),
+        "line": location.map(|(line, _)| line),
+        "column": location.map(|(_, column)| column),
+    })
+}
+
+/// The parameters a script declared, for frontends to show sliders for
+/// and pass back with `-D`.
+fn params_json(params: &[Param]) -> serde_json::Value {
+    let number = |scalar: Scalar| f64::from(scalar);
+    params
+        .iter()
+        .map(|param| {
+            json!({
+                "name": param.name.as_str(),
+                "min": number(param.min),
+                "max": number(param.max),
+                "default": number(param.default),
+                "value": number(param.value),
+            })
+        })
+        .collect()
+}
+
+fn report(format: Format, diagnostic: &Diagnostic, name: &str, source: &str) {
+    match format {
+        Format::Human => eprintln!(
+            "{}: {}",
+            diagnostic.severity,
+            diagnostic.render(name, source)
+        ),
+        Format::Json => println!("{}", to_json(diagnostic, name, source)),
+    }
+}
+
+/// Writes the SVG and, while previewing, keeps the draw commands for the
+/// window.
+#[derive(Default)]
+struct Output {
+    svg: SvgOutput,
+    recording: Option<Recording>,
+}
+
+impl DrawBuffer for Output {
+    fn reset(&mut self) {
+        self.svg.reset();
+        if let Some(recording) = &mut self.recording {
+            recording.reset();
+        }
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        if let Some(recording) = &mut self.recording {
+            recording.draw(command.clone());
+        }
+        self.svg.draw(command);
+    }
+
+    fn new_page(&mut self) {
+        self.svg.new_page();
+        if let Some(recording) = &mut self.recording {
+            recording.new_page();
+        }
+    }
+
+    fn flush(&mut self) {
+        self.svg.flush();
+    }
+}
+
+/// Render the script once, reusing unchanged instructions from `cache`,
+/// and write the SVG of each page.
+fn render(args: &Args, cache: Option<&mut Cache>) -> Result<Output, Failure> {
+    let source = fs::read_to_string(&args.input)
+        .map_err(|err| format!("{}: {err}", args.input.display()))?;
+    let name = args.input.display().to_string();
+    let report = |diagnostic: Diagnostic| report(args.diagnostics, &diagnostic, &name, &source);
+    let program = compile(&source, report)?;
+
+    let mut runtime = Runtime::<Output>::default();
+    runtime.set_log(std::io::stdout());
+    let output = runtime.backend_mut();
+    output.svg.set_merge_lines(args.merge_lines);
+    output.svg.set_pretty(args.pretty);
+    if let Some(units) = args.units {
+        output.svg.set_units(units);
+    }
+    if let Some(decimals) = args.precision {
+        output.svg.set_precision(Some(decimals));
+    }
+    if args.preview {
+        output.recording = Some(Recording::default());
+    }
+    if args.keep_stack {
+        runtime.set_stack_policy(StackPolicy::Keep);
+    }
+    runtime.set_threads(args.threads);
+    for (name, value) in &args.defines {
+        runtime
+            .set_variable(name, value.clone())
+            .map_err(|err| err.to_string())?;
+    }
+    let result = match cache {
+        _ if args.trace => runtime.execute_with_hooks(program, &mut Tracer::default()),
+        Some(cache) => runtime.execute_cached(program, cache),
+        None => runtime.execute(program),
+    };
+    for warning in runtime.warnings() {
+        report(Diagnostic::warning(warning, &source));
+    }
+    if let Err(err) = result {
+        report(Diagnostic::runtime(&err, runtime.expression_span()));
+        return Err(Failure::Diagnosed);
+    }
+    if let Some(path) = &args.params {
+        let manifest = params_json(runtime.params()).to_string();
+        fs::write(path, manifest).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    let output = runtime.finish();
+    let pages = output.svg.pages();
+
+    for (path, page) in page_paths(&args.output, pages.len()).iter().zip(&pages) {
+        fs::write(path, page).map_err(|err| format!("{}: {err}", path.display()))?;
+    }
+    Ok(output)
+}
+
+/// Parse and check `source`, reporting its problems.
+fn compile(source: &str, report: impl Fn(Diagnostic)) -> Result<Program, Failure> {
+    let program = match parse_file(&mut StringTokenizer::new(&source)) {
+        Ok(program) => program,
+        Err(err) => {
+            report(Diagnostic::from(&err));
+            return Err(Failure::Diagnosed);
+        }
+    };
+    let errors = check_program(&program);
+    if !errors.is_empty() {
+        for err in &errors {
+            report(Diagnostic::from(err));
+        }
+        return Err(Failure::Diagnosed);
+    }
+    Ok(program)
+}
+
+/// Render every frame of the animation, rasterize the first page of each
+/// and write them to a GIF or APNG, depending on the output's extension.
+/// The frames all show the window around all of them, at `--dpi`.
+fn animate(anim: &Anim) -> Result<(), Failure> {
+    let args = &anim.args;
+    let extension = args.output.extension().unwrap_or_default();
+    let format = AnimFormat::from_extension(&extension.to_string_lossy()).ok_or_else(|| {
+        format!(
+            "{}: animations are written as .gif or .png",
+            args.output.display()
+        )
+    })?;
+    let Units::Px { dpi } = args.units.unwrap_or_default() else {
+        return Err(Failure::from(
+            "animations are drawn in pixels, give --dpi instead of --mm".to_owned(),
+        ));
+    };
+
+    let source = fs::read_to_string(&args.input)
+        .map_err(|err| format!("{}: {err}", args.input.display()))?;
+    let name = args.input.display().to_string();
+    let report = |diagnostic: Diagnostic| report(args.diagnostics, &diagnostic, &name, &source);
+    let program = compile(&source, report)?;
+
+    let mut pages = Vec::with_capacity(anim.frames);
+    for frame in 0..anim.frames {
+        let mut runtime = Runtime::<Recording>::default();
+        runtime.set_log(std::io::stdout());
+        runtime.set_threads(args.threads);
+        let time = frame as f64 / anim.fps;
+        let variables = args.defines.iter().cloned().chain([
+            (
+                "frame".to_owned(),
+                Value::Scalar(Scalar::from(frame as i64)),
+            ),
+            ("t".to_owned(), Value::Scalar(Scalar::from(time))),
+        ]);
+        for (name, value) in variables {
+            runtime
+                .set_variable(&name, value)
+                .map_err(|err| err.to_string())?;
+        }
+        let result = runtime.execute(program.clone());
+        // The same warnings would come up for every frame
+        if frame == 0 {
+            for warning in runtime.warnings() {
+                report(Diagnostic::warning(warning, &source));
+            }
+        }
+        if let Err(err) = result {
+            report(Diagnostic::runtime(&err, runtime.expression_span()));
+            return Err(Failure::Other(format!("frame {frame} failed to render")));
+        }
+        let page = runtime.finish().into_pages().into_iter().next();
+        pages.push(page.unwrap_or_default());
+    }
+
+    let window = pages
+        .iter()
+        .map(|page| Window::of(page))
+        .reduce(Window::union)
+        .expect("There is at least one frame");
+    let scale = dpi / MM_PER_INCH;
+    let pixels = |mm: f64| (mm * scale).ceil().max(1.0) as usize;
+    let (width, height) = (pixels(window.width), pixels(window.height));
+    if format == AnimFormat::Gif && width.max(height) > usize::from(u16::MAX) {
+        return Err(Failure::from(format!(
+            "frames of {width}x{height} pixels are too large for a GIF"
+        )));
+    }
+    let view = View {
+        origin: (window.x, window.y),
+        scale,
+    };
+    let frames: Vec<_> = pages
+        .iter()
+        .map(|page| {
+            let mut canvas = Canvas::new(width, height);
+            canvas.paint(page, view);
+            canvas
+        })
+        .collect();
+
+    fs::write(&args.output, format.encode(&frames, anim.fps))
+        .map_err(|err| format!("{}: {err}", args.output.display()))?;
+    Ok(())
+}
+
+/// Shows each rendering while watching.
+trait Show {
+    fn show(&self, pages: Vec<Vec<DrawCommand>>);
+
+    /// Whether to keep watching.
+    fn is_open(&self) -> bool;
+}
+
+/// Shows nothing, without `--preview`.
+impl Show for () {
+    fn show(&self, _: Vec<Vec<DrawCommand>>) {}
+
+    fn is_open(&self) -> bool {
+        true
+    }
+}
+
+#[cfg(feature = "preview")]
+impl Show for graze_svg::preview::Preview {
+    fn show(&self, pages: Vec<Vec<DrawCommand>>) {
+        self.show(pages);
+    }
+
+    fn is_open(&self) -> bool {
+        self.is_open()
+    }
+}
+
+/// Open the preview window `--preview` asked for, if any.
+#[cfg(feature = "preview")]
+fn preview(args: &Args) -> Result<Box<dyn Show>, String> {
+    if !args.preview {
+        return Ok(Box::new(()));
+    }
+    let preview = graze_svg::preview::Preview::open()
+        .map_err(|err| format!("can't open the preview window: {err}"))?;
+    Ok(Box::new(preview))
+}
+
+#[cfg(not(feature = "preview"))]
+fn preview(args: &Args) -> Result<Box<dyn Show>, String> {
+    match args.preview {
+        true => Err("this graze was built without the preview feature".into()),
+        false => Ok(Box::new(())),
+    }
+}
+
+/// Render the script every time it is saved, only running the instructions
+/// that changed again, until the preview window is closed.
+fn watch(args: Args) -> Result<(), Failure> {
+    let show = preview(&args)?;
+    let mut cache = Cache::default();
+    let mut rendered = None;
+    while show.is_open() {
+        let modified = fs::metadata(&args.input)
+            .and_then(|metadata| metadata.modified())
+            .map_err(|err| format!("{}: {err}", args.input.display()))?;
+        if rendered != Some(modified) {
+            rendered = Some(modified);
+            match render(&args, Some(&mut cache)) {
+                Ok(output) => {
+                    show.show(output.recording.unwrap_or_default().into_pages());
+                    eprintln!(
+                        "rendered {} ({} instructions reused, {} executed)",
+                        args.output.display(),
+                        cache.reused(),
+                        cache.executed()
+                    );
+                }
+                Err(Failure::Diagnosed) => {}
+                Err(Failure::Other(err)) => eprintln!("error: {err}"),
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+    Ok(())
+}
+
+/// The signature and summary of the builtin `name`.
+fn describe(name: &str) -> Result<String, String> {
+    let runtime = Runtime::<SvgOutput>::default();
+    let doc = runtime
+        .function_doc(name)
+        .ok_or_else(|| format!("no builtin named '{name}'"))?;
+    Ok(format!("{}\n    {}", doc.signature, doc.summary))
+}
+
+/// Rewrite each script in the canonical style.
+fn fmt(paths: impl Iterator<Item = String>) -> Result<(), String> {
+    let paths: Vec<_> = paths.map(PathBuf::from).collect();
+    if paths.is_empty() {
+        return Err(format!("fmt expects at least one script\n{USAGE}"));
+    }
+
+    for path in paths {
+        let source =
+            fs::read_to_string(&path).map_err(|err| format!("{}: {err}", path.display()))?;
+        let program = parse_file(&mut StringTokenizer::new(&source))
+            .map_err(|err| format!("{}: {err}", path.display()))?;
+        let formatted = format_program(&program);
+        if formatted != source {
+            fs::write(&path, formatted).map_err(|err| format!("{}: {err}", path.display()))?;
+        }
+    }
+    Ok(())
+}
+
+fn main() -> ExitCode {
+    let mut args = std::env::args().skip(1).peekable();
+    let result = if args.next_if(|arg| arg == "anim").is_some() {
+        parse_anim_args(args)
+            .map_err(|err| Failure::Other(format!("{err}\n{USAGE}")))
+            .and_then(|anim| animate(&anim))
+    } else if args.next_if(|arg| arg == "fmt").is_some() {
+        fmt(args).map_err(Failure::from)
+    } else if args.next_if(|arg| arg == "doc").is_some() {
+        match (args.next(), args.next()) {
+            (Some(name), None) => describe(&name).map(|doc| println!("{doc}")),
+            _ => Err(format!("doc expects the name of a builtin\n{USAGE}")),
+        }
+        .map_err(Failure::from)
+    } else {
+        parse_args(args)
+            .map_err(|err| Failure::Other(format!("{err}\n{USAGE}")))
+            .and_then(|args| match args.watch {
+                true => watch(args),
+                false => render(&args, None).map(drop),
+            })
+    };
+
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(Failure::Diagnosed) => ExitCode::FAILURE,
+        Err(Failure::Other(err)) => {
+            eprintln!("error: {err}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_output_path() {
+        let args = parse_args(["drawing.grz".to_string()].into_iter()).unwrap();
+        assert_eq!(args.output, PathBuf::from("drawing.svg"));
+        assert!(!args.trace);
+
+        let args = parse_args(["--trace", "x.grz"].into_iter().map(String::from)).unwrap();
+        assert!(args.trace);
+        assert!(!args.merge_lines);
+        assert!(!args.pretty);
+
+        let args = ["--precision", "1", "x.grz"].into_iter().map(String::from);
+        assert_eq!(parse_args(args).unwrap().precision, Some(1));
+        let args = ["x.grz", "--precision", "many"]
+            .into_iter()
+            .map(String::from);
+        assert!(parse_args(args).is_err());
+
+        let args = ["--dpi", "300", "x.grz"].into_iter().map(String::from);
+        let units = parse_args(args).unwrap().units;
+        assert_eq!(units, Some(Units::Px { dpi: 300.0 }));
+        let args = ["--mm", "x.grz"].into_iter().map(String::from);
+        assert_eq!(parse_args(args).unwrap().units, Some(Units::Mm));
+        let args = ["--dpi", "0", "x.grz"].into_iter().map(String::from);
+        assert!(parse_args(args).is_err());
+
+        assert_eq!(
+            parse_args(["x.grz".to_string()].into_iter())
+                .unwrap()
+                .threads,
+            1
+        );
+        let args = ["-j", "4", "x.grz"].into_iter().map(String::from);
+        assert_eq!(parse_args(args).unwrap().threads, 4);
+        let args = ["--jobs", "0", "x.grz"].into_iter().map(String::from);
+        assert!(parse_args(args).is_err());
+
+        assert!(!parse_args(["x.grz".to_string()].into_iter()).unwrap().watch);
+        let args = ["x.grz", "--watch"].into_iter().map(String::from);
+        assert!(parse_args(args).unwrap().watch);
+        let args = ["--preview", "x.grz"].into_iter().map(String::from);
+        let args = parse_args(args).unwrap();
+        assert_eq!((args.preview, args.watch), (true, true));
+
+        let args = ["--diagnostics", "json", "x.grz"]
+            .into_iter()
+            .map(String::from);
+        assert_eq!(parse_args(args).unwrap().diagnostics, Format::Json);
+        let args = ["--diagnostics", "xml", "x.grz"]
+            .into_iter()
+            .map(String::from);
+        assert!(parse_args(args).is_err());
+    }
+
+    #[test]
+    fn test_defines() {
+        let args = [
+            "-D",
+            "w=210",
+            "x.grz",
+            "-D",
+            "scale=0.5",
+            "--define",
+            "title=Plan",
+        ];
+        let args = parse_args(args.into_iter().map(String::from)).unwrap();
+        assert_eq!(
+            args.defines,
+            [
+                ("w".to_string(), Value::Scalar(210.into())),
+                ("scale".to_string(), Value::Scalar(0.5.into())),
+                ("title".to_string(), Value::String("Plan".into())),
+            ]
+        );
+        assert!(parse_define("nothing").is_err());
+        assert!(parse_define("=1").is_err());
+    }
+
+    #[test]
+    fn test_defines_win_over_let() {
+        let dir = std::env::temp_dir().join(format!("graze-defines-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("size.grz");
+        fs::write(&input, "#let size 5\ncircle (pnt2 0 0) $size").unwrap();
+        let render_with = |defines: &[&str]| {
+            let mut args = vec![input.display().to_string(), "-o".into()];
+            args.push(dir.join("size.svg").display().to_string());
+            args.extend(defines.iter().map(|define| define.to_string()));
+            let output = render(&parse_args(args.into_iter()).unwrap(), None).unwrap();
+            output.svg.pages().concat()
+        };
+
+        // 20mm and 5mm in pixels
+        assert!(render_with(&["-D", "size=20"]).contains("r=\"75.591\""));
+        assert!(render_with(&[]).contains("r=\"18.898\""));
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_anim_args() {
+        let args = ["x.grz", "--frames", "60", "-D", "r=2"].map(String::from);
+        let anim = parse_anim_args(args.into_iter()).unwrap();
+        assert_eq!((anim.frames, anim.fps), (60, DEFAULT_FPS));
+        assert_eq!(anim.args.output, PathBuf::from("x.gif"));
+        assert_eq!(anim.args.defines.len(), 1);
+
+        let args = ["--fps", "12.5", "x.grz", "--frames", "2", "-o", "y.png"].map(String::from);
+        let anim = parse_anim_args(args.into_iter()).unwrap();
+        assert_eq!((anim.fps, anim.args.output), (12.5, PathBuf::from("y.png")));
+
+        for args in [&["x.grz"][..], &["x.grz", "--frames", "0"], &["--fps", "0"]] {
+            assert!(parse_anim_args(args.iter().map(|arg| arg.to_string())).is_err());
+        }
+    }
+
+    #[test]
+    fn test_animate() {
+        let dir = std::env::temp_dir().join(format!("graze-anim-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let input = dir.join("move.grz");
+        fs::write(&input, "#let t 0\ncircle (pnt2 (mul 10 $t) 0) 1").unwrap();
+        let output = dir.join("move.gif");
+        let args = [&input, &output].map(|path| path.display().to_string());
+        let args = [&args[0], "--frames", "3", "--fps", "2", "-o", &args[1]];
+        animate(&parse_anim_args(args.iter().map(|arg| arg.to_string())).unwrap()).unwrap();
+
+        // The circle moves 10mm over the frames at 0, 0.5 and 1s, which
+        // are 38 pixels at 96 dpi, plus the circle itself
+        let gif = fs::read(&output).unwrap();
+        assert_eq!(&gif[6..10], [46, 0, 8, 0]);
+        let frames = gif.windows(4).filter(|w| w == b"\x21\xf9\x04\x00");
+        assert_eq!(frames.count(), 3);
+        fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_params_manifest() {
+        let args = ["x.grz", "--params", "x.json"]
+            .into_iter()
+            .map(String::from);
+        assert_eq!(
+            parse_args(args).unwrap().params,
+            Some(PathBuf::from("x.json"))
+        );
+
+        let source = "#param r 1 10 2.5!\ncircle (pnt2 0 0) $r";
+        let program = parse_file(&mut StringTokenizer::new(&source)).unwrap();
+        let mut runtime = Runtime::<SvgOutput>::default();
+        runtime.execute(program).unwrap();
+        assert_eq!(
+            params_json(runtime.params()).to_string(),
+            r#"[{"default":2.5,"max":10.0,"min":1.0,"name":"r","value":2.5}]"#
+        );
+    }
+
+    #[test]
+    fn test_json_diagnostics() {
+        let source = "#screen 10 10\ncircel 1";
+        let span = source.find("circel").unwrap()..source.len();
+        let diagnostic = Diagnostic::runtime(
+            &graze::Error::FunctionNotFound {
+                name: "circel".into(),
+                suggestion: Some("circle".into()),
+            },
+            span,
+        );
+        assert_eq!(
+            to_json(&diagnostic, "a.grz", source),
+            json!({
+                "severity": "error",
+                "code": "E0305",
+                "message": "Function circel not in scope; did you mean circle?",
+                "hint": null,
+                "file": "a.grz",
+                "span": { "start": 14, "end": 22 },
+                "line": 2,
+                "column": 1,
+            })
+        );
+    }
+
+    #[test]
+    fn test_describe() {
+        assert_eq!(
+            describe("circle").unwrap(),
+            "circle center radius -> Circle\n    A circle around a point."
+        );
+        assert_eq!(describe("circel"), Err("no builtin named 'circel'".into()));
+    }
+
+    #[test]
+    fn test_page_paths() {
+        let output = Path::new("out/sheet.svg");
+        assert_eq!(page_paths(output, 1), [PathBuf::from("out/sheet.svg")]);
+        assert_eq!(
+            page_paths(output, 2),
+            [
+                PathBuf::from("out/sheet-1.svg"),
+                PathBuf::from("out/sheet-2.svg")
+            ]
+        );
+    }
+}
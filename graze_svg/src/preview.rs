@@ -0,0 +1,230 @@
+//! A native window showing the pages of the latest rendering one below the
+//! other. Dragging pans and the wheel zooms, and both are kept when a new
+//! rendering comes in; the `0` key fits the pages into the window again.
+//! The window is drawn with [`crate::raster`] and talks to the X server
+//! through Xlib directly, so it needs `libX11` but no other crates.
+//!
+//! ```no_run
+//! let preview = graze_svg::preview::Preview::open().unwrap();
+//! let pages = vec![vec![graze::DrawCommand::Circle {
+//!     at: (graze::Mm(5.0), graze::Mm(5.0)),
+//!     radius: graze::Mm(2.0),
+//! }]];
+//! preview.show(pages);
+//! ```
+
+use std::{
+    ffi::c_ulong,
+    io,
+    sync::{mpsc, Arc, Mutex, PoisonError},
+    thread,
+    time::Duration,
+};
+
+use graze::DrawCommand;
+
+use crate::raster::{Canvas, View, Window};
+
+mod xlib;
+
+/// The size the window opens with, in pixels.
+const SIZE: (usize, usize) = (800, 600);
+/// Pixels kept free around the pages when fitting them into the window.
+const MARGIN: f64 = 16.0;
+/// Millimeters between two pages.
+const GAP: f64 = 10.0;
+/// The gray around the pages.
+const BACKGROUND: u8 = 0xee;
+/// How much one step of the wheel zooms.
+const ZOOM: f64 = 1.25;
+/// How often the window checks for input and new renderings.
+const FRAME: Duration = Duration::from_millis(16);
+/// The keysyms of the keys fitting the pages into the window again.
+const FIT_KEYS: [c_ulong; 2] = [0x30, 0xff50];
+
+/// What the preview shows.
+#[derive(Default)]
+struct Shown {
+    /// Counts the renderings, so the window knows when to draw again.
+    version: u64,
+    pages: Vec<Vec<DrawCommand>>,
+    closed: bool,
+}
+
+/// Shows the preview in a window of its own, drawn by a thread of its own.
+pub struct Preview {
+    shown: Arc<Mutex<Shown>>,
+}
+
+impl Preview {
+    /// Open the window, empty until the first [`Preview::show`]. Fails if
+    /// there is no X server to show it on.
+    pub fn open() -> io::Result<Self> {
+        let shown = Arc::new(Mutex::new(Shown::default()));
+        let drawn = Arc::clone(&shown);
+        let (opened, result) = mpsc::channel();
+        thread::spawn(move || match xlib::Window::open("graze preview", SIZE) {
+            Ok(window) => {
+                let _ = opened.send(Ok(()));
+                run(window, &drawn);
+            }
+            Err(err) => {
+                let _ = opened.send(Err(err));
+            }
+        });
+        result
+            .recv()
+            .map_err(|_| io::Error::other("the preview window failed to open"))??;
+        Ok(Self { shown })
+    }
+
+    /// Show the pages of a new rendering.
+    pub fn show(&self, pages: Vec<Vec<DrawCommand>>) {
+        let mut shown = self.shown.lock().unwrap_or_else(PoisonError::into_inner);
+        shown.version += 1;
+        shown.pages = pages;
+    }
+
+    /// Whether the window is still open.
+    pub fn is_open(&self) -> bool {
+        let shown = self.shown.lock().unwrap_or_else(PoisonError::into_inner);
+        !shown.closed
+    }
+}
+
+/// Where each page goes when they are put one below the other, as the
+/// window it shows and the top of its place, and the window around all of
+/// them.
+fn layout(pages: &[Vec<DrawCommand>]) -> (Vec<(Window, f64)>, Window) {
+    let mut placed = Vec::with_capacity(pages.len());
+    let (mut width, mut top) = (0.0_f64, 0.0);
+    for page in pages {
+        let window = Window::of(page);
+        placed.push((window, top));
+        width = width.max(window.width);
+        top += window.height + GAP;
+    }
+    let all = Window {
+        x: 0.0,
+        y: 0.0,
+        width: width.max(1.0),
+        height: (top - GAP).max(1.0),
+    };
+    (placed, all)
+}
+
+/// Draw `pages` put one below the other as seen through `view`.
+fn paint(canvas: &mut Canvas, pages: &[Vec<DrawCommand>], view: View) {
+    canvas.clear(BACKGROUND);
+    for (page, (window, top)) in pages.iter().zip(layout(pages).0) {
+        let corner = view.pixel((0.0, top));
+        let size = (window.width * view.scale, window.height * view.scale);
+        canvas.fill(corner, size, 255);
+        // The window's corner goes to the top of the page's place
+        let origin = (view.origin.0 + window.x, view.origin.1 + window.y - top);
+        canvas.paint(page, View { origin, ..view });
+    }
+}
+
+/// Draw the latest rendering into `window` and follow the mouse until the
+/// window is closed.
+fn run(mut window: xlib::Window, shown: &Mutex<Shown>) {
+    let mut canvas = Canvas::new(SIZE.0, SIZE.1);
+    let mut pages = Vec::new();
+    let mut version = None;
+    // Fit once there is something to fit
+    let mut view: Option<View> = None;
+    let mut drag = None;
+    loop {
+        let mut changed = false;
+        while let Some(event) = window.poll() {
+            changed = true;
+            match event {
+                xlib::Event::Closed => {
+                    shown.lock().unwrap_or_else(PoisonError::into_inner).closed = true;
+                    return;
+                }
+                xlib::Event::Resized(width, height)
+                    if (width, height) != (canvas.width(), canvas.height()) =>
+                {
+                    canvas = Canvas::new(width, height);
+                }
+                xlib::Event::Pressed { button: 1, at } => drag = Some(at),
+                xlib::Event::Released { button: 1 } => drag = None,
+                xlib::Event::Dragged { to } => {
+                    if let Some(from) = drag {
+                        view = view.map(|v| v.pan((to.0 - from.0, to.1 - from.1)));
+                        drag = Some(to);
+                    }
+                }
+                // The wheel presses buttons 4 and 5
+                xlib::Event::Pressed { button: 4, at } => view = view.map(|v| v.zoom(ZOOM, at)),
+                xlib::Event::Pressed { button: 5, at } => {
+                    view = view.map(|v| v.zoom(1.0 / ZOOM, at));
+                }
+                xlib::Event::Key(key) if FIT_KEYS.contains(&key) => view = None,
+                _ => {}
+            }
+        }
+        {
+            let shown = shown.lock().unwrap_or_else(PoisonError::into_inner);
+            if version != Some(shown.version) {
+                version = Some(shown.version);
+                pages.clone_from(&shown.pages);
+                changed = true;
+            }
+        }
+        if changed && !pages.is_empty() {
+            let size = (canvas.width(), canvas.height());
+            let view = *view.get_or_insert_with(|| View::fit(layout(&pages).1, size, MARGIN));
+            paint(&mut canvas, &pages, view);
+            window.show(&canvas);
+        }
+        thread::sleep(FRAME);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use graze::Mm;
+
+    use super::*;
+
+    fn resized(width: f64, height: f64) -> Vec<DrawCommand> {
+        vec![DrawCommand::Resize {
+            x: Mm(width),
+            y: Mm(height),
+        }]
+    }
+
+    #[test]
+    fn test_layout() {
+        let (placed, all) = layout(&[resized(40.0, 30.0), resized(60.0, 20.0)]);
+        assert_eq!(placed[0].1, 0.0);
+        assert_eq!(placed[1].1, 30.0 + GAP);
+        assert_eq!((all.width, all.height), (60.0, 50.0 + GAP));
+    }
+
+    #[test]
+    fn test_paint() {
+        let mut pages = vec![resized(10.0, 10.0), resized(10.0, 10.0)];
+        pages[1].push(DrawCommand::Line {
+            from: (Mm(0.0), Mm(5.05)),
+            to: (Mm(10.0), Mm(5.05)),
+        });
+        let view = View {
+            origin: (0.0, 0.0),
+            scale: 10.0,
+        };
+        let mut canvas = Canvas::new(100, 300);
+        paint(&mut canvas, &pages, view);
+
+        let gray = |x: usize, y: usize| canvas.pixels()[y * canvas.width() + x];
+        assert_eq!(gray(50, 50), 255);
+        // The gap between the pages
+        assert_eq!(gray(50, 150), BACKGROUND);
+        // The line 5mm from the top of the second page
+        assert!(gray(50, 250) < 128);
+        assert_eq!(gray(50, 240), 255);
+    }
+}
@@ -0,0 +1,348 @@
+//! Just enough of Xlib to show pixels in a window and follow the mouse.
+
+use std::{
+    ffi::{c_char, c_int, c_long, c_uint, c_ulong, c_void, CString},
+    io, ptr,
+};
+
+use crate::raster::Canvas;
+
+/// Opaque Xlib types, only handled by pointer.
+#[repr(C)]
+struct Display(c_void);
+#[repr(C)]
+struct Visual(c_void);
+#[repr(C)]
+struct Image(c_void);
+
+type Atom = c_ulong;
+type Drawable = c_ulong;
+type Gc = *mut c_void;
+
+const KEY_PRESS: c_int = 2;
+const BUTTON_PRESS: c_int = 4;
+const BUTTON_RELEASE: c_int = 5;
+const MOTION_NOTIFY: c_int = 6;
+const EXPOSE: c_int = 12;
+const CONFIGURE_NOTIFY: c_int = 22;
+const CLIENT_MESSAGE: c_int = 33;
+
+const KEY_PRESS_MASK: c_long = 1 << 0;
+const BUTTON_PRESS_MASK: c_long = 1 << 2;
+const BUTTON_RELEASE_MASK: c_long = 1 << 3;
+const BUTTON_MOTION_MASK: c_long = 1 << 13;
+const EXPOSURE_MASK: c_long = 1 << 15;
+const STRUCTURE_NOTIFY_MASK: c_long = 1 << 17;
+
+const Z_PIXMAP: c_int = 2;
+
+/// `XKeyEvent`, `XButtonEvent` and `XMotionEvent`, which only differ in
+/// what `detail` means.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct InputEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    window: Drawable,
+    root: Drawable,
+    subwindow: Drawable,
+    time: c_ulong,
+    x: c_int,
+    y: c_int,
+    x_root: c_int,
+    y_root: c_int,
+    state: c_uint,
+    /// The keycode or button.
+    detail: c_uint,
+    same_screen: c_int,
+}
+
+/// `XConfigureEvent`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ConfigureEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    event: Drawable,
+    window: Drawable,
+    x: c_int,
+    y: c_int,
+    width: c_int,
+    height: c_int,
+    border_width: c_int,
+    above: Drawable,
+    override_redirect: c_int,
+}
+
+/// `XClientMessageEvent`.
+#[repr(C)]
+#[derive(Clone, Copy)]
+struct ClientMessageEvent {
+    kind: c_int,
+    serial: c_ulong,
+    send_event: c_int,
+    display: *mut Display,
+    window: Drawable,
+    message_type: Atom,
+    format: c_int,
+    data: [c_long; 5],
+}
+
+/// `XEvent`, padded to its full size.
+#[repr(C)]
+union XEvent {
+    kind: c_int,
+    input: InputEvent,
+    configure: ConfigureEvent,
+    client: ClientMessageEvent,
+    padding: [c_long; 24],
+}
+
+#[link(name = "X11")]
+extern "C" {
+    fn XOpenDisplay(name: *const c_char) -> *mut Display;
+    fn XCloseDisplay(display: *mut Display) -> c_int;
+    fn XDefaultScreen(display: *mut Display) -> c_int;
+    fn XRootWindow(display: *mut Display, screen: c_int) -> Drawable;
+    fn XDefaultVisual(display: *mut Display, screen: c_int) -> *mut Visual;
+    fn XDefaultDepth(display: *mut Display, screen: c_int) -> c_int;
+    fn XDefaultGC(display: *mut Display, screen: c_int) -> Gc;
+    fn XWhitePixel(display: *mut Display, screen: c_int) -> c_ulong;
+    fn XCreateSimpleWindow(
+        display: *mut Display,
+        parent: Drawable,
+        x: c_int,
+        y: c_int,
+        width: c_uint,
+        height: c_uint,
+        border_width: c_uint,
+        border: c_ulong,
+        background: c_ulong,
+    ) -> Drawable;
+    fn XStoreName(display: *mut Display, window: Drawable, name: *const c_char) -> c_int;
+    fn XSelectInput(display: *mut Display, window: Drawable, mask: c_long) -> c_int;
+    fn XInternAtom(display: *mut Display, name: *const c_char, only_if_exists: c_int) -> Atom;
+    fn XSetWMProtocols(
+        display: *mut Display,
+        window: Drawable,
+        protocols: *mut Atom,
+        count: c_int,
+    ) -> c_int;
+    fn XMapWindow(display: *mut Display, window: Drawable) -> c_int;
+    fn XPending(display: *mut Display) -> c_int;
+    fn XNextEvent(display: *mut Display, event: *mut XEvent) -> c_int;
+    fn XLookupKeysym(event: *mut InputEvent, index: c_int) -> c_ulong;
+    fn XCreateImage(
+        display: *mut Display,
+        visual: *mut Visual,
+        depth: c_uint,
+        format: c_int,
+        offset: c_int,
+        data: *mut c_char,
+        width: c_uint,
+        height: c_uint,
+        bitmap_pad: c_int,
+        bytes_per_line: c_int,
+    ) -> *mut Image;
+    fn XPutImage(
+        display: *mut Display,
+        drawable: Drawable,
+        gc: Gc,
+        image: *mut Image,
+        src_x: c_int,
+        src_y: c_int,
+        dest_x: c_int,
+        dest_y: c_int,
+        width: c_uint,
+        height: c_uint,
+    ) -> c_int;
+    fn XFree(data: *mut c_void) -> c_int;
+    fn XFlush(display: *mut Display) -> c_int;
+}
+
+/// What happened to the window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(super) enum Event {
+    Resized(usize, usize),
+    /// Part of the window has to be drawn again.
+    Exposed,
+    Pressed {
+        button: u32,
+        at: (f64, f64),
+    },
+    Released {
+        button: u32,
+    },
+    /// The mouse moved with a button held down.
+    Dragged {
+        to: (f64, f64),
+    },
+    /// A key with the keysym, e.g. `0x30` for the `0` key.
+    Key(c_ulong),
+    /// The window manager asked to close the window.
+    Closed,
+}
+
+/// A top-level window of the X server at `$DISPLAY`. Every call has to
+/// come from the thread that opened it.
+pub(super) struct Window {
+    display: *mut Display,
+    window: Drawable,
+    screen: c_int,
+    delete: Atom,
+    /// The 32 bit pixels of the last canvas shown.
+    pixels: Vec<u32>,
+}
+
+impl Window {
+    pub fn open(title: &str, (width, height): (usize, usize)) -> io::Result<Self> {
+        let not_supported = |message: &str| io::Error::new(io::ErrorKind::Unsupported, message);
+        // SAFETY: A null name opens the display `$DISPLAY` names, and a null
+        // result is checked before the display is used.
+        let display = unsafe { XOpenDisplay(ptr::null()) };
+        if display.is_null() {
+            return Err(not_supported("can't connect to the X server at $DISPLAY"));
+        }
+        // SAFETY: The display is open, and the strings outlive the calls.
+        unsafe {
+            let screen = XDefaultScreen(display);
+            if !matches!(XDefaultDepth(display, screen), 24 | 32) {
+                XCloseDisplay(display);
+                return Err(not_supported("the X server doesn't show 24 bit color"));
+            }
+            let white = XWhitePixel(display, screen);
+            let window = XCreateSimpleWindow(
+                display,
+                XRootWindow(display, screen),
+                0,
+                0,
+                width as c_uint,
+                height as c_uint,
+                0,
+                white,
+                white,
+            );
+            let title = CString::new(title).unwrap_or_default();
+            XStoreName(display, window, title.as_ptr());
+            XSelectInput(
+                display,
+                window,
+                KEY_PRESS_MASK
+                    | BUTTON_PRESS_MASK
+                    | BUTTON_RELEASE_MASK
+                    | BUTTON_MOTION_MASK
+                    | EXPOSURE_MASK
+                    | STRUCTURE_NOTIFY_MASK,
+            );
+            // Closing the window is left to us instead of ending the process
+            let mut delete = XInternAtom(display, c"WM_DELETE_WINDOW".as_ptr(), 0);
+            XSetWMProtocols(display, window, &mut delete, 1);
+            XMapWindow(display, window);
+            XFlush(display);
+            Ok(Self {
+                display,
+                window,
+                screen,
+                delete,
+                pixels: Vec::new(),
+            })
+        }
+    }
+
+    /// The next thing that happened, if anything did.
+    pub fn poll(&mut self) -> Option<Event> {
+        loop {
+            // SAFETY: The display is open and `XNextEvent` fills in the
+            // event, whose kind tells which of its fields are valid.
+            unsafe {
+                if XPending(self.display) == 0 {
+                    return None;
+                }
+                let mut event = XEvent { padding: [0; 24] };
+                XNextEvent(self.display, &mut event);
+                let at = |input: InputEvent| (f64::from(input.x), f64::from(input.y));
+                let event = match event.kind {
+                    KEY_PRESS => Event::Key(XLookupKeysym(&mut event.input, 0)),
+                    BUTTON_PRESS => Event::Pressed {
+                        button: event.input.detail,
+                        at: at(event.input),
+                    },
+                    BUTTON_RELEASE => Event::Released {
+                        button: event.input.detail,
+                    },
+                    MOTION_NOTIFY => Event::Dragged {
+                        to: at(event.input),
+                    },
+                    EXPOSE => Event::Exposed,
+                    CONFIGURE_NOTIFY => Event::Resized(
+                        event.configure.width.max(1) as usize,
+                        event.configure.height.max(1) as usize,
+                    ),
+                    CLIENT_MESSAGE if event.client.data[0] as Atom == self.delete => Event::Closed,
+                    _ => continue,
+                };
+                return Some(event);
+            }
+        }
+    }
+
+    /// Show `canvas` in the top left corner of the window.
+    pub fn show(&mut self, canvas: &Canvas) {
+        self.pixels.clear();
+        self.pixels.extend(
+            canvas
+                .pixels()
+                .iter()
+                .map(|&gray| u32::from(gray) * 0x01_01_01),
+        );
+        let (width, height) = (canvas.width() as c_uint, canvas.height() as c_uint);
+        // SAFETY: The image only borrows the pixels, which stay put until
+        // `XFree` released it again without touching them.
+        unsafe {
+            let depth = XDefaultDepth(self.display, self.screen) as c_uint;
+            let image = XCreateImage(
+                self.display,
+                XDefaultVisual(self.display, self.screen),
+                depth,
+                Z_PIXMAP,
+                0,
+                self.pixels.as_mut_ptr().cast(),
+                width,
+                height,
+                32,
+                0,
+            );
+            if image.is_null() {
+                return;
+            }
+            let gc = XDefaultGC(self.display, self.screen);
+            XPutImage(
+                self.display,
+                self.window,
+                gc,
+                image,
+                0,
+                0,
+                0,
+                0,
+                width,
+                height,
+            );
+            XFree(image.cast());
+            XFlush(self.display);
+        }
+    }
+}
+
+impl Drop for Window {
+    fn drop(&mut self) {
+        // SAFETY: Closing the display destroys the window along with it.
+        unsafe {
+            XCloseDisplay(self.display);
+        }
+    }
+}
@@ -0,0 +1,276 @@
+//! Python bindings, importable as `graze`.
+//!
+//! Build the extension with `maturin build --features python,pyo3/extension-module`.
+//!
+//! ```python
+//! import graze
+//! svg = graze.render("circle (pnt2 5 5) 2")
+//! graze.stream("circle (pnt2 5 5) 2", lambda kind, data: print(kind, data))
+//! ```
+
+use graze::{parse_file, DrawBuffer, DrawCommand, Marker, Mm, Runtime, StringTokenizer, Transform};
+use pyo3::{
+    exceptions::{PyRuntimeError, PySyntaxError},
+    prelude::*,
+    types::PyDict,
+};
+
+use crate::RenderError;
+
+impl From<RenderError> for PyErr {
+    fn from(err: RenderError) -> Self {
+        match err {
+            RenderError::Parse(err) => PySyntaxError::new_err(err.to_string()),
+            RenderError::Runtime(err) => PyRuntimeError::new_err(err.to_string()),
+        }
+    }
+}
+
+/// Render `source` and return the SVG of its first page.
+#[pyfunction]
+fn render(source: &str) -> PyResult<String> {
+    let pages = crate::render(source)?;
+    Ok(pages.into_iter().next().unwrap_or_default())
+}
+
+/// Render `source` and return the SVG of every page.
+#[pyfunction]
+fn render_pages(source: &str) -> PyResult<Vec<String>> {
+    Ok(crate::render(source)?)
+}
+
+/// Hands each draw command to a Python callback as soon as it is drawn.
+#[derive(Default)]
+struct Stream {
+    callback: Option<Py<PyAny>>,
+    /// The first exception the callback raised. No more commands are
+    /// handed over once it has failed.
+    error: Option<PyErr>,
+}
+
+impl Stream {
+    fn send(
+        &mut self,
+        describe: impl FnOnce(Python<'_>) -> PyResult<(&'static str, Bound<'_, PyDict>)>,
+    ) {
+        let Some(callback) = &self.callback else {
+            return;
+        };
+        if self.error.is_some() {
+            return;
+        }
+        let result = Python::attach(|py| {
+            callback.call1(py, describe(py)?)?;
+            Ok(())
+        });
+        self.error = result.err();
+    }
+}
+
+impl DrawBuffer for Stream {
+    // Commands already handed over can't be taken back
+    fn reset(&mut self) {}
+
+    fn draw(&mut self, command: DrawCommand) {
+        self.send(|py| describe(py, command));
+    }
+
+    fn new_page(&mut self) {
+        self.send(|py| Ok(("new_page", PyDict::new(py))));
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// Describe a command as a kind name and a dict of its fields, in millimeters.
+fn describe<'py>(
+    py: Python<'py>,
+    command: DrawCommand,
+) -> PyResult<(&'static str, Bound<'py, PyDict>)> {
+    let data = PyDict::new(py);
+    let point = |(x, y): (Mm, Mm)| (x.0, y.0);
+    let kind = match command {
+        DrawCommand::Line { from, to } => {
+            data.set_item("from", point(from))?;
+            data.set_item("to", point(to))?;
+            "line"
+        }
+        DrawCommand::Circle { at, radius } => {
+            data.set_item("at", point(at))?;
+            data.set_item("radius", radius.0)?;
+            "circle"
+        }
+        DrawCommand::Resize { x, y } => {
+            data.set_item("width", x.0)?;
+            data.set_item("height", y.0)?;
+            "resize"
+        }
+        DrawCommand::SetView {
+            x,
+            y,
+            width,
+            height,
+            flip_y,
+        } => {
+            data.set_item("x", x.0)?;
+            data.set_item("y", y.0)?;
+            data.set_item("width", width.0)?;
+            data.set_item("height", height.0)?;
+            data.set_item("flip_y", flip_y)?;
+            "view"
+        }
+        DrawCommand::BeginLayer { name } => {
+            data.set_item("name", name.as_str())?;
+            "begin_layer"
+        }
+        DrawCommand::EndLayer => "end_layer",
+        DrawCommand::Text { at, text, size } => {
+            data.set_item("at", point(at))?;
+            data.set_item("text", text.as_str())?;
+            data.set_item("size", size.0)?;
+            "text"
+        }
+        DrawCommand::Image {
+            at,
+            width,
+            height,
+            path,
+        } => {
+            data.set_item("at", point(at))?;
+            data.set_item("width", width.0)?;
+            data.set_item("height", height.0)?;
+            data.set_item("path", path.as_str())?;
+            "image"
+        }
+        DrawCommand::Path { points, closed } => {
+            let points = points.into_iter().map(point).collect::<Vec<_>>();
+            data.set_item("points", points)?;
+            data.set_item("closed", closed)?;
+            "path"
+        }
+        DrawCommand::BeginClip { region } => {
+            let region = region.into_iter().map(point).collect::<Vec<_>>();
+            data.set_item("region", region)?;
+            "begin_clip"
+        }
+        DrawCommand::EndClip => "end_clip",
+        DrawCommand::BeginSymbol { name } => {
+            data.set_item("name", name.as_str())?;
+            "begin_symbol"
+        }
+        DrawCommand::EndSymbol => "end_symbol",
+        DrawCommand::PlaceSymbol { name, transform } => {
+            let Transform { a, b, c, d, e, f } = transform;
+            data.set_item("name", name.as_str())?;
+            data.set_item("matrix", (a, b, c, d, e, f))?;
+            "place_symbol"
+        }
+        DrawCommand::SetLineStyle { style } => {
+            data.set_item("style", style.name())?;
+            data.set_item("dashes", style.dashes())?;
+            "line_style"
+        }
+        DrawCommand::SetMarkers { start, end } => {
+            data.set_item("start", start.map(Marker::name))?;
+            data.set_item("end", end.map(Marker::name))?;
+            "markers"
+        }
+        DrawCommand::Metadata { name, value } => {
+            data.set_item("name", name.as_str())?;
+            data.set_item("value", value.as_str())?;
+            "metadata"
+        }
+    };
+    Ok((kind, data))
+}
+
+/// Run `source` and call `callback(kind, data)` for every draw command
+/// while it runs, and with `"new_page"` between pages. An exception raised
+/// by the callback ends the stream and is raised again once the script
+/// stopped.
+#[pyfunction]
+fn stream(source: &str, callback: Bound<'_, PyAny>) -> PyResult<()> {
+    let program = parse_file(&mut StringTokenizer::new(&source)).map_err(RenderError::from)?;
+    let mut runtime = Runtime::<Stream>::default();
+    runtime.backend_mut().callback = Some(callback.unbind());
+    let result = runtime.execute(program);
+
+    let stream = runtime.finish();
+    if let Some(error) = stream.error {
+        return Err(error);
+    }
+    Ok(result.map_err(RenderError::from)?)
+}
+
+#[pymodule]
+#[pyo3(name = "graze")]
+fn graze_module(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(render, m)?)?;
+    m.add_function(wrap_pyfunction!(render_pages, m)?)?;
+    m.add_function(wrap_pyfunction!(stream, m)?)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use pyo3::types::PyList;
+
+    use super::*;
+
+    #[test]
+    fn test_render() {
+        Python::initialize();
+        Python::attach(|_| {
+            assert!(render("circle (pnt2 5 5) 2").unwrap().contains("<circle"));
+            assert!(render("nope").is_err());
+        });
+    }
+
+    #[test]
+    fn test_stream() {
+        Python::initialize();
+        Python::attach(|py| {
+            let seen = PyList::empty(py);
+            let globals = PyDict::new(py);
+            globals.set_item("seen", &seen).unwrap();
+            let callback = py
+                .eval(
+                    c"lambda kind, data: seen.append(kind)",
+                    Some(&globals),
+                    None,
+                )
+                .unwrap();
+            stream(
+                "circle (pnt2 5 5) 2\n#page\ncircle (pnt2 1 1) 1",
+                callback.clone(),
+            )
+            .unwrap();
+            let kinds: Vec<String> = seen.extract().unwrap();
+            assert_eq!(kinds[kinds.len() - 3..], ["circle", "new_page", "circle"]);
+
+            // Commands drawn before an error were already handed over
+            seen.call_method0("clear").unwrap();
+            assert!(stream("circle (pnt2 5 5) 2\ndiv 1 0", callback).is_err());
+            let kinds: Vec<String> = seen.extract().unwrap();
+            assert!(kinds.contains(&"circle".to_owned()));
+        });
+    }
+
+    #[test]
+    fn test_describe() {
+        Python::initialize();
+        Python::attach(|py| {
+            let (kind, data) = describe(
+                py,
+                DrawCommand::Circle {
+                    at: (Mm(1.0), Mm(2.0)),
+                    radius: Mm(3.0),
+                },
+            )
+            .unwrap();
+            assert_eq!(kind, "circle");
+            let radius: f64 = data.get_item("radius").unwrap().unwrap().extract().unwrap();
+            assert_eq!(radius, 3.0);
+        });
+    }
+}
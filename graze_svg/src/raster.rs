@@ -0,0 +1,676 @@
+//! Gray pixels for the draw commands of a page, for the preview window and
+//! animations. Everything is drawn one pixel wide and antialiased, in
+//! black on white. There is no font to draw text with and images aren't
+//! decoded, so both are outlined in gray where they would be.
+
+use std::{collections::HashMap, f64::consts::TAU};
+
+use graze::{Clipped, DrawBuffer, DrawCommand, Mm};
+
+/// How dark the outlines of text and images are, from 0 to 1.
+const OUTLINE: f64 = 0.4;
+
+/// Keeps the draw commands of every page, to rasterize them later.
+#[derive(Debug, Clone)]
+pub struct Recording {
+    /// The commands of each page, the current one last.
+    pages: Vec<Vec<DrawCommand>>,
+}
+
+impl Default for Recording {
+    fn default() -> Self {
+        Self {
+            pages: vec![Vec::new()],
+        }
+    }
+}
+
+impl Recording {
+    pub fn pages(&self) -> &[Vec<DrawCommand>] {
+        &self.pages
+    }
+
+    pub fn into_pages(self) -> Vec<Vec<DrawCommand>> {
+        self.pages
+    }
+}
+
+impl DrawBuffer for Recording {
+    fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        if let Some(page) = self.pages.last_mut() {
+            page.push(command);
+        }
+    }
+
+    fn new_page(&mut self) {
+        self.pages.push(Vec::new());
+    }
+
+    fn flush(&mut self) {}
+}
+
+/// A rectangle of drawing coordinates in millimeters, with y growing
+/// downwards like on the page.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Window {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+impl Window {
+    /// What `page` shows: the window of its last [`DrawCommand::SetView`],
+    /// else the page of its last [`DrawCommand::Resize`], else everything
+    /// drawn on it.
+    pub fn of(page: &[DrawCommand]) -> Self {
+        let mut resized = None;
+        let mut view = None;
+        for command in page {
+            match *command {
+                DrawCommand::Resize { x, y } => resized = Some((x.0, y.0)),
+                DrawCommand::SetView {
+                    x,
+                    y,
+                    width,
+                    height,
+                    flip_y,
+                } => {
+                    let top = if flip_y { -(y.0 + height.0) } else { y.0 };
+                    view = Some(Window {
+                        x: x.0,
+                        y: top,
+                        width: width.0,
+                        height: height.0,
+                    });
+                }
+                _ => {}
+            }
+        }
+        match (view, resized) {
+            (Some(view), _) => view,
+            (None, Some((width, height))) => Window {
+                x: 0.0,
+                y: 0.0,
+                width,
+                height,
+            },
+            (None, None) => drawn(page),
+        }
+    }
+
+    /// The smallest window showing both.
+    pub fn union(self, other: Self) -> Self {
+        let x = self.x.min(other.x);
+        let y = self.y.min(other.y);
+        Window {
+            x,
+            y,
+            width: (self.x + self.width).max(other.x + other.width) - x,
+            height: (self.y + self.height).max(other.y + other.height) - y,
+        }
+    }
+}
+
+/// The window around everything drawn on `page`, at least a millimeter
+/// wide and tall.
+fn drawn(page: &[DrawCommand]) -> Window {
+    let mut bounds = Bounds::default();
+    for command in page {
+        bounds.draw(command.clone());
+    }
+    let (min, max) = bounds.corners.unwrap_or(((0.0, 0.0), (0.0, 0.0)));
+    Window {
+        x: min.0,
+        y: min.1,
+        width: (max.0 - min.0).max(1.0),
+        height: (max.1 - min.1).max(1.0),
+    }
+}
+
+/// Follows the commands of a page to find the corners of what they draw.
+#[derive(Default)]
+struct Bounds {
+    corners: Option<((f64, f64), (f64, f64))>,
+    flip_y: bool,
+    symbols: HashMap<String, Vec<DrawCommand>>,
+    defining: Vec<(String, Vec<DrawCommand>)>,
+}
+
+impl Bounds {
+    fn include(&mut self, (x, y): (Mm, Mm), margin: f64) {
+        let (x, y) = (x.0, if self.flip_y { -y.0 } else { y.0 });
+        let (min, max) = self.corners.get_or_insert(((x, y), (x, y)));
+        *min = (min.0.min(x - margin), min.1.min(y - margin));
+        *max = (max.0.max(x + margin), max.1.max(y + margin));
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { name } => self.defining.push((name.to_string(), Vec::new())),
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+            }
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command);
+                }
+            }
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let commands = self.symbols.get(name.as_str()).cloned();
+                for command in commands.unwrap_or_default() {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::SetView { flip_y, .. } => self.flip_y = flip_y,
+            DrawCommand::Line { from, to } => {
+                self.include(from, 0.0);
+                self.include(to, 0.0);
+            }
+            DrawCommand::Circle { at, radius } => self.include(at, radius.0.abs()),
+            DrawCommand::Path { points, .. } => {
+                for point in points {
+                    self.include(point, 0.0);
+                }
+            }
+            DrawCommand::Text { at, size, .. } => self.include(at, size.0.abs()),
+            DrawCommand::Image {
+                at, width, height, ..
+            } => {
+                self.include(at, 0.0);
+                self.include((Mm(at.0 .0 + width.0), Mm(at.1 .0 + height.0)), 0.0);
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Where drawing coordinates end up on a canvas.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct View {
+    /// The drawing coordinates at the top left corner of the canvas.
+    pub origin: (f64, f64),
+    /// Pixels per millimeter.
+    pub scale: f64,
+}
+
+impl View {
+    /// Show all of `window`, centered on a canvas `width` by `height`
+    /// pixels with at least `margin` pixels to spare around it.
+    pub fn fit(window: Window, (width, height): (usize, usize), margin: f64) -> Self {
+        let room = |pixels: usize| (pixels as f64 - 2.0 * margin).max(1.0);
+        let scale = (room(width) / window.width).min(room(height) / window.height);
+        let center = (
+            window.x + window.width / 2.0,
+            window.y + window.height / 2.0,
+        );
+        View {
+            origin: (
+                center.0 - width as f64 / 2.0 / scale,
+                center.1 - height as f64 / 2.0 / scale,
+            ),
+            scale,
+        }
+    }
+
+    /// Zoom in by `factor`, keeping what is under the pixel `at` in place.
+    pub fn zoom(self, factor: f64, at: (f64, f64)) -> Self {
+        let scale = self.scale * factor;
+        View {
+            origin: (
+                self.origin.0 + at.0 / self.scale - at.0 / scale,
+                self.origin.1 + at.1 / self.scale - at.1 / scale,
+            ),
+            scale,
+        }
+    }
+
+    /// Move everything by `by` pixels.
+    pub fn pan(self, by: (f64, f64)) -> Self {
+        View {
+            origin: (
+                self.origin.0 - by.0 / self.scale,
+                self.origin.1 - by.1 / self.scale,
+            ),
+            ..self
+        }
+    }
+
+    /// Where the drawing coordinates `point` end up, in pixels.
+    pub fn pixel(&self, point: (f64, f64)) -> (f64, f64) {
+        (
+            (point.0 - self.origin.0) * self.scale,
+            (point.1 - self.origin.1) * self.scale,
+        )
+    }
+}
+
+/// Gray pixels row by row from the top left, 0 for black and 255 for
+/// white.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Canvas {
+    width: usize,
+    height: usize,
+    pixels: Vec<u8>,
+}
+
+impl Canvas {
+    /// A white canvas.
+    pub fn new(width: usize, height: usize) -> Self {
+        Self {
+            width,
+            height,
+            pixels: vec![255; width * height],
+        }
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    pub fn pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Set every pixel to `gray`.
+    pub fn clear(&mut self, gray: u8) {
+        self.pixels.fill(gray);
+    }
+
+    /// Set the pixels from `(x, y)` up to `(x + width, y + height)` to
+    /// `gray`, as far as they are on the canvas.
+    pub fn fill(&mut self, (x, y): (f64, f64), (width, height): (f64, f64), gray: u8) {
+        let columns = |from: f64, to: f64, limit: usize| {
+            let clamp = |at: f64| at.round().clamp(0.0, limit as f64) as usize;
+            clamp(from)..clamp(to)
+        };
+        let rows = columns(y, y + height, self.height);
+        let columns = columns(x, x + width, self.width);
+        for row in rows {
+            self.pixels[row * self.width..][columns.clone()].fill(gray);
+        }
+    }
+
+    /// Draw `page` as seen through `view`.
+    pub fn paint(&mut self, page: &[DrawCommand], view: View) {
+        let mut painter = Clipped::new(Painter {
+            canvas: self,
+            view,
+            flip_y: false,
+            dashes: &[],
+            symbols: HashMap::new(),
+            defining: Vec::new(),
+        });
+        for command in page {
+            painter.draw(command.clone());
+        }
+    }
+
+    /// Darken the pixel at `(x, y)` by `amount` from 0 to 1.
+    fn darken(&mut self, x: i64, y: i64, amount: f64) {
+        if x < 0 || y < 0 || x as usize >= self.width || y as usize >= self.height {
+            return;
+        }
+        let pixel = &mut self.pixels[y as usize * self.width + x as usize];
+        *pixel = (f64::from(*pixel) * (1.0 - amount.clamp(0.0, 1.0))).round() as u8;
+    }
+
+    /// The part of the segment from `from` to `to` on the canvas or next
+    /// to it, as a range of its parameter from 0 to 1.
+    fn visible(&self, from: (f64, f64), to: (f64, f64)) -> Option<(f64, f64)> {
+        if ![from.0, from.1, to.0, to.1].iter().all(|at| at.is_finite()) {
+            return None;
+        }
+        let (dx, dy) = (to.0 - from.0, to.1 - from.1);
+        let (width, height) = (self.width as f64, self.height as f64);
+        let (mut start, mut end) = (0.0_f64, 1.0_f64);
+        for (p, q) in [
+            (-dx, from.0 + 1.0),
+            (dx, width + 1.0 - from.0),
+            (-dy, from.1 + 1.0),
+            (dy, height + 1.0 - from.1),
+        ] {
+            if p == 0.0 {
+                if q < 0.0 {
+                    return None;
+                }
+                continue;
+            }
+            let r = q / p;
+            if p < 0.0 {
+                start = start.max(r);
+            } else {
+                end = end.min(r);
+            }
+        }
+        (start <= end).then_some((start, end))
+    }
+
+    /// Draw an antialiased line a pixel wide, by Xiaolin Wu's method. The
+    /// ends have to be on the canvas or next to it.
+    fn line(&mut self, from: (f64, f64), to: (f64, f64), amount: f64) {
+        let steep = (to.1 - from.1).abs() > (to.0 - from.0).abs();
+        let swap = |(x, y): (f64, f64)| if steep { (y, x) } else { (x, y) };
+        let (mut from, mut to) = (swap(from), swap(to));
+        if from.0 > to.0 {
+            std::mem::swap(&mut from, &mut to);
+        }
+        let gradient = match to.0 - from.0 {
+            dx if dx > 1e-9 => (to.1 - from.1) / dx,
+            _ => 0.0,
+        };
+        for column in from.0.floor() as i64..=to.0.floor() as i64 {
+            // How much of the column the line crosses
+            let left = from.0.max(column as f64);
+            let right = to.0.min(column as f64 + 1.0);
+            if right <= left {
+                continue;
+            }
+            let center = (left + right) / 2.0;
+            let y = from.1 + gradient * (center - from.0) - 0.5;
+            let row = y.floor();
+            let below = y - row;
+            let coverage = (right - left) * amount;
+            for (row, share) in [(row as i64, 1.0 - below), (row as i64 + 1, below)] {
+                match steep {
+                    true => self.darken(row, column, share * coverage),
+                    false => self.darken(column, row, share * coverage),
+                }
+            }
+        }
+    }
+}
+
+/// Draws commands on a canvas, once [`Clipped`] cut them to the clip
+/// regions.
+struct Painter<'c> {
+    canvas: &'c mut Canvas,
+    view: View,
+    /// Whether y grows upwards, after a flipping [`DrawCommand::SetView`].
+    flip_y: bool,
+    dashes: &'static [f64],
+    /// What every symbol defined so far draws.
+    symbols: HashMap<String, Vec<DrawCommand>>,
+    /// The symbols being defined and their commands so far, innermost last.
+    defining: Vec<(String, Vec<DrawCommand>)>,
+}
+
+impl Painter<'_> {
+    fn pixel(&self, (x, y): (Mm, Mm)) -> (f64, f64) {
+        let y = if self.flip_y { -y.0 } else { y.0 };
+        self.view.pixel((x.0, y))
+    }
+
+    /// Draw the polyline through `points`, back to the first one if
+    /// `closed`, dashed in the current line style.
+    fn stroke(&mut self, points: &[(Mm, Mm)], closed: bool, amount: f64) {
+        let mut points = points
+            .iter()
+            .map(|&point| self.pixel(point))
+            .collect::<Vec<_>>();
+        if closed && points.len() > 2 {
+            points.push(points[0]);
+        }
+        let pattern = self
+            .dashes
+            .iter()
+            .map(|dash| dash * self.view.scale)
+            .collect::<Vec<_>>();
+        // Dashes shorter than a pixel would only make the line lighter
+        let period = pattern.iter().sum::<f64>();
+        let solid = period < 2.0;
+
+        // How far along the polyline the segment starts
+        let mut travelled = 0.0;
+        for pair in points.windows(2) {
+            let (from, to) = (pair[0], pair[1]);
+            let length = (to.0 - from.0).hypot(to.1 - from.1);
+            let at = |distance: f64| {
+                let t = if length > 0.0 { distance / length } else { 0.0 };
+                (from.0 + (to.0 - from.0) * t, from.1 + (to.1 - from.1) * t)
+            };
+            if let Some((start, end)) = self.canvas.visible(from, to) {
+                let (mut along, end) = (start * length, end * length);
+                while along < end {
+                    if solid {
+                        self.canvas.line(at(along), at(end), amount);
+                        break;
+                    }
+                    // Find the dash or gap the line is in
+                    let mut phase = (travelled + along) % period;
+                    let mut index = 0;
+                    while phase >= pattern[index] {
+                        phase -= pattern[index];
+                        index = (index + 1) % pattern.len();
+                    }
+                    let next = (along + pattern[index] - phase).min(end);
+                    if index % 2 == 0 {
+                        self.canvas.line(at(along), at(next), amount);
+                    }
+                    along = next;
+                }
+            }
+            travelled += length;
+        }
+    }
+
+    /// Outline the rectangle from `(x, y)` to `(x + width, y + height)` in
+    /// gray.
+    fn outline(&mut self, (x, y): (f64, f64), (width, height): (f64, f64)) {
+        let corners = [
+            (x, y),
+            (x + width, y),
+            (x + width, y + height),
+            (x, y + height),
+        ];
+        let dashes = std::mem::take(&mut self.dashes);
+        self.stroke(&corners.map(|(x, y)| (Mm(x), Mm(y))), true, OUTLINE);
+        self.dashes = dashes;
+    }
+}
+
+impl DrawBuffer for Painter<'_> {
+    fn reset(&mut self) {
+        self.canvas.clear(255);
+    }
+
+    fn draw(&mut self, command: DrawCommand) {
+        match command {
+            DrawCommand::BeginSymbol { name } => self.defining.push((name.to_string(), Vec::new())),
+            DrawCommand::EndSymbol => {
+                if let Some((name, commands)) = self.defining.pop() {
+                    self.symbols.insert(name, commands);
+                }
+            }
+            command if !self.defining.is_empty() => {
+                if let Some((_, commands)) = self.defining.last_mut() {
+                    commands.push(command);
+                }
+            }
+            DrawCommand::PlaceSymbol { name, transform } => {
+                let commands = self.symbols.get(name.as_str()).cloned();
+                for command in commands.unwrap_or_default() {
+                    self.draw(transform.apply(command));
+                }
+            }
+            DrawCommand::Line { from, to } => self.stroke(&[from, to], false, 1.0),
+            DrawCommand::Circle { at, radius } => {
+                // Enough corners to stay within a fraction of a pixel
+                let pixels = radius.0.abs() * self.view.scale;
+                let corners = (pixels.sqrt() * 6.0).ceil().clamp(12.0, 4096.0) as usize;
+                let points = (0..corners)
+                    .map(|i| {
+                        let (sin, cos) = (TAU * i as f64 / corners as f64).sin_cos();
+                        (Mm(at.0 .0 + radius.0 * cos), Mm(at.1 .0 + radius.0 * sin))
+                    })
+                    .collect::<Vec<_>>();
+                self.stroke(&points, true, 1.0);
+            }
+            DrawCommand::Path { points, closed } => self.stroke(&points, closed, 1.0),
+            DrawCommand::Text { at, text, size } => {
+                // Roughly where the letters would be
+                let width = size.0 * 0.6 * text.chars().count() as f64;
+                let corner = (at.0 .0 - width / 2.0, at.1 .0 - size.0 / 2.0);
+                self.outline(corner, (width, size.0));
+            }
+            DrawCommand::Image {
+                at, width, height, ..
+            } => self.outline((at.0 .0, at.1 .0), (width.0, height.0)),
+            DrawCommand::SetView { flip_y, .. } => self.flip_y = flip_y,
+            DrawCommand::SetLineStyle { style } => self.dashes = style.dashes(),
+            // Pixels have no layers, and markers and metadata aren't drawn
+            _ => {}
+        }
+    }
+
+    fn flush(&mut self) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use graze::LineStyle;
+
+    use super::*;
+
+    fn page(source: &str) -> Vec<DrawCommand> {
+        let program = graze::parse_file(&mut graze::StringTokenizer::new(&source)).unwrap();
+        let mut runtime = graze::Runtime::<Recording>::default();
+        runtime.execute(program).unwrap();
+        runtime.finish().into_pages().remove(0)
+    }
+
+    fn dark(canvas: &Canvas, x: usize, y: usize) -> bool {
+        canvas.pixels()[y * canvas.width() + x] < 128
+    }
+
+    #[test]
+    fn test_recording() {
+        let mut recording = Recording::default();
+        recording.draw(DrawCommand::EndLayer);
+        recording.new_page();
+        recording.new_page();
+        assert_eq!(
+            recording.pages(),
+            [vec![DrawCommand::EndLayer], vec![], vec![]]
+        );
+        recording.reset();
+        assert_eq!(recording.pages().len(), 1);
+    }
+
+    #[test]
+    fn test_window() {
+        let drawn = Window::of(&page("circle (pnt2 5 5) 2\nline (pnt2 0 0) (vec2 4 1)"));
+        assert_eq!(
+            drawn,
+            Window {
+                x: 0.0,
+                y: 0.0,
+                width: 7.0,
+                height: 7.0
+            }
+        );
+        let resized = Window::of(&page("#screen 40 30\ncircle (pnt2 5 5) 50"));
+        assert_eq!((resized.width, resized.height), (40.0, 30.0));
+        let flipped = Window::of(&page("#view 0 0 10 20 flip\ncircle (pnt2 5 5) 50"));
+        assert_eq!((flipped.y, flipped.height), (-20.0, 20.0));
+
+        let union = drawn.union(Window {
+            x: -1.0,
+            y: 2.0,
+            width: 2.0,
+            height: 10.0,
+        });
+        assert_eq!(
+            (union.x, union.y, union.width, union.height),
+            (-1.0, 0.0, 8.0, 12.0)
+        );
+    }
+
+    #[test]
+    fn test_view() {
+        let window = Window {
+            x: 10.0,
+            y: 10.0,
+            width: 20.0,
+            height: 10.0,
+        };
+        let view = View::fit(window, (200, 200), 0.0);
+        assert_eq!(view.scale, 10.0);
+        assert_eq!(view.pixel((10.0, 10.0)), (0.0, 50.0));
+
+        let zoomed = view.zoom(2.0, (100.0, 100.0));
+        assert_eq!(zoomed.pixel((20.0, 15.0)), (100.0, 100.0));
+        assert_eq!(zoomed.pixel((10.0, 15.0)), (-100.0, 100.0));
+        assert_eq!(view.pan((5.0, -5.0)).pixel((10.0, 10.0)), (5.0, 45.0));
+    }
+
+    #[test]
+    fn test_paint() {
+        let view = View {
+            origin: (0.0, 0.0),
+            scale: 1.0,
+        };
+        let mut canvas = Canvas::new(20, 20);
+        canvas.paint(&page("line (pnt2 0 10.5) (vec2 20 0)"), view);
+        assert!((0..20).all(|x| dark(&canvas, x, 10)));
+        assert!(!dark(&canvas, 10, 9) && !dark(&canvas, 10, 11));
+
+        let mut canvas = Canvas::new(20, 20);
+        canvas.paint(&page("circle (pnt2 10.5 10.5) 5"), view);
+        assert!(dark(&canvas, 15, 10) && dark(&canvas, 10, 5));
+        assert!(!dark(&canvas, 10, 10));
+
+        // Everything outside the clip region is cut off
+        let mut canvas = Canvas::new(20, 20);
+        let source = "clip (rect (pnt2 0 0) 10 20)\nline (pnt2 0 10.5) (vec2 20 0)\nendclip";
+        canvas.paint(&page(source), view);
+        assert!(dark(&canvas, 5, 10) && !dark(&canvas, 15, 10));
+    }
+
+    #[test]
+    fn test_dashes() {
+        let view = View {
+            origin: (0.0, 0.0),
+            scale: 10.0,
+        };
+        let mut canvas = Canvas::new(100, 10);
+        canvas.paint(
+            &[
+                DrawCommand::SetLineStyle {
+                    style: LineStyle::Hidden,
+                },
+                DrawCommand::Line {
+                    from: (Mm(0.0), Mm(0.55)),
+                    to: (Mm(10.0), Mm(0.55)),
+                },
+            ],
+            view,
+        );
+        // 3mm dashes with gaps of 0.75mm
+        assert!(dark(&canvas, 15, 5) && !dark(&canvas, 33, 5) && dark(&canvas, 45, 5));
+    }
+
+    #[test]
+    fn test_symbols_and_flip() {
+        let view = View {
+            origin: (0.0, -20.0),
+            scale: 1.0,
+        };
+        let mut canvas = Canvas::new(20, 20);
+        let source = "#view 0 0 20 20 flip\n#symbol s\nline (pnt2 0 0.5) (vec2 10 0)\n#end\n\
+                      place \"s\" (pnt2 5 5)";
+        canvas.paint(&page(source), view);
+        // y grows upwards, so the line ends up 5.5 pixels above the bottom
+        assert!(dark(&canvas, 10, 14) && !dark(&canvas, 10, 5));
+    }
+}
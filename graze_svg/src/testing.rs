@@ -0,0 +1,40 @@
+//! Golden tests for the SVG a script renders, like [`graze::testing`]
+//! does for draw commands.
+
+use std::path::Path;
+
+use graze::{parse_file, testing::assert_golden, Runtime, StringTokenizer};
+
+use crate::{RenderError, SvgOutput};
+
+/// Run `source` and render every page indented, so that golden files
+/// diff line by line.
+pub fn render_normalized(source: &str) -> Result<String, RenderError> {
+    let program = parse_file(&mut StringTokenizer::new(&source))?;
+    let mut runtime = Runtime::<SvgOutput>::default();
+    runtime.backend_mut().set_pretty(true);
+    runtime.execute(program)?;
+    Ok(runtime.finish().pages().join("\n"))
+}
+
+/// Check the SVG `source` renders against the golden file at `path`.
+#[track_caller]
+pub fn assert_svg_golden(source: &str, path: impl AsRef<Path>) {
+    match render_normalized(source) {
+        Ok(svg) => assert_golden(&svg, path),
+        Err(err) => panic!("Script failed: {err}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_normalized() {
+        let svg = render_normalized("circle (pnt2 1 1) 1\n#page\ncircle (pnt2 2 2) 1").unwrap();
+        assert_eq!(svg.matches("<svg").count(), 2);
+        assert!(svg.contains("</svg>\n\n<svg"));
+        assert!(svg.contains("\n  <circle"));
+    }
+}
@@ -0,0 +1,163 @@
+use std::{borrow::Cow, fmt::Write};
+
+/// Replace the characters that can't appear literally in text or
+/// attribute values.
+fn escape(text: &str) -> Cow<'_, str> {
+    if !text.contains(['&', '<', '>', '"']) {
+        return Cow::Borrowed(text);
+    }
+    let mut escaped = String::with_capacity(text.len() + 8);
+    for char in text.chars() {
+        match char {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            char => escaped.push(char),
+        }
+    }
+    Cow::Owned(escaped)
+}
+
+/// Put every element of `xml`, as written by [`XmlWriter`], on its own
+/// line, indented by two spaces per level. Elements holding only text
+/// stay on one line.
+pub(crate) fn indent(xml: &str) -> String {
+    let mut indented = String::with_capacity(xml.len() * 2);
+    let mut line = |depth: usize, content: &str| {
+        indented.extend(std::iter::repeat_n("  ", depth));
+        indented.push_str(content);
+        indented.push('\n');
+    };
+
+    let mut depth = 0;
+    let mut rest = xml;
+    while let Some(start) = rest.find('<') {
+        let end = start + rest[start..].find('>').expect("XmlWriter closes every tag") + 1;
+        let tag = &rest[start..end];
+        rest = &rest[end..];
+
+        if tag.starts_with("</") {
+            depth -= 1;
+            line(depth, tag);
+        } else if tag.ends_with("/>") {
+            line(depth, tag);
+        } else {
+            let text = rest.find('<').unwrap_or(rest.len());
+            if rest[text..].starts_with("</") {
+                let close = text + rest[text..].find('>').expect("XmlWriter closes every tag") + 1;
+                line(depth, &format!("{tag}{}", &rest[..close]));
+                rest = &rest[close..];
+            } else {
+                line(depth, tag);
+                depth += 1;
+            }
+        }
+    }
+    indented
+}
+
+/// Writes XML elements one after another into a string.
+#[derive(Debug, Default, Clone)]
+pub(crate) struct XmlWriter {
+    xml: String,
+    /// Names of the elements that are still open, innermost last.
+    open: Vec<&'static str>,
+}
+
+impl XmlWriter {
+    fn start_tag(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.xml.push('<');
+        self.xml.push_str(name);
+        for (attribute, value) in attributes {
+            write!(self.xml, r#" {attribute}="{}""#, escape(value))
+                .expect("Writing to a String never fails");
+        }
+    }
+
+    /// Write an element without content, like `<circle .../>`.
+    pub fn empty(&mut self, name: &str, attributes: &[(&str, String)]) {
+        self.start_tag(name, attributes);
+        self.xml.push_str("/>");
+    }
+
+    /// Write an element holding only `text`.
+    pub fn text(&mut self, name: &str, attributes: &[(&str, String)], text: &str) {
+        self.start_tag(name, attributes);
+        write!(self.xml, ">{}</{name}>", escape(text)).expect("Writing to a String never fails");
+    }
+
+    /// Start an element; everything written until [`XmlWriter::close`]
+    /// goes inside it.
+    pub fn open(&mut self, name: &'static str, attributes: &[(&str, String)]) {
+        self.start_tag(name, attributes);
+        self.xml.push('>');
+        self.open.push(name);
+    }
+
+    /// End the innermost open element, if any.
+    pub fn close(&mut self) {
+        if let Some(name) = self.open.pop() {
+            write!(self.xml, "</{name}>").expect("Writing to a String never fails");
+        }
+    }
+
+    pub fn close_all(&mut self) {
+        while !self.open.is_empty() {
+            self.close();
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.xml.is_empty()
+    }
+
+    /// Everything written so far, with open elements closed.
+    pub fn to_closed(&self) -> String {
+        let mut closed = self.clone();
+        closed.close_all();
+        closed.xml
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_escape() {
+        assert_eq!(escape("a < b"), "a &lt; b");
+        assert_eq!(escape(r#"say "R&D""#), "say &quot;R&amp;D&quot;");
+        assert!(matches!(escape("plain"), Cow::Borrowed(_)));
+    }
+
+    #[test]
+    fn test_nesting() {
+        let mut writer = XmlWriter::default();
+        writer.open("g", &[("id", "a&b".into())]);
+        writer.empty("circle", &[("r", "1".into())]);
+        writer.text("text", &[], "<1>");
+        assert_eq!(
+            writer.to_closed(),
+            r#"<g id="a&amp;b"><circle r="1"/><text>&lt;1&gt;</text></g>"#
+        );
+
+        writer.close();
+        writer.close();
+        assert!(writer.open.is_empty());
+    }
+
+    #[test]
+    fn test_indent() {
+        let xml = r#"<svg><g id="a"><circle r="1"/><text>1 &lt; 2</text><g></g></g></svg>"#;
+        let expected = r#"<svg>
+  <g id="a">
+    <circle r="1"/>
+    <text>1 &lt; 2</text>
+    <g></g>
+  </g>
+</svg>
+"#;
+        assert_eq!(indent(xml), expected);
+    }
+}
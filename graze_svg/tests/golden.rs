@@ -0,0 +1,25 @@
+//! Renders every script in `tests/golden` and compares its draw commands
+//! and SVG against the golden files next to it.
+
+use std::path::Path;
+
+use graze::testing::assert_commands_golden;
+use graze_svg::testing::assert_svg_golden;
+
+#[track_caller]
+fn check(name: &str) {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/golden");
+    let source = std::fs::read_to_string(directory.join(name).with_extension("grz")).unwrap();
+    assert_commands_golden(&source, directory.join(name).with_extension("commands"));
+    assert_svg_golden(&source, directory.join(name).with_extension("svg"));
+}
+
+#[test]
+fn test_drawing() {
+    check("drawing");
+}
+
+#[test]
+fn test_pages() {
+    check("pages");
+}